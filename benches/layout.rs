@@ -0,0 +1,26 @@
+use combine::Parser;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use wev::{css, html, layout::node_to_object, rect::Rect, style::to_styled_node};
+
+fn large_article() -> String {
+    let paragraph = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod \
+                     tempor incididunt ut labore et dolore magna aliqua. 吾輩は猫である。名前はまだ無い。";
+    let body = (0..500)
+        .map(|i| format!("<div><p>{} {}</p><p>{}</p></div>", i, paragraph, paragraph))
+        .collect::<String>();
+    format!("<div>{}</div>", body)
+}
+
+fn bench_layout(c: &mut Criterion) {
+    let article = large_article();
+    let dom = html::html().parse(article.as_str()).unwrap().0;
+    let stylesheet = css::stylesheet("").unwrap();
+    let styled = to_styled_node(&dom[0], &stylesheet).unwrap();
+
+    c.bench_function("layout large article", |b| {
+        b.iter(|| node_to_object(black_box(&styled), Rect::new(0, 0, 80, 40), 0))
+    });
+}
+
+criterion_group!(benches, bench_layout);
+criterion_main!(benches);
@@ -0,0 +1,1519 @@
+#[cfg(feature = "images")]
+use crate::graphics::{self, Graphics, ScaledImages};
+#[cfg(feature = "scripts")]
+use crate::script::{self, ScriptPage};
+use crate::{
+    auth::Credentials,
+    bookmark::{Bookmark, Bookmarks},
+    cache::CacheMode,
+    diff,
+    dom::{Node, NodeType},
+    download::{Download, Downloads, Saving, DOWNLOADS_URL},
+    error::{self, Error},
+    export::export,
+    form::{self, Control},
+    graphics::Placement,
+    hint::{HintEvent, LinkHints},
+    history::{Entry, History},
+    inspector::{Inspector, InspectorEvent},
+    keymap::{action_for_key, Action, Keymap, PENDING_KEY_TIMEOUT},
+    layout::{content_area, node_to_object_with_context, LayoutObject},
+    loader::url_from_input,
+    message::{Messages, MESSAGES_URL},
+    page::{split_fragment, Loading, Page, Progress, Settings},
+    pane::{render_entry, render_separator, shown_page, view_of, BookmarkList, Split},
+    prompt::{bottom_line, Prompt, PromptEvent, PromptKind},
+    render::{strip_colors, visible_area, ColorSupport, PageView, Palette},
+    request::RequestError,
+    style::to_styled_node,
+    terminal::{copy_to_clipboard, TerminalGuard},
+    theme::Theme,
+};
+use crossterm::{
+    cursor::MoveTo,
+    event::{self, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal::SetTitle,
+    ExecutableCommand,
+};
+use ratatui::{
+    prelude::*,
+    widgets::{Clear, Paragraph},
+    TerminalOptions, Viewport,
+};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::{
+    fs,
+    io::{stdout, Result, Stdout, Write},
+    mem,
+    path::PathBuf,
+    process::{self, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// `Options` are the user's settings for how pages are displayed.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// The widest the page content may be, in columns.
+    pub max_width: Option<u16>,
+    /// The tallest an image may be, in rows.
+    pub max_image_height: Option<u16>,
+    pub theme: Theme,
+    /// The height of a viewport below the prompt to show pages in, in place of the
+    /// alternate screen. What it shows last is left in the terminal's scrollback.
+    pub inline: Option<u16>,
+}
+
+/// The narrowest the inspector pane gets, unless the screen is narrower.
+const INSPECTOR_MIN_WIDTH: u16 = 30;
+
+/// Returns the scroll position after `action`, keeping the last page of content in view.
+fn scroll_to(scroll: u16, action: &Action, content_height: u16, viewport_height: u16) -> u16 {
+    let max_scroll = content_height.saturating_sub(viewport_height);
+    let page = viewport_height.max(1) as i32;
+    let scroll = match action {
+        Action::ScrollLines(lines) => scroll as i32 + lines,
+        Action::ScrollPages(pages) => scroll as i32 + pages * page,
+        Action::ScrollToTop => 0,
+        Action::ScrollToBottom => max_scroll as i32,
+        _ => scroll as i32,
+    };
+    scroll.clamp(0, max_scroll as i32) as u16
+}
+
+/// The program that opens a URL with the desktop's default browser.
+const OPENER: &str = if cfg!(target_os = "macos") {
+    "open"
+} else {
+    "xdg-open"
+};
+
+/// Opens `url` with `OPENER`, without waiting for the browser.
+fn open_externally(url: &str) -> Result<()> {
+    let mut child = process::Command::new(OPENER)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // reaped on another thread so that it does not linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Describes the innermost element at (`x`, `y`) of `object` like `a#top.nav https://a.com/`:
+/// its tag, id and classes, and the absolute URL it links to or loads.
+fn describe_element(object: &LayoutObject, x: u16, y: u16, page: &Page) -> Option<String> {
+    let element = object.hit_test(x, y).into_iter().rev().find_map(|object| {
+        match object.node?.node_type {
+            NodeType::Element(e) => Some(e),
+            _ => None,
+        }
+    })?;
+    let mut description = element.tag_name.clone();
+    if let Some(id) = element.id() {
+        description += &format!("#{}", id);
+    }
+    for class in element.classes() {
+        description += &format!(".{}", class);
+    }
+    if let Some(url) = element
+        .attributes
+        .get("href")
+        .or(element.attributes.get("src"))
+    {
+        description += &format!(" {}", page.resolve(url));
+    }
+    Some(description)
+}
+
+/// Returns the horizontal scroll position after scrolling by `columns`.
+fn scroll_columns(hscroll: u16, columns: i32, max_hscroll: u16) -> u16 {
+    (hscroll as i32 + columns).clamp(0, max_hscroll as i32) as u16
+}
+
+/// Shows on the status bar how far a page wider than the screen is scrolled horizontally,
+/// with arrows towards the hidden sides.
+fn hscroll_indicator(hscroll: u16, max_hscroll: u16) -> String {
+    format!(
+        "{}{}/{}{}",
+        if hscroll > 0 { "◂ " } else { "  " },
+        hscroll,
+        max_hscroll,
+        if hscroll < max_hscroll { " ▸" } else { "  " }
+    )
+}
+
+/// `SigningIn` is a page whose origin asked to be signed in to, while the user types
+/// the credentials to load it again with.
+#[derive(Debug, PartialEq, Eq)]
+struct SigningIn {
+    url: String,
+    origin: String,
+    /// The user name, once it has been typed.
+    user: Option<String>,
+}
+
+/// Returns the origin that `error` says asked to be signed in to.
+fn unauthorized_origin(error: &Error) -> Option<String> {
+    match error {
+        Error::Network(RequestError::Unauthorized { origin, .. }) => Some(origin.clone()),
+        _ => None,
+    }
+}
+
+/// The terminal title for `page`: its `<title>`, or its URL when it has none.
+fn window_title(page: &Page) -> String {
+    format!("{} - wev", page.title().unwrap_or_else(|| page.url.clone()))
+}
+
+/// Makes `page`, loaded from `url`, the new page in `history`.
+/// A page that failed to load is replaced with an error page, so that it can be reloaded.
+fn show(
+    page: error::Result<Page>,
+    url: &str,
+    history: &mut History,
+    current: &mut Entry,
+) -> Result<()> {
+    let page = page.unwrap_or_else(|e| Page::error(url, &e));
+    history.visit(
+        current,
+        Entry {
+            page,
+            scroll: 0,
+            focus: None,
+            reveal_fragment: true,
+        },
+    );
+    stdout().execute(SetTitle(window_title(&current.page)))?;
+    Ok(())
+}
+
+/// Starts loading `url` as a new page, which is shown as it arrives.
+/// A link to another fragment of the current page only scrolls it.
+fn navigate(url: &str, current: &mut Entry, settings: &Settings) -> Option<Loading> {
+    let (location, fragment) = split_fragment(url);
+    if fragment.is_some() && location == split_fragment(&current.page.url).0 {
+        current.page.url = url.to_string();
+        current.reveal_fragment = true;
+        return None;
+    }
+    Some(Loading::start(url, settings))
+}
+
+/// Shows `page`, what `loading` has received so far, in place of the part shown before.
+/// The first part becomes a new page in `history`.
+fn show_progress(
+    page: Page,
+    loading: &mut Loading,
+    history: &mut History,
+    current: &mut Entry,
+) -> Result<()> {
+    if loading.in_history {
+        current.page = page;
+    } else {
+        history.visit(
+            current,
+            Entry {
+                page,
+                scroll: 0,
+                focus: None,
+                reveal_fragment: true,
+            },
+        );
+        loading.in_history = true;
+    }
+    stdout().execute(SetTitle(window_title(&current.page)))?;
+    Ok(())
+}
+
+/// Starts submitting the form containing the control at `path` of `page`, whose response
+/// is shown as a new page as it arrives. Returns a message for the status line instead if the
+/// control is not in a form.
+fn submit(
+    path: &[usize],
+    page: &Page,
+    settings: &Settings,
+) -> std::result::Result<Loading, &'static str> {
+    let submission = form::submission(page.dom(), path).ok_or("not in a form")?;
+    Ok(Loading::submit(page, &submission, settings))
+}
+
+/// Returns the path of the focusable element after (or before) `focus` in `dom`, wrapping around.
+/// Without a focus, it is the first (or last) one.
+fn move_focus(dom: &Node, focus: Option<&Vec<usize>>, forward: bool) -> Option<Vec<usize>> {
+    let focusables = dom.paths(&form::is_focusable);
+    let n = focusables.len();
+    if n == 0 {
+        return None;
+    }
+    let i = focus.and_then(|focus| focusables.iter().position(|p| p == focus));
+    let next = match (i, forward) {
+        (Some(i), true) => (i + 1) % n,
+        (Some(i), false) => (i + n - 1) % n,
+        (None, true) => 0,
+        (None, false) => n - 1,
+    };
+    Some(focusables[next].clone())
+}
+
+/// Opens or closes the `<details>` element whose summary is at `path`.
+fn toggle_details(dom: &mut Node, path: &[usize]) {
+    let Some((_, parent)) = path.split_last() else {
+        return;
+    };
+    if let Some(NodeType::Element(details)) = dom.node_at_mut(parent).map(|n| &mut n.node_type) {
+        if details.tag_name == "details" && details.attributes.remove("open").is_none() {
+            details.attributes.insert("open".into(), String::new());
+        }
+    }
+}
+
+/// Changes the value of a text field as typed with `code`. Enter starts a new line.
+fn edit_field(node: &mut Node, code: KeyCode) {
+    let mut value = form::value(node);
+    match code {
+        KeyCode::Char(c) => value.push(c),
+        KeyCode::Enter => value.push('\n'),
+        KeyCode::Backspace => {
+            value.pop();
+        }
+        _ => return,
+    }
+    form::set_value(node, value);
+}
+
+/// Returns whether `event` may change what is on the screen. Only these events redraw it;
+/// the terminal backend then writes only the cells that differ from the last draw.
+fn changes_screen(event: &event::Event) -> bool {
+    match event {
+        event::Event::Key(key) => key.kind == KeyEventKind::Press,
+        event::Event::Resize(..) => true,
+        _ => false,
+    }
+}
+
+/// `App` is the browser: the pages it shows, and what the user is doing with them.
+/// Each turn of its loop takes in what has loaded, draws the screen if anything changed,
+/// and updates itself by the next event.
+///
+/// The styled tree and the layout tree borrow the page, so what a draw needs of them is kept
+/// in a `PageView` instead, until the page changes.
+pub struct App {
+    options: Options,
+    /// What pages are loaded and styled with.
+    settings: Settings,
+    keymap: Keymap,
+    palette: Palette,
+    #[cfg(feature = "images")]
+    graphics: Graphics,
+    #[cfg(feature = "images")]
+    scaled_images: ScaledImages,
+    placements: Vec<Placement>,
+    /// The shown page, laid out and painted. It is emptied whenever the page or its DOM
+    /// changes, and made again on the next draw; scrolling and overlays keep it.
+    view: Option<PageView>,
+    /// The links on screen, as their targets and where they start.
+    visible_links: Vec<(String, Rect)>,
+    link_hints: Option<LinkHints>,
+    /// The images currently drawn on the terminal with a graphics protocol.
+    #[cfg(feature = "images")]
+    shown_placements: Vec<Placement>,
+    current: Entry,
+    history: History,
+    loading: Option<Loading>,
+    /// A response that is not a page, waiting for the user to choose where to save it.
+    download: Option<Download>,
+    downloads: Downloads,
+    signing_in: Option<SigningIn>,
+    /// Whether a text field of the focused element takes the keys typed.
+    editing: bool,
+    /// Whether to scroll the focused (or inspected) element into view on the next draw.
+    reveal_focus: bool,
+    /// Whether the element under the focus is to be described on the next draw, where it is
+    /// hit-tested in the layout.
+    describe_element: bool,
+    inspector: Option<Inspector>,
+    split: Option<Split>,
+    /// The whole screen, and the height of the focused pane, when they were last drawn.
+    screen: Rect,
+    viewport_height: u16,
+    /// Columns scrolled to the right, and the most the page can be.
+    hscroll: u16,
+    max_hscroll: u16,
+    content_height: u16,
+    pending: Option<char>,
+    pending_since: Instant,
+    prompt: Option<Prompt>,
+    messages: Messages,
+    bookmarks_path: Option<PathBuf>,
+    bookmarks: Bookmarks,
+    bookmark_list: Option<BookmarkList>,
+    /// Whether anything on the screen may have changed since it was last drawn.
+    dirty: bool,
+    quit: bool,
+}
+
+impl App {
+    /// Starts loading `url` with `settings`. An empty page stands in for it until its content
+    /// arrives.
+    pub fn new(url: &str, options: Options, settings: Settings) -> error::Result<App> {
+        let mut loading = Loading::start(url, &settings);
+        let page = Page::from_html(url, "", &settings)?;
+        loading.in_history = true;
+        let bookmarks_path = Bookmarks::default_path();
+        let mut messages = Messages::default();
+        let bookmarks = match bookmarks_path.as_deref().map(Bookmarks::load) {
+            Some(Ok(bookmarks)) => bookmarks,
+            Some(Err(e)) => {
+                messages.push(format!("failed to read bookmarks: {}", e));
+                Bookmarks::default()
+            }
+            None => Bookmarks::default(),
+        };
+        Ok(App {
+            options,
+            settings,
+            keymap: Keymap::default(),
+            palette: Palette::detect(options.theme),
+            #[cfg(feature = "images")]
+            graphics: Graphics::detect(),
+            #[cfg(feature = "images")]
+            scaled_images: ScaledImages::default(),
+            placements: vec![],
+            view: None,
+            visible_links: vec![],
+            link_hints: None,
+            #[cfg(feature = "images")]
+            shown_placements: vec![],
+            current: Entry {
+                page,
+                scroll: 0,
+                focus: None,
+                reveal_fragment: true,
+            },
+            history: History::default(),
+            loading: Some(loading),
+            download: None,
+            downloads: Downloads::default(),
+            signing_in: None,
+            editing: false,
+            reveal_focus: false,
+            describe_element: false,
+            inspector: None,
+            split: None,
+            screen: Rect::default(),
+            viewport_height: 0,
+            hscroll: 0,
+            max_hscroll: 0,
+            content_height: 0,
+            pending: None,
+            pending_since: Instant::now(),
+            prompt: None,
+            messages,
+            bookmarks_path,
+            bookmarks,
+            bookmark_list: None,
+            dirty: true,
+            quit: false,
+        })
+    }
+
+    /// Makes keys do what `keymap` binds them to.
+    pub fn with_keymap(mut self, keymap: Keymap) -> App {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Shows the app in the terminal until the user quits, or the process is asked
+    /// to stop by SIGINT or SIGTERM.
+    pub fn run(mut self) -> Result<()> {
+        let terminated = Arc::new(AtomicBool::new(false));
+        for signal in [SIGINT, SIGTERM] {
+            signal_hook::flag::register(signal, Arc::clone(&terminated))?;
+        }
+        let _guard = TerminalGuard::enter(self.options.inline.is_none())?;
+        stdout().execute(SetTitle(window_title(&self.current.page)))?;
+        let viewport = match self.options.inline {
+            Some(height) => Viewport::Inline(height),
+            None => Viewport::Fullscreen,
+        };
+        let mut terminal = Terminal::with_options(
+            CrosstermBackend::new(stdout()),
+            TerminalOptions { viewport },
+        )?;
+        terminal.clear()?;
+
+        while !self.quit && !terminated.load(Ordering::Relaxed) {
+            self.poll_loading()?;
+            if self.messages.expire(Instant::now()) {
+                self.dirty = true;
+            }
+            // the screen is left as it is until something changes it, see `changes_screen`
+            if self.dirty {
+                self.dirty = false;
+                self.draw(&mut terminal)?;
+            }
+
+            if self.pending.is_some() && self.pending_since.elapsed() >= PENDING_KEY_TIMEOUT {
+                self.pending = None;
+            }
+            if event::poll(Duration::from_millis(16))? {
+                self.handle_event(event::read()?)?;
+            }
+        }
+
+        if self.options.inline.is_some() {
+            // the page is left in the scrollback, without the status line and overlays
+            self.loading = None;
+            self.editing = false;
+            self.messages.dismiss();
+            self.prompt = None;
+            self.link_hints = None;
+            self.draw(&mut terminal)?;
+            queue!(
+                stdout(),
+                MoveTo(0, self.screen.bottom().saturating_sub(1)),
+                Print("\r\n")
+            )?;
+            stdout().flush()?;
+        }
+        Ok(())
+    }
+
+    /// Runs a command typed on the command line.
+    fn run_command(&mut self, command: &str) -> Result<()> {
+        match command {
+            "" => {}
+            "messages" | "mes" => {
+                self.bookmark_list = None;
+                let page = Ok(self.messages.to_page());
+                show(page, MESSAGES_URL, &mut self.history, &mut self.current)?;
+                self.view = None;
+            }
+            "downloads" | "dl" => {
+                self.bookmark_list = None;
+                let page = Ok(self.downloads.to_page());
+                show(page, DOWNLOADS_URL, &mut self.history, &mut self.current)?;
+                self.view = None;
+            }
+            "offline" => {
+                let transport = &self.settings.transport;
+                transport.set_offline(!transport.is_offline());
+                self.messages.push(if transport.is_offline() {
+                    "offline: pages are loaded from the cache only"
+                } else {
+                    "online"
+                });
+            }
+            _ if command.split_whitespace().next() == Some("resume") => {
+                let number = command.split_whitespace().nth(1).map(str::parse);
+                let resumed = match number {
+                    Some(Err(_)) => Err(format!("not a download number: {}", command)),
+                    Some(Ok(number)) => self.downloads.resume(Some(number)),
+                    None => self.downloads.resume(None),
+                };
+                self.messages.push(resumed.unwrap_or_else(|e| e));
+            }
+            _ => self.messages.push(format!("unknown command: {}", command)),
+        }
+        Ok(())
+    }
+
+    /// Returns the DOM of the current page, to change it. The layout of the page is dropped,
+    /// so that it and the `NodeId`s it refers to are made again from the changed tree.
+    fn dom_mut(&mut self) -> &mut Node {
+        self.view = None;
+        self.current.page.dom_mut()
+    }
+
+    /// Returns the absolute URL of the focused link, if a link is focused.
+    fn focused_link(&self) -> Option<String> {
+        let path = self.current.focus.as_ref()?;
+        let element = self.current.page.dom().node_at(path)?.element()?;
+        let href = element.attributes.get("href")?;
+        Some(self.current.page.resolve(href))
+    }
+
+    /// Takes in what has arrived of the page being loaded, and of the download being saved.
+    fn poll_loading(&mut self) -> Result<()> {
+        let (changed, finished) = self.downloads.poll();
+        for message in finished {
+            self.messages.push(message);
+        }
+        if changed {
+            self.dirty = true;
+            if self.current.page.url == DOWNLOADS_URL {
+                self.current.page = self.downloads.to_page();
+                self.view = None;
+            }
+        }
+        if let Some(load) = &mut self.loading {
+            match load.poll() {
+                Progress::Pending => {}
+                Progress::Download(download) => {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::Download,
+                        input: download.file_name.clone(),
+                    });
+                    self.download = Some(download);
+                    self.loading = None;
+                    self.dirty = true;
+                }
+                Progress::NotModified => {
+                    // a reload of a page that has not changed keeps it, scrolled as it was
+                    if !self.current.page.failed && self.current.page.url == load.url {
+                        self.messages.push("the page has not changed");
+                        self.current.focus = load.replaced.take().map(|(_, focus)| focus);
+                        self.loading = None;
+                        self.dirty = true;
+                    }
+                }
+                Progress::Partial(page) => {
+                    show_progress(page, load, &mut self.history, &mut self.current)?;
+                    self.view = None;
+                    self.dirty = true;
+                }
+                Progress::Done(page) => {
+                    self.dirty = true;
+                    let page = page.unwrap_or_else(|e| {
+                        if let Some(origin) = unauthorized_origin(&e) {
+                            // the error page stays until the page is loaded again
+                            self.messages.push(match origin.starts_with("http:") {
+                                true => format!("{}; the password is sent unencrypted", e),
+                                false => e.to_string(),
+                            });
+                            self.prompt = Some(Prompt {
+                                kind: PromptKind::User,
+                                input: String::new(),
+                            });
+                            self.signing_in = Some(SigningIn {
+                                url: load.url.clone(),
+                                origin,
+                                user: None,
+                            });
+                        }
+                        Page::error(&load.url, &e)
+                    });
+                    show_progress(page, load, &mut self.history, &mut self.current)?;
+                    self.view = None;
+                    // what was focused before a reload stays focused where it went, if it is still there
+                    if let Some((dom, focus)) = load.replaced.take() {
+                        let diff = diff::diff(&dom, self.current.page.dom());
+                        self.current.focus = diff.map(&focus).map(<[usize]>::to_vec);
+                    }
+                    // the element named by the fragment may not have arrived at first
+                    self.current.reveal_fragment |= self.current.scroll == 0;
+                    self.loading = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+        terminal.draw(|frame| self.draw_frame(frame))?;
+
+        #[cfg(feature = "images")]
+        if self.graphics.is_protocol() && self.placements != self.shown_placements {
+            match self.graphics.clear_sequence() {
+                Some(clear) => queue!(stdout(), Print(clear))?,
+                // repaint the whole screen to erase the pixels of the images drawn before
+                None if !self.shown_placements.is_empty() => terminal.clear()?,
+                None => {}
+            }
+            let page = shown_page(&self.current, &self.bookmark_list);
+            let cell_size = graphics::cell_size();
+            for placement in &self.placements {
+                if let Some(image) = page.images.get(&placement.src) {
+                    queue!(
+                        stdout(),
+                        MoveTo(placement.area.x, placement.area.y),
+                        Print(self.graphics.encode(image, placement, cell_size))
+                    )?;
+                }
+            }
+            stdout().flush()?;
+            self.shown_placements = self.placements.clone();
+        }
+        Ok(())
+    }
+
+    fn draw_frame(&mut self, frame: &mut Frame) {
+        let _span = tracing::debug_span!("render").entered();
+        let screen = frame.size();
+        self.screen = screen;
+        // the inspector pane takes the right of the screen, and the page the rest
+        let pane = self.inspector.as_ref().map(|_| {
+            let width = (screen.width * 2 / 5)
+                .max(INSPECTOR_MIN_WIDTH)
+                .min(screen.width);
+            Rect {
+                x: screen.right() - width,
+                width,
+                ..screen
+            }
+        });
+        let area = Rect {
+            width: screen.width - pane.map_or(0, |pane| pane.width),
+            ..screen
+        };
+        let (area, other_area) = match &self.split {
+            Some(split) => {
+                let (area, other_area) = split.areas(area);
+                (area, Some(other_area))
+            }
+            None => (area, None),
+        };
+        self.viewport_height = area.height;
+        if let (Some(split), Some(other_area)) = (&mut self.split, other_area) {
+            render_entry(
+                &mut split.other,
+                &mut split.other_view,
+                other_area,
+                self.options,
+                self.palette,
+                frame.buffer_mut(),
+            );
+            render_separator(split, area, other_area, frame.buffer_mut());
+        }
+        // not `shown_page`, which would borrow all of `current` while its scroll is clamped
+        let page = match &self.bookmark_list {
+            Some(list) => &list.page,
+            None => &self.current.page,
+        };
+        self.placements.clear();
+        // the page is laid out from the top, and moved down to its pane when drawn
+        let content = Rect {
+            y: 0,
+            ..content_area(area, self.options.max_width)
+        };
+        if let Some(view) = view_of(&mut self.view, page, content, self.options, self.palette) {
+            self.content_height = view.height;
+            self.max_hscroll = match &self.bookmark_list {
+                Some(_) => 0,
+                None => view.right.saturating_sub(content.right()),
+            };
+            self.hscroll = self.hscroll.min(self.max_hscroll);
+            let document = page.document();
+            let areas_at = |path: &[usize]| {
+                document
+                    .id_at(path)
+                    .map(|id| view.areas_of(id))
+                    .unwrap_or_default()
+            };
+            let focus_areas = match (&self.bookmark_list, &self.current.focus) {
+                (None, Some(path)) => areas_at(path),
+                _ => vec![],
+            };
+            let inspected_areas = match (&self.bookmark_list, &self.inspector) {
+                (None, Some(inspector)) => inspector
+                    .selected(page.dom())
+                    .map(|path| areas_at(&path))
+                    .unwrap_or_default(),
+                _ => vec![],
+            };
+            // the inspected element is revealed instead of the focused one while inspecting
+            let revealed = if self.inspector.is_some() {
+                &inspected_areas
+            } else {
+                &focus_areas
+            };
+            let scroll = match &self.bookmark_list {
+                Some(list) => list.scroll(area.height),
+                None => {
+                    if self.current.reveal_fragment {
+                        let anchor = page
+                            .fragment()
+                            .and_then(|fragment| document.anchor(fragment))
+                            .and_then(|id| view.areas_of(id).first().map(|area| area.y));
+                        if let Some(y) = anchor {
+                            self.current.scroll = y;
+                        }
+                        self.current.reveal_fragment = false;
+                    }
+                    if let Some(focused) = revealed.first().filter(|_| self.reveal_focus) {
+                        if focused.y < self.current.scroll {
+                            self.current.scroll = focused.y;
+                        } else if focused.bottom() > self.current.scroll.saturating_add(area.height)
+                        {
+                            self.current.scroll = focused.bottom() - area.height;
+                        }
+                    }
+                    self.reveal_focus = false;
+                    // the page may have become shorter since it was scrolled, e.g. after a reload
+                    self.current.scroll = self
+                        .current
+                        .scroll
+                        .min(self.content_height.saturating_sub(area.height));
+                    self.current.scroll
+                }
+            };
+            if mem::take(&mut self.describe_element) && self.bookmark_list.is_none() {
+                // without a focus, the element at the top left of the screen is described
+                let (x, y) = focus_areas
+                    .first()
+                    .map_or((content.x + self.hscroll, scroll), |area| (area.x, area.y));
+                // hit-testing needs the layout tree, which is not kept, so the page is laid out again
+                let description = to_styled_node(page.dom(), &page.stylesheet).and_then(|styled| {
+                    let context = page.layout_context(self.options.max_image_height);
+                    let object = node_to_object_with_context(&styled, content, 0, &context);
+                    describe_element(&object, x, y, page)
+                });
+                self.messages
+                    .push(description.unwrap_or_else(|| "no element here".into()));
+            }
+            let mut pane_buf = Buffer::empty(Rect { y: 0, ..area });
+            pane_buf.set_style(pane_buf.area, self.palette.base_style());
+            let buf = &mut pane_buf;
+            // moving the buffer right over the page draws the page scrolled to the left
+            buf.area.x += self.hscroll;
+            view.paint(buf, scroll);
+            for focused in &focus_areas {
+                if let Some(area) = visible_area(*focused, scroll, buf) {
+                    buf.set_style(area, Style::new().add_modifier(Modifier::REVERSED));
+                }
+            }
+            for inspected in &inspected_areas {
+                if let Some(area) = visible_area(*inspected, scroll, buf) {
+                    buf.set_style(area, Style::new().bg(Color::Blue));
+                }
+            }
+            view.image_placements(scroll, buf, &mut self.placements);
+            self.visible_links = view
+                .links
+                .iter()
+                .filter_map(|(href, areas)| {
+                    let area = areas
+                        .iter()
+                        .find_map(|area| visible_area(*area, scroll, buf))?;
+                    Some((href.clone(), area))
+                })
+                .collect();
+            #[cfg(feature = "images")]
+            for placement in &self.placements {
+                if self.graphics.is_protocol() {
+                    graphics::reserve(placement.area, buf);
+                } else if let Some(image) = page
+                    .images
+                    .get(&placement.src)
+                    .filter(|_| self.palette.support != ColorSupport::NoColor)
+                {
+                    // images are made of colored cells, so they are left out without colors
+                    self.scaled_images.draw(image, placement, buf, self.palette);
+                }
+            }
+            buf.area.x -= self.hscroll;
+            buf.area.y = area.y;
+            frame.buffer_mut().merge(buf);
+            for placement in &mut self.placements {
+                placement.area.x -= self.hscroll;
+                placement.area.y += area.y;
+            }
+            for (_, link) in &mut self.visible_links {
+                link.x -= self.hscroll;
+                link.y += area.y;
+            }
+        }
+        if let (Some(inspector), Some(pane)) = (&self.inspector, pane) {
+            inspector.render(&self.current.page, pane, frame.buffer_mut());
+        }
+        if let Some(hints) = &self.link_hints {
+            hints.render(frame.buffer_mut());
+        }
+        if let Some(prompt) = &self.prompt {
+            prompt.render(frame);
+        } else if let Some(status) = self
+            .messages
+            .current()
+            .map(str::to_string)
+            .or(self.editing.then(|| "editing (Esc to stop)".into()))
+            .or(self
+                .loading
+                .as_ref()
+                .map(|load| format!("loading {}... (Esc to stop)", load.url)))
+            .or(self.downloads.active().next().map(Saving::describe))
+        {
+            let area = bottom_line(area);
+            frame.render_widget(Clear, area);
+            frame.render_widget(Paragraph::new(status), area);
+        }
+        if self.prompt.is_none() && self.max_hscroll > 0 {
+            let indicator = hscroll_indicator(self.hscroll, self.max_hscroll);
+            let width = (indicator.width() as u16).min(area.width);
+            let area = bottom_line(area);
+            let area = Rect {
+                x: area.right() - width,
+                width,
+                ..area
+            };
+            frame.render_widget(Clear, area);
+            frame.render_widget(Paragraph::new(indicator), area);
+        }
+        if self.palette.support == ColorSupport::NoColor {
+            strip_colors(frame.buffer_mut());
+        }
+    }
+
+    fn handle_event(&mut self, event: event::Event) -> Result<()> {
+        self.dirty |= changes_screen(&event);
+        let event::Event::Key(key) = event else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+        // raw mode turns Ctrl-C into a key, which quits from anywhere as SIGINT would
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.quit = true;
+            return Ok(());
+        }
+        self.messages.dismiss();
+        self.handle_key(key)
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Result<()> {
+        if let Some(open) = &mut self.prompt {
+            let kind = open.kind;
+            match open.handle_key(key) {
+                Some(PromptEvent::Submit(input)) => {
+                    self.prompt = None;
+                    match kind {
+                        PromptKind::Url => {
+                            self.bookmark_list = None;
+                            self.view = None;
+                            self.loading = navigate(
+                                &url_from_input(&input),
+                                &mut self.current,
+                                &self.settings,
+                            );
+                        }
+                        PromptKind::Export(format) => {
+                            let width = content_area(self.screen, self.options.max_width).width;
+                            let page = shown_page(&self.current, &self.bookmark_list);
+                            self.messages.push(
+                                match fs::write(&input, export(page, width, format)) {
+                                    Ok(()) => format!("exported to {}", input),
+                                    Err(e) => format!("failed to export to {}: {}", input, e),
+                                },
+                            );
+                        }
+                        PromptKind::Command => self.run_command(&input)?,
+                        PromptKind::Download => {
+                            if let Some(download) = self.download.take() {
+                                self.downloads.start(download, &input);
+                            }
+                        }
+                        PromptKind::User => {
+                            if let Some(signing_in) = &mut self.signing_in {
+                                signing_in.user = Some(input);
+                                self.prompt = Some(Prompt {
+                                    kind: PromptKind::Password,
+                                    input: String::new(),
+                                });
+                            }
+                        }
+                        PromptKind::Password => {
+                            if let Some(SigningIn {
+                                url,
+                                origin,
+                                user: Some(user),
+                            }) = self.signing_in.take()
+                            {
+                                let password = input;
+                                let credentials = Credentials { user, password };
+                                self.settings.transport.sign_in(&origin, credentials);
+                                // the page takes the place of the error page
+                                let mut loading = Loading::start_with(
+                                    &url,
+                                    CacheMode::Revalidate,
+                                    &self.settings,
+                                );
+                                loading.in_history = true;
+                                self.loading = Some(loading);
+                            }
+                        }
+                    }
+                }
+                Some(PromptEvent::Cancel) => {
+                    self.prompt = None;
+                    // dropping the response closes its connection without reading the rest
+                    self.download = None;
+                    self.signing_in = None;
+                }
+                None => {}
+            }
+            return Ok(());
+        }
+
+        if self.editing {
+            let Some(path) = self.current.focus.clone() else {
+                self.editing = false;
+                return Ok(());
+            };
+            let is_text_area = self
+                .current
+                .page
+                .dom()
+                .node_at(&path)
+                .and_then(|node| node.element())
+                .is_some_and(|e| Control::of(e) == Some(Control::TextArea));
+            match key.code {
+                KeyCode::Esc => self.editing = false,
+                // Enter in a single-line field submits its form
+                KeyCode::Enter if !is_text_area => {
+                    self.editing = false;
+                    match submit(&path, &self.current.page, &self.settings) {
+                        Ok(loading) => self.loading = Some(loading),
+                        Err(message) => self.messages.push(message),
+                    }
+                }
+                code => {
+                    if let Some(node) = self.dom_mut().node_at_mut(&path) {
+                        edit_field(node, code);
+                    }
+                }
+            }
+            return Ok(());
+        }
+
+        if self.loading.is_some() && key.code == KeyCode::Esc {
+            self.loading = None;
+            self.messages.push("stopped loading");
+            return Ok(());
+        }
+
+        if let Some(hints) = &mut self.link_hints {
+            match hints.handle_key(key) {
+                Some(HintEvent::Follow(href)) => {
+                    self.link_hints = None;
+                    let url = self.current.page.resolve(&href);
+                    self.loading = navigate(&url, &mut self.current, &self.settings);
+                }
+                Some(HintEvent::Cancel) => self.link_hints = None,
+                None => {}
+            }
+            return Ok(());
+        }
+
+        if let Some(list) = &mut self.bookmark_list {
+            // the list is a page of its own, made again for each selection
+            self.view = None;
+            match key.code {
+                KeyCode::Char('j') | KeyCode::Down => list.select(&self.bookmarks, 1),
+                KeyCode::Char('k') | KeyCode::Up => list.select(&self.bookmarks, -1),
+                KeyCode::Enter => {
+                    if let Some(bookmark) = self.bookmarks.bookmarks.get(list.selected) {
+                        self.loading = navigate(&bookmark.url, &mut self.current, &self.settings);
+                    }
+                    self.bookmark_list = None;
+                }
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('B') => self.bookmark_list = None,
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if let Some(open) = &mut self.inspector {
+            if open.handle_key(key, self.current.page.dom()) == Some(InspectorEvent::Close) {
+                self.inspector = None;
+            }
+            self.reveal_focus = true;
+            return Ok(());
+        }
+
+        match action_for_key(key, &mut self.pending, &self.keymap) {
+            Some(Action::Quit) => self.quit = true,
+            Some(Action::Split { vertical }) => match &mut self.split {
+                Some(split) => split.vertical = vertical,
+                None => {
+                    self.split = Some(Split {
+                        vertical,
+                        other: Entry {
+                            page: self.current.page.clone(),
+                            scroll: self.current.scroll,
+                            focus: None,
+                            reveal_fragment: false,
+                        },
+                        other_history: History::default(),
+                        other_view: None,
+                        focus_second: false,
+                    })
+                }
+            },
+            Some(Action::SwitchPane) => {
+                if let Some(split) = &mut self.split {
+                    split.switch(&mut self.current, &mut self.history, &mut self.view);
+                    stdout().execute(SetTitle(window_title(&self.current.page)))?;
+                    // a page still loading would be shown in the wrong pane
+                    if self.loading.take().is_some() {
+                        self.messages.push("stopped loading");
+                    }
+                }
+            }
+            Some(Action::ClosePane) => self.split = None,
+            Some(Action::DescribeElement) => self.describe_element = true,
+            #[cfg(feature = "scripts")]
+            Some(Action::Script(name)) => {
+                let mut page = ScriptPage::new(&self.current.page, self.current.scroll);
+                let ran = script::run_key_script(&name, &mut page);
+                let ScriptPage {
+                    scroll,
+                    follow,
+                    message,
+                    ..
+                } = page;
+                self.current.scroll = scroll;
+                if let Some(url) = follow {
+                    self.loading = navigate(&url, &mut self.current, &self.settings);
+                }
+                match ran {
+                    Ok(()) => message.into_iter().for_each(|m| self.messages.push(m)),
+                    Err(e) => self
+                        .messages
+                        .push(format!("script `{}` failed: {}", name, e)),
+                }
+            }
+            Some(Action::OpenCommandLine) => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Command,
+                    input: String::new(),
+                })
+            }
+            Some(Action::OpenExternally) => {
+                self.messages.push(match self.focused_link() {
+                    Some(url) => match open_externally(&url) {
+                        Ok(()) => format!("opened {} with {}", url, OPENER),
+                        Err(e) => format!("failed to run {}: {}", OPENER, e),
+                    },
+                    None => "no link is focused".into(),
+                });
+            }
+            Some(action @ (Action::CopyUrl | Action::CopyLink)) => {
+                let url = if action == Action::CopyUrl {
+                    Some(self.current.page.url.clone())
+                } else {
+                    self.focused_link()
+                };
+                self.messages.push(match url {
+                    Some(url) => copy_to_clipboard(&url)?,
+                    None => "no link is focused".into(),
+                });
+            }
+            Some(Action::Inspect) => {
+                self.inspector = Some(Inspector::default());
+                self.bookmark_list = None;
+                self.view = None;
+            }
+            Some(Action::Export(format)) => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::Export(format),
+                    input: String::new(),
+                })
+            }
+            Some(Action::OpenAddressBar) => self.prompt = Some(Prompt::default()),
+            Some(action @ (Action::Back | Action::Forward)) => {
+                let moved = if action == Action::Back {
+                    self.history.back(&mut self.current)
+                } else {
+                    self.history.forward(&mut self.current)
+                };
+                if moved {
+                    self.view = None;
+                    stdout().execute(SetTitle(window_title(&self.current.page)))?;
+                }
+            }
+            Some(Action::AddBookmark) => {
+                let bookmark = Bookmark {
+                    title: self
+                        .current
+                        .page
+                        .title()
+                        .unwrap_or_else(|| self.current.page.url.clone()),
+                    url: self.current.page.url.clone(),
+                };
+                self.messages.push(if !self.bookmarks.add(bookmark) {
+                    format!("{} is already bookmarked", self.current.page.url)
+                } else if let Some(path) = &self.bookmarks_path {
+                    match self.bookmarks.save(path) {
+                        Ok(()) => format!("bookmarked {}", self.current.page.url),
+                        Err(e) => format!("failed to save bookmarks: {}", e),
+                    }
+                } else {
+                    "bookmarked for this session only: no data directory".into()
+                });
+            }
+            Some(Action::Reload { hard }) => {
+                let focus = self.current.focus.take();
+                if self.current.page.url == MESSAGES_URL {
+                    // the message list is made here rather than loaded
+                    self.current.page = self.messages.to_page();
+                    self.view = None;
+                } else if self.current.page.url == DOWNLOADS_URL {
+                    self.current.page = self.downloads.to_page();
+                    self.view = None;
+                } else {
+                    // the page arrives in the background, in place of the one shown
+                    // a reload asks whether the cached page changed, and a hard one refetches it
+                    let mode = if hard {
+                        CacheMode::Reload
+                    } else {
+                        CacheMode::Revalidate
+                    };
+                    let mut loading =
+                        Loading::start_with(&self.current.page.url, mode, &self.settings);
+                    loading.in_history = true;
+                    loading.replaced =
+                        focus.map(|focus| (Box::new(self.current.page.dom().clone()), focus));
+                    self.loading = Some(loading);
+                }
+            }
+            Some(Action::ShowLinkHints) => {
+                let hints = LinkHints::new(&self.visible_links);
+                if hints.is_empty() {
+                    self.messages.push("no links on screen");
+                } else {
+                    self.link_hints = Some(hints);
+                }
+            }
+            Some(Action::OpenBookmarks) => {
+                self.bookmark_list = Some(BookmarkList::new(&self.bookmarks));
+                self.view = None;
+            }
+            Some(Action::ScrollColumns(columns)) => {
+                self.hscroll = scroll_columns(self.hscroll, columns, self.max_hscroll);
+            }
+            Some(action @ (Action::FocusNext | Action::FocusPrevious)) => {
+                let forward = action == Action::FocusNext;
+                self.current.focus = move_focus(
+                    self.current.page.dom(),
+                    self.current.focus.as_ref(),
+                    forward,
+                );
+                self.reveal_focus = true;
+            }
+            Some(Action::Activate) => {
+                let Some(path) = self.current.focus.clone() else {
+                    return Ok(());
+                };
+                let Some(element) = self
+                    .current
+                    .page
+                    .dom()
+                    .node_at(&path)
+                    .and_then(|n| n.element())
+                else {
+                    return Ok(());
+                };
+                let control = Control::of(element);
+                let href = element.attributes.get("href").cloned();
+                let is_summary = element.tag_name == "summary";
+                match control {
+                    Some(control) if control.is_editable() => self.editing = true,
+                    Some(Control::Checkbox | Control::Radio) => form::toggle(self.dom_mut(), &path),
+                    Some(Control::Select) => {
+                        if let Some(select) = self.dom_mut().node_at_mut(&path) {
+                            form::select_next_option(select);
+                        }
+                    }
+                    Some(Control::Button { submit: true }) => {
+                        match submit(&path, &self.current.page, &self.settings) {
+                            Ok(loading) => self.loading = Some(loading),
+                            Err(message) => self.messages.push(message),
+                        }
+                    }
+                    Some(_) => {}
+                    None if is_summary => toggle_details(self.dom_mut(), &path),
+                    None => {
+                        if let Some(href) = href {
+                            let url = self.current.page.resolve(&href);
+                            self.loading = navigate(&url, &mut self.current, &self.settings);
+                        }
+                    }
+                }
+            }
+            Some(action) => {
+                self.current.scroll = scroll_to(
+                    self.current.scroll,
+                    &action,
+                    self.content_height,
+                    self.viewport_height,
+                );
+            }
+            None if self.pending.is_some() => self.pending_since = Instant::now(),
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        changes_screen, describe_element, hscroll_indicator, move_focus, scroll_columns, scroll_to,
+        toggle_details, unauthorized_origin, Action, App, Error, Options, Palette, Prompt,
+        RequestError,
+    };
+    use crate::{
+        layout::node_to_object,
+        page::{Page, Settings},
+        render::render,
+        style::to_styled_node,
+    };
+    use combine::Parser;
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+    use ratatui::{
+        backend::TestBackend, buffer::Buffer, layout::Rect, Terminal, TerminalOptions, Viewport,
+    };
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_move_focus() {
+        let page = Page::from_html(
+            "about:test",
+            r#"<p><a href="/a">a</a><input name="q"><a>none</a></p>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        let first = move_focus(page.dom(), None, true).unwrap();
+        assert_eq!(first, vec![0, 0]);
+        let second = move_focus(page.dom(), Some(&first), true).unwrap();
+        assert_eq!(second, vec![0, 1]);
+        assert_eq!(move_focus(page.dom(), Some(&second), true), Some(first));
+        assert_eq!(move_focus(page.dom(), None, false), Some(second));
+
+        let page = Page::from_html("about:test", "<p>no links</p>", &Settings::default()).unwrap();
+        assert_eq!(move_focus(page.dom(), None, true), None);
+    }
+
+    #[test]
+    fn test_toggle_details() {
+        let mut page = Page::from_html(
+            "about:test",
+            "<details><summary>More</summary><p>hidden</p></details>",
+            &Settings::default(),
+        )
+        .unwrap();
+        let summary = move_focus(page.dom(), None, true).unwrap();
+        let area = Rect::new(0, 0, 20, 5);
+        let lines = |page: &Page| {
+            let styled = to_styled_node(page.dom(), &page.stylesheet).unwrap();
+            let object = node_to_object(&styled, area, 0);
+            let mut buf = Buffer::empty(area);
+            render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+            (0..area.height)
+                .map(|y| {
+                    (0..area.width)
+                        .map(|x| buf.get(x, y).symbol())
+                        .collect::<String>()
+                        .trim_end()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(&page), vec!["▸ More", "", "", "", ""]);
+
+        toggle_details(page.dom_mut(), &summary);
+        assert_eq!(lines(&page), vec!["▾ More", "hidden", "", "", ""]);
+        toggle_details(page.dom_mut(), &summary);
+        assert_eq!(lines(&page)[1], "");
+    }
+
+    #[test]
+    fn test_horizontal_scroll() {
+        assert_eq!(scroll_columns(0, -1, 10), 0);
+        assert_eq!(scroll_columns(3, 1, 10), 4);
+        assert_eq!(scroll_columns(10, 1, 10), 10);
+        assert_eq!(hscroll_indicator(0, 10), "  0/10 ▸");
+        assert_eq!(hscroll_indicator(10, 10), "◂ 10/10  ");
+
+        let html = "<pre>0123456789</pre>";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 4, 1), 0);
+        let mut buf = Buffer::empty(Rect::new(3, 0, 4, 1));
+        render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+        let row = (3..7).map(|x| buf.get(x, 0).symbol()).collect::<String>();
+        assert_eq!(row, "3456");
+    }
+
+    #[test]
+    fn test_changes_screen() {
+        let press = key('j');
+        let mut release = key('j');
+        release.kind = KeyEventKind::Release;
+        assert!(changes_screen(&Event::Key(press)));
+        assert!(!changes_screen(&Event::Key(release)));
+        assert!(changes_screen(&Event::Resize(80, 24)));
+        assert!(!changes_screen(&Event::FocusGained));
+    }
+
+    #[test]
+    fn test_app() {
+        let mut app = App::new("about:blank", Options::default(), Settings::default()).unwrap();
+        app.loading = None;
+        app.current.page =
+            Page::from_html("about:test", "<p>hello</p>", &Settings::default()).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(20, 3)).unwrap();
+        terminal.draw(|frame| app.draw_frame(frame)).unwrap();
+        terminal.backend().assert_buffer(&Buffer::with_lines(vec![
+            format!("{:20}", "hello"),
+            " ".repeat(20),
+            " ".repeat(20),
+        ]));
+
+        app.dirty = false;
+        app.handle_event(Event::Key(key('v'))).unwrap();
+        assert!(app.dirty);
+        assert!(app.split.is_some());
+        app.handle_event(Event::Key(key('q'))).unwrap();
+        assert!(app.quit);
+
+        // Ctrl-C quits even while typing in the address bar
+        app.quit = false;
+        app.prompt = Some(Prompt::default());
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        app.handle_event(Event::Key(ctrl_c)).unwrap();
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_app_layout() {
+        let mut app = App::new("about:blank", Options::default(), Settings::default()).unwrap();
+        app.loading = None;
+        let html =
+            r#"<p>one</p><p>two</p><p>three</p><details><summary>more</summary>four</details>"#;
+        app.current.page = Page::from_html("about:test", html, &Settings::default()).unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(10, 2)).unwrap();
+        terminal.draw(|frame| app.draw_frame(frame)).unwrap();
+        assert!(app.view.is_some());
+
+        // scrolling and moving the focus draw the page as it was laid out
+        app.handle_event(Event::Key(key('j'))).unwrap();
+        app.handle_event(Event::Key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE)))
+            .unwrap();
+        assert!(app.view.is_some());
+        terminal.draw(|frame| app.draw_frame(frame)).unwrap();
+        let lines = |terminal: &Terminal<TestBackend>| {
+            let buf = terminal.backend().buffer();
+            (0..2)
+                .map(|y| (0..10).map(|x| buf.get(x, y).symbol()).collect::<String>())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(&terminal), vec!["three     ", "▸ more    "]);
+
+        // opening the details changes the page, which is laid out again
+        app.handle_event(Event::Key(KeyEvent::new(
+            KeyCode::Enter,
+            KeyModifiers::NONE,
+        )))
+        .unwrap();
+        assert!(app.view.is_none());
+        terminal.draw(|frame| app.draw_frame(frame)).unwrap();
+        assert_eq!(lines(&terminal), vec!["three     ", "▾ more    "]);
+        assert_eq!(app.content_height, 5);
+    }
+
+    #[test]
+    fn test_app_messages() {
+        let mut app = App::new("about:blank", Options::default(), Settings::default()).unwrap();
+        app.loading = None;
+        app.current.page =
+            Page::from_html("about:test", "<p>hello</p>", &Settings::default()).unwrap();
+        for c in [':', 'x', '\r'] {
+            let code = match c {
+                '\r' => KeyCode::Enter,
+                c => KeyCode::Char(c),
+            };
+            let key = KeyEvent::new(code, KeyModifiers::NONE);
+            app.handle_event(Event::Key(key)).unwrap();
+        }
+        let mut terminal = Terminal::new(TestBackend::new(20, 2)).unwrap();
+        terminal.draw(|frame| app.draw_frame(frame)).unwrap();
+        terminal.backend().assert_buffer(&Buffer::with_lines(vec![
+            format!("{:20}", "hello"),
+            format!("{:20}", "unknown command: x"),
+        ]));
+
+        // a key press takes the message off the status line, but not out of the history
+        app.handle_event(Event::Key(key('j'))).unwrap();
+        assert_eq!(app.messages.current(), None);
+        assert_eq!(
+            app.messages.to_page().dom().text_content(),
+            "Messagesunknown command: x"
+        );
+    }
+
+    #[test]
+    fn test_app_inline() {
+        let mut app = App::new("about:blank", Options::default(), Settings::default()).unwrap();
+        app.loading = None;
+        app.current.page =
+            Page::from_html("about:test", "<p>hello</p>", &Settings::default()).unwrap();
+        // an inline viewport starts below what is already on the terminal
+        let viewport = Viewport::Fixed(Rect::new(0, 2, 10, 2));
+        let mut terminal =
+            Terminal::with_options(TestBackend::new(10, 4), TerminalOptions { viewport }).unwrap();
+        terminal.draw(|frame| app.draw_frame(frame)).unwrap();
+        terminal.backend().assert_buffer(&Buffer::with_lines(vec![
+            " ".repeat(10),
+            " ".repeat(10),
+            format!("{:10}", "hello"),
+            " ".repeat(10),
+        ]));
+    }
+
+    #[test]
+    fn test_unauthorized_origin() {
+        let error = Error::Network(RequestError::Unauthorized {
+            origin: "https://example.com".into(),
+            realm: Some("private".into()),
+        });
+        assert_eq!(
+            unauthorized_origin(&error),
+            Some("https://example.com".into())
+        );
+        assert_eq!(
+            error.to_string(),
+            "https://example.com asks for a user name and password for \"private\""
+        );
+        let error = Error::Network(RequestError::Status(reqwest::StatusCode::FORBIDDEN));
+        assert_eq!(unauthorized_origin(&error), None);
+    }
+
+    #[test]
+    fn test_scroll_to() {
+        assert_eq!(scroll_to(0, &Action::ScrollLines(1), 100, 20), 1);
+        assert_eq!(scroll_to(0, &Action::ScrollLines(-1), 100, 20), 0);
+        assert_eq!(scroll_to(10, &Action::ScrollPages(1), 100, 20), 30);
+        assert_eq!(scroll_to(70, &Action::ScrollPages(1), 100, 20), 80);
+        assert_eq!(scroll_to(30, &Action::ScrollToTop, 100, 20), 0);
+        assert_eq!(scroll_to(0, &Action::ScrollToBottom, 100, 20), 80);
+        assert_eq!(scroll_to(0, &Action::ScrollToBottom, 10, 20), 0);
+    }
+
+    #[test]
+    fn test_describe_element() {
+        let html = r#"<div id="main" class="a b"><p>aaaa</p><p><a href="c.html">c</a></p></div>"#;
+        let page = Page::from_html("https://a.com/dir/", html, &Settings::default()).unwrap();
+        let node = to_styled_node(page.dom(), &page.stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 10, 5), 0);
+
+        let describe = |x, y| describe_element(&object, x, y, &page);
+        assert_eq!(describe(0, 0), Some("p".into()));
+        assert_eq!(describe(0, 1), Some("a https://a.com/dir/c.html".into()));
+        assert_eq!(describe(2, 1), Some("div#main.a.b".into()));
+        assert_eq!(describe(20, 20), None);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    cache::CacheMode,
+    dom::NodeType,
+    error::{Error, Result},
+    loader::{self, ContentKind, Resource},
+    page::{Page, Settings},
+    queue::Priority,
+    request, resources,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::task::JoinSet;
+use tracing::Instrument;
+
+/// The page of a saved directory, which opening the directory opens.
+pub const INDEX: &str = "index.html";
+/// The subdirectory of a saved directory that the stylesheets and images are stored in.
+const ASSETS: &str = "assets";
+
+/// `Saved` is what `save` stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Saved {
+    /// The saved page, to be opened offline.
+    pub path: PathBuf,
+    /// How many stylesheets and images were stored with it.
+    pub assets: usize,
+    /// The locations of the stylesheets and images that could not be fetched. The page
+    /// refers to them where they are, so they load when it is opened online.
+    pub failed: Vec<String>,
+}
+
+/// Returns the page to open for `path`: the page saved in it if it is a saved directory.
+pub fn index(path: &Path) -> PathBuf {
+    match path.is_dir() {
+        true => path.join(INDEX),
+        false => path.to_path_buf(),
+    }
+}
+
+/// Saves the page at `url` in `dir` with its stylesheets and images, so that it can be read
+/// without the network. The page is stored as `index.html`, with its references to them
+/// made relative and its links made absolute. A page that is not HTML is stored as it is.
+pub fn save(url: &str, dir: &Path, settings: &Settings) -> Result<Saved> {
+    request::runtime().block_on(fetch(url, dir, settings))
+}
+
+/// Saves `url` as `save` does, on the network runtime.
+pub async fn fetch(url: &str, dir: &Path, settings: &Settings) -> Result<Saved> {
+    store(url, dir, settings)
+        .instrument(tracing::info_span!("save", url))
+        .await
+}
+
+async fn store(url: &str, dir: &Path, settings: &Settings) -> Result<Saved> {
+    let mode = CacheMode::Normal;
+    let resource = loader::load(url, mode, Priority::Document, settings).await?;
+    fs::create_dir_all(dir)?;
+    if ContentKind::of(resource.content_type.as_deref()) != ContentKind::Html {
+        let path = dir.join(file_name(&resource.base_url));
+        fs::write(&path, &resource.body)?;
+        return Ok(Saved {
+            path,
+            assets: 0,
+            failed: vec![],
+        });
+    }
+    let mut page = Page::from_resource(&resource, None, settings)?;
+
+    let references = resources::collect(page.dom());
+    let mut fetches = JoinSet::new();
+    let mut tasks = HashMap::new();
+    let mut order = vec![];
+    let stylesheets = references
+        .stylesheets
+        .iter()
+        .map(|r| (r, Priority::Stylesheet));
+    let images = references.images.iter().map(|r| (r, Priority::Image));
+    for (reference, priority) in stylesheets.chain(images) {
+        if order.contains(reference) {
+            continue;
+        }
+        order.push(reference.clone());
+        let location = page.resolve(reference);
+        let task = fetches.spawn({
+            let (location, settings) = (location.clone(), settings.clone());
+            async move { loader::load(&location, mode, priority, &settings).await }
+        });
+        tasks.insert(task.id(), (reference.clone(), location));
+    }
+
+    let mut fetched = HashMap::new();
+    while let Some(joined) = fetches.join_next_with_id().await {
+        let (id, resource) = match joined {
+            Ok((id, resource)) => (id, resource),
+            // a fetch that panicked fails like one that could not be made
+            Err(e) => (e.id(), Err(Error::Io(e.into()))),
+        };
+        let (reference, location) = tasks.remove(&id).expect("every fetch is a task");
+        fetched.insert(reference, (location, resource));
+    }
+
+    // the files are named in document order, whichever arrived first
+    let mut saved = Saved {
+        path: dir.join(INDEX),
+        assets: 0,
+        failed: vec![],
+    };
+    let mut names = HashSet::new();
+    let mut local = HashMap::new();
+    for reference in order {
+        let Some((location, resource)) = fetched.remove(&reference) else {
+            continue;
+        };
+        let Resource { base_url, body, .. } = match resource {
+            Ok(resource) => resource,
+            Err(e) => {
+                tracing::warn!(url = location, "not saving a subresource: {}", e);
+                saved.failed.push(location.clone());
+                local.insert(reference, location);
+                continue;
+            }
+        };
+        fs::create_dir_all(dir.join(ASSETS))?;
+        let name = unique_name(&file_name(&base_url), &mut names);
+        fs::write(dir.join(ASSETS).join(&name), body)?;
+        saved.assets += 1;
+        local.insert(reference, format!("{}/{}", ASSETS, name));
+    }
+
+    rewrite(&mut page, &local);
+    fs::write(&saved.path, page.dom().to_html())?;
+    Ok(saved)
+}
+
+/// Points the references of `page` to its stylesheets and images at `local`, where they
+/// were saved by the reference, and makes its links absolute, as they are not saved.
+fn rewrite(page: &mut Page, local: &HashMap<String, String>) {
+    let selector = "link[href], img[src], a[href], area[href], form[action]";
+    for path in page.document().query_paths(selector) {
+        let Some(node) = page.dom().node_at(&path) else {
+            continue;
+        };
+        let Some(element) = node.element() else {
+            continue;
+        };
+        let name = match element.tag_name.as_str() {
+            "img" => "src",
+            "form" => "action",
+            _ => "href",
+        };
+        let value = &element.attributes[name];
+        let rewritten = match local.get(value) {
+            Some(local) => local.clone(),
+            // a link within the page stays one
+            None if value.starts_with('#') || value.is_empty() => continue,
+            None => page.resolve(value),
+        };
+        if let Some(NodeType::Element(e)) = page
+            .dom_mut()
+            .node_at_mut(&path)
+            .map(|node| &mut node.node_type)
+        {
+            e.attributes.insert(name.into(), rewritten);
+        }
+    }
+}
+
+/// Names the file `url` is saved as after the last segment of its path, with only the
+/// characters that are safe in a file name on every system.
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let segment = path.trim_end_matches('/').rsplit(['/', '\\']).next();
+    let name = sanitize(segment.unwrap_or_default());
+    match name.trim_matches('.') {
+        "" => "asset".into(),
+        name => name.into(),
+    }
+}
+
+/// Replaces the characters of `name` that are not safe in a file name on every system with `_`.
+pub fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "._-".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+/// Returns `name`, or `name` numbered before its extension if it is in `names`, and adds
+/// it to them.
+pub fn unique_name(name: &str, names: &mut HashSet<String>) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    let mut unique = name.to_string();
+    let mut n = 1;
+    while names.contains(&unique) {
+        n += 1;
+        unique = format!("{}-{}{}", stem, n, extension);
+    }
+    names.insert(unique.clone());
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_name, index, save, unique_name, INDEX};
+    use crate::page::Settings;
+    use std::{collections::HashSet, fs};
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("https://a.com/css/main.css?v=2"), "main.css");
+        assert_eq!(file_name("https://a.com/"), "a.com");
+        assert_eq!(file_name("https://a.com/a%20b.png#x"), "a_20b.png");
+        assert_eq!(file_name("/tmp/dir/.."), "asset");
+
+        let mut names = HashSet::new();
+        assert_eq!(unique_name("a.png", &mut names), "a.png");
+        assert_eq!(unique_name("a.png", &mut names), "a-2.png");
+        assert_eq!(unique_name("a.png", &mut names), "a-3.png");
+        assert_eq!(unique_name("a", &mut names), "a");
+        assert_eq!(unique_name("a", &mut names), "a-2");
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("wev-archive-{}", std::process::id()));
+        let (site, saved) = (dir.join("site"), dir.join("saved"));
+        fs::create_dir_all(site.join("img")).unwrap();
+        fs::write(site.join("a.css"), "p { color: red; }").unwrap();
+        fs::write(site.join("img").join("a.css"), "p { color: blue; }").unwrap();
+        fs::write(site.join("img").join("cat.png"), "not really a png").unwrap();
+        let html = r##"<link rel="stylesheet" href="a.css"><link rel="stylesheet" href="img/a.css"><img src="img/cat.png"><img src="missing.png"><a href="other.html">other</a><a href="#top">top</a>"##;
+        fs::write(site.join("page.html"), html).unwrap();
+
+        let path = site.join("page.html");
+        let result = save(&path.to_string_lossy(), &saved, &Settings::default()).unwrap();
+        let page = fs::read_to_string(saved.join(INDEX)).unwrap();
+        let asset = |name| fs::read_to_string(saved.join("assets").join(name)).unwrap();
+        let (red, blue, cat) = (asset("a.css"), asset("a-2.css"), asset("cat.png"));
+        let index = index(&saved);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result.path, saved.join(INDEX));
+        assert_eq!(index, saved.join(INDEX));
+        assert_eq!(result.assets, 3);
+        let missing = site.join("missing.png").to_string_lossy().into_owned();
+        assert_eq!(result.failed, vec![missing.clone()]);
+        assert_eq!(
+            (red.as_str(), blue.as_str()),
+            ("p { color: red; }", "p { color: blue; }")
+        );
+        assert_eq!(cat, "not really a png");
+        let other = site.join("other.html").to_string_lossy().into_owned();
+        assert_eq!(
+            page,
+            format!(
+                r##"<link href="assets/a.css" rel="stylesheet"><link href="assets/a-2.css" rel="stylesheet"><img src="assets/cat.png"><img src="{}"><a href="{}">other</a><a href="#top">top</a>"##,
+                missing, other
+            )
+        );
+    }
+}
@@ -0,0 +1,683 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{
+    header::{HeaderMap, WWW_AUTHENTICATE},
+    Method,
+};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+/// `Credentials` are a user name and password to sign in to a host with.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Credentials {
+    pub user: String,
+    pub password: String,
+}
+
+/// `Algorithm` is how a digest is made of the credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Md5,
+    /// MD5 over a session key, which is made once of the credentials and the nonces.
+    Md5Sess,
+}
+
+/// `Digest` is what a server asking for Digest authentication (RFC 7616) gives to answer with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub algorithm: Algorithm,
+    /// Whether the server takes `qop=auth`, which guards against replayed answers.
+    pub qop_auth: bool,
+    /// Whether the credentials were right, but the nonce they were sent with has expired.
+    pub stale: bool,
+}
+
+/// `Challenge` is a way a server asks to be signed in to, in `WWW-Authenticate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Challenge {
+    Basic { realm: Option<String> },
+    Digest(Digest),
+}
+
+impl Challenge {
+    pub fn realm(&self) -> Option<&str> {
+        match self {
+            Challenge::Basic { realm } => realm.as_deref(),
+            Challenge::Digest(digest) => Some(&digest.realm),
+        }
+    }
+
+    fn is_stale(&self) -> bool {
+        matches!(self, Challenge::Digest(digest) if digest.stale)
+    }
+}
+
+/// Splits `value` at the commas that are not in quoted strings.
+fn split_list(value: &str) -> Vec<&str> {
+    let mut items = vec![];
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items
+}
+
+/// Returns the value of a parameter as it is written, taking quoted strings out of their quotes.
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(quoted) => {
+            let mut unquoted = String::new();
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                unquoted.push(if c == '\\' {
+                    chars.next().unwrap_or(c)
+                } else {
+                    c
+                });
+            }
+            unquoted
+        }
+        None => value.to_string(),
+    }
+}
+
+/// Makes a challenge of the `scheme` and the `params` a server gave, if it can be answered.
+fn challenge(scheme: &str, params: &HashMap<String, String>) -> Option<Challenge> {
+    match scheme.to_ascii_lowercase().as_str() {
+        "basic" => Some(Challenge::Basic {
+            realm: params.get("realm").cloned(),
+        }),
+        "digest" => {
+            let algorithm = match params.get("algorithm").map(|a| a.to_ascii_lowercase()) {
+                None => Algorithm::Md5,
+                Some(a) if a == "md5" => Algorithm::Md5,
+                Some(a) if a == "md5-sess" => Algorithm::Md5Sess,
+                // SHA-256 and the like are not made here
+                Some(_) => return None,
+            };
+            let qop = params.get("qop");
+            let qop_auth = qop.is_some_and(|qop| qop.split(',').any(|q| q.trim() == "auth"));
+            if qop.is_some() && !qop_auth {
+                // auth-int digests the body as well, which is not done here
+                return None;
+            }
+            Some(Challenge::Digest(Digest {
+                realm: params.get("realm").cloned().unwrap_or_default(),
+                nonce: params.get("nonce")?.clone(),
+                opaque: params.get("opaque").cloned(),
+                algorithm,
+                qop_auth,
+                stale: params
+                    .get("stale")
+                    .is_some_and(|stale| stale.eq_ignore_ascii_case("true")),
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the challenges of the `WWW-Authenticate` headers in `headers` that can be answered,
+/// Digest ones first since they do not give the password away.
+pub fn challenges(headers: &HeaderMap) -> Vec<Challenge> {
+    let mut parsed = vec![];
+    let mut current: Option<(String, HashMap<String, String>)> = None;
+    let items = headers
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(split_list);
+    for item in items {
+        let item = item.trim();
+        // an item starting with a name that is not a parameter starts another challenge
+        let (first, rest) = item.split_once(char::is_whitespace).unwrap_or((item, ""));
+        let param = if first.is_empty() || first.contains('=') {
+            item
+        } else {
+            parsed.extend(current.take());
+            current = Some((first.to_string(), HashMap::new()));
+            rest.trim()
+        };
+        if let (Some((name, value)), Some((_, params))) = (param.split_once('='), &mut current) {
+            params.insert(name.trim().to_ascii_lowercase(), unquote(value));
+        }
+    }
+    parsed.extend(current);
+    let mut challenges = parsed
+        .iter()
+        .filter_map(|(scheme, params)| challenge(scheme, params))
+        .collect::<Vec<_>>();
+    challenges.sort_by_key(|challenge| matches!(challenge, Challenge::Basic { .. }));
+    challenges
+}
+
+/// Returns the MD5 digest of `data` (RFC 1321), which Digest authentication is made of.
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for block in message.chunks(64) {
+        let words: Vec<u32> = block
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i / 16 * 4 + i % 4]);
+            (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+        }
+        for (word, added) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(added);
+        }
+    }
+    let mut digest = [0; 16];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn md5_hex(data: &str) -> String {
+    md5(data.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns the `Authorization` header that answers `challenge` with `credentials` for a `method`
+/// request of `uri`, its path and query. A Digest answer counts the requests made with the same
+/// nonce, `count`, and is made with the client's own nonce, `cnonce`.
+pub fn authorization(
+    challenge: &Challenge,
+    credentials: &Credentials,
+    method: &Method,
+    uri: &str,
+    count: u32,
+    cnonce: &str,
+) -> String {
+    let digest = match challenge {
+        Challenge::Basic { .. } => {
+            let pair = format!("{}:{}", credentials.user, credentials.password);
+            return format!("Basic {}", STANDARD.encode(pair));
+        }
+        Challenge::Digest(digest) => digest,
+    };
+    let mut secret = md5_hex(&format!(
+        "{}:{}:{}",
+        credentials.user, digest.realm, credentials.password
+    ));
+    if digest.algorithm == Algorithm::Md5Sess {
+        secret = md5_hex(&format!("{}:{}:{}", secret, digest.nonce, cnonce));
+    }
+    let request = md5_hex(&format!("{}:{}", method, uri));
+    let count = format!("{:08x}", count);
+    let response = if digest.qop_auth {
+        md5_hex(&format!(
+            "{}:{}:{}:{}:auth:{}",
+            secret, digest.nonce, count, cnonce, request
+        ))
+    } else {
+        md5_hex(&format!("{}:{}:{}", secret, digest.nonce, request))
+    };
+    let quote = |value: &str| format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut header = format!(
+        "Digest username={}, realm={}, nonce={}, uri={}, response=\"{}\"",
+        quote(&credentials.user),
+        quote(&digest.realm),
+        quote(&digest.nonce),
+        quote(uri),
+        response
+    );
+    if digest.algorithm == Algorithm::Md5Sess {
+        header += ", algorithm=MD5-sess";
+    }
+    if let Some(opaque) = &digest.opaque {
+        header += &format!(", opaque={}", quote(opaque));
+    }
+    if digest.qop_auth {
+        header += &format!(", qop=auth, nc={}, cnonce=\"{}\"", count, cnonce);
+    }
+    header
+}
+
+/// `Netrc` is a `.netrc` file, where the credentials of hosts are kept for programs like curl.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Netrc {
+    machines: Vec<(Option<String>, Credentials)>,
+}
+
+impl Netrc {
+    /// Parses `content`: `machine`, `login` and `password` entries, and a `default` for the
+    /// hosts without one. Macros and accounts are skipped.
+    pub fn parse(content: &str) -> Netrc {
+        let mut machines = vec![];
+        let mut current: Option<(Option<String>, Credentials)> = None;
+        let mut tokens = content.split_whitespace();
+        while let Some(token) = tokens.next() {
+            match token {
+                "machine" | "default" => {
+                    machines.extend(current.take());
+                    let host = match token {
+                        "machine" => Some(tokens.next().unwrap_or_default().to_string()),
+                        _ => None,
+                    };
+                    let credentials = Credentials {
+                        user: String::new(),
+                        password: String::new(),
+                    };
+                    current = Some((host, credentials));
+                }
+                "login" | "password" | "account" => {
+                    let value = tokens.next().unwrap_or_default().to_string();
+                    match (token, &mut current) {
+                        ("login", Some((_, credentials))) => credentials.user = value,
+                        ("password", Some((_, credentials))) => credentials.password = value,
+                        _ => {}
+                    }
+                }
+                "macdef" => {
+                    machines.extend(current.take());
+                    // a macro runs to the next empty line, which whitespace splitting loses
+                    break;
+                }
+                _ => {}
+            }
+        }
+        machines.extend(current);
+        Netrc { machines }
+    }
+
+    /// Returns where the file is: `$NETRC`, or `~/.netrc`.
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("NETRC")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".netrc")))
+    }
+
+    /// Reads the file, if there is one.
+    pub fn load() -> Option<Netrc> {
+        let content = fs::read_to_string(Netrc::default_path()?).ok()?;
+        Some(Netrc::parse(&content))
+    }
+
+    /// Returns the credentials for `host`, or the default ones.
+    pub fn credentials(&self, host: &str) -> Option<&Credentials> {
+        let machine = self
+            .machines
+            .iter()
+            .find(|(name, _)| name.as_deref() == Some(host))
+            .or_else(|| self.machines.iter().find(|(name, _)| name.is_none()));
+        machine.map(|(_, credentials)| credentials)
+    }
+}
+
+/// `Session` is what is known of signing in to an origin.
+#[derive(Debug, Default)]
+struct Session {
+    /// The credentials the user typed for the origin.
+    credentials: Option<Credentials>,
+    /// The last challenge of the origin, which later requests to it answer without being asked.
+    challenge: Option<Challenge>,
+    /// How many requests have answered the nonce of a Digest challenge.
+    count: u32,
+}
+
+/// `Authenticator` answers the challenges of origins with credentials the user typed,
+/// set in the config file for their host, or kept in `.netrc`, in that order.
+/// Credentials that were typed are remembered until the program exits.
+/// Nothing is sent to an origin before it asks, and an origin is its scheme, host and port,
+/// so what is learned over https is never sent over plain http or to another port.
+#[derive(Debug, Default)]
+pub struct Authenticator {
+    configured: BTreeMap<String, Credentials>,
+    netrc: Option<Netrc>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+/// Returns the origin of `url`, its scheme, host and port, which sessions are kept by.
+pub fn origin(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
+
+fn host(url: &Url) -> String {
+    url.host_str().unwrap_or_default().to_ascii_lowercase()
+}
+
+/// Returns a nonce of this client's own, which differs between calls.
+fn cnonce() -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    md5_hex(&format!("{:?}:{}", time, std::process::id()))[..16].to_string()
+}
+
+impl Authenticator {
+    pub fn new(configured: BTreeMap<String, Credentials>, netrc: Option<Netrc>) -> Authenticator {
+        let configured = configured
+            .into_iter()
+            .map(|(host, credentials)| (host.to_ascii_lowercase(), credentials))
+            .collect();
+        Authenticator {
+            configured,
+            netrc,
+            sessions: Mutex::default(),
+        }
+    }
+
+    /// Returns the credentials to answer `challenge` of the origin of `url` with.
+    /// Basic credentials are the password itself, so over plain http they are only those
+    /// the user typed for the origin, agreeing to send them there.
+    fn credentials(
+        &self,
+        url: &Url,
+        session: &Session,
+        challenge: &Challenge,
+    ) -> Option<Credentials> {
+        let typed = session.credentials.clone();
+        if url.scheme() != "https" && matches!(challenge, Challenge::Basic { .. }) {
+            return typed;
+        }
+        let host = host(url);
+        typed
+            .or_else(|| self.configured.get(&host).cloned())
+            .or_else(|| self.netrc.as_ref()?.credentials(&host).cloned())
+    }
+
+    /// Signs in to `origin`, as `origin` returns it, with `credentials` for the rest of the
+    /// session.
+    pub fn remember(&self, origin: &str, credentials: Credentials) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.entry(origin.to_string()).or_default().credentials = Some(credentials);
+    }
+
+    /// Returns the `Authorization` header for a `method` request of `url`, once its origin
+    /// has asked to be signed in to.
+    pub fn authorization(&self, method: &Method, url: &Url) -> Option<String> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let session = sessions.get_mut(&origin(url))?;
+        let challenge = session.challenge.clone()?;
+        let credentials = self.credentials(url, session, &challenge)?;
+        session.count += 1;
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        Some(authorization(
+            &challenge,
+            &credentials,
+            method,
+            &uri,
+            session.count,
+            &cnonce(),
+        ))
+    }
+
+    /// Takes in the challenges of a `401 Unauthorized` response to a request of `url`,
+    /// which was `authorized` or not, and returns whether to make the request again.
+    /// Typed credentials that were refused are forgotten, so that the user is asked again.
+    pub fn challenged(&self, url: &Url, headers: &HeaderMap, authorized: bool) -> bool {
+        let Some(challenge) = challenges(headers).into_iter().next() else {
+            return false;
+        };
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let session = sessions.entry(origin(url)).or_default();
+        let retry = if authorized && !challenge.is_stale() {
+            session.credentials = None;
+            false
+        } else {
+            self.credentials(url, session, &challenge).is_some()
+        };
+        if session.challenge.as_ref() != Some(&challenge) {
+            session.count = 0;
+        }
+        session.challenge = Some(challenge);
+        retry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        authorization, challenges, md5_hex, origin, Algorithm, Authenticator, Challenge,
+        Credentials, Digest, Netrc,
+    };
+    use reqwest::{
+        header::{HeaderMap, HeaderValue, WWW_AUTHENTICATE},
+        Method,
+    };
+    use std::collections::BTreeMap;
+    use url::Url;
+
+    fn credentials(user: &str, password: &str) -> Credentials {
+        Credentials {
+            user: user.into(),
+            password: password.into(),
+        }
+    }
+
+    fn www_authenticate(values: &[&'static str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(WWW_AUTHENTICATE, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_challenges() {
+        let digest = Digest {
+            realm: "a, \"b\"".into(),
+            nonce: "n".into(),
+            opaque: None,
+            algorithm: Algorithm::Md5Sess,
+            qop_auth: true,
+            stale: true,
+        };
+        assert_eq!(
+            challenges(&www_authenticate(&[
+                "Basic realm=\"files\", Digest realm=\"a, \\\"b\\\"\", nonce=n, qop=\"auth,auth-int\", algorithm=MD5-sess, stale=TRUE",
+                "Negotiate abc==",
+            ])),
+            vec![
+                Challenge::Digest(digest),
+                Challenge::Basic {
+                    realm: Some("files".into())
+                }
+            ]
+        );
+        assert_eq!(
+            challenges(&www_authenticate(&[
+                "Digest realm=x, nonce=n, algorithm=SHA-256",
+                "Digest realm=x, nonce=n, qop=auth-int",
+                "Bearer",
+            ])),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        assert_eq!(md5_hex(""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(
+            md5_hex(&"a".repeat(100)),
+            "36a92cc94a9e0fa21f625f8bfb007adf"
+        );
+        let basic = Challenge::Basic { realm: None };
+        assert_eq!(
+            authorization(
+                &basic,
+                &credentials("Aladdin", "open sesame"),
+                &Method::GET,
+                "/",
+                1,
+                ""
+            ),
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+        // the example of RFC 2617
+        let digest = Challenge::Digest(Digest {
+            realm: "testrealm@host.com".into(),
+            nonce: "dcd98b7102dd2f0e8b11d0f600bfb0c093".into(),
+            opaque: Some("5ccc069c403ebaf9f0171e9517f40e41".into()),
+            algorithm: Algorithm::Md5,
+            qop_auth: true,
+            stale: false,
+        });
+        assert_eq!(
+            authorization(
+                &digest,
+                &credentials("Mufasa", "Circle Of Life"),
+                &Method::GET,
+                "/dir/index.html",
+                1,
+                "0a4f113b"
+            ),
+            "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/dir/index.html\", \
+             response=\"6629fae49393a05397450978507c4ef1\", \
+             opaque=\"5ccc069c403ebaf9f0171e9517f40e41\", qop=auth, nc=00000001, \
+             cnonce=\"0a4f113b\""
+        );
+    }
+
+    #[test]
+    fn test_netrc() {
+        let netrc = Netrc::parse(
+            "machine example.com login alice password secret\n\
+             machine other.com\n  login bob\n  account x\n  password hunter2\n\
+             default login anonymous password guest\n",
+        );
+        assert_eq!(
+            netrc.credentials("example.com"),
+            Some(&credentials("alice", "secret"))
+        );
+        assert_eq!(
+            netrc.credentials("other.com"),
+            Some(&credentials("bob", "hunter2"))
+        );
+        assert_eq!(
+            netrc.credentials("unknown.com"),
+            Some(&credentials("anonymous", "guest"))
+        );
+        assert_eq!(Netrc::parse("").credentials("example.com"), None);
+    }
+
+    #[test]
+    fn test_authenticator() {
+        let url = Url::parse("https://Example.com/private?page=1").unwrap();
+        let basic = www_authenticate(&["Basic realm=\"private\""]);
+        let authenticator = Authenticator::default();
+        assert_eq!(authenticator.authorization(&Method::GET, &url), None);
+        // without credentials, the user is to be asked for them
+        assert!(!authenticator.challenged(&url, &basic, false));
+        authenticator.remember(&origin(&url), credentials("Aladdin", "open sesame"));
+        assert_eq!(
+            authenticator.authorization(&Method::GET, &url).as_deref(),
+            Some("Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")
+        );
+        // refused credentials are forgotten
+        assert!(!authenticator.challenged(&url, &basic, true));
+        assert_eq!(authenticator.authorization(&Method::GET, &url), None);
+
+        let configured = BTreeMap::from([("EXAMPLE.com".into(), credentials("Mufasa", "x"))]);
+        let authenticator = Authenticator::new(configured, None);
+        let digest = www_authenticate(&["Digest realm=\"r\", nonce=\"1\", qop=\"auth\""]);
+        assert!(authenticator.challenged(&url, &digest, false));
+        let header = authenticator.authorization(&Method::GET, &url).unwrap();
+        assert!(header.starts_with("Digest username=\"Mufasa\""));
+        assert!(header.contains("uri=\"/private?page=1\""));
+        assert!(header.contains("nc=00000001"));
+        assert!(authenticator
+            .authorization(&Method::GET, &url)
+            .unwrap()
+            .contains("nc=00000002"));
+        // an expired nonce is answered again with the same credentials
+        let stale = www_authenticate(&["Digest realm=\"r\", nonce=\"2\", qop=auth, stale=true"]);
+        assert!(authenticator.challenged(&url, &stale, true));
+        assert!(authenticator
+            .authorization(&Method::GET, &url)
+            .unwrap()
+            .contains("nc=00000001"));
+        assert!(!authenticator.challenged(&url, &digest, true));
+    }
+
+    #[test]
+    fn test_origins() {
+        let https = Url::parse("https://example.com/private").unwrap();
+        let basic = www_authenticate(&["Basic realm=\"private\""]);
+        let configured = BTreeMap::from([("example.com".into(), credentials("alice", "x"))]);
+        let authenticator = Authenticator::new(configured, None);
+        assert_eq!(origin(&https), "https://example.com");
+        // nothing is sent before the origin asks
+        assert_eq!(authenticator.authorization(&Method::GET, &https), None);
+        assert!(authenticator.challenged(&https, &basic, false));
+        assert!(authenticator.authorization(&Method::GET, &https).is_some());
+
+        // what https was answered with is not sent over http, or to another port
+        for other in [
+            "http://example.com/private",
+            "https://example.com:8443/private",
+        ] {
+            let other = Url::parse(other).unwrap();
+            assert_eq!(authenticator.authorization(&Method::GET, &other), None);
+        }
+
+        // over http, Basic credentials are only those the user typed for it
+        let http = Url::parse("http://example.com/private").unwrap();
+        assert!(!authenticator.challenged(&http, &basic, false));
+        assert_eq!(authenticator.authorization(&Method::GET, &http), None);
+        authenticator.remember(&origin(&http), credentials("bob", "y"));
+        assert_eq!(
+            authenticator.authorization(&Method::GET, &http).as_deref(),
+            Some("Basic Ym9iOnk=")
+        );
+        // Digest does not give the password away, so the configured credentials answer it
+        let http = Url::parse("http://example.com:8080/").unwrap();
+        let digest = www_authenticate(&["Digest realm=\"r\", nonce=\"1\""]);
+        assert!(authenticator.challenged(&http, &digest, false));
+        assert!(authenticator
+            .authorization(&Method::GET, &http)
+            .unwrap()
+            .starts_with("Digest username=\"alice\""));
+    }
+}
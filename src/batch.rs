@@ -0,0 +1,174 @@
+use crate::{
+    archive::{sanitize, unique_name},
+    error::{Error, Result},
+    export::{export, Format},
+    loader::url_from_input,
+    page::{Page, Settings},
+    request,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use tokio::task::JoinSet;
+
+/// The longest a file name made of a URL may be, before its extension.
+const MAX_NAME_LENGTH: usize = 100;
+
+/// `Status` is how dumping a page of a batch went.
+#[derive(Debug)]
+pub enum Status {
+    /// The page was written to `path`.
+    Saved { url: String, path: PathBuf },
+    /// The page failed to load, or to be written.
+    Failed { url: String, error: Error },
+}
+
+/// Returns the pages `list` names, one on each line as they are typed on the command line.
+/// Blank lines and lines starting with `#` are skipped.
+pub fn parse_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(url_from_input)
+        .collect()
+}
+
+/// Names the file the page at `url` is dumped to after its host and path, with only the
+/// characters that are safe in a file name on every system.
+pub fn file_name(url: &str, format: Format) -> String {
+    let location = url.split('#').next().unwrap_or_default();
+    let location = ["https://", "http://"]
+        .iter()
+        .find_map(|scheme| location.strip_prefix(scheme))
+        .unwrap_or(location);
+    let name = sanitize(location.trim_matches('/'))
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+    let name = match name.trim_matches(['.', '_']) {
+        "" => "page",
+        name => name,
+    };
+    let extension = match format {
+        Format::Json => "json",
+        Format::Plain | Format::Ansi | Format::Linear => "txt",
+    };
+    format!("{}.{}", name, extension)
+}
+
+/// Loads `urls` at the same time and dumps each, `width` columns wide, to a file of its own
+/// in `dir`, as `wev dump` writes it, loading them with `settings`. A page the server answers with an error status is
+/// written as the page explaining it, unless `fail` is set.
+/// `report` is called with how each went as it finishes.
+pub fn dump(
+    urls: &[String],
+    dir: &Path,
+    width: u16,
+    format: Format,
+    fail: bool,
+    settings: &Settings,
+    mut report: impl FnMut(&Status),
+) -> Result<Vec<Status>> {
+    fs::create_dir_all(dir)?;
+    // the names are given up front, so that they do not depend on which page loads first
+    let mut names = HashSet::new();
+    let paths = urls
+        .iter()
+        .map(|url| dir.join(unique_name(&file_name(url, format), &mut names)))
+        .collect::<Vec<_>>();
+    request::runtime().block_on(async {
+        let mut fetches = JoinSet::new();
+        let mut tasks = HashMap::new();
+        for (i, url) in urls.iter().enumerate() {
+            let (url, settings) = (url.clone(), settings.clone());
+            let task = fetches.spawn(async move {
+                match Page::fetch(&url, &settings).await {
+                    Err(e) if !fail && e.status().is_some() => Ok(Page::error(&url, &e)),
+                    page => page,
+                }
+            });
+            tasks.insert(task.id(), i);
+        }
+        let mut statuses = Vec::with_capacity(urls.len());
+        while let Some(joined) = fetches.join_next_with_id().await {
+            let (i, page) = match joined {
+                Ok((id, page)) => (tasks[&id], page),
+                // a page whose task panicked is not left out, but reported as failed
+                Err(e) => (tasks[&e.id()], Err(Error::Io(e.into()))),
+            };
+            let url = urls[i].clone();
+            let written = page.and_then(|page| {
+                fs::write(&paths[i], export(&page, width, format))?;
+                Ok(())
+            });
+            let status = match written {
+                Ok(()) => Status::Saved {
+                    url,
+                    path: paths[i].clone(),
+                },
+                Err(error) => Status::Failed { url, error },
+            };
+            report(&status);
+            statuses.push(status);
+        }
+        Ok(statuses)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump, file_name, parse_list, Status};
+    use crate::{export::Format, page::Settings};
+    use std::fs;
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list("# reading list\nexample.com/a\n\n  https://b.org/  \n./c.html\n"),
+            ["https://example.com/a", "https://b.org/", "./c.html"]
+        );
+        assert_eq!(
+            file_name("https://example.com/docs/intro?x=1#top", Format::Plain),
+            "example.com_docs_intro_x_1.txt"
+        );
+        assert_eq!(file_name("https://b.org/", Format::Json), "b.org.json");
+        assert_eq!(file_name("/tmp/c.html", Format::Linear), "tmp_c.html.txt");
+    }
+
+    #[test]
+    fn test_dump() {
+        let dir = std::env::temp_dir().join(format!("wev-batch-{}", std::process::id()));
+        let (pages, out) = (dir.join("pages"), dir.join("out"));
+        fs::create_dir_all(&pages).unwrap();
+        fs::write(pages.join("a.html"), "<p>first</p>").unwrap();
+        fs::write(pages.join("b.html"), "<p>second</p>").unwrap();
+        let urls = ["a.html", "b.html", "missing.html", "a.html"]
+            .map(|name| pages.join(name).to_string_lossy().into_owned());
+
+        let mut reported = 0;
+        let settings = Settings::default();
+        let statuses = dump(&urls, &out, 20, Format::Plain, false, &settings, |_| {
+            reported += 1
+        })
+        .unwrap();
+        let mut written = fs::read_dir(&out)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        written.sort();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(reported, 4);
+        let failed = statuses
+            .iter()
+            .filter_map(|status| match status {
+                Status::Failed { url, .. } => Some(url),
+                Status::Saved { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(failed, [&urls[2]]);
+        assert_eq!(written, ["first\n", "first\n", "second\n"]);
+    }
+}
@@ -1,53 +1,546 @@
-use combine::Parser;
-use crossterm::terminal;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
 use ratatui::layout::Rect;
-use std::{env, io::Result};
-use wev::{css, dom::Node, html, layout::node_to_object, style::to_styled_node};
-
-fn main() -> Result<()> {
-    let args = env::args().collect::<Vec<_>>();
-    let content = match args[1].as_str() {
-        "-w" => wev::request::html_from_www(&args[2]).unwrap(),
-        "-l" => wev::request::html_from_local(&args[2]).unwrap(),
-        _ => panic!("argument `{}` is not supported", args[1]),
-    };
-    let content = content
-        .chars()
-        .map(|c| if c == '\n' { ' ' } else { c })
-        .collect::<String>();
-    let node = html::html().parse(content.as_str()).unwrap().0;
-
-    let root_node = Box::new(Node {
-        node_type: wev::dom::NodeType::Element(wev::dom::Element {
-            tag_name: "".into(),
-            attributes: vec![].into_iter().collect(),
-        }),
-        children: node,
-    });
-
-    let style_tag = wev::cssom::SimpleSelector::TypeSelector {
-        tag_name: "style".into(),
+#[cfg(feature = "tui")]
+use std::io::Write;
+use std::{
+    fmt, fs, io,
+    path::Path,
+    process::ExitCode,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::Level;
+#[cfg(feature = "tui")]
+use wev::{app::Options, repl::Repl};
+use wev::{
+    archive,
+    batch::{self, Status},
+    config::Config,
+    export::{export, Format},
+    layout::content_area,
+    loader::url_from_input,
+    logging::Logger,
+    Browser, Error, Page, Result,
+};
+
+/// The exit status when the page could not be fetched: there was no connection, it timed out,
+/// it was not in the cache while offline or a filter blocked it.
+const EXIT_NETWORK: u8 = 3;
+/// The exit status when the server answered with an error status, with `--fail`.
+const EXIT_HTTP: u8 = 4;
+/// The exit status when the page could not be parsed or decoded, or cannot be shown.
+const EXIT_CONTENT: u8 = 5;
+
+/// Whether `--quiet` was given, which leaves out every message but the page.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// How many milliseconds `--polite` leaves at least between the requests to a host.
+const POLITE_HOST_DELAY: u64 = 1000;
+
+/// The page to open: a URL, a `file://` URL, a path or a bare domain.
+fn page_arg() -> Arg {
+    Arg::new("page")
+        .value_name("URL|PATH")
+        .help("The page to open: a URL, a path or a bare domain")
+        .required(true)
+}
+
+/// The width to write pages at.
+fn width_arg() -> Arg {
+    Arg::new("width")
+        .long("width")
+        .value_name("N")
+        .value_parser(value_parser!(u16))
+        .help("The width to render the page at, instead of the terminal's")
+}
+
+/// The flags that write a page as something other than plain text.
+fn format_args() -> [Arg; 3] {
+    [
+        Arg::new("ansi")
+            .long("ansi")
+            .action(ArgAction::SetTrue)
+            .help("Write colors and text modifiers as ANSI escape sequences"),
+        Arg::new("json")
+            .long("json")
+            .action(ArgAction::SetTrue)
+            .conflicts_with("ansi")
+            .help("Write the DOM tree as JSON instead of how the page is rendered"),
+        Arg::new("linear")
+            .long("linear")
+            .action(ArgAction::SetTrue)
+            .conflicts_with_all(["ansi", "json"])
+            .help("Write the page a line at a time for screen readers, with headings, links, images, lists and tables described"),
+    ]
+}
+
+/// Describes the command line: `wev <url|path>` to browse, or `wev dump <url|path>` to
+/// write a page out. The flags for requests and styles go with either, and override the
+/// config file. Without the `tui` feature, there is no `repl`, and pages cannot be browsed.
+fn command() -> Command {
+    let number = |name: &'static str, help: &'static str| {
+        Arg::new(name)
+            .long(name)
+            .value_name("N")
+            .value_parser(value_parser!(u16))
+            .help(help)
     };
-    let css = wev::dom::select(&root_node, &style_tag);
-
-    let css = css
-        .first()
-        .and_then(|n| n.children.first())
-        .and_then(|style| style.to_text())
-        .unwrap_or_default();
-    let stylesheet = css::stylesheet(&css);
-    let nodes = to_styled_node(&root_node, &stylesheet);
-    let (width, height) = terminal::size()?;
-    let object = node_to_object(
-        nodes.as_ref().unwrap(),
-        Rect {
-            x: 0,
-            y: 0,
-            width,
-            height,
-        },
-        0,
+    let command = Command::new("wev")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("A web browser in the terminal")
+        .after_help(
+            "Exit status:\n  \
+            0  Success\n  \
+            1  Any other failure, like a file that cannot be written\n  \
+            2  The command line is not valid\n  \
+            3  The page could not be fetched: no connection, a timeout, offline or blocked\n  \
+            4  The server answered with an error status, with --fail\n  \
+            5  The page could not be parsed or decoded, or cannot be shown",
+        )
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .arg(page_arg())
+        .arg(number(
+            "inline",
+            "Show pages in a viewport N rows high below the prompt, instead of the whole screen",
+        ))
+        .arg(
+            Arg::new("export")
+                .long("export")
+                .value_name("FILE")
+                .help("Write the whole page to FILE instead of showing it"),
+        )
+        .args(format_args())
+        .arg(number("max-width", "The widest the page content may be, in columns").global(true))
+        .arg(number("max-image-height", "The tallest an image may be, in rows").global(true))
+        .arg(
+            Arg::new("theme")
+                .long("theme")
+                .value_name("NAME")
+                .global(true)
+                .help("A built-in theme or one in the themes file"),
+        )
+        .arg(
+            Arg::new("user-stylesheet")
+                .long("user-stylesheet")
+                .value_name("FILE")
+                .global(true)
+                .help("A CSS file to style every page with, under the rules of the page"),
+        )
+        .arg(
+            Arg::new("user-agent")
+                .long("user-agent")
+                .value_name("UA")
+                .global(true)
+                .help("The User-Agent to send, in place of the one in the config file"),
+        )
+        .arg(
+            Arg::new("proxy")
+                .long("proxy")
+                .value_name("URL")
+                .global(true)
+                .help("The proxy to make every request through"),
+        )
+        .arg(
+            Arg::new("header")
+                .long("header")
+                .value_name("NAME: VALUE")
+                .action(ArgAction::Append)
+                .global(true)
+                .help("A header to send with every request; may be given more than once"),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("SECONDS")
+                .value_parser(value_parser!(u64))
+                .global(true)
+                .help("How long to wait for a connection, and then for a response; 0 waits as long as it takes"),
+        )
+        .arg(
+            Arg::new("polite")
+                .long("polite")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Obey robots.txt and wait between requests to a host, for scripted runs"),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Load pages from the cache only, until `:offline` is typed again"),
+        )
+        .arg(
+            Arg::new("local")
+                .short('l')
+                .long("local")
+                .action(ArgAction::SetTrue)
+                .help("Open a directory saved with `wev save`, offline"),
+        )
+        .arg(
+            Arg::new("fail")
+                .short('f')
+                .long("fail")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Fail when the server answers with an error status, instead of writing out the page explaining it"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .help("Write nothing but the page, not even why it failed; the exit status tells"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .global(true)
+                .help("Log requests and failures to standard error; -vv adds how long each phase takes, -vvv everything"),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("FILE")
+                .global(true)
+                .help("Write a detailed log to FILE, to attach to a bug report"),
+        )
+        .subcommand(
+            Command::new("dump")
+                .about("Write the whole page to standard output")
+                .arg(page_arg())
+                .arg(width_arg())
+                .args(format_args()),
+        )
+        .subcommand(
+            Command::new("batch")
+                .about("Write each of a list of pages to a file of its own, loading them at the same time")
+                .arg(
+                    Arg::new("list")
+                        .value_name("FILE")
+                        .help("The pages to write, a URL, a path or a bare domain on each line")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .value_name("DIR")
+                        .required(true)
+                        .help("The directory to write the pages to"),
+                )
+                .arg(width_arg())
+                .args(format_args()),
+        )
+        .subcommand(
+            Command::new("save")
+                .about("Save the page with its stylesheets and images, to be read offline")
+                .arg(page_arg())
+                .arg(
+                    Arg::new("dir")
+                        .value_name("DIR")
+                        .help("The directory to save the page in, to be opened with `wev -l DIR`")
+                        .required(true),
+                ),
+        );
+    #[cfg(feature = "tui")]
+    let command = command.subcommand(
+        Command::new("repl")
+            .about("Load the page and explore it from a prompt: query elements, show their styles and where they are laid out, and add CSS")
+            .arg(page_arg())
+            .arg(width_arg()),
     );
+    command
+}
+
+/// Logs to standard error as verbosely as `--verbose` says, and to `--log-file` with how long
+/// each phase takes as well. Standard error shows through the pages being browsed unless it is
+/// redirected.
+fn configure_logging(args: &ArgMatches) -> Result<()> {
+    let level = match args.get_count("verbose") {
+        0 => None,
+        1 => Some(Level::INFO),
+        2 => Some(Level::DEBUG),
+        _ => Some(Level::TRACE),
+    };
+    let log_file = args.get_one::<String>("log-file");
+    if level.is_none() && log_file.is_none() {
+        return Ok(());
+    }
+    let mut logger = Logger::new();
+    if let Some(level) = level {
+        logger = logger.with_output(level, io::stderr());
+    }
+    if let Some(path) = log_file {
+        let level = level.map_or(Level::DEBUG, |level| level.max(Level::DEBUG));
+        logger = logger.with_output(level, fs::File::create(path)?);
+    }
+    logger.install()
+}
+
+/// Overrides `config`, the config file, with the options in `args`.
+fn override_config(config: &mut Config, args: &ArgMatches) -> Result<()> {
+    let request = &mut config.request;
+    if let Some(user_agent) = args.get_one::<String>("user-agent") {
+        request.user_agent = user_agent.clone();
+    }
+    if let Some(proxy) = args.get_one::<String>("proxy") {
+        request.proxy = Some(proxy.clone());
+    }
+    for header in args.get_many::<String>("header").into_iter().flatten() {
+        request.add_header(header)?;
+    }
+    if let Some(&timeout) = args.get_one::<u64>("timeout") {
+        request.connect_timeout = timeout;
+        request.read_timeout = timeout;
+    }
+    if args.get_flag("polite") {
+        request.robots_txt = true;
+        request.host_delay = request.host_delay.max(POLITE_HOST_DELAY);
+    }
+    if let Some(path) = args.get_one::<String>("user-stylesheet") {
+        config.user_stylesheet = Some(path.into());
+    }
+    if let Some(theme) = args.get_one::<String>("theme") {
+        config.theme = Some(theme.clone());
+    }
+    if let Some(&max_width) = args.get_one::<u16>("max-width") {
+        config.max_width = Some(max_width);
+    }
+    if let Some(&max_image_height) = args.get_one::<u16>("max-image-height") {
+        config.max_image_height = Some(max_image_height);
+    }
+    Ok(())
+}
+
+fn format(args: &ArgMatches) -> Format {
+    if args.get_flag("ansi") {
+        Format::Ansi
+    } else if args.get_flag("json") {
+        Format::Json
+    } else if args.get_flag("linear") {
+        Format::Linear
+    } else {
+        Format::Plain
+    }
+}
+
+/// Writes `message` to standard error, unless `--quiet` was given.
+fn note(message: impl fmt::Display) {
+    if !QUIET.load(Ordering::Relaxed) {
+        eprintln!("wev: {}", message);
+    }
+}
+
+/// Returns the exit status that tells scripts why `error` happened.
+fn exit_code(error: &Error) -> ExitCode {
+    let code = match error {
+        Error::Network(e) if e.status().is_some() => EXIT_HTTP,
+        Error::Network(_) | Error::Blocked(_) | Error::InvalidUrl(_) => EXIT_NETWORK,
+        Error::Decode(_)
+        | Error::Html(_)
+        | Error::Css(_)
+        | Error::Json(_)
+        | Error::Unsupported(_) => EXIT_CONTENT,
+        Error::Io(_) | Error::Config(_) => return ExitCode::FAILURE,
+    };
+    ExitCode::from(code)
+}
+
+/// Tells why `url` failed to be `done`, and returns the exit status for it.
+fn failed(done: &str, url: &str, error: &Error) -> ExitCode {
+    note(format_args!("failed to {} {}: {}", done, url, error));
+    exit_code(error)
+}
+
+/// Loads `url` to be written out. An error status from the server is written out as the page
+/// explaining it, as it is shown when browsing, unless `fail` is set.
+fn load(browser: &Browser, url: &str, fail: bool) -> Result<Page> {
+    match browser.load(url) {
+        Err(e) if !fail && e.status().is_some() => Ok(Page::error(&url_from_input(url), &e)),
+        loaded => loaded,
+    }
+}
+
+/// The width of the content of the page in the terminal, or in an 80 column one if it has none.
+fn terminal_width(max_width: Option<u16>) -> u16 {
+    #[cfg(feature = "tui")]
+    let viewport = crossterm::terminal::size()
+        .map(|(width, height)| Rect::new(0, 0, width, height))
+        .unwrap_or(Rect::new(0, 0, 80, 24));
+    #[cfg(not(feature = "tui"))]
+    let viewport = Rect::new(0, 0, 80, 24);
+    content_area(viewport, max_width).width
+}
+
+/// Does what the command line says. It fails with an exit status telling why when a page to
+/// be written out fails to load.
+fn run() -> Result<ExitCode> {
+    let matches = command().get_matches();
+    let args = match matches.subcommand() {
+        Some((_, args)) => args,
+        None => &matches,
+    };
+    QUIET.store(args.get_flag("quiet"), Ordering::Relaxed);
+    let fail = args.get_flag("fail");
+    configure_logging(args)?;
+    let mut config = Config::load()?;
+    override_config(&mut config, args)?;
+    let browser = Browser::new(config)?;
+    let max_width = browser.config().max_width;
+    let width = || match args.get_one::<u16>("width") {
+        Some(&width) => width,
+        None => terminal_width(max_width),
+    };
+
+    if matches.subcommand_matches("batch").is_some() {
+        let list = args
+            .get_one::<String>("list")
+            .expect("clap requires the list");
+        let out = args.get_one::<String>("out").expect("clap requires --out");
+        let urls = batch::parse_list(&fs::read_to_string(list)?);
+        let quiet = QUIET.load(Ordering::Relaxed);
+        let statuses = batch::dump(
+            &urls,
+            Path::new(out),
+            width(),
+            format(args),
+            fail,
+            browser.settings(),
+            |status| match status {
+                _ if quiet => {}
+                Status::Saved { url, path } => println!("ok {} -> {}", url, path.display()),
+                Status::Failed { url, error } => eprintln!("failed {}: {}", url, error),
+            },
+        )?;
+        // the first page of the list that failed says the exit status
+        let errors = urls.iter().filter_map(|url| {
+            statuses.iter().find_map(|status| match status {
+                Status::Failed { url: failed, error } if failed == url => Some(error),
+                _ => None,
+            })
+        });
+        let errors = errors.collect::<Vec<_>>();
+        return Ok(match errors.first() {
+            Some(error) => {
+                note(format_args!(
+                    "{} of {} pages failed",
+                    errors.len(),
+                    urls.len()
+                ));
+                exit_code(error)
+            }
+            None => ExitCode::SUCCESS,
+        });
+    }
+    let local = matches.subcommand().is_none() && args.get_flag("local");
+    let offline = args.get_flag("offline") || local;
+    browser.settings().transport.set_offline(offline);
+    let mut url = args
+        .get_one::<String>("page")
+        .expect("clap requires the page")
+        .clone();
+    if local {
+        url = archive::index(Path::new(&url))
+            .to_string_lossy()
+            .into_owned();
+    }
+    let url = &url;
+
+    if matches.subcommand_matches("dump").is_some() {
+        let page = match load(&browser, url, fail) {
+            Ok(page) => page,
+            Err(e) => return Ok(failed("load", url, &e)),
+        };
+        print!("{}", export(&page, width(), format(args)));
+        return Ok(ExitCode::SUCCESS);
+    }
+    #[cfg(feature = "tui")]
+    if matches.subcommand_matches("repl").is_some() {
+        let page = match load(&browser, url, fail) {
+            Ok(page) => page,
+            Err(e) => return Ok(failed("load", url, &e)),
+        };
+        let mut repl = Repl::new(page, width());
+        println!("Loaded {}; type `help` for the commands", url);
+        let mut line = String::new();
+        loop {
+            print!("> ");
+            io::stdout().flush()?;
+            line.clear();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Ok(ExitCode::SUCCESS);
+            }
+            match repl.eval(&line) {
+                Some(reply) if reply.is_empty() => {}
+                Some(reply) => println!("{}", reply.trim_end()),
+                None => return Ok(ExitCode::SUCCESS),
+            }
+        }
+    }
+    if matches.subcommand_matches("save").is_some() {
+        let dir = args
+            .get_one::<String>("dir")
+            .expect("clap requires the dir");
+        let saved = match archive::save(&url_from_input(url), Path::new(dir), browser.settings()) {
+            Ok(saved) => saved,
+            Err(e) => return Ok(failed("save", url, &e)),
+        };
+        for location in &saved.failed {
+            note(format_args!(
+                "failed to save {}, which is left online",
+                location
+            ));
+        }
+        if QUIET.load(Ordering::Relaxed) {
+            return Ok(ExitCode::SUCCESS);
+        }
+        println!(
+            "Saved {} with {} file{} to {}",
+            url,
+            saved.assets,
+            if saved.assets == 1 { "" } else { "s" },
+            saved.path.display()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(path) = args.get_one::<String>("export") {
+        let width = terminal_width(max_width);
+        let page = match load(&browser, url, fail) {
+            Ok(page) => page,
+            Err(e) => return Ok(failed("load", url, &e)),
+        };
+        fs::write(path, export(&page, width, format(args)))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    browse(&browser, url, args)
+}
+
+/// Browses `url` in the terminal with the options in `args`.
+#[cfg(feature = "tui")]
+fn browse(browser: &Browser, url: &str, args: &ArgMatches) -> Result<ExitCode> {
+    let options = Options {
+        inline: args.get_one::<u16>("inline").copied(),
+        ..browser.options()
+    };
+    browser.app(url, options)?.run()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Fails, as pages cannot be browsed without the `tui` feature.
+#[cfg(not(feature = "tui"))]
+fn browse(_: &Browser, _: &str, _: &ArgMatches) -> Result<ExitCode> {
+    Err(Error::Config(
+        "wev was built without the browser; write pages out with `wev dump`".into(),
+    ))
+}
 
-    wev::start(&object)
+fn main() -> ExitCode {
+    run().unwrap_or_else(|e| {
+        note(&e);
+        exit_code(&e)
+    })
 }
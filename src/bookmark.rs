@@ -0,0 +1,172 @@
+use crate::{
+    css,
+    dom::{Element, Node, Text},
+    page::Page,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub title: String,
+    pub url: String,
+}
+
+/// `Bookmarks` is the user's list of saved pages, stored as TOML.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmarks {
+    #[serde(default, rename = "bookmark")]
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl Bookmarks {
+    /// `$XDG_DATA_HOME/wev/bookmarks.toml`, falling back to `~/.local/share/wev/bookmarks.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let data_home = env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+        Some(data_home.join("wev").join("bookmarks.toml"))
+    }
+
+    /// Reads bookmarks from `path`. A missing file is an empty list.
+    pub fn load(path: &Path) -> io::Result<Bookmarks> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Bookmarks::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, content)
+    }
+
+    /// Adds `bookmark` unless its URL is already bookmarked. Returns whether it was added.
+    pub fn add(&mut self, bookmark: Bookmark) -> bool {
+        if self.bookmarks.iter().any(|b| b.url == bookmark.url) {
+            return false;
+        }
+        self.bookmarks.push(bookmark);
+        true
+    }
+
+    /// Builds a page listing the bookmarks, with the `selected` one highlighted.
+    pub fn to_page(&self, selected: usize) -> Page {
+        let entries = if self.bookmarks.is_empty() {
+            vec![Element::new(
+                "p".into(),
+                Default::default(),
+                vec![Text::new(
+                    "No bookmarks yet. Press `b` on a page to add one.".into(),
+                )],
+            )]
+        } else {
+            self.bookmarks
+                .iter()
+                .enumerate()
+                .map(|(i, bookmark)| {
+                    let attributes = if i == selected {
+                        vec![("class".into(), "selected".into())]
+                    } else {
+                        vec![]
+                    };
+                    Element::new(
+                        "p".into(),
+                        attributes.into_iter().collect(),
+                        vec![Element::new(
+                            "a".into(),
+                            vec![("href".into(), bookmark.url.clone())]
+                                .into_iter()
+                                .collect(),
+                            vec![Text::new(bookmark.title.clone())],
+                        )],
+                    )
+                })
+                .collect()
+        };
+        let heading = Element::new(
+            "p".into(),
+            Default::default(),
+            vec![Element::new(
+                "b".into(),
+                Default::default(),
+                vec![Text::new("Bookmarks".into())],
+            )],
+        );
+        let dom = Element::new(
+            "".into(),
+            Default::default(),
+            std::iter::once(heading)
+                .chain(entries)
+                .collect::<Vec<Box<Node>>>(),
+        );
+
+        let stylesheet = css::stylesheet(".selected { background-color: #444444; }")
+            .expect("the stylesheet of the bookmark list is valid");
+        Page::new("wev:bookmarks", dom, stylesheet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bookmark, Bookmarks};
+    use crate::dom::NodeType;
+
+    fn bookmark(url: &str) -> Bookmark {
+        Bookmark {
+            title: format!("title of {}", url),
+            url: url.into(),
+        }
+    }
+
+    #[test]
+    fn test_add() {
+        let mut bookmarks = Bookmarks::default();
+        assert!(bookmarks.add(bookmark("https://example.com")));
+        assert!(bookmarks.add(bookmark("https://example.org")));
+        assert!(!bookmarks.add(bookmark("https://example.com")));
+        assert_eq!(bookmarks.bookmarks.len(), 2);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("wev-test-{}", std::process::id()))
+            .join("bookmarks.toml");
+        assert_eq!(Bookmarks::load(&path).unwrap(), Bookmarks::default());
+
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.add(bookmark("https://example.com"));
+        bookmarks.add(bookmark("./index.html"));
+        bookmarks.save(&path).unwrap();
+        assert_eq!(Bookmarks::load(&path).unwrap(), bookmarks);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_to_page() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.add(bookmark("a"));
+        bookmarks.add(bookmark("b"));
+        let page = bookmarks.to_page(1);
+
+        let classes = page.dom().children[1..]
+            .iter()
+            .map(|p| match &p.node_type {
+                NodeType::Element(e) => e.attributes.get("class").cloned(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(classes, vec![None, Some("selected".into())]);
+    }
+}
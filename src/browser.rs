@@ -0,0 +1,112 @@
+#[cfg(feature = "tui")]
+use crate::{
+    app::{App, Options},
+    keymap::Keymap,
+    theme::Theme,
+};
+use crate::{
+    config::Config,
+    css,
+    cssom::Stylesheet,
+    error::Result,
+    filter::Filter,
+    loader::url_from_input,
+    page::{Page, Settings},
+};
+use std::{fs, sync::Arc};
+
+/// `Browser` is wev set up as a config says, for programs that load and show pages with it.
+/// Each keeps what its pages are loaded and styled with to itself, so browsers of different
+/// configs can be used side by side.
+///
+/// ```no_run
+/// # fn main() -> wev::Result<()> {
+/// let browser = wev::Browser::new(wev::config::Config::default())?;
+/// let page = browser.load("example.com")?;
+/// println!("{:?}", page.title());
+/// for link in page.links() {
+///     println!("{} -> {}", link.text, link.url);
+/// }
+/// print!("{}", page.render_to_string(80));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Browser {
+    config: Config,
+    #[cfg(feature = "tui")]
+    theme: Theme,
+    #[cfg(feature = "tui")]
+    keymap: Keymap,
+    settings: Settings,
+}
+
+impl Browser {
+    /// Sets up requests, stylesheets and what pages are loaded with as `config` says.
+    pub fn new(config: Config) -> Result<Browser> {
+        #[cfg(feature = "tui")]
+        let theme = match &config.theme {
+            Some(name) => Theme::load(name)?,
+            None => Theme::default(),
+        };
+        #[cfg(feature = "tui")]
+        let keymap = Keymap::parse(&config.keys)?;
+        let user_stylesheet = match &config.user_stylesheet {
+            Some(path) => css::stylesheet(&fs::read_to_string(path)?)?,
+            None => Stylesheet::new(vec![]),
+        };
+        let settings = Settings {
+            transport: Arc::new(config.request.transport()?),
+            features: config.features,
+            filter: Arc::new(Filter::from_config(&config.filter)?),
+            user_stylesheet: Arc::new(user_stylesheet),
+        };
+        Ok(Browser {
+            config,
+            #[cfg(feature = "tui")]
+            theme,
+            #[cfg(feature = "tui")]
+            keymap,
+            settings,
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns what pages are loaded and styled with, to load them without the browser.
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Loads `input`, a URL, a path or a bare domain as typed in the address bar,
+    /// with its stylesheets and images.
+    pub fn load(&self, input: &str) -> Result<Page> {
+        Page::load(&url_from_input(input), &self.settings)
+    }
+
+    /// Makes a page of `html`, as if it was loaded from `url`, without loading what it refers to.
+    pub fn from_html(&self, url: &str, html: &str) -> Result<Page> {
+        Page::from_html(url, html, &self.settings)
+    }
+
+    /// Returns how pages are displayed as the config says.
+    #[cfg(feature = "tui")]
+    pub fn options(&self) -> Options {
+        Options {
+            max_width: self.config.max_width,
+            max_image_height: self.config.max_image_height,
+            theme: self.theme,
+            inline: None,
+        }
+    }
+
+    /// Makes the interactive browser, starting at `input`, with `options` and the keys of
+    /// the config.
+    #[cfg(feature = "tui")]
+    pub fn app(&self, input: &str, options: Options) -> Result<App> {
+        let app = App::new(&url_from_input(input), options, self.settings.clone())?;
+        Ok(app.with_keymap(self.keymap.clone()))
+    }
+}
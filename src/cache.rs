@@ -0,0 +1,302 @@
+use reqwest::header::{
+    HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// `CacheMode` is how a request uses the cache.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Fresh responses are used as they are, and stale ones are revalidated.
+    #[default]
+    Normal,
+    /// Every cached response is revalidated, as a reload does.
+    Revalidate,
+    /// The cache is not read, as a hard reload does. The response is still stored.
+    Reload,
+}
+
+/// `Metadata` is what is kept about a cached response besides its body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Metadata {
+    url: String,
+    content_type: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// When the response was stored or last revalidated, in seconds since the Unix epoch.
+    stored_at: u64,
+    /// How many seconds the response stays fresh after `stored_at`.
+    max_age: u64,
+    /// The length of the body, to tell a body that was not fully written.
+    length: usize,
+}
+
+/// `Entry` is a cached response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    metadata: Metadata,
+    pub body: Vec<u8>,
+}
+
+impl Entry {
+    pub fn content_type(&self) -> Option<&str> {
+        self.metadata.content_type.as_deref()
+    }
+
+    /// Returns whether the response can be used without asking the server at `now`.
+    pub fn is_fresh(&self, now: u64) -> bool {
+        now < self
+            .metadata
+            .stored_at
+            .saturating_add(self.metadata.max_age)
+    }
+
+    /// The headers that ask the server whether the response has changed since it was stored.
+    pub fn validators(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let mut insert = |name, value: &Option<String>| {
+            if let Some(value) = value.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        };
+        insert(IF_NONE_MATCH, &self.metadata.etag);
+        insert(IF_MODIFIED_SINCE, &self.metadata.last_modified);
+        headers
+    }
+}
+
+/// Returns for how many seconds a response with `headers` stays fresh,
+/// or `None` if it must not be stored (`Cache-Control: no-store`).
+fn max_age(headers: &HeaderMap) -> Option<u64> {
+    let cache_control = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    let mut max_age = 0;
+    for directive in &cache_control {
+        match directive.split_once('=') {
+            _ if directive == "no-store" => return None,
+            // a response without a lifetime is revalidated every time it is used
+            _ if directive == "no-cache" => return Some(0),
+            Some(("max-age", seconds)) => {
+                max_age = seconds.trim_matches('"').parse().unwrap_or_default()
+            }
+            _ => {}
+        }
+    }
+    Some(max_age)
+}
+
+fn header(headers: &HeaderMap, name: impl reqwest::header::AsHeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Returns a name for the files of `url` that does not change between runs (64-bit FNV-1a).
+fn key(url: &str) -> String {
+    let hash = url.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// `Cache` is a directory of responses, each kept as a metadata file and a body file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Cache {
+        Cache { dir }
+    }
+
+    /// Returns where responses are cached: `$XDG_CACHE_HOME/wev/http`,
+    /// or `~/.cache/wev/http` without `XDG_CACHE_HOME`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache_home = env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+        Some(cache_home.join("wev").join("http"))
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = key(url);
+        (
+            self.dir.join(format!("{}.toml", key)),
+            self.dir.join(format!("{}.body", key)),
+        )
+    }
+
+    /// Returns the response cached for `url`, fresh or not.
+    pub fn lookup(&self, url: &str) -> Option<Entry> {
+        let (metadata_path, body_path) = self.paths(url);
+        let metadata: Metadata = toml::from_str(&fs::read_to_string(metadata_path).ok()?).ok()?;
+        let body = fs::read(body_path).ok()?;
+        // another URL with the same key, or a body that was being replaced
+        if metadata.url != url || metadata.length != body.len() {
+            return None;
+        }
+        Some(Entry { metadata, body })
+    }
+
+    /// Stores `body`, the response to `url` with `headers`, received at `now`.
+    /// Responses that cannot be reused are not stored.
+    pub fn store(&self, url: &str, headers: &HeaderMap, body: &[u8], now: u64) -> io::Result<()> {
+        let Some(max_age) = max_age(headers) else {
+            return self.remove(url);
+        };
+        let metadata = Metadata {
+            url: url.into(),
+            content_type: header(headers, CONTENT_TYPE),
+            etag: header(headers, ETAG),
+            last_modified: header(headers, LAST_MODIFIED),
+            stored_at: now,
+            max_age,
+            length: body.len(),
+        };
+        if max_age == 0 && metadata.etag.is_none() && metadata.last_modified.is_none() {
+            return self.remove(url);
+        }
+        fs::create_dir_all(&self.dir)?;
+        let (metadata_path, body_path) = self.paths(url);
+        fs::write(body_path, body)?;
+        fs::write(
+            metadata_path,
+            toml::to_string(&metadata).map_err(io::Error::other)?,
+        )
+    }
+
+    /// Keeps `entry` for `url` after the server said at `now` that it has not changed,
+    /// with the lifetime and validators of `headers`, the headers of that answer.
+    pub fn refresh(
+        &self,
+        url: &str,
+        entry: &mut Entry,
+        headers: &HeaderMap,
+        now: u64,
+    ) -> io::Result<()> {
+        let Some(max_age) = max_age(headers) else {
+            return self.remove(url);
+        };
+        let metadata = &mut entry.metadata;
+        metadata.stored_at = now;
+        metadata.max_age = max_age;
+        if let Some(etag) = header(headers, ETAG) {
+            metadata.etag = Some(etag);
+        }
+        if let Some(last_modified) = header(headers, LAST_MODIFIED) {
+            metadata.last_modified = Some(last_modified);
+        }
+        let (metadata_path, _) = self.paths(url);
+        fs::write(
+            metadata_path,
+            toml::to_string(metadata).map_err(io::Error::other)?,
+        )
+    }
+
+    pub fn remove(&self, url: &str) -> io::Result<()> {
+        let (metadata_path, body_path) = self.paths(url);
+        for path in [metadata_path, body_path] {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{max_age, Cache};
+    use reqwest::header::{HeaderMap, HeaderValue, CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_max_age() {
+        assert_eq!(max_age(&headers(&[])), Some(0));
+        assert_eq!(
+            max_age(&headers(&[("cache-control", "public, max-age=60")])),
+            Some(60)
+        );
+        assert_eq!(
+            max_age(&headers(&[("cache-control", "max-age=60, no-cache")])),
+            Some(0)
+        );
+        assert_eq!(
+            max_age(&headers(&[("cache-control", "private, no-store")])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("wev-cache-{}", std::process::id()));
+        let cache = Cache::new(dir.clone());
+        let url = "https://example.com/";
+        assert_eq!(cache.lookup(url), None);
+
+        let fresh = headers(&[
+            ("cache-control", "max-age=60"),
+            ("content-type", "text/html"),
+        ]);
+        cache.store(url, &fresh, b"<p>hi</p>", 100).unwrap();
+        let entry = cache.lookup(url).unwrap();
+        assert_eq!(entry.body, b"<p>hi</p>");
+        assert_eq!(entry.content_type(), Some("text/html"));
+        assert!(entry.is_fresh(159));
+        assert!(!entry.is_fresh(160));
+        assert!(entry.validators().is_empty());
+        assert_eq!(cache.lookup("https://example.com/other"), None);
+
+        // a stale response with a validator is revalidated instead of fetched again
+        let validated = headers(&[("etag", "\"v1\"")]);
+        cache.store(url, &validated, b"<p>hi</p>", 200).unwrap();
+        let mut entry = cache.lookup(url).unwrap();
+        assert!(!entry.is_fresh(200));
+        assert_eq!(entry.validators()[IF_NONE_MATCH], "\"v1\"");
+        let mut not_modified = HeaderMap::new();
+        not_modified.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=10"));
+        not_modified.insert(ETAG, HeaderValue::from_static("\"v2\""));
+        cache.refresh(url, &mut entry, &not_modified, 300).unwrap();
+        let entry = cache.lookup(url).unwrap();
+        assert!(entry.is_fresh(305));
+        assert_eq!(entry.validators()[IF_NONE_MATCH], "\"v2\"");
+
+        // responses that cannot be reused take the place of the cached one
+        cache.store(url, &headers(&[]), b"", 400).unwrap();
+        assert_eq!(cache.lookup(url), None);
+        cache.store(url, &fresh, b"<p>hi</p>", 500).unwrap();
+        cache
+            .store(url, &headers(&[("cache-control", "no-store")]), b"", 500)
+            .unwrap();
+        assert_eq!(cache.lookup(url), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
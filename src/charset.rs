@@ -0,0 +1,238 @@
+use encoding_rs::{Decoder, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// How far into a document a `<meta>` declaring its charset is looked for, as browsers do.
+const SNIFF_LENGTH: usize = 1024;
+
+/// Returns the `charset` parameter of `content_type`, like `Shift_JIS` in
+/// `text/html; charset=Shift_JIS`.
+pub fn charset_param(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches(['"', '\'']))
+    })
+}
+
+/// Returns the encoding declared in `label`, where a document declared as UTF-16 but read
+/// as bytes that were not is UTF-8, as browsers take it.
+fn encoding_for(label: &str) -> Option<&'static Encoding> {
+    let encoding = Encoding::for_label(label.as_bytes())?;
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        return Some(UTF_8);
+    }
+    Some(encoding)
+}
+
+/// Returns the charset a `<meta charset>` or `<meta http-equiv="Content-Type">` near the
+/// start of `content` declares.
+fn meta_charset(content: &[u8]) -> Option<&'static Encoding> {
+    let head = &content[..content.len().min(SNIFF_LENGTH)];
+    let head = head.to_ascii_lowercase();
+    let mut rest = head.as_slice();
+    while let Some(start) = find(rest, b"<meta") {
+        let tag = &rest[start..];
+        let tag = &tag[..find(tag, b">").unwrap_or(tag.len())];
+        if let Some(i) = find(tag, b"charset=") {
+            let value = tag[i + "charset=".len()..]
+                .iter()
+                .skip_while(|&&b| b == b'"' || b == b'\'' || b.is_ascii_whitespace())
+                .take_while(|&&b| {
+                    !matches!(b, b'"' | b'\'' | b';' | b'/') && !b.is_ascii_whitespace()
+                })
+                .map(|&b| b as char)
+                .collect::<String>();
+            if let Some(encoding) = encoding_for(&value) {
+                return Some(encoding);
+            }
+        }
+        rest = &rest[start + tag.len()..];
+    }
+    None
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Returns the encoding of `content`, a document of `content_type`: the one its byte order
+/// mark, its content type or a `<meta>` in it declares, in that order. Without any, it is
+/// UTF-8, or windows-1252 if it is not valid UTF-8, as older pages without one often are.
+pub fn encoding_of(content: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    detect(content, content_type, true)
+}
+
+/// Returns the encoding of `content` as `encoding_of` does, where `content` may be only
+/// the start of the document unless it is `complete`.
+fn detect(content: &[u8], content_type: Option<&str>, complete: bool) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(content) {
+        return encoding;
+    }
+    content_type
+        .and_then(charset_param)
+        .and_then(encoding_for)
+        .or_else(|| meta_charset(content))
+        .unwrap_or(match std::str::from_utf8(content) {
+            Ok(_) => UTF_8,
+            // the start of a document may end in the middle of a character
+            Err(e) if !complete && e.error_len().is_none() => UTF_8,
+            Err(_) => WINDOWS_1252,
+        })
+}
+
+/// Decodes `content`, a document of `content_type`, into text.
+/// Bytes that are not valid in its encoding become U+FFFD.
+pub fn decode(content: &[u8], content_type: Option<&str>) -> String {
+    let (text, _, _) = encoding_of(content, content_type).decode(content);
+    text.into_owned()
+}
+
+/// Decodes `bytes`, the next part of a document, with `decoder`. `last` is whether it ends it.
+fn decode_with(decoder: &mut Decoder, bytes: &[u8], last: bool) -> String {
+    let length = decoder
+        .max_utf8_buffer_length(bytes.len())
+        .unwrap_or(bytes.len() * 3 + 16);
+    let mut text = String::with_capacity(length);
+    let _ = decoder.decode_to_string(bytes, &mut text, last);
+    text
+}
+
+/// `StreamDecoder` decodes a document as it arrives. Its encoding is told from its first
+/// bytes as `encoding_of` tells it, which the rest of the document can no longer change.
+#[derive(Debug)]
+pub struct StreamDecoder {
+    content_type: Option<String>,
+    /// The first bytes, until there are enough of them to tell the encoding.
+    head: Vec<u8>,
+    decoder: Option<Decoder>,
+}
+
+impl StreamDecoder {
+    /// Makes a decoder for a document of `content_type`.
+    pub fn new(content_type: Option<&str>) -> StreamDecoder {
+        StreamDecoder {
+            content_type: content_type.map(str::to_string),
+            head: vec![],
+            decoder: None,
+        }
+    }
+
+    /// Decodes `bytes`, the next part of the document, into what can be decoded of it so far.
+    /// A character split between parts comes out with the part that completes it.
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        if let Some(decoder) = &mut self.decoder {
+            return decode_with(decoder, bytes, false);
+        }
+        self.head.extend_from_slice(bytes);
+        if self.head.len() < SNIFF_LENGTH {
+            return String::new();
+        }
+        let head = std::mem::take(&mut self.head);
+        let encoding = detect(&head, self.content_type.as_deref(), false);
+        decode_with(self.decoder.insert(encoding.new_decoder()), &head, false)
+    }
+
+    /// Decodes what is left once the whole document has arrived.
+    pub fn finish(&mut self) -> String {
+        let head = std::mem::take(&mut self.head);
+        let content_type = self.content_type.as_deref();
+        let decoder = self
+            .decoder
+            .get_or_insert_with(|| encoding_of(&head, content_type).new_decoder());
+        decode_with(decoder, &head, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{charset_param, decode, encoding_of, StreamDecoder};
+    use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_8, WINDOWS_1252};
+
+    #[test]
+    fn test_charset_param() {
+        assert_eq!(
+            charset_param("text/html; charset=Shift_JIS"),
+            Some("Shift_JIS")
+        );
+        assert_eq!(
+            charset_param("text/html;Charset=\"euc-jp\""),
+            Some("euc-jp")
+        );
+        assert_eq!(charset_param("text/html"), None);
+    }
+
+    #[test]
+    fn test_encoding_of() {
+        assert_eq!(encoding_of(b"<p>hi</p>", None), UTF_8);
+        assert_eq!(
+            encoding_of(b"<p>hi</p>", Some("text/html; charset=shift_jis")),
+            SHIFT_JIS
+        );
+        assert_eq!(
+            encoding_of(b"<meta charset=\"EUC-JP\"><p>hi</p>", None),
+            EUC_JP
+        );
+        assert_eq!(
+            encoding_of(
+                b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift_JIS\">",
+                Some("text/html")
+            ),
+            SHIFT_JIS
+        );
+        // the content type wins over the document
+        assert_eq!(
+            encoding_of(b"<meta charset=euc-jp>", Some("text/html; charset=utf-8")),
+            UTF_8
+        );
+        assert_eq!(encoding_of(b"<meta charset=utf-16>", None), UTF_8);
+        assert_eq!(encoding_of(b"caf\xe9", None), WINDOWS_1252);
+        assert_eq!(
+            encoding_of(b"\xef\xbb\xbf<meta charset=euc-jp>", None),
+            UTF_8
+        );
+    }
+
+    #[test]
+    fn test_decode() {
+        // "日本語" in Shift_JIS and EUC-JP
+        assert_eq!(
+            decode(
+                b"\x93\xfa\x96\x7b\x8c\xea",
+                Some("text/html; charset=Shift_JIS")
+            ),
+            "日本語"
+        );
+        assert_eq!(
+            decode(b"<meta charset=euc-jp>\xc6\xfc\xcb\xdc\xb8\xec", None),
+            "<meta charset=euc-jp>日本語"
+        );
+        assert_eq!(decode(b"caf\xe9", Some("text/html")), "café");
+        assert_eq!(decode("\u{feff}é".as_bytes(), None), "é");
+    }
+
+    #[test]
+    fn test_stream_decoder() {
+        let decode_stream = |content: &[u8], content_type, size| {
+            let mut decoder = StreamDecoder::new(content_type);
+            let mut text = String::new();
+            for part in content.chunks(size) {
+                text += &decoder.push(part);
+            }
+            text + &decoder.finish()
+        };
+        let content = "<p>日本語</p>".repeat(200);
+        // parts that split characters, and that end before the encoding can be told
+        for size in [1, 7, 1000, 5000] {
+            assert_eq!(decode_stream(content.as_bytes(), None, size), content);
+        }
+        let shift_jis = b"\x93\xfa\x96\x7b\x8c\xea".repeat(300);
+        assert_eq!(
+            decode_stream(&shift_jis, Some("text/html; charset=Shift_JIS"), 3),
+            "日本語".repeat(300)
+        );
+        assert_eq!(decode_stream(b"caf\xe9", None, 1), "café");
+        assert_eq!(decode_stream(b"", None, 1), "");
+    }
+}
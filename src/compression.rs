@@ -0,0 +1,276 @@
+use flate2::write::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::{
+    fmt, io,
+    io::Write,
+    mem::{self, take},
+};
+
+/// `Coding` is a content coding that bodies can be decoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl Coding {
+    /// Returns the coding named `name` in `Accept-Encoding` or `Content-Encoding`.
+    pub fn from_name(name: &str) -> Option<Coding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "identity" => Some(Coding::Identity),
+            "gzip" | "x-gzip" => Some(Coding::Gzip),
+            "deflate" => Some(Coding::Deflate),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Coding::Identity => "identity",
+            Coding::Gzip => "gzip",
+            Coding::Deflate => "deflate",
+        }
+    }
+}
+
+impl fmt::Display for Coding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How many bytes tell whether a body is encoded as it is labelled. Two tell gzip and zlib,
+/// but raw deflate has no header, so it takes failing to decode the start of a body
+/// to tell it is not.
+const SNIFF_LENGTH: usize = 256;
+
+/// `State` is how far a `Decoder` has got.
+enum State {
+    /// Collecting the first bytes, which tell whether the body is encoded as labelled.
+    Sniffing(Vec<u8>),
+    Identity,
+    Gzip(GzDecoder<Vec<u8>>),
+    /// `deflate` as it should be sent: a zlib stream.
+    Zlib(ZlibDecoder<Vec<u8>>),
+    /// `deflate` as some servers send it: a raw deflate stream.
+    Deflate(DeflateDecoder<Vec<u8>>),
+    /// A coding that cannot be decoded, by its name.
+    Unsupported(String),
+}
+
+/// `Decoder` decodes a body from the content coding its `Content-Encoding` names, as it arrives.
+/// A body that is labelled but turns out not to be encoded, as some servers send,
+/// is taken as it is.
+pub struct Decoder {
+    /// The coding the body is labelled with.
+    label: Coding,
+    /// The coding the body is decoded from, which is the label until the first bytes tell.
+    coding: Coding,
+    state: State,
+}
+
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("label", &self.label)
+            .field("coding", &self.coding)
+            .finish()
+    }
+}
+
+/// Returns whether `bytes` start like a zlib stream: a deflate method and a header checksum.
+fn is_zlib(bytes: &[u8]) -> bool {
+    match bytes {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+impl Decoder {
+    /// Makes a decoder for a body whose `Content-Encoding` is `content_encoding`.
+    pub fn new(content_encoding: Option<&str>) -> Decoder {
+        // of several codings, only the last applied is undone, which is all servers use
+        let name = content_encoding
+            .and_then(|encoding| encoding.rsplit(',').next())
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+        let (label, state) = match name.map(|name| (name, Coding::from_name(name))) {
+            None | Some((_, Some(Coding::Identity))) => (Coding::Identity, State::Identity),
+            Some((_, Some(coding))) => (coding, State::Sniffing(vec![])),
+            Some((name, None)) => (Coding::Identity, State::Unsupported(name.to_string())),
+        };
+        Decoder {
+            label,
+            coding: label,
+            state,
+        }
+    }
+
+    /// Returns the coding the body is decoded from, once its first bytes have told.
+    pub fn coding(&self) -> Coding {
+        self.coding
+    }
+
+    /// Decides how the body is encoded from its first bytes, `head`, and decodes them.
+    fn start(&mut self, head: Vec<u8>) -> io::Result<Vec<u8>> {
+        self.state = match self.label {
+            Coding::Gzip if head.starts_with(&[0x1f, 0x8b]) => State::Gzip(GzDecoder::new(vec![])),
+            Coding::Deflate if is_zlib(&head) => State::Zlib(ZlibDecoder::new(vec![])),
+            Coding::Deflate => {
+                let mut decoder = DeflateDecoder::new(vec![]);
+                // what is not raw deflate either was not encoded at all
+                match decoder.write_all(&head) {
+                    Ok(()) => {
+                        self.state = State::Deflate(decoder);
+                        return self.take_output();
+                    }
+                    Err(_) => {
+                        self.coding = Coding::Identity;
+                        State::Identity
+                    }
+                }
+            }
+            _ => {
+                self.coding = Coding::Identity;
+                State::Identity
+            }
+        };
+        self.push(&head)
+    }
+
+    fn take_output(&mut self) -> io::Result<Vec<u8>> {
+        Ok(match &mut self.state {
+            State::Gzip(decoder) => take(decoder.get_mut()),
+            State::Zlib(decoder) => take(decoder.get_mut()),
+            State::Deflate(decoder) => take(decoder.get_mut()),
+            _ => vec![],
+        })
+    }
+
+    /// Decodes `chunk`, the next part of the body, returning what it decodes to so far.
+    pub fn push(&mut self, chunk: &[u8]) -> io::Result<Vec<u8>> {
+        match &mut self.state {
+            State::Sniffing(head) => {
+                head.extend_from_slice(chunk);
+                if head.len() < SNIFF_LENGTH {
+                    return Ok(vec![]);
+                }
+                let head = take(head);
+                self.start(head)
+            }
+            State::Identity => Ok(chunk.to_vec()),
+            State::Gzip(decoder) => {
+                decoder.write_all(chunk)?;
+                self.take_output()
+            }
+            State::Zlib(decoder) => {
+                decoder.write_all(chunk)?;
+                self.take_output()
+            }
+            State::Deflate(decoder) => {
+                decoder.write_all(chunk)?;
+                self.take_output()
+            }
+            State::Unsupported(name) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("the body is encoded in `{}`, which cannot be decoded", name),
+            )),
+        }
+    }
+
+    /// Decodes what is left after the whole body has been pushed, checking that the body
+    /// was complete and, for gzip and zlib, that its checksum matches.
+    pub fn finish(&mut self) -> io::Result<Vec<u8>> {
+        if let State::Sniffing(head) = &mut self.state {
+            let head = take(head);
+            let mut decoded = self.start(head)?;
+            decoded.extend(self.finish()?);
+            return Ok(decoded);
+        }
+        match &mut self.state {
+            State::Gzip(decoder) => decoder.try_finish()?,
+            State::Zlib(decoder) => decoder.try_finish()?,
+            State::Deflate(decoder) => decoder.try_finish()?,
+            _ => {}
+        }
+        let decoded = self.take_output()?;
+        if let State::Gzip(decoder) = mem::replace(&mut self.state, State::Identity) {
+            // a gzip stream that ended before its trailer is not complete
+            decoder.finish()?;
+        }
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coding, Decoder};
+    use flate2::{
+        write::{DeflateEncoder, GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    fn decode(content_encoding: Option<&str>, body: &[u8]) -> std::io::Result<(Vec<u8>, Coding)> {
+        let mut decoder = Decoder::new(content_encoding);
+        let mut decoded = vec![];
+        // a byte at a time, as badly as a body can arrive
+        for byte in body {
+            decoded.extend(decoder.push(&[*byte])?);
+        }
+        decoded.extend(decoder.finish()?);
+        Ok((decoded, decoder.coding()))
+    }
+
+    #[test]
+    fn test_decoder() {
+        let text = b"<p>compressed</p>".repeat(100);
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        gzip.write_all(&text).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        zlib.write_all(&text).unwrap();
+        let zlib = zlib.finish().unwrap();
+        let mut deflate = DeflateEncoder::new(vec![], Compression::default());
+        deflate.write_all(&text).unwrap();
+        let deflate = deflate.finish().unwrap();
+
+        assert_eq!(
+            decode(None, &text).unwrap(),
+            (text.clone(), Coding::Identity)
+        );
+        assert_eq!(
+            decode(Some("gzip"), &gzip).unwrap(),
+            (text.clone(), Coding::Gzip)
+        );
+        assert_eq!(
+            decode(Some("Deflate"), &zlib).unwrap(),
+            (text.clone(), Coding::Deflate)
+        );
+        assert_eq!(
+            decode(Some("deflate"), &deflate).unwrap(),
+            (text.clone(), Coding::Deflate)
+        );
+        // servers that label bodies they did not encode
+        assert_eq!(
+            decode(Some("gzip"), &text).unwrap(),
+            (text.clone(), Coding::Identity)
+        );
+        assert_eq!(
+            decode(Some("deflate"), &text).unwrap(),
+            (text.clone(), Coding::Identity)
+        );
+        assert_eq!(
+            decode(Some("gzip"), b"").unwrap(),
+            (vec![], Coding::Identity)
+        );
+
+        // a body cut short or corrupted is an error
+        assert!(decode(Some("gzip"), &gzip[..gzip.len() - 4]).is_err());
+        let mut corrupted = gzip.clone();
+        let last = corrupted.len() - 5;
+        corrupted[last] ^= 0xff;
+        assert!(decode(Some("gzip"), &corrupted).is_err());
+        assert!(decode(Some("br"), &text).is_err());
+    }
+}
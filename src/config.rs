@@ -0,0 +1,168 @@
+#[cfg(feature = "net")]
+use crate::request::RequestConfig;
+use crate::{
+    error::{Error, Result},
+    filter::FilterConfig,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// `Features` are the parts of pages that can be turned off, to save time or bandwidth.
+/// It is the `[features]` table of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct Features {
+    /// Whether the images of pages are fetched and shown.
+    pub images: bool,
+    /// Whether the stylesheets pages link to are fetched. Their `<style>`s apply either way.
+    pub stylesheets: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            images: true,
+            stylesheets: true,
+        }
+    }
+}
+
+/// `Config` is the config file, read at startup. Everything in it can be left out,
+/// and the options on the command line override it.
+///
+/// ```toml
+/// theme = "dark"
+/// max-width = 100
+///
+/// [keys]
+/// "C-d" = "page-down"
+///
+/// [features]
+/// images = false
+///
+/// [filter]
+/// hide = ["#cookie-banner"]
+///
+/// [request]
+/// proxy = "socks5://localhost:1080"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct Config {
+    /// A built-in theme or one in the themes file.
+    pub theme: Option<String>,
+    /// The widest the page content may be, in columns.
+    pub max_width: Option<u16>,
+    /// The tallest an image may be, in rows.
+    pub max_image_height: Option<u16>,
+    /// A CSS file to style every page with, relative to the directory of the config file.
+    pub user_stylesheet: Option<PathBuf>,
+    /// The actions to bind keys to, in place of what they do by default, like
+    /// `"C-d" = "page-down"`.
+    pub keys: BTreeMap<String, String>,
+    pub features: Features,
+    pub filter: FilterConfig,
+    #[cfg(feature = "net")]
+    pub request: RequestConfig,
+}
+
+/// Returns the directory of the config files: `$XDG_CONFIG_HOME/wev`,
+/// or `~/.config/wev` without `XDG_CONFIG_HOME`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("wev"))
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/wev/config.toml`, falling back to `~/.config/wev/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("config.toml"))
+    }
+
+    /// Reads the config file. Without one, the defaults are used.
+    pub fn load() -> Result<Config> {
+        let Some(path) = Config::default_path() else {
+            return Ok(Config::default());
+        };
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => Config::parse(&content)
+                .map_err(|e| Error::Config(format!("{} is not valid: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(dir) = path.parent() {
+            let paths = config.user_stylesheet.iter_mut();
+            for path in paths.chain(config.filter.lists.iter_mut()) {
+                *path = dir.join(&*path);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Reads `content`, a config file.
+    pub fn parse(content: &str) -> Result<Config> {
+        let config: Config = toml::from_str(content).map_err(|e| Error::Config(e.to_string()))?;
+        #[cfg(feature = "net")]
+        config.request.check_accept_encodings()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Features};
+
+    #[test]
+    fn test_config() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::default().features,
+            Features {
+                images: true,
+                stylesheets: true
+            }
+        );
+        let config = Config::parse(
+            r#"
+            theme = "dark"
+            max-width = 100
+            user-stylesheet = "user.css"
+
+            [keys]
+            "C-d" = "page-down"
+
+            [features]
+            images = false
+
+            [filter]
+            hide = ["aside"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.theme.as_deref(), Some("dark"));
+        assert_eq!(
+            (config.max_width, config.max_image_height),
+            (Some(100), None)
+        );
+        assert_eq!(config.keys["C-d"], "page-down");
+        assert!(!config.features.images);
+        assert!(config.features.stylesheets);
+        assert_eq!(config.filter.hide, ["aside"]);
+        #[cfg(feature = "net")]
+        {
+            let config = Config::parse("[request]\nread-timeout = 5").unwrap();
+            assert_eq!(config.request.read_timeout, 5);
+            assert_eq!(config.request.connect_timeout, 10);
+        }
+
+        assert!(Config::parse("colour = \"red\"").is_err());
+        assert!(Config::parse("[features]\nscripts = true").is_err());
+    }
+}
@@ -1,20 +1,137 @@
-use crate::cssom::*;
+use crate::{cssom::*, error::Error};
 use combine::{
+    attempt, between, eof,
     error::StreamError,
     many, many1, optional,
     parser::{
-        char::{char, letter, spaces, string},
+        char::{alpha_num, char, digit, hex_digit, letter, space, spaces, string},
         choice::choice,
     },
-    sep_by, sep_end_by, ParseError, Parser, Stream,
+    satisfy, sep_by1, sep_end_by, skip_many1, ParseError, Parser, Stream,
 };
 
 fn css_value<Input>() -> impl Parser<Input, Output = CSSValue>
 where
     Input: Stream<Token = char>,
 {
-    let keyword = many1(letter()).map(|s| CSSValue::Keyword(s));
-    keyword
+    let keyword = many1(letter().or(char('-'))).map(CSSValue::Keyword);
+    choice((length(), color(), keyword))
+}
+
+fn number<Input>() -> impl Parser<Input, Output = f32>
+where
+    Input: Stream<Token = char>,
+{
+    (
+        many1::<String, _, _>(digit()),
+        optional((char('.'), many1::<String, _, _>(digit()))),
+    )
+        .map(|(int, frac)| match frac {
+            Some((_, frac)) => format!("{}.{}", int, frac),
+            None => int,
+        })
+        .map(|n| n.parse::<f32>().unwrap_or_default())
+}
+
+fn length<Input>() -> impl Parser<Input, Output = CSSValue>
+where
+    Input: Stream<Token = char>,
+{
+    let unit = choice((
+        attempt(string("px")).map(|_| Unit::Px),
+        attempt(string("em")).map(|_| Unit::Em),
+        attempt(string("ch")).map(|_| Unit::Ch),
+    ));
+    // NOTE: a unitless length is only valid for `0`, but we leniently read it as pixels.
+    (number(), optional(unit)).map(|(n, unit)| CSSValue::Length(n, unit.unwrap_or(Unit::Px)))
+}
+
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok();
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    match hex.len() {
+        3 | 4 => {
+            let r = digit(0)? * 17;
+            let g = digit(1)? * 17;
+            let b = digit(2)? * 17;
+            let a = if hex.len() == 4 { digit(3)? * 17 } else { 255 };
+            Some(Color { r, g, b, a })
+        }
+        6 | 8 => {
+            let r = byte(0)?;
+            let g = byte(2)?;
+            let b = byte(4)?;
+            let a = if hex.len() == 8 { byte(6)? } else { 255 };
+            Some(Color { r, g, b, a })
+        }
+        _ => None,
+    }
+}
+
+/// Parses `#rgb`, `#rrggbb` (with optional alpha) and `rgb()` / `rgba()` colors.
+/// Named colors are parsed as keywords and resolved by `Color::from_value`.
+fn color<Input>() -> impl Parser<Input, Output = CSSValue>
+where
+    Input: Stream<Token = char>,
+{
+    let hex = (char('#'), many1::<String, _, _>(hex_digit())).and_then(|(_, hex)| {
+        parse_hex_color(&hex).map(CSSValue::Color).ok_or_else(|| {
+            <Input::Error as ParseError<char, _, _>>::StreamError::message_static_message(
+                "invalid hex color",
+            )
+        })
+    });
+
+    let component = (
+        number(),
+        optional(char('%')),
+        spaces(),
+        optional(char(',').or(char('/'))),
+        spaces(),
+    )
+        .map(|(n, percent, _, _, _)| (n, percent.is_some()));
+    let rgb = attempt((
+        string("rgb"),
+        optional(char('a')),
+        char('(').skip(spaces()),
+        many1::<Vec<(f32, bool)>, _, _>(component),
+        char(')'),
+    ))
+    .and_then(|(_, _, _, components, _)| {
+        let channel = |(n, percent): (f32, bool)| {
+            let n = if percent { n * 2.55 } else { n };
+            n.round().clamp(0.0, 255.0) as u8
+        };
+        let alpha = |(n, percent): (f32, bool)| {
+            let n = if percent { n / 100.0 } else { n };
+            (n * 255.0).round().clamp(0.0, 255.0) as u8
+        };
+        match components[..] {
+            [r, g, b] => Ok(CSSValue::Color(Color::rgb(
+                channel(r),
+                channel(g),
+                channel(b),
+            ))),
+            [r, g, b, a] => Ok(CSSValue::Color(Color {
+                a: alpha(a),
+                ..Color::rgb(channel(r), channel(g), channel(b))
+            })),
+            _ => Err(
+                <Input::Error as ParseError<char, _, _>>::StreamError::message_static_message(
+                    "rgb() takes three or four components",
+                ),
+            ),
+        }
+    });
+
+    choice((hex, rgb))
+}
+
+fn property_name<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+{
+    many1(letter().or(char('-')))
 }
 
 fn declaration<Input>() -> impl Parser<Input, Output = Declaration>
@@ -22,7 +139,7 @@ where
     Input: Stream<Token = char>,
 {
     (
-        many1(letter()).skip(spaces()),
+        property_name().skip(spaces()),
         char(':').skip(spaces()),
         css_value(),
     )
@@ -36,11 +153,62 @@ where
     sep_end_by(declaration().skip(spaces()), char(';').skip(spaces()))
 }
 
-fn selectors<Input>() -> impl Parser<Input, Output = Vec<Selector>>
+/// Parses a comma-separated list of complex selectors like `div.note a[href], p > b`.
+fn selectors<Input>() -> impl Parser<Input, Output = Vec<ComplexSelector>>
 where
     Input: Stream<Token = char>,
 {
-    sep_by(simple_selector().skip(spaces()), char(',').skip(spaces()))
+    sep_by1(complex_selector().skip(spaces()), char(',').skip(spaces()))
+}
+
+/// Parses compound selectors joined by combinators, like `div.note > a[href]`.
+fn complex_selector<Input>() -> impl Parser<Input, Output = ComplexSelector>
+where
+    Input: Stream<Token = char>,
+{
+    // whitespace before a `,` or a `{` is not a combinator, so the pair is tried as a whole
+    let rest = many::<Vec<_>, _, _>(attempt((combinator(), compound_selector())));
+    (compound_selector(), rest).map(|(first, rest)| {
+        let (combinators, compounds): (Vec<_>, Vec<_>) = rest.into_iter().unzip();
+        ComplexSelector {
+            compounds: std::iter::once(first).chain(compounds).collect(),
+            combinators,
+        }
+    })
+}
+
+fn combinator<Input>() -> impl Parser<Input, Output = Combinator>
+where
+    Input: Stream<Token = char>,
+{
+    let child = || char('>').skip(spaces()).map(|_| Combinator::Child);
+    choice((
+        (skip_many1(space()), optional(child()))
+            .map(|(_, child)| child.unwrap_or(Combinator::Descendant)),
+        child(),
+    ))
+}
+
+/// Parses a compound selector like `div.note`: simple selectors with nothing between them.
+fn compound_selector<Input>() -> impl Parser<Input, Output = CompoundSelector>
+where
+    Input: Stream<Token = char>,
+{
+    many1(simple_selector())
+}
+
+/// Parses the value of an attribute selector: a name, or a quoted string, in which a `,`
+/// or a `]` does not end the selector.
+fn attribute_value<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+{
+    let quoted = |quote| between(char(quote), char(quote), many(satisfy(move |c| c != quote)));
+    choice((
+        quoted('"'),
+        quoted('\''),
+        many1(alpha_num().or(char('-')).or(char('_'))),
+    ))
 }
 
 fn simple_selector<Input>() -> impl Parser<Input, Output = SimpleSelector>
@@ -48,21 +216,28 @@ where
     Input: Stream<Token = char>,
 {
     let universal_selector = char('*').map(|_| SimpleSelector::UniversalSelector);
-    let class_selector = (char('.'), many1(letter()))
+    let class_selector = (char('.'), many1(alpha_num().or(char('-')).or(char('_'))))
         .map(|(_, class_name)| SimpleSelector::ClassSelector { class_name });
+    let id_selector = (char('#'), many1(alpha_num().or(char('-')).or(char('_'))))
+        .map(|(_, id)| SimpleSelector::IdSelector { id });
+    // tag names like `h1` have digits after the first letter
+    let tag_name = (letter(), many::<String, _, _>(alpha_num()))
+        .map(|(first, rest)| format!("{}{}", first, rest));
+    // whitespace after the tag name is only skipped before a `[`, as it is a combinator
     let type_or_attribute_selector = (
-        many1(letter()).skip(spaces()),
+        tag_name,
         optional((
-            char('[').skip(spaces()),
-            many1(letter()),
-            choice((string("="), string("~="))),
-            many1(letter()),
+            attempt((spaces(), char('['))).skip(spaces()),
+            many1(letter().or(char('-'))),
+            optional((choice((string("="), string("~="))), attribute_value())),
             char(']'),
         )),
     )
         .and_then(|(tag_name, opts)| match opts {
-            Some((_, attribute, op, value, _)) => {
+            Some((_, attribute, op_value, _)) => {
+                let (op, value) = op_value.unwrap_or(("", String::new()));
                 let op = match op {
+                    "" => AttributeSelectorOp::Exist,
                     "=" => AttributeSelectorOp::Eq,
                     "~=" => AttributeSelectorOp::Contain,
                     _ => {
@@ -84,6 +259,7 @@ where
     choice((
         universal_selector,
         class_selector,
+        id_selector,
         type_or_attribute_selector,
     ))
 }
@@ -104,11 +280,29 @@ where
         })
 }
 
-pub fn stylesheet(raw: &str) -> Stylesheet {
+/// Parses `raw`, a comma-separated list of complex selectors like `div.note a[href], p > b`.
+pub fn selector_list(raw: &str) -> Result<Vec<ComplexSelector>, Error> {
+    (spaces(), selectors(), eof())
+        .parse(raw)
+        .map(|((_, selectors, _), _)| selectors)
+        .map_err(|e| Error::Css(format!("invalid selector `{}`: {}", raw, e)))
+}
+
+/// Parses a whole string as a color, such as `#1e90ff`, `rgb(0, 0, 255)` or `navy`.
+pub fn color_value(raw: &str) -> Option<Color> {
+    css_value()
+        .skip(eof())
+        .parse(raw.trim())
+        .ok()
+        .and_then(|(value, _)| Color::from_value(&value))
+}
+
+/// Parses `raw` into a stylesheet.
+pub fn stylesheet(raw: &str) -> Result<Stylesheet, Error> {
     rules()
         .parse(raw)
         .map(|(rules, _)| Stylesheet::new(rules))
-        .unwrap()
+        .map_err(|e| Error::Css(e.to_string()))
 }
 
 fn rules<Input>() -> impl Parser<Input, Output = Vec<Rule>>
@@ -122,8 +316,15 @@ where
 #[cfg(test)]
 mod tests {
     use crate::{
-        css::{declarations, rule, selectors, simple_selector},
-        cssom::{AttributeSelectorOp, CSSValue, Declaration, Rule, SimpleSelector},
+        css::{
+            color_value, css_value, declarations, rule, selector_list, selectors, simple_selector,
+            stylesheet,
+        },
+        cssom::{
+            AttributeSelectorOp, CSSValue, Color, Combinator, ComplexSelector, Declaration, Rule,
+            SimpleSelector, Unit,
+        },
+        error::Error,
     };
     use combine::Parser;
 
@@ -147,6 +348,112 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_css_value() {
+        assert_eq!(
+            css_value().parse("bold"),
+            Ok((CSSValue::Keyword("bold".to_string()), ""))
+        );
+        assert_eq!(
+            css_value().parse("lower-roman"),
+            Ok((CSSValue::Keyword("lower-roman".to_string()), ""))
+        );
+        assert_eq!(
+            css_value().parse("10px"),
+            Ok((CSSValue::Length(10.0, Unit::Px), ""))
+        );
+        assert_eq!(
+            css_value().parse("1.5em"),
+            Ok((CSSValue::Length(1.5, Unit::Em), ""))
+        );
+        assert_eq!(
+            css_value().parse("4ch"),
+            Ok((CSSValue::Length(4.0, Unit::Ch), ""))
+        );
+        assert_eq!(
+            css_value().parse("0"),
+            Ok((CSSValue::Length(0.0, Unit::Px), ""))
+        );
+    }
+
+    #[test]
+    fn test_color_value() {
+        assert_eq!(
+            css_value().parse("#f00"),
+            Ok((CSSValue::Color(Color::rgb(255, 0, 0)), ""))
+        );
+        assert_eq!(
+            css_value().parse("#1e90ff"),
+            Ok((CSSValue::Color(Color::rgb(0x1e, 0x90, 0xff)), ""))
+        );
+        assert_eq!(
+            css_value().parse("#00000080"),
+            Ok((
+                CSSValue::Color(Color {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 0x80
+                }),
+                ""
+            ))
+        );
+        assert_eq!(
+            css_value().parse("rgb(10, 20, 30)"),
+            Ok((CSSValue::Color(Color::rgb(10, 20, 30)), ""))
+        );
+        assert_eq!(
+            css_value().parse("rgba(100%, 0%, 0%, 0.5)"),
+            Ok((
+                CSSValue::Color(Color {
+                    r: 255,
+                    g: 0,
+                    b: 0,
+                    a: 128
+                }),
+                ""
+            ))
+        );
+        assert_eq!(
+            css_value().parse("rgb(1 2 3)"),
+            Ok((CSSValue::Color(Color::rgb(1, 2, 3)), ""))
+        );
+        assert_eq!(
+            css_value().parse("red"),
+            Ok((CSSValue::Keyword("red".to_string()), ""))
+        );
+        assert!(css_value().parse("#12345").is_err());
+    }
+
+    #[test]
+    fn test_color() {
+        assert_eq!(color_value("#1e90ff"), Some(Color::rgb(0x1e, 0x90, 0xff)));
+        assert_eq!(color_value(" navy "), Some(Color::rgb(0, 0, 128)));
+        assert_eq!(color_value("rgb(1, 2, 3)"), Some(Color::rgb(1, 2, 3)));
+        assert_eq!(color_value("nothing"), None);
+        assert_eq!(color_value("#fff extra"), None);
+    }
+
+    #[test]
+    fn test_hyphenated_declaration() {
+        assert_eq!(
+            declarations().parse("min-width: 10ch; font-weight: bold"),
+            Ok((
+                vec![
+                    Declaration {
+                        name: "min-width".to_string(),
+                        value: CSSValue::Length(10.0, Unit::Ch)
+                    },
+                    Declaration {
+                        name: "font-weight".to_string(),
+                        value: CSSValue::Keyword("bold".to_string())
+                    }
+                ],
+                ""
+            ))
+        );
+    }
+
     #[test]
     fn test_selectors() {
         assert_eq!(
@@ -158,10 +465,12 @@ mod tests {
                         attribute: "foo".to_string(),
                         op: AttributeSelectorOp::Eq,
                         value: "bar".to_string()
-                    },
+                    }
+                    .into(),
                     SimpleSelector::TypeSelector {
                         tag_name: "a".to_string(),
                     }
+                    .into()
                 ],
                 ""
             ))
@@ -207,6 +516,90 @@ mod tests {
                 ""
             ))
         );
+
+        assert_eq!(
+            simple_selector().parse("#main-1"),
+            Ok((
+                SimpleSelector::IdSelector {
+                    id: "main-1".to_string(),
+                },
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_selector_list() {
+        let a_href = SimpleSelector::AttributeSelector {
+            tag_name: "a".into(),
+            op: AttributeSelectorOp::Exist,
+            attribute: "href".into(),
+            value: "".into(),
+        };
+        assert_eq!(a_href.to_string(), "a[href]");
+        assert_eq!(
+            selector_list("div.note  a[href], h1>b").unwrap(),
+            vec![
+                ComplexSelector {
+                    compounds: vec![
+                        vec![
+                            SimpleSelector::TypeSelector {
+                                tag_name: "div".into()
+                            },
+                            SimpleSelector::ClassSelector {
+                                class_name: "note".into()
+                            }
+                        ],
+                        vec![a_href]
+                    ],
+                    combinators: vec![Combinator::Descendant],
+                },
+                ComplexSelector {
+                    compounds: vec![
+                        vec![SimpleSelector::TypeSelector {
+                            tag_name: "h1".into()
+                        }],
+                        vec![SimpleSelector::TypeSelector {
+                            tag_name: "b".into()
+                        }]
+                    ],
+                    combinators: vec![Combinator::Child],
+                }
+            ]
+        );
+        assert!(selector_list("").is_err());
+        assert!(selector_list("a >").is_err());
+        assert!(selector_list("a, ").is_err());
+    }
+
+    #[test]
+    fn test_selector_list_syntax() {
+        let list = |raw| {
+            selector_list(raw)
+                .unwrap()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(list(" ul>li  a.x#y , p "), ["ul > li a.x#y", "p"]);
+        assert_eq!(list("div > p a"), ["div > p a"]);
+        assert_eq!(list(r#"a[title="a,b"], b"#), [r#"a[title="a,b"]"#, "b"]);
+        assert_eq!(list("a[title='x y']"), [r#"a[title="x y"]"#]);
+        assert_eq!(list("a [data-x=b-1]"), ["a[data-x=b-1]"]);
+        assert!(selector_list("a > > b").is_err());
+        assert!(selector_list("a[title=\"x]").is_err());
+    }
+
+    #[test]
+    fn test_complex_rules() {
+        let stylesheet = stylesheet(".x a { color: red } p { text-transform: uppercase }").unwrap();
+        let selectors = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.selectors[0].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(selectors, [".x a", "p"]);
+        assert_eq!(stylesheet.rules[0].selectors[0].specificity(), 11);
     }
 
     #[test]
@@ -220,7 +613,8 @@ mod tests {
                         attribute: "foo".to_string(),
                         op: AttributeSelectorOp::Eq,
                         value: "bar".to_string()
-                    }],
+                    }
+                    .into()],
                     declarations: vec![]
                 },
                 ""
@@ -237,13 +631,15 @@ mod tests {
                             attribute: "foo".to_string(),
                             op: AttributeSelectorOp::Eq,
                             value: "bar".to_string()
-                        },
+                        }
+                        .into(),
                         SimpleSelector::AttributeSelector {
                             tag_name: "testtest".to_string(),
                             attribute: "piyo".to_string(),
                             op: AttributeSelectorOp::Contain,
                             value: "guoo".to_string()
                         }
+                        .into()
                     ],
                     declarations: vec![]
                 },
@@ -260,7 +656,8 @@ mod tests {
                         attribute: "foo".to_string(),
                         op: AttributeSelectorOp::Eq,
                         value: "bar".to_string()
-                    }],
+                    }
+                    .into()],
                     declarations: vec![
                         Declaration {
                             name: "aa".to_string(),
@@ -276,4 +673,9 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_invalid_stylesheet() {
+        assert!(matches!(stylesheet("p { color: red"), Err(Error::Css(_))));
+    }
 }
@@ -1,8 +1,9 @@
-use crate::dom::{Node, NodeType};
+use crate::dom::{Document, Node, NodeId, NodeType};
+use std::fmt;
 
 /// `Stylesheet` represents a single stylesheet.
 /// It consists of multiple rules, which are called "rule-list" in the standard (https://www.w3.org/TR/css-syntax-3/).
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
 }
@@ -14,27 +15,22 @@ impl Stylesheet {
 }
 
 /// `Rule` represents a single CSS rule.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Rule {
-    pub selectors: Vec<Selector>, // a comma-separated list of selectors
+    pub selectors: Vec<ComplexSelector>, // a comma-separated list of selectors
     pub declarations: Vec<Declaration>,
 }
 
 impl Rule {
-    pub fn matches(&self, n: &Box<Node>) -> bool {
-        self.selectors.iter().any(|s| s.matches(n))
+    /// Returns whether a selector of the rule matches the node `id` of `document`.
+    pub fn matches(&self, document: &Document, id: NodeId) -> bool {
+        self.selectors.iter().any(|s| s.matches(document, id))
     }
 }
 
-/// NOTE: This is not compliant to the standard for simplicity.
-///
-/// In the standard, *a selector* is *a chain* of one or more sequences of simple selectors separated by combinators,
-/// where a sequence of simple selectors is a chain of simple selectors that are not separated by a combinator.
-pub type Selector = SimpleSelector;
-
 /// `SimpleSelector` represents a simple selector defined in the following standard:
 /// https://www.w3.org/TR/selectors-3/#selector-syntax
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum SimpleSelector {
     UniversalSelector,
     TypeSelector {
@@ -49,12 +45,15 @@ pub enum SimpleSelector {
     ClassSelector {
         class_name: String,
     },
+    IdSelector {
+        id: String,
+    },
     // TODO (enhancement): support multiple attribute selectors like `a[href=bar][ping=foo]`
     // TODO (enhancement): support more attribute selectors
 }
 
 impl SimpleSelector {
-    pub fn matches(&self, n: &Box<Node>) -> bool {
+    pub fn matches(&self, n: &Node) -> bool {
         match self {
             SimpleSelector::UniversalSelector => true,
             SimpleSelector::TypeSelector { tag_name } => match n.node_type {
@@ -70,23 +69,23 @@ impl SimpleSelector {
                 NodeType::Element(ref e) => {
                     e.tag_name.as_str() == tag_name
                         && match op {
+                            AttributeSelectorOp::Exist => e.attributes.contains_key(attribute),
                             AttributeSelectorOp::Eq => e.attributes.get(attribute) == Some(value),
                             AttributeSelectorOp::Contain => e
                                 .attributes
                                 .get(attribute)
-                                .map(|value_| {
-                                    value_
-                                        .split_ascii_whitespace()
-                                        .find(|v| v == value)
-                                        .is_some()
-                                })
+                                .map(|value_| value_.split_ascii_whitespace().any(|v| v == value))
                                 .unwrap_or(false),
                         }
                 }
                 _ => false,
             },
             SimpleSelector::ClassSelector { class_name } => match n.node_type {
-                NodeType::Element(ref e) => e.attributes.get("class") == Some(class_name),
+                NodeType::Element(ref e) => e.has_class(class_name),
+                _ => false,
+            },
+            SimpleSelector::IdSelector { id } => match n.node_type {
+                NodeType::Element(ref e) => e.id() == Some(id),
                 _ => false,
             },
         }
@@ -97,14 +96,138 @@ impl SimpleSelector {
             SimpleSelector::UniversalSelector => 0,
             SimpleSelector::TypeSelector { .. } => 1,
             SimpleSelector::AttributeSelector { .. } | SimpleSelector::ClassSelector { .. } => 10,
+            SimpleSelector::IdSelector { .. } => 100,
+        }
+    }
+}
+
+/// `CompoundSelector` is simple selectors written with nothing between them, like `div.note`,
+/// all of which match the same element.
+pub type CompoundSelector = Vec<SimpleSelector>;
+
+/// `Combinator` is how the elements two compound selectors match are related.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Combinator {
+    /// Whitespace: the second is inside the first.
+    Descendant,
+    /// `>`: the second is a child of the first.
+    Child,
+}
+
+/// `ComplexSelector` is compound selectors joined by combinators, like `div.note > a[href]`.
+/// The last compound selector matches the element itself, and the others its ancestors.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ComplexSelector {
+    pub compounds: Vec<CompoundSelector>,
+    /// The combinator after each compound selector but the last.
+    pub combinators: Vec<Combinator>,
+}
+
+/// A simple selector on its own is a complex selector of one compound selector.
+impl From<SimpleSelector> for ComplexSelector {
+    fn from(selector: SimpleSelector) -> Self {
+        ComplexSelector {
+            compounds: vec![vec![selector]],
+            combinators: vec![],
         }
     }
 }
 
+impl ComplexSelector {
+    /// Returns the specificity of the selector, that of its simple selectors added up.
+    pub fn specificity(&self) -> u32 {
+        self.compounds
+            .iter()
+            .flatten()
+            .map(SimpleSelector::specificity)
+            .sum()
+    }
+
+    /// Returns the ID the rightmost compound selector requires of the element it matches,
+    /// like `b` of `div > #b.note`, with which the element can be looked up instead of searched for.
+    pub fn id(&self) -> Option<&str> {
+        self.compounds.last()?.iter().find_map(|s| match s {
+            SimpleSelector::IdSelector { id } => Some(id.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns whether the selector matches the node `id` of `document`.
+    pub fn matches(&self, document: &Document, id: NodeId) -> bool {
+        !self.compounds.is_empty() && self.matches_from(self.compounds.len() - 1, document, id)
+    }
+
+    /// Returns whether the compound selectors up to the `i`th match `id` and its ancestors.
+    fn matches_from(&self, i: usize, document: &Document, id: NodeId) -> bool {
+        if !self.compounds[i]
+            .iter()
+            .all(|s| s.matches(document.node(id)))
+        {
+            return false;
+        }
+        if i == 0 {
+            return true;
+        }
+        match self.combinators[i - 1] {
+            Combinator::Child => document
+                .parent(id)
+                .is_some_and(|parent| self.matches_from(i - 1, document, parent)),
+            Combinator::Descendant => document
+                .ancestors(id)
+                .any(|ancestor| self.matches_from(i - 1, document, ancestor)),
+        }
+    }
+}
+
+/// Writes the selector back as CSS.
+impl fmt::Display for ComplexSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, compound) in self.compounds.iter().enumerate() {
+            match i.checked_sub(1).map(|i| self.combinators[i]) {
+                Some(Combinator::Descendant) => write!(f, " ")?,
+                Some(Combinator::Child) => write!(f, " > ")?,
+                None => {}
+            }
+            compound.iter().try_for_each(|s| write!(f, "{}", s))?;
+        }
+        Ok(())
+    }
+}
+
 /// `AttributeSelectorOp` is an operator which is allowed to use.
+/// Writes the selector back as CSS.
+impl fmt::Display for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SimpleSelector::UniversalSelector => write!(f, "*"),
+            SimpleSelector::TypeSelector { tag_name } => write!(f, "{}", tag_name),
+            SimpleSelector::AttributeSelector {
+                tag_name,
+                op,
+                attribute,
+                value,
+            } => {
+                let op = match op {
+                    AttributeSelectorOp::Exist => return write!(f, "{}[{}]", tag_name, attribute),
+                    AttributeSelectorOp::Eq => "=",
+                    AttributeSelectorOp::Contain => "~=",
+                };
+                let name = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+                match !value.is_empty() && value.chars().all(name) {
+                    true => write!(f, "{}[{}{}{}]", tag_name, attribute, op, value),
+                    false => write!(f, "{}[{}{}{:?}]", tag_name, attribute, op, value),
+                }
+            }
+            SimpleSelector::ClassSelector { class_name } => write!(f, ".{}", class_name),
+            SimpleSelector::IdSelector { id } => write!(f, "#{}", id),
+        }
+    }
+}
+
 /// See https://www.w3.org/TR/selectors-3/#attribute-selectors to check the full list of available operators.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum AttributeSelectorOp {
+    Exist,   // no operator: the attribute is present
     Eq,      // =
     Contain, // ~=
 }
@@ -116,7 +239,7 @@ pub enum AttributeSelectorOp {
 /// - properties, which are mostly used in "qualified rules" like `.foo {bar: piyo}` https://www.w3.org/Style/CSS/all-descriptors.en.html
 ///
 /// For simplicity, we handle two types of declarations together.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Declaration {
     pub name: String,
     pub value: CSSValue,
@@ -127,15 +250,289 @@ pub struct Declaration {
 #[derive(Debug, PartialEq, Clone)]
 pub enum CSSValue {
     Keyword(String),
+    Length(f32, Unit),
+    Color(Color),
+}
+
+/// Writes the value back as CSS.
+impl fmt::Display for CSSValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CSSValue::Keyword(keyword) => write!(f, "{}", keyword),
+            CSSValue::Length(length, unit) => {
+                let unit = match unit {
+                    Unit::Px => "px",
+                    Unit::Em => "em",
+                    Unit::Ch => "ch",
+                };
+                write!(f, "{}{}", length, unit)
+            }
+            CSSValue::Color(Color { r, g, b, a: 255 }) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            CSSValue::Color(Color { r, g, b, a }) => {
+                write!(f, "rgba({}, {}, {}, {:.2})", r, g, b, *a as f32 / 255.0)
+            }
+        }
+    }
+}
+
+/// `Unit` represents some of the length units defined at [CSS Values and Units Module Level 3](https://www.w3.org/TR/css-values-3/#lengths).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Unit {
+    Px,
+    Em,
+    Ch,
+}
+
+/// `Color` represents a `<color>` value defined at [CSS Color Module Level 3](https://www.w3.org/TR/css-color-3/).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    /// Looks up a named color such as `red` or `rebeccapurple`, case-insensitively.
+    /// See https://www.w3.org/TR/css-color-3/#svg-color
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("transparent") {
+            return Some(Self {
+                a: 0,
+                ..Self::rgb(0, 0, 0)
+            });
+        }
+        let name = name.to_ascii_lowercase();
+        NAMED_COLORS
+            .binary_search_by_key(&name.as_str(), |(n, _)| n)
+            .ok()
+            .map(|i| {
+                let rgb = NAMED_COLORS[i].1;
+                Self::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+            })
+    }
+
+    /// Resolves a computed value into a color, accepting both color values and named color keywords.
+    pub fn from_value(value: &CSSValue) -> Option<Self> {
+        match value {
+            CSSValue::Color(color) => Some(*color),
+            CSSValue::Keyword(keyword) => Self::from_name(keyword),
+            _ => None,
+        }
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.a == 0
+    }
 }
 
+/// The named colors of https://www.w3.org/TR/css-color-3/#svg-color, sorted by name.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use crate::{
-        cssom::{AttributeSelectorOp, SimpleSelector},
+        cssom::{AttributeSelectorOp, CSSValue, Color, SimpleSelector, Unit, NAMED_COLORS},
         dom::Element,
     };
 
+    #[test]
+    fn test_display() {
+        let selector = SimpleSelector::AttributeSelector {
+            tag_name: "a".into(),
+            op: AttributeSelectorOp::Contain,
+            attribute: "rel".into(),
+            value: "nofollow".into(),
+        };
+        assert_eq!(selector.to_string(), "a[rel~=nofollow]");
+        assert_eq!(
+            SimpleSelector::ClassSelector {
+                class_name: "note".into()
+            }
+            .to_string(),
+            ".note"
+        );
+        assert_eq!(CSSValue::Length(1.5, Unit::Em).to_string(), "1.5em");
+        assert_eq!(
+            CSSValue::Color(Color::rgb(255, 0, 16)).to_string(),
+            "#ff0010"
+        );
+        assert_eq!(
+            CSSValue::Color(Color::from_name("transparent").unwrap()).to_string(),
+            "rgba(0, 0, 0, 0.00)"
+        );
+    }
+
+    #[test]
+    fn test_named_colors() {
+        assert!(NAMED_COLORS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(Color::from_name("red"), Some(Color::rgb(255, 0, 0)));
+        assert_eq!(
+            Color::from_name("RebeccaPurple"),
+            Some(Color::rgb(0x66, 0x33, 0x99))
+        );
+        assert!(Color::from_name("transparent").unwrap().is_transparent());
+        assert_eq!(Color::from_name("bold"), None);
+        assert_eq!(
+            Color::from_value(&CSSValue::Keyword("navy".into())),
+            Some(Color::rgb(0, 0, 128))
+        );
+    }
+
     #[test]
     fn test_universal_selector_behaviour() {
         let e = &Element::new(
@@ -267,7 +664,7 @@ mod tests {
             "p".to_string(),
             [
                 ("id".to_string(), "test".to_string()),
-                ("class".to_string(), "testclass".to_string()),
+                ("class".to_string(), "testclass other".to_string()),
             ]
             .into_iter()
             .collect(),
@@ -282,6 +679,14 @@ mod tests {
             true
         );
 
+        assert_eq!(
+            (SimpleSelector::ClassSelector {
+                class_name: "other".into(),
+            })
+            .matches(e),
+            true
+        );
+
         assert_eq!(
             (SimpleSelector::ClassSelector {
                 class_name: "invalid".into(),
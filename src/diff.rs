@@ -0,0 +1,239 @@
+use crate::dom::{Node, NodeType};
+use std::collections::HashMap;
+
+/// How many pairs of children are compared at most to find those two versions of an element
+/// share. Children past that, in the middle of a long list that changed, are taken as replaced.
+const MAX_COMPARISONS: usize = 1 << 20;
+
+/// `Change` is a subtree that differs between two versions of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A subtree only the new document has, at its path there.
+    Added(Vec<usize>),
+    /// A subtree only the old document has, at its path there.
+    Removed(Vec<usize>),
+    /// A node both documents have, at its path in each, whose attributes or text changed.
+    /// Its children may have changed as well, which are changes of their own.
+    Changed { old: Vec<usize>, new: Vec<usize> },
+}
+
+/// `Diff` is how a document changed between two loads: its changes in document order, and
+/// where the nodes it kept went, so that what was focused or scrolled to can be found again.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+    /// The path in the new document of each node of the old one that it kept.
+    kept: HashMap<Vec<usize>, Vec<usize>>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the path in the new document of the node at `path` in the old one,
+    /// unless it was removed.
+    pub fn map(&self, path: &[usize]) -> Option<&[usize]> {
+        self.kept.get(path).map(|path| path.as_slice())
+    }
+}
+
+/// Returns whether `old` and `new` are taken to be the same node, changed or not:
+/// both texts, comments, doctypes or documents, or elements with the same tag name and `id`.
+fn same_node(old: &Node, new: &Node) -> bool {
+    match (&old.node_type, &new.node_type) {
+        (NodeType::Text(_), NodeType::Text(_))
+        | (NodeType::Comment(_), NodeType::Comment(_))
+        | (NodeType::Doctype(_), NodeType::Doctype(_))
+        | (NodeType::Document, NodeType::Document) => true,
+        (NodeType::Element(old), NodeType::Element(new)) => {
+            old.tag_name == new.tag_name && old.id() == new.id()
+        }
+        _ => false,
+    }
+}
+
+/// Compares `old`, a document as it was loaded before, with `new`, as it was loaded again.
+pub fn diff(old: &Node, new: &Node) -> Diff {
+    let mut diff = Diff::default();
+    if same_node(old, new) {
+        compare(old, new, &mut vec![], &mut vec![], &mut diff);
+    } else {
+        diff.changes.push(Change::Removed(vec![]));
+        diff.changes.push(Change::Added(vec![]));
+    }
+    diff
+}
+
+/// Compares `old` and `new`, the same node at `old_path` and `new_path`, and their children.
+fn compare(
+    old: &Node,
+    new: &Node,
+    old_path: &mut Vec<usize>,
+    new_path: &mut Vec<usize>,
+    diff: &mut Diff,
+) {
+    diff.kept.insert(old_path.clone(), new_path.clone());
+    if old.node_type != new.node_type {
+        diff.changes.push(Change::Changed {
+            old: old_path.clone(),
+            new: new_path.clone(),
+        });
+    }
+    let pairs = common(&old.children, &new.children);
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in pairs
+        .into_iter()
+        .chain([(old.children.len(), new.children.len())])
+    {
+        for i in i..next_i {
+            diff.changes.push(Change::Removed(child_path(old_path, i)));
+        }
+        for j in j..next_j {
+            diff.changes.push(Change::Added(child_path(new_path, j)));
+        }
+        if next_i < old.children.len() {
+            old_path.push(next_i);
+            new_path.push(next_j);
+            compare(
+                &old.children[next_i],
+                &new.children[next_j],
+                old_path,
+                new_path,
+                diff,
+            );
+            old_path.pop();
+            new_path.pop();
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+}
+
+fn child_path(path: &[usize], i: usize) -> Vec<usize> {
+    [path, &[i]].concat()
+}
+
+/// Pairs up the children `old` and `new` share, in order: as many as there can be of those
+/// that did not change at all, and between those, of those that are the same nodes changed.
+fn common(old: &[Box<Node>], new: &[Box<Node>]) -> Vec<(usize, usize)> {
+    let unchanged = |old: &Node, new: &Node| old == new;
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in longest_common(old, new, unchanged)
+        .into_iter()
+        .chain([(old.len(), new.len())])
+    {
+        pairs.extend(
+            longest_common(&old[i..next_i], &new[j..next_j], same_node)
+                .into_iter()
+                .map(|(k, l)| (i + k, j + l)),
+        );
+        if next_i < old.len() {
+            pairs.push((next_i, next_j));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    pairs
+}
+
+/// Returns the pairs of a longest common subsequence of `old` and `new`, whose nodes are
+/// taken as common if `f` holds for them.
+fn longest_common(
+    old: &[Box<Node>],
+    new: &[Box<Node>],
+    f: impl Fn(&Node, &Node) -> bool,
+) -> Vec<(usize, usize)> {
+    // most changes leave the start and the end of a list of children as they were
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| f(old, new))
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| f(old, new))
+        .count();
+    let mut pairs = (0..prefix).map(|i| (i, i)).collect::<Vec<_>>();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    let (n, m) = (old_end - prefix, new_end - prefix);
+    if n * m <= MAX_COMPARISONS {
+        let (old, new) = (&old[prefix..old_end], &new[prefix..new_end]);
+        // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+        let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lengths[i][j] = if f(&old[i], &new[j]) {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if f(&old[i], &new[j]) {
+                pairs.push((prefix + i, prefix + j));
+                (i, j) = (i + 1, j + 1);
+            } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (old_end + k, new_end + k)));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Change};
+    use crate::{
+        dom::{AttrMap, Element, Node},
+        html,
+    };
+
+    fn parse(html: &str) -> Box<Node> {
+        Element::new("".into(), AttrMap::new(), html::parse(html).unwrap())
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = parse(r#"<h1>news</h1><ul><li>a</li><li>b</li></ul><p id="x">old</p><hr>"#);
+        let new = parse(r#"<h1>News!</h1><ul><li>new</li><li>a</li></ul><p id="y">old</p><hr>"#);
+        let diff = diff(&old, &new);
+        assert_eq!(
+            diff.changes,
+            [
+                Change::Changed {
+                    old: vec![0, 0],
+                    new: vec![0, 0]
+                },
+                Change::Added(vec![1, 0]),
+                Change::Removed(vec![1, 1]),
+                Change::Removed(vec![2]),
+                Change::Added(vec![2]),
+            ]
+        );
+        // positions on the old page are found on the new one
+        assert_eq!(diff.map(&[1, 0]), Some(&[1, 1][..]));
+        assert_eq!(diff.map(&[1, 0, 0]), Some(&[1, 1, 0][..]));
+        assert_eq!(diff.map(&[1, 1]), None);
+        assert_eq!(diff.map(&[3]), Some(&[3][..]));
+        assert_eq!(diff.map(&[2]), None);
+
+        assert!(super::diff(&old, &old).is_empty());
+        assert_eq!(
+            super::diff(&old, &parse("<p>other</p>")).changes,
+            [
+                Change::Removed(vec![0]),
+                Change::Removed(vec![1]),
+                Change::Removed(vec![2]),
+                Change::Removed(vec![3]),
+                Change::Added(vec![0])
+            ]
+        );
+    }
+}
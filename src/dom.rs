@@ -1,45 +1,676 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, cell::OnceCell, collections::HashMap};
 
-use crate::cssom::Selector;
+use crate::{
+    css,
+    cssom::ComplexSelector,
+    error::{Error, Result},
+};
 
 pub type AttrMap = HashMap<String, String>;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Node {
     pub node_type: NodeType,
     pub children: Vec<Box<Node>>,
+    /// Where the node was in the HTML it was parsed from, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+}
+
+/// Nodes are equal when their trees are, wherever they were parsed from.
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.node_type == other.node_type && self.children == other.children
+    }
+}
+
+/// `Span` is where a node was in the HTML it was parsed from: the bytes from `start` up to
+/// `end`, its start tag through its end tag for an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Returns the line and the column the span starts at in `source`, the HTML it was
+    /// parsed from, counted from 1 with the column in characters.
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        let before = source.get(..self.start).unwrap_or(source);
+        let line = before.matches('\n').count() + 1;
+        let column = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count()
+            + 1;
+        (line, column)
+    }
 }
 
 impl Node {
+    /// Makes the root of a document whose top-level nodes are `children`.
+    pub fn document(children: Vec<Box<Node>>) -> Box<Node> {
+        Box::new(Node {
+            node_type: NodeType::Document,
+            children,
+            span: None,
+        })
+    }
+
     pub fn to_text(&self) -> Option<String> {
         match &self.node_type {
-            NodeType::Element { .. } => None,
             NodeType::Text(Text { data }) => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn element(&self) -> Option<&Element> {
+        match &self.node_type {
+            NodeType::Element(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Concatenates the text of all descendant text nodes.
+    pub fn text_content(&self) -> String {
+        std::iter::once(self)
+            .chain(self.descendants())
+            .filter_map(|n| match &n.node_type {
+                NodeType::Text(Text { data }) => Some(data.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the descendants of the node in document order, without the node itself.
+    /// Ancestors and siblings, which a node does not know, are found through a `Document`.
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants {
+            stack: vec![self.children.iter()],
+        }
+    }
+
+    /// Returns the children that are elements, leaving out text.
+    pub fn children_elements(&self) -> impl Iterator<Item = &Node> {
+        self.children
+            .iter()
+            .map(|c| c.as_ref())
+            .filter(|c| c.element().is_some())
+    }
+
+    /// Returns the descendant reached by following `path`, a list of child indices.
+    pub fn node_at(&self, path: &[usize]) -> Option<&Node> {
+        match path.split_first() {
+            Some((&i, rest)) => self.children.get(i)?.node_at(rest),
+            None => Some(self),
+        }
+    }
+
+    pub fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        match path.split_first() {
+            Some((&i, rest)) => self.children.get_mut(i)?.node_at_mut(rest),
+            None => Some(self),
+        }
+    }
+
+    /// Writes the node back as HTML, with its text and attribute values escaped.
+    /// An element without a tag name, like the root of a page, is written as its children.
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        self.write_html(&mut html);
+        html
+    }
+
+    fn write_html(&self, html: &mut String) {
+        let element = match &self.node_type {
+            NodeType::Text(Text { data }) => return html.push_str(&escape(data, false)),
+            NodeType::Comment(Comment { data }) => {
+                return html.push_str(&format!("<!--{}-->", data))
+            }
+            NodeType::Doctype(Doctype { name }) => {
+                return html.push_str(&format!("<!DOCTYPE {}>", name))
+            }
+            NodeType::Document => {
+                self.children
+                    .iter()
+                    .for_each(|child| child.write_html(html));
+                return;
+            }
+            NodeType::Element(element) => element,
+        };
+        let tag_name = element.tag_name.as_str();
+        if tag_name.is_empty() {
+            self.children
+                .iter()
+                .for_each(|child| child.write_html(html));
+            return;
+        }
+        html.push('<');
+        html.push_str(tag_name);
+        // sorted, so that the same tree is always written the same
+        let mut attributes = element.attributes.iter().collect::<Vec<_>>();
+        attributes.sort();
+        for (name, value) in attributes {
+            html.push(' ');
+            html.push_str(name);
+            if !value.is_empty() {
+                html.push_str(&format!("=\"{}\"", escape(value, true)));
+            }
+        }
+        html.push('>');
+        if is_void(tag_name) {
+            return;
+        }
+        for child in &self.children {
+            match &child.node_type {
+                // the text of a script or a stylesheet is not HTML, so it is not escaped
+                NodeType::Text(Text { data }) if RAW_TEXT_ELEMENTS.contains(&tag_name) => {
+                    html.push_str(data)
+                }
+                _ => child.write_html(html),
+            }
+        }
+        html.push_str(&format!("</{}>", tag_name));
+    }
+
+    /// Dumps the tree under the node as JSON, which `from_json` reads back without parsing HTML.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a DOM tree can always be written as JSON")
+    }
+
+    /// Reads back a tree `to_json` dumped.
+    pub fn from_json(json: &str) -> Result<Box<Node>> {
+        serde_json::from_str(json).map_err(|e| Error::Json(e.to_string()))
+    }
+
+    /// Returns the paths of the descendants for which `f` holds, in document order.
+    pub fn paths(&self, f: &impl Fn(&Node) -> bool) -> Vec<Vec<usize>> {
+        let mut paths = vec![];
+        let mut path = vec![];
+        self.collect_paths(f, &mut path, &mut paths);
+        paths
+    }
+
+    fn collect_paths(
+        &self,
+        f: &impl Fn(&Node) -> bool,
+        path: &mut Vec<usize>,
+        paths: &mut Vec<Vec<usize>>,
+    ) {
+        for (i, child) in self.children.iter().enumerate() {
+            path.push(i);
+            if f(child) {
+                paths.push(path.clone());
+            }
+            child.collect_paths(f, path, paths);
+            path.pop();
         }
     }
 }
 
-pub fn select<'a>(node: &'a Node, selector: &'a Selector) -> Vec<&'a Box<Node>> {
-    node.children
+/// `Descendants` walks the descendants of a node in document order, without recursion.
+#[derive(Debug, Clone)]
+pub struct Descendants<'a> {
+    /// The children left to visit at each depth, the deepest last.
+    stack: Vec<std::slice::Iter<'a, Box<Node>>>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<&'a Node> {
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(node) => {
+                    self.stack.push(node.children.iter());
+                    return Some(node);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Elements that have no end tag or children.
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Returns whether elements named `tag_name` are void: they have no end tag or children.
+pub fn is_void(tag_name: &str) -> bool {
+    VOID_ELEMENTS
         .iter()
-        .filter(|&n| selector.matches(n))
-        .chain(node.children.iter().flat_map(|n| select(n, selector)))
+        .any(|void| void.eq_ignore_ascii_case(tag_name))
+}
+
+/// Elements whose text is written as it is.
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+/// Escapes `text` for HTML: `&`, `<` and `>`, and `"` in an attribute value.
+fn escape(text: &str, in_attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if in_attribute => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns `node` itself and its descendants that `selector` matches, in document order.
+/// A simple selector is turned into a complex one with `into()`.
+pub fn select<'a>(node: &'a Node, selector: &ComplexSelector) -> Vec<&'a Node> {
+    let document = Document::new(node);
+    std::iter::once(document.root())
+        .chain(document.descendants(document.root()))
+        .filter(|&id| selector.matches(&document, id))
+        .map(|id| document.node(id))
         .collect()
 }
 
-#[derive(Debug, PartialEq)]
+/// Returns the descendants of `node` that `selector`, a selector list like
+/// `div.note > a[href], h1`, matches, in document order. A selector that cannot be parsed
+/// matches nothing.
+pub fn query<'a>(node: &'a Node, selector: &str) -> Vec<&'a Node> {
+    Document::new(node).query(selector)
+}
+
+/// Returns the first descendant of `node` in document order that `selector` matches,
+/// as `query` does.
+pub fn query_first<'a>(node: &'a Node, selector: &str) -> Option<&'a Node> {
+    Document::new(node).query_first(selector)
+}
+
+/// Returns the paths of the descendants of `node` that `selector` matches, as `query` does,
+/// so that they can be found again in the tree to change them.
+pub fn query_paths(node: &Node, selector: &str) -> Vec<Vec<usize>> {
+    Document::new(node).query_paths(selector)
+}
+
+/// `Step` is a step of a path like `html/body/div[2]/p`: the children of an element that
+/// have a tag name, or any (`*`), and which of them, counted from 1, if it says.
+struct Step<'p> {
+    tag_name: &'p str,
+    position: Option<usize>,
+}
+
+impl<'p> Step<'p> {
+    fn parse(step: &'p str) -> Option<Step<'p>> {
+        let (tag_name, position) = match step.strip_suffix(']') {
+            Some(step) => {
+                let (tag_name, position) = step.split_once('[')?;
+                (
+                    tag_name,
+                    Some(position.trim().parse().ok().filter(|&p| p > 0)?),
+                )
+            }
+            None => (step, None),
+        };
+        let tag_name = tag_name.trim();
+        let valid = tag_name == "*" || tag_name.chars().all(|c| c.is_ascii_alphanumeric());
+        (valid && !tag_name.is_empty()).then_some(Step { tag_name, position })
+    }
+
+    /// Returns the children of `node` the step goes to.
+    fn apply<'a>(&self, node: &'a Node) -> Vec<&'a Node> {
+        node.children_elements()
+            .filter(|child| {
+                self.tag_name == "*"
+                    || child
+                        .element()
+                        .is_some_and(|e| e.tag_name.eq_ignore_ascii_case(self.tag_name))
+            })
+            .enumerate()
+            .filter(|(i, _)| self.position.is_none_or(|p| p == i + 1))
+            .map(|(_, child)| child)
+            .collect()
+    }
+}
+
+/// Returns the elements `path`, like `html/body/div[2]/p`, leads to from `node`, in document
+/// order. Each step goes to the children with a tag name, or to any with `*`, and a number in
+/// brackets picks one of them, counted from 1. A path that cannot be parsed leads nowhere.
+pub fn query_path<'a>(node: &'a Node, path: &str) -> Vec<&'a Node> {
+    let steps = path
+        .trim()
+        .trim_start_matches('/')
+        .split('/')
+        .map(Step::parse)
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+    if steps.is_empty() {
+        return vec![];
+    }
+    steps.iter().fold(vec![node], |nodes, step| {
+        nodes
+            .into_iter()
+            .flat_map(|node| step.apply(node))
+            .collect()
+    })
+}
+
+/// `NodeId` is where a node is in a `Document`: its place in document order, counted from
+/// the root at 0. It stays the same for the styled node and the layout objects made from the
+/// node, as they are made from the same tree, until the tree changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(pub usize);
+
+/// `Links` are the nodes next to a node in a `Document`.
+#[derive(Debug, Clone, Copy)]
+struct Links {
+    parent: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+    first_child: Option<NodeId>,
+    /// The last of its descendants in document order, or itself.
+    last_descendant: NodeId,
+}
+
+/// `Index` is what a `Document` knows of a tree besides its nodes: where each node is next
+/// to the others, and which elements have each `id` and `name`. It does not borrow the tree,
+/// so it can be kept with the tree and made again only when the tree changes.
+/// It stands in for an arena of nodes: the tree stays the `Box<Node>`s the parser, layout and
+/// serialization work on, and the `NodeId`s and links an arena would keep are kept here.
+#[derive(Debug, Clone, Default)]
+pub struct Index {
+    links: Vec<Links>,
+    /// The elements with each `id`, in document order.
+    ids: HashMap<String, Vec<NodeId>>,
+    /// The first `<a>` with each `name`, which URL fragments refer to like IDs.
+    names: HashMap<String, NodeId>,
+}
+
+impl Index {
+    /// Indexes the tree under `root`.
+    pub fn new(root: &Node) -> Index {
+        let mut index = Index::default();
+        index.add(root, None);
+        index
+    }
+
+    /// Adds `node` and its descendants, returning the ID of `node`.
+    fn add(&mut self, node: &Node, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.links.len());
+        self.links.push(Links {
+            parent,
+            previous_sibling: None,
+            next_sibling: None,
+            first_child: None,
+            last_descendant: id,
+        });
+        if let Some(e) = node.element() {
+            if let Some(name) = e.id() {
+                self.ids.entry(name.to_string()).or_default().push(id);
+            }
+            if let Some(name) = e.attributes.get("name").filter(|_| e.tag_name == "a") {
+                self.names.entry(name.clone()).or_insert(id);
+            }
+        }
+        let mut previous: Option<NodeId> = None;
+        for child in &node.children {
+            let child = self.add(child, Some(id));
+            match previous {
+                Some(previous) => {
+                    self.links[previous.0].next_sibling = Some(child);
+                    self.links[child.0].previous_sibling = Some(previous);
+                }
+                None => self.links[id.0].first_child = Some(child),
+            }
+            previous = Some(child);
+        }
+        self.links[id.0].last_descendant = NodeId(self.links.len() - 1);
+        id
+    }
+}
+
+/// `Document` is an index over the nodes of a tree, in document order, where each node knows
+/// its parent and siblings as well as its children, for traversals upward and sideways, which
+/// the tree itself cannot make. The tree still owns its nodes.
+/// As it borrows the tree, it cannot outlive a change to the tree: it is made again from the
+/// changed tree, and so are the `NodeId`s kept from it. A tree that is kept, like a page's,
+/// keeps its `Index` too, so that one is made from the two without indexing the tree again.
+#[derive(Debug)]
+pub struct Document<'a> {
+    root: &'a Node,
+    index: Cow<'a, Index>,
+    /// The nodes in document order, listed when one is first asked for.
+    nodes: OnceCell<Vec<&'a Node>>,
+}
+
+impl<'a> Document<'a> {
+    /// Indexes the tree under `root`.
+    pub fn new(root: &'a Node) -> Document<'a> {
+        Document {
+            root,
+            index: Cow::Owned(Index::new(root)),
+            nodes: OnceCell::new(),
+        }
+    }
+
+    /// Makes a document of the tree under `root` with `index`, which was made from it.
+    pub fn with_index(root: &'a Node, index: &'a Index) -> Document<'a> {
+        Document {
+            root,
+            index: Cow::Borrowed(index),
+            nodes: OnceCell::new(),
+        }
+    }
+
+    fn links(&self, id: NodeId) -> &Links {
+        &self.index.links[id.0]
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn node(&self, id: NodeId) -> &'a Node {
+        let nodes = self.nodes.get_or_init(|| {
+            let mut nodes = Vec::with_capacity(self.index.links.len());
+            let mut stack = vec![self.root];
+            while let Some(node) = stack.pop() {
+                nodes.push(node);
+                stack.extend(node.children.iter().rev().map(|child| child.as_ref()));
+            }
+            nodes
+        });
+        nodes[id.0]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.links(id).parent
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links(id).previous_sibling
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.links(id).next_sibling
+    }
+
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + use<'_, 'a> {
+        std::iter::successors(self.links(id).first_child, |&child| {
+            self.next_sibling(child)
+        })
+    }
+
+    /// Returns the ancestors of `id`, its parent first.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + use<'_, 'a> {
+        std::iter::successors(self.parent(id), |&parent| self.parent(parent))
+    }
+
+    /// Returns the descendants of `id` in document order.
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> {
+        (id.0 + 1..=self.links(id).last_descendant.0).map(NodeId)
+    }
+
+    /// Returns the first element whose `id` is `name`, as `getElementById` does.
+    pub fn element_by_id(&self, name: &str) -> Option<NodeId> {
+        self.elements_with_id(name).first().copied()
+    }
+
+    /// Returns the elements whose `id` is `name`, which should be one but may be more.
+    pub fn elements_with_id(&self, name: &str) -> &[NodeId] {
+        self.index.ids.get(name).map_or(&[], |ids| ids.as_slice())
+    }
+
+    /// Returns the element a URL fragment `name` refers to: the first with the `id`,
+    /// or else the first `<a>` with the `name`.
+    pub fn anchor(&self, name: &str) -> Option<NodeId> {
+        self.element_by_id(name)
+            .or_else(|| self.index.names.get(name).copied())
+    }
+
+    /// Returns the elements named `tag_name` in document order.
+    fn elements_named<'b>(
+        &'b self,
+        tag_name: &'b str,
+    ) -> impl Iterator<Item = NodeId> + use<'a, 'b> {
+        self.descendants(self.root()).filter(move |&id| {
+            self.node(id)
+                .element()
+                .is_some_and(|e| e.tag_name.eq_ignore_ascii_case(tag_name))
+        })
+    }
+
+    /// Returns the `<head>` element, if the document has one.
+    pub fn head(&self) -> Option<NodeId> {
+        self.elements_named("head").next()
+    }
+
+    /// Returns the `<body>` element, if the document has one.
+    pub fn body(&self) -> Option<NodeId> {
+        self.elements_named("body").next()
+    }
+
+    /// Returns the text of the first `<title>`, with its whitespace collapsed, unless it is empty.
+    pub fn title(&self) -> Option<String> {
+        let title = self
+            .node(self.elements_named("title").next()?)
+            .text_content();
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!title.is_empty()).then_some(title)
+    }
+
+    /// Returns the CSS of each `<style>` element, in document order.
+    pub fn stylesheets(&self) -> Vec<String> {
+        self.elements_named("style")
+            .map(|id| self.node(id).text_content())
+            .collect()
+    }
+
+    /// Returns the `href` of the first `<base>` that has one, which relative URLs are
+    /// resolved against.
+    pub fn base_url(&self) -> Option<&'a str> {
+        self.elements_named("base")
+            .find_map(|id| self.node(id).element()?.attributes.get("href"))
+            .map(|href| href.as_str())
+    }
+
+    /// Returns the node reached from the root by following `path`, a list of child indices.
+    pub fn id_at(&self, path: &[usize]) -> Option<NodeId> {
+        path.iter()
+            .try_fold(self.root(), |id, &i| self.children(id).nth(i))
+    }
+
+    /// Writes the document back as HTML, after a doctype unless it has its own.
+    pub fn serialize(&self) -> String {
+        let root = self.node(self.root());
+        let has_doctype = root
+            .children
+            .iter()
+            .any(|c| matches!(c.node_type, NodeType::Doctype(_)));
+        match has_doctype {
+            true => root.to_html(),
+            false => format!("<!DOCTYPE html>{}", root.to_html()),
+        }
+    }
+
+    /// Returns the path from the root to `id`, the inverse of `id_at`.
+    pub fn path(&self, id: NodeId) -> Vec<usize> {
+        let mut path = std::iter::successors(Some(id), |&id| self.parent(id))
+            .filter(|&id| id != self.root())
+            .map(|id| {
+                std::iter::successors(self.previous_sibling(id), |&id| self.previous_sibling(id))
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        path.reverse();
+        path
+    }
+
+    /// Returns the descendants of the root that `selector`, a selector list like
+    /// `div.note > a[href], h1`, matches, in document order. A selector that cannot be parsed
+    /// matches nothing.
+    pub fn query(&self, selector: &str) -> Vec<&'a Node> {
+        self.matching(selector).map(|id| self.node(id)).collect()
+    }
+
+    /// Returns the first descendant of the root in document order that `selector` matches,
+    /// as `query` does.
+    pub fn query_first(&self, selector: &str) -> Option<&'a Node> {
+        self.matching(selector).next().map(|id| self.node(id))
+    }
+
+    /// Returns the paths of the descendants of the root that `selector` matches, as `query`
+    /// does, so that they can be found again in the tree to change them.
+    pub fn query_paths(&self, selector: &str) -> Vec<Vec<usize>> {
+        self.matching(selector).map(|id| self.path(id)).collect()
+    }
+
+    fn matching(&self, selector: &str) -> impl Iterator<Item = NodeId> + use<'_, 'a> {
+        let selectors = css::selector_list(selector).unwrap_or_default();
+        // selectors that each require an ID are matched only against the elements with it
+        let candidates = match selectors.iter().map(|s| s.id()).collect::<Option<Vec<_>>>() {
+            Some(ids) => {
+                let mut candidates = ids
+                    .into_iter()
+                    .flat_map(|id| self.elements_with_id(id).iter().copied())
+                    .filter(|&id| id != self.root())
+                    .collect::<Vec<_>>();
+                candidates.sort();
+                candidates.dedup();
+                candidates
+            }
+            None => self.descendants(self.root()).collect(),
+        };
+        candidates
+            .into_iter()
+            .filter(move |&id| selectors.iter().any(|s| s.matches(self, id)))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum NodeType {
     Element(Element),
     Text(Text),
+    /// A `<!-- comment -->`, which is not rendered.
+    Comment(Comment),
+    /// The `<!DOCTYPE>` of a document, which is not rendered.
+    Doctype(Doctype),
+    /// The root of a parsed document, whose children are its top-level nodes.
+    Document,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Element {
     pub tag_name: String,
     pub attributes: AttrMap,
 }
 
 impl Element {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(tag_name: String, attributes: AttrMap, children: Vec<Box<Node>>) -> Box<Node> {
         Box::new(Node {
             node_type: NodeType::Element(Element {
@@ -47,20 +678,310 @@ impl Element {
                 attributes,
             }),
             children,
+            span: None,
         })
     }
+
+    pub fn id(&self) -> Option<&str> {
+        self.attributes.get("id").map(|id| id.as_str())
+    }
+
+    /// Returns the classes in the `class` attribute, which separates them with whitespace.
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.attributes
+            .get("class")
+            .into_iter()
+            .flat_map(|classes| classes.split_ascii_whitespace())
+    }
+
+    pub fn has_class(&self, class_name: &str) -> bool {
+        self.classes().any(|class| class == class_name)
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Text {
     pub data: String,
 }
 
 impl Text {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(data: String) -> Box<Node> {
         Box::new(Node {
             node_type: NodeType::Text(Text { data }),
             children: vec![],
+            span: None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub data: String,
+}
+
+impl Comment {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(data: String) -> Box<Node> {
+        Box::new(Node {
+            node_type: NodeType::Comment(Comment { data }),
+            children: vec![],
+            span: None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Doctype {
+    /// What follows `<!DOCTYPE`, like `html`.
+    pub name: String,
+}
+
+impl Doctype {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(name: String) -> Box<Node> {
+        Box::new(Node {
+            node_type: NodeType::Doctype(Doctype { name }),
+            children: vec![],
+            span: None,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        query, query_first, query_path, select, AttrMap, Document, Element, Node, NodeId, Text,
+    };
+    use crate::{css, cssom::SimpleSelector, html};
+
+    #[test]
+    fn test_query() {
+        let html = r#"<div class="note"><p><a href="/a">a</a><a>b</a></p></div><div><a href="/c">c</a><h1><a href="/d">d</a></h1></div>"#;
+        let dom = Element::new("".into(), Default::default(), html::parse(html).unwrap());
+        let texts = |nodes: Vec<&Node>| nodes.iter().map(|n| n.text_content()).collect::<Vec<_>>();
+
+        assert_eq!(texts(query(&dom, "div.note a[href]")), ["a"]);
+        assert_eq!(texts(query(&dom, "div a[href]")), ["a", "c", "d"]);
+        assert_eq!(texts(query(&dom, "div > a")), ["c"]);
+        assert_eq!(texts(query(&dom, "div>h1 a, p > a")), ["a", "b", "d"]);
+        assert_eq!(texts(query(&dom, "h1")), ["d"]);
+        assert_eq!(
+            query_first(&dom, "a[href]").map(|n| n.text_content()),
+            Some("a".into())
+        );
+        assert_eq!(query_first(&dom, "table"), None);
+        assert!(query(&dom, "div >").is_empty());
+        assert!(query(&dom, "> a").is_empty());
+    }
+
+    #[test]
+    fn test_query_path() {
+        let html = "<html><body><div><p>a</p></div><div><p>b</p><p>c</p><span>d</span></div></body></html>";
+        let dom = Node::document(html::parse(html).unwrap());
+        let texts = |path| {
+            query_path(&dom, path)
+                .iter()
+                .map(|n| n.text_content())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts("html/body/div[2]/p"), ["b", "c"]);
+        assert_eq!(texts("/html/body/div/p"), ["a", "b", "c"]);
+        assert_eq!(texts("html/body/div[2]/p[2]"), ["c"]);
+        assert_eq!(texts("html/body/*[2]/*[3]"), ["d"]);
+        assert_eq!(texts("html/BODY/div[3]"), Vec::<String>::new());
+        for invalid in ["", "html//body", "html/body/div[0]", "html/div[x]", "a b"] {
+            assert!(query_path(&dom, invalid).is_empty(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let html = r#"<div class="note"><p><a href="/a">a</a></p><div><a>b</a></div></div>"#;
+        let dom = &html::parse(html).unwrap()[0];
+        let texts = |nodes: Vec<&Node>| nodes.iter().map(|n| n.text_content()).collect::<Vec<_>>();
+        let selector = |raw| css::selector_list(raw).unwrap().remove(0);
+
+        // the node itself is matched as well as its descendants
+        let div = SimpleSelector::TypeSelector {
+            tag_name: "div".into(),
+        };
+        assert_eq!(texts(select(dom, &div.into())), ["ab", "b"]);
+        assert_eq!(texts(select(dom, &selector("div.note"))), ["ab"]);
+        assert_eq!(texts(select(dom, &selector("div > a"))), ["b"]);
+        assert_eq!(texts(select(dom, &selector("div.note a"))), ["a", "b"]);
+        assert!(select(dom, &selector("p > div")).is_empty());
+    }
+
+    #[test]
+    fn test_to_html() {
+        let html = r#"<div class="a" id="b"><p>text <b>bold</b></p><img alt="x" src="a.png"><input disabled><p></p></div>"#;
+        let dom = Element::new("".into(), Default::default(), html::parse(html).unwrap());
+        assert_eq!(dom.to_html(), html);
+        // what is written parses back into the same tree, with its doctype and comments
+        let written = Document::new(&dom).serialize();
+        assert!(written.starts_with("<!DOCTYPE html><div"));
+        let reparsed = Node::document(html::parse(&written).unwrap());
+        assert_eq!(Document::new(&reparsed).serialize(), written);
+        let html = "<!DOCTYPE html><!-- top --><p>a<!--b--></p>";
+        let dom = Node::document(html::parse(html).unwrap());
+        assert_eq!(Document::new(&dom).serialize(), html);
+
+        let mut attributes = AttrMap::new();
+        attributes.insert("title".into(), r#"say "<hi>" & go"#.into());
+        let p = Element::new(
+            "p".into(),
+            attributes,
+            vec![Text::new("1 < 2 & 3 > 2".into())],
+        );
+        assert_eq!(
+            p.to_html(),
+            r#"<p title="say &quot;&lt;hi&gt;&quot; &amp; go">1 &lt; 2 &amp; 3 &gt; 2</p>"#
+        );
+        let style = Element::new(
+            "style".into(),
+            AttrMap::new(),
+            vec![Text::new("a > b {}".into())],
+        );
+        assert_eq!(style.to_html(), "<style>a > b {}</style>");
+    }
+
+    #[test]
+    fn test_classes() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("id".into(), "main".into());
+        attributes.insert("class".into(), " note\twide  note-2 ".into());
+        let node = Element::new("div".into(), attributes, vec![]);
+        let element = node.element().unwrap();
+        assert_eq!(element.id(), Some("main"));
+        assert_eq!(
+            element.classes().collect::<Vec<_>>(),
+            ["note", "wide", "note-2"]
+        );
+        assert!(element.has_class("wide"));
+        assert!(!element.has_class("note wide"));
+        assert!(!element.has_class("not"));
+
+        let node = Element::new("p".into(), AttrMap::new(), vec![]);
+        let element = node.element().unwrap();
+        assert_eq!(element.id(), None);
+        assert_eq!(element.classes().count(), 0);
+    }
+
+    #[test]
+    fn test_descendants() {
+        let html = "<div><p>a<b>b</b></p>c</div><hr>";
+        let dom = Element::new("".into(), AttrMap::new(), html::parse(html).unwrap());
+        let labels = dom
+            .descendants()
+            .map(|n| match n.element() {
+                Some(e) => e.tag_name.clone(),
+                None => n.to_text().unwrap(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["div", "p", "a", "b", "b", "c", "hr"]);
+        assert_eq!(dom.text_content(), "abc");
+        assert_eq!(Text::new("a".into()).descendants().count(), 0);
+
+        let div = &dom.children[0];
+        let children = div.children_elements().collect::<Vec<_>>();
+        assert_eq!(children, [div.children[0].as_ref()]);
+    }
+
+    #[test]
+    fn test_json() {
+        let html = r#"<div class="a"><p>text <b>bold</b></p><img src="a.png"></div>"#;
+        let dom = Element::new("".into(), Default::default(), html::parse(html).unwrap());
+        assert_eq!(Node::from_json(&dom.to_json()).unwrap(), dom);
+        assert_eq!(
+            Text::new("hi".into()).to_json(),
+            r#"{"node_type":{"Text":{"data":"hi"}},"children":[]}"#
+        );
+        assert!(Node::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_document() {
+        let html = "<ul><li>a</li><li>b<b>c</b></li></ul><p>d</p>";
+        let dom = Element::new("".into(), Default::default(), html::parse(html).unwrap());
+        let document = Document::new(&dom);
+        let ul = document.id_at(&[0]).unwrap();
+        let second = document.id_at(&[0, 1]).unwrap();
+        let b = document.id_at(&[0, 1, 1]).unwrap();
+        assert_eq!(document.node(b).text_content(), "c");
+        assert_eq!(document.parent(b), Some(second));
+        assert_eq!(
+            document.ancestors(b).collect::<Vec<_>>(),
+            [second, ul, document.root()]
+        );
+        assert_eq!(document.previous_sibling(second), document.id_at(&[0, 0]));
+        assert_eq!(document.next_sibling(second), None);
+        assert_eq!(document.next_sibling(ul), document.id_at(&[1]));
+        assert_eq!(document.children(ul).count(), 2);
+        assert_eq!(document.descendants(ul).count(), 6);
+        assert_eq!(document.path(b), [0, 1, 1]);
+        assert_eq!(document.path(document.root()), Vec::<usize>::new());
+        assert_eq!(document.id_at(&[0, 2]), None);
+    }
+
+    #[test]
+    fn test_document_after_change() {
+        let mut dom = Node::document(html::parse("<p>a</p><p>b</p>").unwrap());
+        let document = Document::new(&dom);
+        assert_eq!(document.next_sibling(NodeId(1)), document.id_at(&[1]));
+
+        dom.children.remove(0);
+        dom.children[0].children.push(Text::new("c".into()));
+        let document = Document::new(&dom);
+        let p = document.id_at(&[0]).unwrap();
+        assert_eq!(document.next_sibling(p), None);
+        assert_eq!(document.children(p).count(), 2);
+        assert_eq!(document.node(p).text_content(), "bc");
+        assert_eq!(document.path(NodeId(3)), [0, 1]);
+        assert_eq!(document.id_at(&[1]), None);
+    }
+
+    #[test]
+    fn test_document_accessors() {
+        let html = r#"<html><head><title> A
+            page </title><base href="/docs/"><style>p {}</style></head><body><style>a {}</style><p>text</p></body></html>"#;
+        let dom = Node::document(html::parse(html).unwrap());
+        let document = Document::new(&dom);
+        assert_eq!(document.head(), document.id_at(&[0, 0]));
+        assert_eq!(document.body(), document.id_at(&[0, 1]));
+        assert_eq!(document.title(), Some("A page".into()));
+        assert_eq!(document.stylesheets(), ["p {}", "a {}"]);
+        assert_eq!(document.base_url(), Some("/docs/"));
+
+        let dom = Node::document(html::parse("<p>text</p><title> </title>").unwrap());
+        let document = Document::new(&dom);
+        assert_eq!(document.head(), None);
+        assert_eq!(document.title(), None);
+        assert!(document.stylesheets().is_empty());
+        assert_eq!(document.base_url(), None);
+    }
+
+    #[test]
+    fn test_element_by_id() {
+        let html = r#"<p id="a">a</p><a name="b">b</a><div><p id="a">c</p><a id="b" class="x">d</a></div>"#;
+        let dom = Element::new("".into(), AttrMap::new(), html::parse(html).unwrap());
+        let document = Document::new(&dom);
+        // the first element with an ID wins, and an ID wins over a name
+        assert_eq!(document.element_by_id("a"), document.id_at(&[0]));
+        assert_eq!(document.element_by_id("b"), document.id_at(&[2, 1]));
+        assert_eq!(document.anchor("b"), document.id_at(&[2, 1]));
+        assert_eq!(document.element_by_id("c"), None);
+
+        let texts = |selector| {
+            query(&dom, selector)
+                .iter()
+                .map(|n| n.text_content())
+                .collect::<Vec<_>>()
+        };
+        // as a selector, an ID matches every element with it
+        assert_eq!(texts("#a"), ["a", "c"]);
+        assert_eq!(texts("div > #b.x, #a"), ["a", "c", "d"]);
+        assert_eq!(texts("div #a"), ["c"]);
+        assert_eq!(texts("#a, p"), ["a", "c"]);
+    }
+}
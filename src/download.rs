@@ -0,0 +1,575 @@
+use crate::{
+    cssom::Stylesheet,
+    dom::{Element, Node, Text},
+    error::{Error, Result},
+    page::Page,
+    request::{self, ResponseBody, Transport},
+};
+use reqwest::{
+    header::{CONTENT_RANGE, ETAG, LAST_MODIFIED},
+    Response, StatusCode,
+};
+use std::sync::{
+    mpsc::{self, Receiver, Sender, TryRecvError},
+    Arc,
+};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+/// Returns the name to save a response from `url` as: the `filename` of its
+/// `Content-Disposition`, or else the last segment of the URL's path.
+fn file_name(url: &str, content_disposition: Option<&str>) -> String {
+    let from_header = content_disposition.and_then(|value| {
+        value.split(';').find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            (name.trim().eq_ignore_ascii_case("filename"))
+                .then(|| value.trim().trim_matches('"').to_string())
+        })
+    });
+    let from_url = || {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        path.rsplit('/').next().map(str::to_string)
+    };
+    from_header
+        .or_else(from_url)
+        // a name from the server must not lead out of the current directory
+        .map(|name| {
+            name.rsplit(['/', '\\'])
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .filter(|name| !name.is_empty() && name != "." && name != "..")
+        .unwrap_or_else(|| "download".into())
+}
+
+/// Returns what tells whether the body of `response` is still the same when the rest of it is
+/// asked for: its `ETag`, unless that is weak, or else its `Last-Modified`.
+fn validator(response: &Response) -> Option<String> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    header(ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(LAST_MODIFIED))
+        .map(str::to_string)
+}
+
+/// Returns the offset the part of a body that `content_range`, like `bytes 100-199/200`,
+/// describes starts at.
+fn range_start(content_range: &str) -> Option<u64> {
+    let range = content_range.strip_prefix("bytes ")?;
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+/// Formats a number of bytes for people, like `512 B` or `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// `Download` is a response that is not a page, waiting for the user to choose where to save it.
+#[derive(Debug)]
+pub struct Download {
+    pub url: String,
+    pub content_type: String,
+    /// The size of the body, if the server tells.
+    pub length: Option<u64>,
+    /// The name the file is offered to be saved as.
+    pub file_name: String,
+    validator: Option<String>,
+    body: ResponseBody,
+}
+
+impl Download {
+    pub fn new(url: &str, body: ResponseBody) -> Download {
+        let response = body.response();
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let content_type = header(reqwest::header::CONTENT_TYPE).unwrap_or_default();
+        let disposition = header(reqwest::header::CONTENT_DISPOSITION);
+        // the length of an encoded body is not the length of the file it decodes to
+        let length = response
+            .content_length()
+            .filter(|_| header(reqwest::header::CONTENT_ENCODING).is_none());
+        Download {
+            url: url.to_string(),
+            content_type,
+            length,
+            file_name: file_name(url, disposition.as_deref()),
+            validator: validator(response),
+            body,
+        }
+    }
+
+    /// Starts writing the body to `path` on the network runtime.
+    pub fn save(self, path: &str) -> Saving {
+        let (sender, updates) = mpsc::channel();
+        let transport = self.body.transport().clone();
+        let body = self.body;
+        let destination = path.to_string();
+        let validator = self.validator;
+        request::runtime().spawn(async move {
+            match File::create(&destination).await {
+                Ok(file) => {
+                    let _ = sender.send(Ok(Update::Started(0, validator)));
+                    write(body, file, &sender).await;
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e.into()));
+                }
+            }
+        });
+        Saving {
+            url: self.url,
+            path: path.to_string(),
+            length: self.length,
+            written: 0,
+            validator: None,
+            transport,
+            updates,
+        }
+    }
+}
+
+/// `Update` is what the task writing a download sends at a time.
+#[derive(Debug)]
+enum Update {
+    /// Writing started at this offset into the file, 0 unless it was resumed, of a body
+    /// that this validator tells apart from a changed one.
+    Started(u64, Option<String>),
+    /// This many more bytes were written.
+    Written(u64),
+}
+
+/// Writes the rest of `body` to `file`, telling `sender` how far it got.
+async fn write(mut body: ResponseBody, mut file: File, sender: &Sender<Result<Update>>) {
+    loop {
+        let result = match body.chunk().await {
+            Ok(None) => break,
+            Ok(Some(chunk)) => file
+                .write_all(&chunk)
+                .await
+                .map(|()| Update::Written(chunk.len() as u64))
+                .map_err(Error::from),
+            Err(e) => Err(e),
+        };
+        let failed = result.is_err();
+        // the receiver is gone when the download was abandoned
+        if sender.send(result).is_err() || failed {
+            break;
+        }
+    }
+    if let Err(e) = file.flush().await {
+        let _ = sender.send(Err(e.into()));
+    }
+}
+
+/// `SaveProgress` is what has become of a download being saved since it was last polled.
+#[derive(Debug)]
+pub enum SaveProgress {
+    Pending,
+    /// More of the body was written.
+    Written,
+    /// The whole body was written, or writing it failed.
+    Done(Result<u64>),
+}
+
+/// `Saving` is a download being written to a file in the background.
+#[derive(Debug)]
+pub struct Saving {
+    pub url: String,
+    pub path: String,
+    pub length: Option<u64>,
+    pub written: u64,
+    /// The `ETag` or `Last-Modified` of the body, to ask for the rest of it only if it has not
+    /// changed since.
+    validator: Option<String>,
+    /// What the download was requested with, to ask for the rest of it with.
+    transport: Arc<Transport>,
+    updates: Receiver<Result<Update>>,
+}
+
+impl Saving {
+    pub fn poll(&mut self) -> SaveProgress {
+        let mut written = false;
+        loop {
+            match self.updates.try_recv() {
+                Ok(Ok(Update::Started(offset, validator))) => {
+                    self.written = offset;
+                    self.validator = validator;
+                    written = true;
+                }
+                Ok(Ok(Update::Written(n))) => {
+                    self.written += n;
+                    written = true;
+                }
+                Ok(Err(e)) => return SaveProgress::Done(Err(e)),
+                Err(TryRecvError::Empty) if written => return SaveProgress::Written,
+                Err(TryRecvError::Empty) => return SaveProgress::Pending,
+                Err(TryRecvError::Disconnected) => return SaveProgress::Done(Ok(self.written)),
+            }
+        }
+    }
+
+    /// Starts saving the rest of the download after what was written, asking the server for
+    /// only that part if the body has not changed since. If the server sends all of it
+    /// instead, or another part, or the body cannot be told to be the same, the file is
+    /// written again from the start.
+    pub fn resume(&self) -> Saving {
+        let (sender, updates) = mpsc::channel();
+        let (url, path) = (self.url.clone(), self.path.clone());
+        let transport = self.transport.clone();
+        let (offset, if_range) = match &self.validator {
+            Some(validator) => (self.written, Some(validator.clone())),
+            None => (0, None),
+        };
+        request::runtime().spawn(async move {
+            let mut response =
+                request::get_range(&transport, &url, offset, if_range.as_deref()).await;
+            let starts_at = |response: &Result<Response>| {
+                let response = response.as_ref().ok()?;
+                let content_range = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+                (response.status() == StatusCode::PARTIAL_CONTENT)
+                    .then(|| range_start(content_range))
+                    .flatten()
+            };
+            let rest = starts_at(&response) == Some(offset);
+            let partial = response
+                .as_ref()
+                .is_ok_and(|response| response.status() == StatusCode::PARTIAL_CONTENT);
+            if partial && !rest {
+                // a part other than the rest is of no use
+                response = request::get_range(&transport, &url, 0, None).await;
+            }
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+            let file = if rest && offset > 0 {
+                OpenOptions::new().append(true).open(&path).await
+            } else {
+                File::create(&path).await
+            };
+            match file {
+                Ok(file) => {
+                    let start = if rest { offset } else { 0 };
+                    let _ = sender.send(Ok(Update::Started(start, validator(&response))));
+                    write(ResponseBody::new(response, transport), file, &sender).await;
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e.into()));
+                }
+            }
+        });
+        Saving {
+            url: self.url.clone(),
+            path: self.path.clone(),
+            length: self.length,
+            written: self.written,
+            validator: self.validator.clone(),
+            transport: self.transport.clone(),
+            updates,
+        }
+    }
+
+    /// Describes how far the download is, like `saving a.pdf: 1.5 MB of 3.0 MB (50%)`.
+    pub fn describe(&self) -> String {
+        format!("saving {}: {}", self.path, self.progress())
+    }
+
+    /// Tells how much has been written, like `1.5 MB of 3.0 MB (50%)`.
+    fn progress(&self) -> String {
+        let written = format_size(self.written);
+        match self.length.filter(|&length| length > 0) {
+            Some(length) => format!(
+                "{} of {} ({}%)",
+                written,
+                format_size(length),
+                self.written * 100 / length
+            ),
+            None => written,
+        }
+    }
+}
+
+/// The URL of the page listing the downloads.
+pub const DOWNLOADS_URL: &str = "wev:downloads";
+
+/// `TransferState` is how a download that is being or was saved stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferState {
+    Active,
+    Completed,
+    /// Saving failed, for the reason given. It can be resumed.
+    Failed(String),
+}
+
+/// `Transfer` is a download the user chose to save, while and after it is saved.
+#[derive(Debug)]
+pub struct Transfer {
+    pub saving: Saving,
+    pub state: TransferState,
+}
+
+/// `Downloads` are the downloads saved in this session, the first started first.
+#[derive(Debug, Default)]
+pub struct Downloads {
+    transfers: Vec<Transfer>,
+}
+
+impl Downloads {
+    pub fn transfers(&self) -> &[Transfer] {
+        &self.transfers
+    }
+
+    /// Starts saving `download` to `path`.
+    pub fn start(&mut self, download: Download, path: &str) {
+        self.add(download.save(path));
+    }
+
+    fn add(&mut self, saving: Saving) {
+        self.transfers.push(Transfer {
+            saving,
+            state: TransferState::Active,
+        });
+    }
+
+    /// Returns the downloads being saved.
+    pub fn active(&self) -> impl Iterator<Item = &Saving> {
+        self.transfers
+            .iter()
+            .filter(|transfer| transfer.state == TransferState::Active)
+            .map(|transfer| &transfer.saving)
+    }
+
+    /// Takes in how far the active downloads got. Returns whether any did, and a message for
+    /// each that finished.
+    pub fn poll(&mut self) -> (bool, Vec<String>) {
+        let mut changed = false;
+        let mut finished = vec![];
+        for transfer in &mut self.transfers {
+            if transfer.state != TransferState::Active {
+                continue;
+            }
+            let saving = &mut transfer.saving;
+            match saving.poll() {
+                SaveProgress::Pending => {}
+                SaveProgress::Written => changed = true,
+                SaveProgress::Done(Ok(size)) => {
+                    finished.push(format!("saved {} to {}", format_size(size), saving.path));
+                    transfer.state = TransferState::Completed;
+                    changed = true;
+                }
+                SaveProgress::Done(Err(e)) => {
+                    finished.push(format!("failed to save {}: {}", saving.path, e));
+                    transfer.state = TransferState::Failed(e.to_string());
+                    changed = true;
+                }
+            }
+        }
+        (changed, finished)
+    }
+
+    /// Resumes the failed download numbered `number`, counting from 1 as they are listed,
+    /// or the last one that failed without a number. Returns what was resumed.
+    pub fn resume(&mut self, number: Option<usize>) -> std::result::Result<String, String> {
+        let is_failed = |transfer: &Transfer| matches!(transfer.state, TransferState::Failed(_));
+        let index = match number {
+            Some(number) => number
+                .checked_sub(1)
+                .filter(|&i| i < self.transfers.len())
+                .ok_or_else(|| format!("no download {}", number))?,
+            None => self
+                .transfers
+                .iter()
+                .rposition(is_failed)
+                .ok_or("no download has failed")?,
+        };
+        let transfer = &mut self.transfers[index];
+        if !is_failed(transfer) {
+            return Err(format!("download {} has not failed", index + 1));
+        }
+        transfer.saving = transfer.saving.resume();
+        transfer.state = TransferState::Active;
+        Ok(format!("resuming {}", transfer.saving.path))
+    }
+
+    /// Builds a page listing the downloads with how far each got.
+    pub fn to_page(&self) -> Page {
+        let paragraph =
+            |text: String| Element::new("p".into(), Default::default(), vec![Text::new(text)]);
+        let entries = if self.transfers.is_empty() {
+            vec![paragraph("No downloads yet.".into())]
+        } else {
+            self.transfers
+                .iter()
+                .enumerate()
+                .map(|(i, transfer)| {
+                    let saving = &transfer.saving;
+                    let state = match &transfer.state {
+                        TransferState::Active => saving.progress(),
+                        TransferState::Completed => {
+                            format!("done, {}", format_size(saving.written))
+                        }
+                        TransferState::Failed(reason) => format!(
+                            "failed at {}: {} (`:resume {}` to resume)",
+                            saving.progress(),
+                            reason,
+                            i + 1
+                        ),
+                    };
+                    paragraph(format!(
+                        "{}. {} from {}: {}",
+                        i + 1,
+                        saving.path,
+                        saving.url,
+                        state
+                    ))
+                })
+                .collect()
+        };
+        let heading = Element::new(
+            "p".into(),
+            Default::default(),
+            vec![Element::new(
+                "b".into(),
+                Default::default(),
+                vec![Text::new("Downloads".into())],
+            )],
+        );
+        let dom = Element::new(
+            "".into(),
+            Default::default(),
+            std::iter::once(heading)
+                .chain(entries)
+                .collect::<Vec<Box<Node>>>(),
+        );
+
+        Page::new(DOWNLOADS_URL, dom, Stylesheet::new(vec![]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{file_name, format_size, range_start, Downloads, Saving, TransferState, Update};
+    use crate::error::{Error, Result};
+    use std::sync::{mpsc, Arc};
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("https://a.com/docs/b.pdf?x=1", None), "b.pdf");
+        assert_eq!(
+            file_name(
+                "https://a.com/get",
+                Some(r#"attachment; filename="report.zip""#)
+            ),
+            "report.zip"
+        );
+        assert_eq!(
+            file_name(
+                "https://a.com/get",
+                Some("attachment; filename=../../.bashrc")
+            ),
+            ".bashrc"
+        );
+        assert_eq!(file_name("https://a.com/", None), "download");
+    }
+
+    #[test]
+    fn test_range_start() {
+        assert_eq!(range_start("bytes 100-199/200"), Some(100));
+        assert_eq!(range_start("bytes 0-99/*"), Some(0));
+        assert_eq!(range_start("bytes */200"), None);
+        assert_eq!(range_start("items 1-2/3"), None);
+    }
+
+    #[test]
+    fn test_saving() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+
+        let (sender, mut saving) = saving("a.pdf");
+        sender.send(Ok(Update::Written(1024))).unwrap();
+        saving.poll();
+        assert_eq!(saving.describe(), "saving a.pdf: 1.0 KB of 2.0 KB (50%)");
+    }
+
+    #[test]
+    fn test_downloads() {
+        let mut downloads = Downloads::default();
+        assert!(downloads
+            .to_page()
+            .dom()
+            .text_content()
+            .contains("No downloads"));
+        let (done, saving_a) = saving("a.pdf");
+        let (failing, saving_b) = saving("b.pdf");
+        downloads.add(saving_a);
+        downloads.add(saving_b);
+        assert_eq!(downloads.poll(), (false, vec![]));
+
+        done.send(Ok(Update::Started(0, None))).unwrap();
+        done.send(Ok(Update::Written(2048))).unwrap();
+        drop(done);
+        failing.send(Ok(Update::Written(1024))).unwrap();
+        failing
+            .send(Err(Error::Decode("cut short".into())))
+            .unwrap();
+        let (changed, messages) = downloads.poll();
+        assert!(changed);
+        assert_eq!(messages[0], "saved 2.0 KB to a.pdf");
+        assert!(messages[1].starts_with("failed to save b.pdf"));
+        assert_eq!(downloads.active().count(), 0);
+        assert_eq!(downloads.transfers()[0].state, TransferState::Completed);
+
+        let text = downloads.to_page().dom().text_content();
+        assert!(text.contains("1. a.pdf from https://a.com/a.pdf: done, 2.0 KB"));
+        assert!(text.contains("2. b.pdf from https://a.com/b.pdf: failed at 1.0 KB of 2.0 KB"));
+        assert!(text.contains(":resume 2"));
+        assert_eq!(
+            downloads.resume(Some(1)),
+            Err("download 1 has not failed".into())
+        );
+        assert_eq!(downloads.resume(Some(3)), Err("no download 3".into()));
+    }
+
+    /// Returns a download of 2 KB being saved to `path`, and where its updates are sent.
+    fn saving(path: &str) -> (mpsc::Sender<Result<Update>>, Saving) {
+        let (sender, updates) = mpsc::channel();
+        let saving = Saving {
+            url: format!("https://a.com/{}", path),
+            path: path.into(),
+            length: Some(2048),
+            written: 0,
+            validator: None,
+            transport: Arc::default(),
+            updates,
+        };
+        (sender, saving)
+    }
+}
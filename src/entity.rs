@@ -0,0 +1,344 @@
+use std::borrow::Cow;
+
+/// The named character references that `decode` replaces, sorted by name: the common ones,
+/// for Latin-1, punctuation, arrows, math and Greek letters.
+const ENTITIES: &[(&str, char)] = &[
+    ("AElig", 'Æ'),
+    ("Aacute", 'Á'),
+    ("Acirc", 'Â'),
+    ("Agrave", 'À'),
+    ("Alpha", 'Α'),
+    ("Aring", 'Å'),
+    ("Atilde", 'Ã'),
+    ("Auml", 'Ä'),
+    ("Beta", 'Β'),
+    ("Ccedil", 'Ç'),
+    ("Dagger", '‡'),
+    ("Delta", 'Δ'),
+    ("ETH", 'Ð'),
+    ("Eacute", 'É'),
+    ("Ecirc", 'Ê'),
+    ("Egrave", 'È'),
+    ("Euml", 'Ë'),
+    ("Gamma", 'Γ'),
+    ("Iacute", 'Í'),
+    ("Icirc", 'Î'),
+    ("Igrave", 'Ì'),
+    ("Iuml", 'Ï'),
+    ("Lambda", 'Λ'),
+    ("Ntilde", 'Ñ'),
+    ("OElig", 'Œ'),
+    ("Oacute", 'Ó'),
+    ("Ocirc", 'Ô'),
+    ("Ograve", 'Ò'),
+    ("Omega", 'Ω'),
+    ("Oslash", 'Ø'),
+    ("Otilde", 'Õ'),
+    ("Ouml", 'Ö'),
+    ("Phi", 'Φ'),
+    ("Pi", 'Π'),
+    ("Prime", '″'),
+    ("Psi", 'Ψ'),
+    ("Scaron", 'Š'),
+    ("Sigma", 'Σ'),
+    ("THORN", 'Þ'),
+    ("Theta", 'Θ'),
+    ("Uacute", 'Ú'),
+    ("Ucirc", 'Û'),
+    ("Ugrave", 'Ù'),
+    ("Uuml", 'Ü'),
+    ("Yacute", 'Ý'),
+    ("Yuml", 'Ÿ'),
+    ("aacute", 'á'),
+    ("acirc", 'â'),
+    ("acute", '´'),
+    ("aelig", 'æ'),
+    ("agrave", 'à'),
+    ("alpha", 'α'),
+    ("amp", '&'),
+    ("and", '∧'),
+    ("ang", '∠'),
+    ("apos", '\''),
+    ("aring", 'å'),
+    ("asymp", '≈'),
+    ("atilde", 'ã'),
+    ("auml", 'ä'),
+    ("bdquo", '„'),
+    ("beta", 'β'),
+    ("brvbar", '¦'),
+    ("bull", '•'),
+    ("cap", '∩'),
+    ("ccedil", 'ç'),
+    ("cedil", '¸'),
+    ("cent", '¢'),
+    ("chi", 'χ'),
+    ("circ", 'ˆ'),
+    ("clubs", '♣'),
+    ("copy", '©'),
+    ("crarr", '↵'),
+    ("cup", '∪'),
+    ("curren", '¤'),
+    ("dagger", '†'),
+    ("darr", '↓'),
+    ("deg", '°'),
+    ("delta", 'δ'),
+    ("diams", '♦'),
+    ("divide", '÷'),
+    ("eacute", 'é'),
+    ("ecirc", 'ê'),
+    ("egrave", 'è'),
+    ("empty", '∅'),
+    ("emsp", '\u{2003}'),
+    ("ensp", '\u{2002}'),
+    ("epsilon", 'ε'),
+    ("equiv", '≡'),
+    ("eta", 'η'),
+    ("eth", 'ð'),
+    ("euml", 'ë'),
+    ("euro", '€'),
+    ("exist", '∃'),
+    ("fnof", 'ƒ'),
+    ("forall", '∀'),
+    ("frac12", '½'),
+    ("frac14", '¼'),
+    ("frac34", '¾'),
+    ("frasl", '⁄'),
+    ("gamma", 'γ'),
+    ("ge", '≥'),
+    ("gt", '>'),
+    ("hArr", '⇔'),
+    ("harr", '↔'),
+    ("hearts", '♥'),
+    ("hellip", '…'),
+    ("iacute", 'í'),
+    ("icirc", 'î'),
+    ("iexcl", '¡'),
+    ("igrave", 'ì'),
+    ("infin", '∞'),
+    ("int", '∫'),
+    ("iota", 'ι'),
+    ("iquest", '¿'),
+    ("isin", '∈'),
+    ("iuml", 'ï'),
+    ("kappa", 'κ'),
+    ("lArr", '⇐'),
+    ("lambda", 'λ'),
+    ("laquo", '«'),
+    ("larr", '←'),
+    ("ldquo", '“'),
+    ("le", '≤'),
+    ("lowast", '∗'),
+    ("loz", '◊'),
+    ("lrm", '\u{200e}'),
+    ("lsaquo", '‹'),
+    ("lsquo", '‘'),
+    ("lt", '<'),
+    ("macr", '¯'),
+    ("mdash", '—'),
+    ("micro", 'µ'),
+    ("middot", '·'),
+    ("minus", '−'),
+    ("mu", 'μ'),
+    ("nabla", '∇'),
+    ("nbsp", '\u{a0}'),
+    ("ndash", '–'),
+    ("ne", '≠'),
+    ("not", '¬'),
+    ("notin", '∉'),
+    ("ntilde", 'ñ'),
+    ("nu", 'ν'),
+    ("oacute", 'ó'),
+    ("ocirc", 'ô'),
+    ("oelig", 'œ'),
+    ("ograve", 'ò'),
+    ("oline", '‾'),
+    ("omega", 'ω'),
+    ("omicron", 'ο'),
+    ("oplus", '⊕'),
+    ("or", '∨'),
+    ("ordf", 'ª'),
+    ("ordm", 'º'),
+    ("oslash", 'ø'),
+    ("otilde", 'õ'),
+    ("otimes", '⊗'),
+    ("ouml", 'ö'),
+    ("para", '¶'),
+    ("part", '∂'),
+    ("permil", '‰'),
+    ("perp", '⊥'),
+    ("phi", 'φ'),
+    ("pi", 'π'),
+    ("plusmn", '±'),
+    ("pound", '£'),
+    ("prime", '′'),
+    ("prod", '∏'),
+    ("prop", '∝'),
+    ("psi", 'ψ'),
+    ("quot", '\"'),
+    ("rArr", '⇒'),
+    ("radic", '√'),
+    ("raquo", '»'),
+    ("rarr", '→'),
+    ("rdquo", '”'),
+    ("reg", '®'),
+    ("rho", 'ρ'),
+    ("rlm", '\u{200f}'),
+    ("rsaquo", '›'),
+    ("rsquo", '’'),
+    ("sbquo", '‚'),
+    ("scaron", 'š'),
+    ("sdot", '⋅'),
+    ("sect", '§'),
+    ("shy", '\u{ad}'),
+    ("sigma", 'σ'),
+    ("sigmaf", 'ς'),
+    ("sim", '∼'),
+    ("spades", '♠'),
+    ("sub", '⊂'),
+    ("sube", '⊆'),
+    ("sum", '∑'),
+    ("sup", '⊃'),
+    ("sup1", '¹'),
+    ("sup2", '²'),
+    ("sup3", '³'),
+    ("supe", '⊇'),
+    ("szlig", 'ß'),
+    ("tau", 'τ'),
+    ("there4", '∴'),
+    ("theta", 'θ'),
+    ("thinsp", '\u{2009}'),
+    ("thorn", 'þ'),
+    ("tilde", '˜'),
+    ("times", '×'),
+    ("trade", '™'),
+    ("uacute", 'ú'),
+    ("uarr", '↑'),
+    ("ucirc", 'û'),
+    ("ugrave", 'ù'),
+    ("uml", '¨'),
+    ("upsilon", 'υ'),
+    ("uuml", 'ü'),
+    ("xi", 'ξ'),
+    ("yacute", 'ý'),
+    ("yen", '¥'),
+    ("yuml", 'ÿ'),
+    ("zeta", 'ζ'),
+    ("zwj", '\u{200d}'),
+    ("zwnj", '\u{200c}'),
+];
+
+/// The longest name in `ENTITIES`.
+const MAX_NAME_LEN: usize = 7;
+
+/// Returns the character `name`, the name of a named character reference without its `&`
+/// and `;`, stands for.
+fn named(name: &str) -> Option<char> {
+    ENTITIES
+        .binary_search_by_key(&name, |&(name, _)| name)
+        .ok()
+        .map(|i| ENTITIES[i].1)
+}
+
+/// Reads the numeric character reference at the start of `reference`, which follows `&#`,
+/// like `169;` or `x1F600;`. Returns the character and how long the reference is, with its
+/// `;` if it has one. A number that is not a Unicode scalar value stands for U+FFFD.
+fn numeric(reference: &str) -> Option<(char, usize)> {
+    let (radix, digits_start) = match reference.as_bytes().first() {
+        Some(b'x' | b'X') => (16, 1),
+        _ => (10, 0),
+    };
+    let digits = reference[digits_start..]
+        .find(|c: char| !c.is_digit(radix))
+        .unwrap_or(reference.len() - digits_start);
+    if digits == 0 {
+        return None;
+    }
+    let end = digits_start + digits;
+    let number = reference[digits_start..end]
+        .chars()
+        .try_fold(0u32, |number, digit| {
+            number
+                .checked_mul(radix)?
+                .checked_add(digit.to_digit(radix)?)
+        });
+    let c = number
+        .filter(|&number| number != 0)
+        .and_then(char::from_u32)
+        .unwrap_or(char::REPLACEMENT_CHARACTER);
+    let semicolon = usize::from(reference[end..].starts_with(';'));
+    Some((c, end + semicolon))
+}
+
+/// Replaces the character references in `text`, a text or an attribute value as written
+/// in HTML, with the characters they stand for, like `&amp;` with `&` and `&#169;` with `©`.
+/// A named reference that is not known, or that does not end with `;`, is left as it is.
+pub fn decode(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some((c, len)) = rest.strip_prefix("&#").and_then(numeric) {
+            decoded.push(c);
+            rest = &rest[len + 2..];
+            continue;
+        }
+        let reference = rest[1..]
+            .split_once(';')
+            .map(|(name, _)| name)
+            .filter(|name| name.len() <= MAX_NAME_LEN)
+            .and_then(|name| Some((name, named(name)?)));
+        match reference {
+            Some((name, c)) => {
+                decoded.push(c);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, ENTITIES, MAX_NAME_LEN};
+
+    #[test]
+    fn test_decode() {
+        assert!(ENTITIES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(ENTITIES.iter().all(|(name, _)| name.len() <= MAX_NAME_LEN));
+
+        assert_eq!(decode("no references"), "no references");
+        assert_eq!(decode("1 &lt; 2 &amp;&amp; 3 &gt; 2"), "1 < 2 && 3 > 2");
+        assert_eq!(
+            decode("&quot;caf&eacute;&quot;&nbsp;&copy;&hellip;"),
+            "\"café\"\u{a0}©…"
+        );
+        assert_eq!(decode("&Eacute;&eacute;"), "Éé");
+        // unknown, unterminated and bare ampersands are kept
+        assert_eq!(decode("&bogus; &amp &; a & b &"), "&bogus; &amp &; a & b &");
+        assert_eq!(decode("&&lt;"), "&<");
+    }
+
+    #[test]
+    fn test_numeric() {
+        assert_eq!(decode("&#169; &#xA9; &#XA9;"), "© © ©");
+        assert_eq!(decode("&#x1F600;&#128512;"), "😀😀");
+        // the `;` can be left out
+        assert_eq!(decode("&#65&#x42 z"), "AB z");
+        // surrogates, beyond U+10FFFF, overflowing and NUL are replaced
+        assert_eq!(
+            decode("&#xD800; &#x110000; &#99999999999999; &#0;"),
+            "\u{fffd} \u{fffd} \u{fffd} \u{fffd}"
+        );
+        // without digits it is not a reference
+        assert_eq!(decode("&#; &#x; &#z"), "&#; &#x; &#z");
+    }
+}
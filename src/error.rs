@@ -0,0 +1,77 @@
+#[cfg(feature = "net")]
+use crate::request::RequestError;
+#[cfg(feature = "net")]
+use reqwest::StatusCode;
+use std::io;
+
+/// `Error` is why a page could not be loaded or built, wherever along the way it failed.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A file could not be read or written.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A request failed, or the server answered with an error.
+    #[cfg(feature = "net")]
+    #[error(transparent)]
+    Network(#[from] RequestError),
+    #[error("`{0}` is not a valid URL")]
+    InvalidUrl(String),
+    /// The content is not what its type says, like an image that cannot be decoded.
+    #[error("the content could not be decoded: {0}")]
+    Decode(String),
+    #[error("the HTML could not be parsed: {0}")]
+    Html(String),
+    #[error("the CSS could not be parsed: {0}")]
+    Css(String),
+    /// A DOM tree dumped as JSON could not be read back.
+    #[error("the JSON could not be read: {0}")]
+    Json(String),
+    /// The request was not made, as a filter rule says.
+    #[error("`{0}` was blocked by a filter")]
+    Blocked(String),
+    /// The content is of a type that cannot be shown, by its content type.
+    #[error("{0} cannot be shown")]
+    Unsupported(String),
+    /// The config file, or an option standing in for it, is not valid.
+    #[error("{0}")]
+    Config(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(feature = "net")]
+impl Error {
+    /// Returns the status the server answered with, if it answered with an error.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Network(e) => e.status(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::Error;
+    use crate::{css, request::RequestError};
+    use reqwest::StatusCode;
+    use std::io;
+
+    #[test]
+    fn test_error() {
+        let error = Error::from(RequestError::Status(StatusCode::NOT_FOUND));
+        assert_eq!(error.status(), Some(StatusCode::NOT_FOUND));
+        assert_eq!(error.to_string(), "the server answered 404 Not Found");
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(error.status(), None);
+        assert_eq!(error.to_string(), "no such file");
+        assert_eq!(
+            Error::Unsupported("application/pdf".into()).to_string(),
+            "application/pdf cannot be shown"
+        );
+        assert!(css::stylesheet("p {")
+            .unwrap_err()
+            .to_string()
+            .starts_with("the CSS could not be parsed"));
+    }
+}
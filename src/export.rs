@@ -0,0 +1,246 @@
+use crate::{
+    css,
+    error::Result,
+    layout::{node_to_object_with_context, LayoutContext},
+    linear::linearize,
+    page::{Page, Settings},
+    render::{render, Palette},
+    style::to_styled_node,
+};
+use ratatui::{
+    buffer::{Buffer, Cell},
+    layout::Rect,
+    style::{Color, Modifier},
+};
+
+/// `Format` is how an exported page is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Only the characters of the page.
+    Plain,
+    /// The characters with their colors and text modifiers as ANSI escape sequences.
+    Ansi,
+    /// The DOM tree of the page as JSON, rather than how it is rendered.
+    Json,
+    /// The page read out a line at a time, for screen readers, as `linearize` writes it.
+    Linear,
+}
+
+/// Renders all of `page`, not just what fits in a viewport, `width` columns wide.
+/// Images are exported as their alt text.
+pub fn export(page: &Page, width: u16, format: Format) -> String {
+    let _span = tracing::debug_span!("export", url = page.url, ?format).entered();
+    match format {
+        Format::Json => return page.dom().to_json(),
+        Format::Linear => return linearize(page),
+        Format::Plain | Format::Ansi => {}
+    }
+    let Some(styled) = to_styled_node(page.dom(), &page.stylesheet) else {
+        return String::new();
+    };
+    let object = node_to_object_with_context(
+        &styled,
+        Rect {
+            x: 0,
+            y: 0,
+            width,
+            height: u16::MAX,
+        },
+        0,
+        &LayoutContext::default(),
+    );
+
+    // a buffer holds at most `u16::MAX` cells, so long pages are rendered a slice at a time
+    let slice_height = (u16::MAX / width.max(1)).max(1);
+    let mut result = String::new();
+    for scroll in (0..object.area.bottom()).step_by(slice_height as usize) {
+        let height = slice_height.min(object.area.bottom() - scroll);
+        let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+        render(&object, &mut buf, scroll, Palette::TRUE_COLOR);
+        result.push_str(&match format {
+            Format::Plain => to_plain(&buf),
+            Format::Ansi => to_ansi(&buf),
+            Format::Json | Format::Linear => unreachable!("{:?} is not rendered", format),
+        });
+    }
+    result
+}
+
+/// Renders `html`, styled with `css` after its own `<style>`s, as the browser draws the top
+/// of it on a screen of `area`, but without a terminal, so that what pages look like can be
+/// checked in tests. Images are drawn as their alt text.
+pub fn render_to_buffer(html: &str, css: &str, area: Rect) -> Result<Buffer> {
+    let mut page = Page::from_html("about:blank", html, &Settings::default())?;
+    page.stylesheet.rules.extend(css::stylesheet(css)?.rules);
+    // the page is laid out from the top, and moved to `area` once drawn
+    let mut buf = Buffer::empty(Rect { x: 0, y: 0, ..area });
+    if let Some(styled) = to_styled_node(page.dom(), &page.stylesheet) {
+        let object = node_to_object_with_context(&styled, buf.area, 0, &LayoutContext::default());
+        render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+    }
+    buf.area = area;
+    Ok(buf)
+}
+
+/// Renders `html` as `render_to_buffer` does, as plain text: a line for each row of `area`,
+/// without the blanks at their ends.
+pub fn render_to_string(html: &str, css: &str, area: Rect) -> Result<String> {
+    Ok(to_plain(&render_to_buffer(html, css, area)?))
+}
+
+fn lines(buf: &Buffer) -> impl Iterator<Item = &[Cell]> {
+    buf.content.chunks(buf.area.width.max(1) as usize)
+}
+
+fn to_plain(buf: &Buffer) -> String {
+    let mut result = String::new();
+    for line in lines(buf) {
+        let text = line.iter().map(|cell| cell.symbol()).collect::<String>();
+        result.push_str(text.trim_end());
+        result.push('\n');
+    }
+    result
+}
+
+/// The SGR parameters that select `color`, with `base` being 30 for foreground and 40 for background.
+fn color_parameters(color: Color, base: u8) -> String {
+    match color {
+        Color::Reset => format!("{}", base + 9),
+        Color::Black => format!("{}", base),
+        Color::Red => format!("{}", base + 1),
+        Color::Green => format!("{}", base + 2),
+        Color::Yellow => format!("{}", base + 3),
+        Color::Blue => format!("{}", base + 4),
+        Color::Magenta => format!("{}", base + 5),
+        Color::Cyan => format!("{}", base + 6),
+        Color::Gray => format!("{}", base + 7),
+        Color::DarkGray => format!("{}", base + 60),
+        Color::LightRed => format!("{}", base + 61),
+        Color::LightGreen => format!("{}", base + 62),
+        Color::LightYellow => format!("{}", base + 63),
+        Color::LightBlue => format!("{}", base + 64),
+        Color::LightMagenta => format!("{}", base + 65),
+        Color::LightCyan => format!("{}", base + 66),
+        Color::White => format!("{}", base + 67),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        Color::Indexed(i) => format!("{};5;{}", base + 8, i),
+    }
+}
+
+fn sgr(cell: &Cell) -> String {
+    let mut parameters = vec!["0".to_string()];
+    let modifiers = [
+        (Modifier::BOLD, "1"),
+        (Modifier::ITALIC, "3"),
+        (Modifier::UNDERLINED, "4"),
+        (Modifier::CROSSED_OUT, "9"),
+    ];
+    for (modifier, parameter) in modifiers {
+        if cell.modifier.contains(modifier) {
+            parameters.push(parameter.into());
+        }
+    }
+    if cell.fg != Color::Reset {
+        parameters.push(color_parameters(cell.fg, 30));
+    }
+    if cell.bg != Color::Reset {
+        parameters.push(color_parameters(cell.bg, 40));
+    }
+    format!("\x1b[{}m", parameters.join(";"))
+}
+
+fn to_ansi(buf: &Buffer) -> String {
+    let mut result = String::new();
+    let reset = sgr(&Cell::default());
+    for line in lines(buf) {
+        // trailing blank cells are left out, as in plain text
+        let end = line
+            .iter()
+            .rposition(|cell| *cell != Cell::default())
+            .map_or(0, |i| i + 1);
+        let mut current = reset.clone();
+        for cell in &line[..end] {
+            let next = sgr(cell);
+            if next != current {
+                result.push_str(&next);
+                current = next;
+            }
+            result.push_str(cell.symbol());
+        }
+        if current != reset {
+            result.push_str("\x1b[0m");
+        }
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, render_to_buffer, render_to_string, Format};
+    use crate::page::{Page, Settings};
+    use ratatui::{
+        buffer::Buffer,
+        layout::Rect,
+        style::{Color, Modifier, Style},
+    };
+
+    #[test]
+    fn test_export_long_page() {
+        let html = "<p>line</p>".repeat(2000);
+        let page = Page::from_html("about:test", &html, &Settings::default()).unwrap();
+        assert_eq!(export(&page, 100, Format::Plain), "line\n".repeat(2000));
+    }
+
+    #[test]
+    fn test_export() {
+        let page = Page::from_html(
+            "about:test",
+            r#"<p>first <b>bold</b></p><p style="">second</p><img alt="chart">"#,
+            &Settings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            export(&page, 20, Format::Plain),
+            "first bold\nsecond\n[chart]\n"
+        );
+
+        let ansi = export(&page, 20, Format::Ansi);
+        assert!(ansi.starts_with("first \x1b[0;1mbold\x1b[0m"));
+        assert!(ansi.contains("\x1b[0;3;38;2;128;128;128m[chart]"));
+    }
+
+    #[test]
+    fn test_export_uppercase_tags() {
+        let html = "<SCRIPT>var x = 1;</SCRIPT><STYLE>P { color: red; }</STYLE><P>shown</P>";
+        let page = Page::from_html("about:test", html, &Settings::default()).unwrap();
+        assert_eq!(export(&page, 20, Format::Plain), "shown\n");
+    }
+
+    #[test]
+    fn test_render_to_buffer() {
+        let html =
+            r#"<h1><b>Title</b></h1><p class="note">a note that wraps</p><p id="gone">hidden</p>"#;
+        let css = ".note { color: red; } #gone { display: none; }";
+        let area = Rect::new(2, 3, 10, 4);
+        let buf = render_to_buffer(html, css, area).unwrap();
+        let mut expected =
+            Buffer::with_lines(vec!["Title     ", "a note tha", "t wraps   ", "          "]);
+        expected.area = area;
+        expected.set_style(
+            Rect::new(2, 3, 5, 1),
+            Style::new().add_modifier(Modifier::BOLD),
+        );
+        let red = Style::new().fg(Color::Rgb(255, 0, 0));
+        expected.set_style(Rect::new(2, 4, 10, 1), red);
+        expected.set_style(Rect::new(2, 5, 7, 1), red);
+        assert_eq!(buf, expected);
+
+        assert_eq!(
+            render_to_string(html, "", Rect::new(0, 0, 20, 3)).unwrap(),
+            "Title\na note that wraps\nhidden\n"
+        );
+        assert!(render_to_buffer(html, "p {", area).is_err());
+    }
+}
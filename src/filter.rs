@@ -0,0 +1,451 @@
+use crate::{
+    css,
+    cssom::ComplexSelector,
+    dom::{Document, Node, NodeType},
+    error::{Error, Result},
+};
+use serde::Deserialize;
+use std::{fmt, fs, path::PathBuf};
+
+/// `FilterConfig` is what is blocked, dropped and rewritten on every page.
+/// It is the `[filter]` table of the config file.
+///
+/// ```toml
+/// [filter]
+/// lists = ["easylist.txt"]
+/// block = ["||ads.example.com^"]
+/// hide = ["#cookie-banner", ".consent"]
+///
+/// [[filter.rewrite]]
+/// selector = "img"
+/// attribute = "src"
+/// from = "data-src"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct FilterConfig {
+    /// Files of adblock-style rules, relative to the directory of the config file.
+    pub lists: Vec<PathBuf>,
+    /// Adblock-style rules, as a line of a list is written.
+    pub block: Vec<String>,
+    /// The selectors of the elements to drop from pages, with what they contain.
+    pub hide: Vec<String>,
+    pub rewrite: Vec<RewriteConfig>,
+}
+
+/// `RewriteConfig` is an attribute to change on the elements a selector matches: set to
+/// `value`, to the value of the attribute `from`, or, with neither, removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct RewriteConfig {
+    pub selector: String,
+    pub attribute: String,
+    pub value: Option<String>,
+    pub from: Option<String>,
+}
+
+/// `FilterRule` is a rule of a `Filter`. Rules can block requests, change pages once they
+/// are parsed and before they are styled, or both.
+pub trait FilterRule: fmt::Debug + Send + Sync {
+    /// Returns whether the request for `url` is not to be made.
+    fn blocks(&self, _url: &str) -> bool {
+        false
+    }
+
+    /// Changes `dom`, a page that was just parsed.
+    fn apply(&self, _dom: &mut Node) {}
+}
+
+/// `Filter` is the rules every request and page goes through, in order.
+#[derive(Debug, Default)]
+pub struct Filter {
+    rules: Vec<Box<dyn FilterRule>>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    pub fn with_rule(mut self, rule: impl FilterRule + 'static) -> Filter {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Makes the rules `config` says, reading its lists.
+    pub fn from_config(config: &FilterConfig) -> Result<Filter> {
+        let mut filter = Filter::new();
+        for path in &config.lists {
+            let list = fs::read_to_string(path).map_err(|e| {
+                Error::Config(format!(
+                    "cannot read the filter list {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            filter = filter.with_rule(Blocklist::parse(&list));
+        }
+        if !config.block.is_empty() {
+            filter = filter.with_rule(Blocklist::parse(&config.block.join("\n")));
+        }
+        if !config.hide.is_empty() {
+            filter = filter.with_rule(Hide::new(&config.hide.join(","))?);
+        }
+        for rewrite in &config.rewrite {
+            let change = match (&rewrite.value, &rewrite.from) {
+                (Some(_), Some(_)) => {
+                    return Err(Error::Config(format!(
+                        "the rewrite of `{}` has both a value and an attribute to take it from",
+                        rewrite.attribute
+                    )))
+                }
+                (Some(value), None) => Change::Set(value.clone()),
+                (None, Some(from)) => Change::CopyFrom(from.clone()),
+                (None, None) => Change::Remove,
+            };
+            filter = filter.with_rule(Rewrite::new(&rewrite.selector, &rewrite.attribute, change)?);
+        }
+        Ok(filter)
+    }
+
+    /// Returns whether a rule blocks the request for `url`.
+    pub fn blocks(&self, url: &str) -> bool {
+        self.rules.iter().any(|rule| rule.blocks(url))
+    }
+
+    /// Changes `dom` by each rule in turn.
+    pub fn apply(&self, dom: &mut Node) {
+        for rule in &self.rules {
+            rule.apply(dom);
+        }
+    }
+}
+
+/// Where a `Pattern` may match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    /// Anywhere in the URL.
+    None,
+    /// At its start, after `|`.
+    Start,
+    /// At the start of its host or of one of its subdomains, after `||`.
+    Domain,
+}
+
+/// `Pattern` is the URLs a rule of an adblock-style list matches, like `||ads.example.com^`.
+/// `*` matches anything, and `^` a character that is not part of a name, like `/` or `?`,
+/// or the end of the URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    anchor: Anchor,
+    /// Lowercased, without its anchors.
+    text: String,
+    /// Whether it has to match up to the end of the URL, written with `|` at the end.
+    to_end: bool,
+}
+
+impl Pattern {
+    /// Reads a rule, unless it has options after `$`, which are not understood.
+    fn parse(rule: &str) -> Option<Pattern> {
+        if rule.contains('$') {
+            return None;
+        }
+        let (anchor, rule) = if let Some(rule) = rule.strip_prefix("||") {
+            (Anchor::Domain, rule)
+        } else if let Some(rule) = rule.strip_prefix('|') {
+            (Anchor::Start, rule)
+        } else {
+            (Anchor::None, rule)
+        };
+        let (rule, to_end) = match rule.strip_suffix('|') {
+            Some(rule) => (rule, true),
+            None => (rule, false),
+        };
+        Some(Pattern {
+            anchor,
+            text: rule.to_ascii_lowercase(),
+            to_end,
+        })
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        let url = url.to_ascii_lowercase();
+        let starts = match self.anchor {
+            Anchor::None => (0..=url.len()).collect(),
+            Anchor::Start => vec![0],
+            Anchor::Domain => {
+                let Some(host_start) = url.find("://").map(|i| i + 3) else {
+                    return false;
+                };
+                let host = url[host_start..]
+                    .split(['/', '?', '#', ':'])
+                    .next()
+                    .unwrap_or_default();
+                std::iter::once(host_start)
+                    .chain(host.match_indices('.').map(|(i, _)| host_start + i + 1))
+                    .collect::<Vec<_>>()
+            }
+        };
+        starts
+            .into_iter()
+            .any(|start| glob(self.text.as_bytes(), &url.as_bytes()[start..], self.to_end))
+    }
+}
+
+/// Returns whether `c` separates the parts of a URL, for `^`.
+fn is_separator(c: u8) -> bool {
+    !(c.is_ascii_alphanumeric() || matches!(c, b'_' | b'-' | b'.' | b'%'))
+}
+
+/// Returns whether `pattern` matches the start of `text`, or all of it if `to_end`.
+fn glob(pattern: &[u8], text: &[u8], to_end: bool) -> bool {
+    match pattern.split_first() {
+        None => !to_end || text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..], to_end)),
+        Some((b'^', rest)) => match text.split_first() {
+            Some((&c, text)) => is_separator(c) && glob(rest, text, to_end),
+            None => glob(rest, text, to_end),
+        },
+        Some((c, rest)) => text.first() == Some(c) && glob(rest, &text[1..], to_end),
+    }
+}
+
+/// Returns the paths of the elements of `dom` any of `selectors` matches, in document order.
+fn matching_paths(dom: &Node, selectors: &[ComplexSelector]) -> Vec<Vec<usize>> {
+    let document = Document::new(dom);
+    document
+        .descendants(document.root())
+        .filter(|&id| selectors.iter().any(|s| s.matches(&document, id)))
+        .map(|id| document.path(id))
+        .collect()
+}
+
+/// Removes the nodes at `paths`, in document order, with what they contain.
+fn remove(dom: &mut Node, paths: &[Vec<usize>]) {
+    // the last first, so that the paths of those before stay where they were
+    for path in paths.iter().rev() {
+        if let Some((&i, parent)) = path.split_last() {
+            if let Some(parent) = dom.node_at_mut(parent) {
+                if i < parent.children.len() {
+                    parent.children.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// `Blocklist` is an adblock-style list, like EasyList: a rule a line, blocking the URLs it
+/// matches unless it starts with `@@`, which allows them again, and `##selector` dropping the
+/// elements the selector matches. Lines starting with `!` are comments. Rules with options,
+/// for some domains only, or with selectors that cannot be parsed are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist {
+    block: Vec<Pattern>,
+    allow: Vec<Pattern>,
+    hide: Vec<ComplexSelector>,
+}
+
+impl Blocklist {
+    pub fn parse(list: &str) -> Blocklist {
+        let mut blocklist = Blocklist::default();
+        for line in list.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+                continue;
+            }
+            if let Some((domains, selector)) = line.split_once("##") {
+                if domains.is_empty() {
+                    blocklist
+                        .hide
+                        .extend(css::selector_list(selector).unwrap_or_default());
+                }
+            } else if line.contains("#@#") || line.contains("#?#") {
+                continue;
+            } else if let Some(rule) = line.strip_prefix("@@") {
+                blocklist.allow.extend(Pattern::parse(rule));
+            } else {
+                blocklist.block.extend(Pattern::parse(line));
+            }
+        }
+        blocklist
+    }
+}
+
+impl FilterRule for Blocklist {
+    fn blocks(&self, url: &str) -> bool {
+        self.block.iter().any(|pattern| pattern.matches(url))
+            && !self.allow.iter().any(|pattern| pattern.matches(url))
+    }
+
+    fn apply(&self, dom: &mut Node) {
+        if !self.hide.is_empty() {
+            let paths = matching_paths(dom, &self.hide);
+            remove(dom, &paths);
+        }
+    }
+}
+
+/// `Hide` drops the elements some selectors match, like cookie banners, with what they contain.
+#[derive(Debug, Clone)]
+pub struct Hide {
+    selectors: Vec<ComplexSelector>,
+}
+
+impl Hide {
+    /// Drops what `selectors`, a selector list like `#cookies, .consent`, matches.
+    pub fn new(selectors: &str) -> Result<Hide> {
+        Ok(Hide {
+            selectors: css::selector_list(selectors)?,
+        })
+    }
+}
+
+impl FilterRule for Hide {
+    fn apply(&self, dom: &mut Node) {
+        let paths = matching_paths(dom, &self.selectors);
+        remove(dom, &paths);
+    }
+}
+
+/// `Change` is what a `Rewrite` does to an attribute.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Set(String),
+    /// Sets it to the value of another attribute, if the element has it.
+    CopyFrom(String),
+    Remove,
+}
+
+/// `Rewrite` changes an attribute of the elements a selector matches, like `src` of images
+/// that are only loaded by scripts from `data-src`.
+#[derive(Debug, Clone)]
+pub struct Rewrite {
+    selectors: Vec<ComplexSelector>,
+    attribute: String,
+    change: Change,
+}
+
+impl Rewrite {
+    pub fn new(selectors: &str, attribute: &str, change: Change) -> Result<Rewrite> {
+        Ok(Rewrite {
+            selectors: css::selector_list(selectors)?,
+            attribute: attribute.to_ascii_lowercase(),
+            change,
+        })
+    }
+}
+
+impl FilterRule for Rewrite {
+    fn apply(&self, dom: &mut Node) {
+        for path in matching_paths(dom, &self.selectors) {
+            let Some(NodeType::Element(element)) =
+                dom.node_at_mut(&path).map(|node| &mut node.node_type)
+            else {
+                continue;
+            };
+            let value = match &self.change {
+                Change::Set(value) => Some(value.clone()),
+                Change::CopyFrom(from) => match element.attributes.get(from) {
+                    Some(value) => Some(value.clone()),
+                    None => continue,
+                },
+                Change::Remove => None,
+            };
+            match value {
+                Some(value) => element.attributes.insert(self.attribute.clone(), value),
+                None => element.attributes.remove(&self.attribute),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Blocklist, Change, Filter, FilterConfig, FilterRule, Hide, Rewrite};
+    use crate::{dom::Node, html};
+
+    fn parse(html: &str) -> Box<Node> {
+        Node::document(html::parse(html).unwrap())
+    }
+
+    #[test]
+    fn test_blocklist() {
+        let list = Blocklist::parse(
+            "[Adblock Plus 2.0]\n\
+             ! a comment\n\
+             ||ads.example.com^\n\
+             /banner/*.gif\n\
+             |http://tracker.\n\
+             .js|\n\
+             ||cdn.example.com^$third-party\n\
+             @@||ads.example.com/allowed/\n\
+             ##.sponsored\n\
+             example.org##.local-only",
+        );
+        assert!(list.blocks("https://ads.example.com/a.png"));
+        assert!(list.blocks("https://img.ads.example.com/"));
+        assert!(list.blocks("https://ADS.example.com"));
+        assert!(!list.blocks("https://badads.example.com/"));
+        assert!(!list.blocks("https://example.com/?ads.example.com"));
+        assert!(!list.blocks("https://ads.example.com/allowed/a.png"));
+        assert!(list.blocks("https://a.com/img/banner/top.gif"));
+        assert!(list.blocks("http://tracker.a.com/"));
+        assert!(!list.blocks("https://a.com/?r=http://tracker.a.com/"));
+        assert!(list.blocks("https://a.com/app.js"));
+        assert!(!list.blocks("https://a.com/app.json"));
+        // rules with options are left out
+        assert!(!list.blocks("https://cdn.example.com/a.css"));
+
+        let mut dom = parse(r#"<p class="sponsored">ad</p><p class="local-only">kept</p>"#);
+        list.apply(&mut dom);
+        assert_eq!(dom.text_content(), "kept");
+    }
+
+    #[test]
+    fn test_hide_and_rewrite() {
+        let mut dom = parse(
+            r#"<div id="cookies"><p>accept?</p></div><p>text<img data-src="a.png"><img src="b.png"></p><a href="x" target="_blank">x</a><div class="consent"></div>"#,
+        );
+        Hide::new("#cookies, .consent").unwrap().apply(&mut dom);
+        Rewrite::new("img", "src", Change::CopyFrom("data-src".into()))
+            .unwrap()
+            .apply(&mut dom);
+        Rewrite::new("a", "target", Change::Remove)
+            .unwrap()
+            .apply(&mut dom);
+        assert_eq!(
+            dom.to_html(),
+            r#"<p>text<img data-src="a.png" src="a.png"><img src="b.png"></p><a href="x">x</a>"#
+        );
+        assert!(Hide::new("p >").is_err());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config: FilterConfig = toml::from_str(
+            r#"
+            block = ["||ads.test^"]
+            hide = ["aside"]
+
+            [[rewrite]]
+            selector = "a"
+            attribute = "rel"
+            value = "noopener"
+            "#,
+        )
+        .unwrap();
+        let filter = Filter::from_config(&config).unwrap();
+        assert!(filter.blocks("https://ads.test/a.js"));
+        assert!(!filter.blocks("https://a.test/"));
+        let mut dom = parse(r#"<aside>ad</aside><a href="b">b</a>"#);
+        filter.apply(&mut dom);
+        assert_eq!(dom.to_html(), r#"<a href="b" rel="noopener">b</a>"#);
+
+        let config: FilterConfig = toml::from_str(
+            "[[rewrite]]\nselector = \"a\"\nattribute = \"rel\"\nvalue = \"x\"\nfrom = \"y\"",
+        )
+        .unwrap();
+        assert!(Filter::from_config(&config).is_err());
+        let config: FilterConfig = toml::from_str("lists = [\"/nonexistent/list.txt\"]").unwrap();
+        assert!(Filter::from_config(&config).is_err());
+    }
+}
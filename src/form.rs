@@ -0,0 +1,474 @@
+use crate::{
+    dom::{Element, Node, NodeType},
+    style::StyledNode,
+};
+use url::form_urlencoded;
+
+/// `Control` is the kind of an interactive form element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Text { password: bool },
+    TextArea,
+    Checkbox,
+    Radio,
+    Select,
+    Button { submit: bool },
+}
+
+impl Control {
+    pub fn of(element: &Element) -> Option<Control> {
+        let ty = element
+            .attributes
+            .get("type")
+            .map(|t| t.to_ascii_lowercase());
+        match element.tag_name.as_str() {
+            "input" => match ty.as_deref().unwrap_or("text") {
+                "hidden" => None,
+                "checkbox" => Some(Control::Checkbox),
+                "radio" => Some(Control::Radio),
+                "submit" | "image" => Some(Control::Button { submit: true }),
+                "button" | "reset" => Some(Control::Button { submit: false }),
+                "password" => Some(Control::Text { password: true }),
+                _ => Some(Control::Text { password: false }),
+            },
+            "textarea" => Some(Control::TextArea),
+            "select" => Some(Control::Select),
+            "button" => Some(Control::Button {
+                submit: !matches!(ty.as_deref(), Some("button" | "reset")),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether the control takes typed text.
+    pub fn is_editable(&self) -> bool {
+        matches!(self, Control::Text { .. } | Control::TextArea)
+    }
+}
+
+/// Whether `node` can be focused with Tab: an enabled form control, a link or the summary
+/// of a `<details>` element.
+pub fn is_focusable(node: &Node) -> bool {
+    node.element().is_some_and(|e| {
+        (e.tag_name == "a" && e.attributes.contains_key("href"))
+            || e.tag_name == "summary"
+            || (Control::of(e).is_some() && !e.attributes.contains_key("disabled"))
+    })
+}
+
+/// Returns the current value of a text field or text area.
+/// Edits are kept in the `value` attribute, which a text area otherwise takes from its text.
+pub fn value(node: &Node) -> String {
+    match node.element() {
+        Some(e) if e.tag_name == "textarea" => e
+            .attributes
+            .get("value")
+            .cloned()
+            .unwrap_or_else(|| node.text_content()),
+        Some(e) => e.attributes.get("value").cloned().unwrap_or_default(),
+        None => String::new(),
+    }
+}
+
+pub fn set_value(node: &mut Node, value: String) {
+    if let NodeType::Element(e) = &mut node.node_type {
+        e.attributes.insert("value".into(), value);
+    }
+}
+
+fn is_checked(element: &Element) -> bool {
+    element.attributes.contains_key("checked")
+}
+
+fn set_checked(node: &mut Node, checked: bool) {
+    if let NodeType::Element(e) = &mut node.node_type {
+        if checked {
+            e.attributes.insert("checked".into(), String::new());
+        } else {
+            e.attributes.remove("checked");
+        }
+    }
+}
+
+/// Returns the path of the `<form>` that contains the node at `path`.
+pub fn form_of(root: &Node, path: &[usize]) -> Option<Vec<usize>> {
+    (0..path.len())
+        .rev()
+        .map(|len| &path[..len])
+        .find_map(|prefix| {
+            root.node_at(prefix)?
+                .element()
+                .filter(|e| e.tag_name == "form")
+                .map(|_| prefix.to_vec())
+        })
+}
+
+/// Checks or unchecks a checkbox, or checks a radio button and unchecks the others
+/// with the same name in its form.
+pub fn toggle(root: &mut Node, path: &[usize]) {
+    let Some(element) = root.node_at(path).and_then(|n| n.element()) else {
+        return;
+    };
+    match Control::of(element) {
+        Some(Control::Checkbox) => {
+            let checked = is_checked(element);
+            if let Some(node) = root.node_at_mut(path) {
+                set_checked(node, !checked);
+            }
+        }
+        Some(Control::Radio) => {
+            let name = element.attributes.get("name").cloned();
+            let scope = form_of(root, path).unwrap_or_default();
+            let group = root
+                .node_at(&scope)
+                .map(|form| {
+                    form.paths(&|n| {
+                        n.element().is_some_and(|e| {
+                            Control::of(e) == Some(Control::Radio)
+                                && name.is_some()
+                                && e.attributes.get("name") == name.as_ref()
+                        })
+                    })
+                })
+                .unwrap_or_default();
+            for radio in group {
+                let radio = [scope.as_slice(), &radio].concat();
+                if let Some(node) = root.node_at_mut(&radio) {
+                    set_checked(node, false);
+                }
+            }
+            if let Some(node) = root.node_at_mut(path) {
+                set_checked(node, true);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_option(node: &Node) -> bool {
+    node.element().is_some_and(|e| e.tag_name == "option")
+}
+
+/// Returns the index among the options of a `<select>` of the selected one.
+fn selected_index(options: &[&Node]) -> usize {
+    options
+        .iter()
+        .position(|o| {
+            o.element()
+                .is_some_and(|e| e.attributes.contains_key("selected"))
+        })
+        .unwrap_or(0)
+}
+
+/// Selects the option after the selected one of a `<select>`, wrapping around to the first.
+pub fn select_next_option(select: &mut Node) {
+    let options = select
+        .children_elements()
+        .filter(|c| is_option(c))
+        .collect::<Vec<_>>();
+    if options.is_empty() {
+        return;
+    }
+    let next = (selected_index(&options) + 1) % options.len();
+    for (i, option) in select
+        .children
+        .iter_mut()
+        .filter(|c| is_option(c))
+        .enumerate()
+    {
+        if let NodeType::Element(e) = &mut option.node_type {
+            if i == next {
+                e.attributes.insert("selected".into(), String::new());
+            } else {
+                e.attributes.remove("selected");
+            }
+        }
+    }
+}
+
+fn option_value(option: &Node) -> String {
+    option
+        .element()
+        .and_then(|e| e.attributes.get("value").cloned())
+        .unwrap_or_else(|| option.text_content().trim().to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+/// `FormData` is the names and values of the controls of a form, in document order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FormData {
+    pub entries: Vec<(String, String)>,
+}
+
+impl FormData {
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Encodes the entries as `application/x-www-form-urlencoded`, like `q=wev+browser&lang=en`.
+    pub fn urlencoded(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&self.entries)
+            .finish()
+    }
+}
+
+/// `Submission` is a form submitted by the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submission {
+    pub method: Method,
+    /// The `action` of the form as written, relative to the page. Empty means the page itself.
+    pub action: String,
+    pub data: FormData,
+}
+
+/// Builds the submission of the form containing the control at `path`.
+/// The control is the submitter, and so is sent too if it is a named submit button.
+pub fn submission(root: &Node, path: &[usize]) -> Option<Submission> {
+    let form_path = form_of(root, path)?;
+    let form = root.node_at(&form_path)?;
+    let form_element = form.element()?;
+
+    let mut data = FormData::default();
+    for relative in form.paths(&|n| n.element().is_some()) {
+        let Some(node) = form.node_at(&relative) else {
+            continue;
+        };
+        let Some(element) = node.element() else {
+            continue;
+        };
+        let Some(name) = element.attributes.get("name") else {
+            continue;
+        };
+        if element.attributes.contains_key("disabled") {
+            continue;
+        }
+        let value = match Control::of(element) {
+            Some(Control::Text { .. } | Control::TextArea) => Some(value(node)),
+            Some(Control::Checkbox | Control::Radio) => is_checked(element).then(|| {
+                element
+                    .attributes
+                    .get("value")
+                    .cloned()
+                    .unwrap_or_else(|| "on".into())
+            }),
+            Some(Control::Select) => {
+                let options = node
+                    .children_elements()
+                    .filter(|c| is_option(c))
+                    .collect::<Vec<_>>();
+                options
+                    .get(selected_index(&options))
+                    .map(|o| option_value(o))
+            }
+            Some(Control::Button { submit: true })
+                if [form_path.as_slice(), &relative].concat() == path =>
+            {
+                Some(element.attributes.get("value").cloned().unwrap_or_default())
+            }
+            Some(Control::Button { .. }) => None,
+            // hidden inputs
+            None if element.tag_name == "input" => {
+                Some(element.attributes.get("value").cloned().unwrap_or_default())
+            }
+            None => None,
+        };
+        if let Some(value) = value {
+            data.append(name, &value);
+        }
+    }
+
+    let method = match form_element.attributes.get("method") {
+        Some(method) if method.eq_ignore_ascii_case("post") => Method::Post,
+        _ => Method::Get,
+    };
+    Some(Submission {
+        method,
+        action: form_element
+            .attributes
+            .get("action")
+            .cloned()
+            .unwrap_or_default(),
+        data,
+    })
+}
+
+/// Concatenates the text of the styled text nodes under `node`.
+fn styled_text(node: &StyledNode) -> String {
+    match node.node_type {
+        NodeType::Text(text) => text.data.clone(),
+        NodeType::Comment(_) | NodeType::Doctype(_) => String::new(),
+        NodeType::Element(_) | NodeType::Document => {
+            node.children.iter().map(styled_text).collect()
+        }
+    }
+}
+
+/// How many characters a text field shows unless `size` or `cols` says otherwise.
+const DEFAULT_FIELD_WIDTH: usize = 20;
+
+/// Returns the text a form control is drawn as, in place of its children.
+pub fn widget_text(node: &StyledNode) -> Option<String> {
+    let NodeType::Element(element) = node.node_type else {
+        return None;
+    };
+    let control = Control::of(element)?;
+    let attribute = |name: &str| element.attributes.get(name);
+    let text = match control {
+        Control::Text { password } => {
+            let value = attribute("value").cloned().unwrap_or_default();
+            let value = if password {
+                "*".repeat(value.chars().count())
+            } else {
+                value
+            };
+            field_text(element, value, "size")
+        }
+        Control::TextArea => {
+            let value = attribute("value")
+                .cloned()
+                .unwrap_or_else(|| styled_text(node));
+            field_text(element, value.replace('\n', " "), "cols")
+        }
+        Control::Checkbox if is_checked(element) => "[x]".into(),
+        Control::Checkbox => "[ ]".into(),
+        Control::Radio if is_checked(element) => "(*)".into(),
+        Control::Radio => "( )".into(),
+        Control::Select => {
+            let options = node
+                .children
+                .iter()
+                .filter(|c| matches!(c.node_type, NodeType::Element(e) if e.tag_name == "option"))
+                .collect::<Vec<_>>();
+            let selected = options
+                .iter()
+                .find(|o| {
+                    matches!(o.node_type, NodeType::Element(e) if e.attributes.contains_key("selected"))
+                })
+                .or(options.first())
+                .map(|o| styled_text(o).trim().to_string())
+                .unwrap_or_default();
+            format!("[{} ▾]", selected)
+        }
+        Control::Button { submit } => {
+            let label = if element.tag_name == "button" {
+                styled_text(node).trim().to_string()
+            } else {
+                attribute("value")
+                    .cloned()
+                    .unwrap_or_else(|| if submit { "Submit" } else { "Button" }.into())
+            };
+            format!("[ {} ]", label)
+        }
+    };
+    Some(text)
+}
+
+/// Draws a text field as `[value   ]`, `width_attribute` characters wide.
+/// Long values show their end, where typing happens. An empty field shows its placeholder.
+fn field_text(element: &Element, value: String, width_attribute: &str) -> String {
+    let width = element
+        .attributes
+        .get(width_attribute)
+        .and_then(|w| w.trim().parse::<usize>().ok())
+        .filter(|w| *w > 0)
+        .unwrap_or(DEFAULT_FIELD_WIDTH);
+    let shown = if value.is_empty() {
+        element
+            .attributes
+            .get("placeholder")
+            .cloned()
+            .unwrap_or_default()
+    } else {
+        value
+    };
+    let chars = shown.chars().collect::<Vec<_>>();
+    let visible = chars[chars.len().saturating_sub(width)..]
+        .iter()
+        .collect::<String>();
+    format!("[{:<width$}]", visible, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        form_of, is_focusable, select_next_option, set_value, submission, toggle, widget_text,
+        Method,
+    };
+    use crate::{
+        css, html,
+        page::{Page, Settings},
+        style::to_styled_node,
+    };
+    use combine::Parser;
+
+    const FORM: &str = r#"<form action="/search" method="post"><input name="q" value="rust"><input type="hidden" name="lang" value="en"><input type="checkbox" name="safe"><input type="radio" name="size" value="s" checked><input type="radio" name="size" value="l"><select name="sort"><option value="new">Newest</option><option>Oldest</option></select><textarea name="note">hi</textarea><button name="go" value="1">Go</button></form>"#;
+
+    #[test]
+    fn test_submission() {
+        let mut page = Page::from_html("about:test", FORM, &Settings::default()).unwrap();
+        let root = page.dom_mut();
+        let form = root.paths(&|n| n.element().is_some_and(|e| e.tag_name == "form"))[0].clone();
+        let child = |i: usize| [form.as_slice(), &[i]].concat();
+        assert_eq!(form_of(root, &child(0)), Some(form.clone()));
+
+        set_value(root.node_at_mut(&child(0)).unwrap(), "wev browser".into());
+        toggle(root, &child(2));
+        toggle(root, &child(4));
+        select_next_option(root.node_at_mut(&child(5)).unwrap());
+
+        let submitted = submission(root, &child(7)).unwrap();
+        assert_eq!(submitted.method, Method::Post);
+        assert_eq!(submitted.action, "/search");
+        assert_eq!(
+            submitted.data.urlencoded(),
+            "q=wev+browser&lang=en&safe=on&size=l&sort=Oldest&note=hi&go=1"
+        );
+        // submitting from a text field leaves out the button
+        let submitted = submission(root, &child(0)).unwrap();
+        assert!(!submitted.data.entries.iter().any(|(name, _)| name == "go"));
+    }
+
+    #[test]
+    fn test_widget_text() {
+        let dom = html::nodes().parse(FORM).unwrap().0;
+        let stylesheet = css::stylesheet("").unwrap();
+        let form = to_styled_node(&dom[0], &stylesheet).unwrap();
+        let texts = form
+            .children
+            .iter()
+            .map(|c| widget_text(c).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                format!("[{:<20}]", "rust"),
+                "[ ]".into(),
+                "(*)".into(),
+                "( )".into(),
+                "[Newest ▾]".into(),
+                format!("[{:<20}]", "hi"),
+                "[ Go ]".into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_focusable() {
+        let dom = html::nodes()
+            .parse(r#"<p><a href="/">a</a><a>b</a><input disabled><input></p>"#)
+            .unwrap()
+            .0;
+        let focusable = dom[0]
+            .children
+            .iter()
+            .map(|c| is_focusable(c))
+            .collect::<Vec<_>>();
+        assert_eq!(focusable, vec![true, false, false, true]);
+    }
+}
@@ -0,0 +1,342 @@
+#[cfg(feature = "images")]
+use crate::render::Palette;
+#[cfg(feature = "images")]
+use base64::{engine::general_purpose::STANDARD, Engine};
+#[cfg(feature = "images")]
+use image::{imageops::FilterType, DynamicImage, ImageFormat, RgbaImage};
+#[cfg(feature = "images")]
+use ratatui::style::Color;
+use ratatui::{buffer::Buffer, layout::Rect};
+use std::env;
+#[cfg(feature = "images")]
+use std::{collections::HashMap, fmt::Write, io::Cursor};
+
+/// `Graphics` is how images are drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Graphics {
+    /// https://sw.kovidgoyal.net/kitty/graphics-protocol/
+    Kitty,
+    /// https://iterm2.com/documentation-images.html
+    Iterm2,
+    Sixel,
+    /// Two pixels per cell, using the foreground and background colors of `▀`.
+    HalfBlocks,
+}
+
+impl Graphics {
+    /// Guesses the graphics protocol of the terminal from its environment variables.
+    pub fn detect() -> Self {
+        let var = |name| env::var(name).unwrap_or_default();
+        Self::from_env(&var("TERM"), &var("TERM_PROGRAM"), &var("KITTY_WINDOW_ID"))
+    }
+
+    fn from_env(term: &str, term_program: &str, kitty_window_id: &str) -> Self {
+        if !kitty_window_id.is_empty() || term.contains("kitty") || term_program == "ghostty" {
+            Graphics::Kitty
+        } else if matches!(term_program, "iTerm.app" | "WezTerm") {
+            Graphics::Iterm2
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            Graphics::Sixel
+        } else {
+            Graphics::HalfBlocks
+        }
+    }
+
+    /// Whether images are drawn with escape sequences rather than into the cell buffer.
+    pub fn is_protocol(&self) -> bool {
+        *self != Graphics::HalfBlocks
+    }
+
+    /// The escape sequence that removes the images drawn so far, if the protocol has one.
+    pub fn clear_sequence(&self) -> Option<&'static str> {
+        match self {
+            Graphics::Kitty => Some("\x1b_Ga=d,d=A,q=2\x1b\\"),
+            _ => None,
+        }
+    }
+
+    /// Encodes the visible part of `image` as an escape sequence filling `placement`.
+    #[cfg(feature = "images")]
+    /// `cell_size` is the size of a terminal cell in pixels.
+    pub fn encode(
+        &self,
+        image: &DynamicImage,
+        placement: &Placement,
+        cell_size: (u16, u16),
+    ) -> String {
+        let image = placement.crop(image);
+        let Rect { width, height, .. } = placement.area;
+        match self {
+            Graphics::Kitty => {
+                let data = STANDARD.encode(png(&image));
+                let chunks = data.as_bytes().chunks(4096).collect::<Vec<_>>();
+                let mut result = String::new();
+                for (i, chunk) in chunks.iter().enumerate() {
+                    let more = (i + 1 < chunks.len()) as u8;
+                    let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+                    if i == 0 {
+                        let _ = write!(
+                            result,
+                            "\x1b_Ga=T,f=100,c={},r={},C=1,q=2,m={};{}\x1b\\",
+                            width, height, more, chunk
+                        );
+                    } else {
+                        let _ = write!(result, "\x1b_Gm={};{}\x1b\\", more, chunk);
+                    }
+                }
+                result
+            }
+            Graphics::Iterm2 => {
+                let data = png(&image);
+                format!(
+                    "\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=0:{}\x07",
+                    data.len(),
+                    width,
+                    height,
+                    STANDARD.encode(data)
+                )
+            }
+            Graphics::Sixel => {
+                let (cell_width, cell_height) = cell_size;
+                let image = image
+                    .resize_exact(
+                        (width as u32 * cell_width as u32).max(1),
+                        (height as u32 * cell_height as u32).max(1),
+                        FilterType::Triangle,
+                    )
+                    .to_rgba8();
+                sixel(&image)
+            }
+            Graphics::HalfBlocks => String::new(),
+        }
+    }
+}
+
+#[cfg(feature = "images")]
+fn png(image: &DynamicImage) -> Vec<u8> {
+    let mut data = Cursor::new(vec![]);
+    // encoding into memory only fails for images PNG cannot represent
+    let _ = image.to_rgba8().write_to(&mut data, ImageFormat::Png);
+    data.into_inner()
+}
+
+/// Encodes `image` as sixels, quantized to a 6x6x6 color cube.
+/// Pixels that are mostly transparent are left undrawn.
+#[cfg(feature = "images")]
+fn sixel(image: &RgbaImage) -> String {
+    let level = |c: u8| (c as u32 * 5 + 127) / 255;
+    let index = |p: &image::Rgba<u8>| {
+        let [r, g, b, a] = p.0;
+        (a >= 128).then(|| (level(r) * 36 + level(g) * 6 + level(b)) as usize)
+    };
+
+    let (width, height) = image.dimensions();
+    let mut result = format!("\x1bPq\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        let percent = |l: usize| l * 100 / 5;
+        let _ = write!(
+            result,
+            "#{};2;{};{};{}",
+            i,
+            percent(i / 36),
+            percent(i / 6 % 6),
+            percent(i % 6)
+        );
+    }
+    for band in (0..height).step_by(6) {
+        // the sixels of each color in this band, one per column
+        let mut colors: HashMap<usize, Vec<u8>> = HashMap::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                if let Some(i) = index(image.get_pixel(x, y)) {
+                    colors.entry(i).or_insert_with(|| vec![0; width as usize])[x as usize] |=
+                        1 << (y - band);
+                }
+            }
+        }
+        let mut colors = colors.into_iter().collect::<Vec<_>>();
+        colors.sort_by_key(|(i, _)| *i);
+        for (n, (i, sixels)) in colors.iter().enumerate() {
+            if n > 0 {
+                result.push('$');
+            }
+            let _ = write!(result, "#{}", i);
+            for run in sixels.chunk_by(|a, b| a == b) {
+                let c = (run[0] + 63) as char;
+                if run.len() > 3 {
+                    let _ = write!(result, "!{}{}", run.len(), c);
+                } else {
+                    result.extend(std::iter::repeat_n(c, run.len()));
+                }
+            }
+        }
+        result.push('-');
+    }
+    result.push_str("\x1b\\");
+    result
+}
+
+/// `Placement` is where an image is drawn on screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub src: String,
+    /// The visible part of the image, in screen cells.
+    pub area: Rect,
+    /// Rows of the image scrolled out above the screen.
+    pub hidden_rows: u16,
+    /// The height of the whole image, in cells.
+    pub rows: u16,
+}
+
+#[cfg(feature = "images")]
+impl Placement {
+    /// Cuts the rows of `image` that are not visible.
+    fn crop(&self, image: &DynamicImage) -> DynamicImage {
+        if self.area.height >= self.rows {
+            return image.clone();
+        }
+        let rows = self.rows.max(1) as u32;
+        let top = image.height() * self.hidden_rows as u32 / rows;
+        let height = (image.height() * self.area.height as u32 / rows).max(1);
+        image.crop_imm(0, top, image.width(), height)
+    }
+}
+
+/// `ScaledImages` caches images resized to cells, so that they are not resized on every frame.
+#[cfg(feature = "images")]
+#[derive(Debug, Default)]
+pub struct ScaledImages {
+    images: HashMap<(String, u16, u16), RgbaImage>,
+}
+
+#[cfg(feature = "images")]
+impl ScaledImages {
+    const CAPACITY: usize = 64;
+
+    /// Draws `image` into `buf` with half blocks, each cell showing two pixels stacked vertically.
+    pub fn draw(
+        &mut self,
+        image: &DynamicImage,
+        placement: &Placement,
+        buf: &mut Buffer,
+        palette: Palette,
+    ) {
+        let Rect { width, .. } = placement.area;
+        let key = (placement.src.clone(), width, placement.rows);
+        if !self.images.contains_key(&key) && self.images.len() >= Self::CAPACITY {
+            self.images.clear();
+        }
+        let scaled = self.images.entry(key).or_insert_with(|| {
+            image
+                .resize_exact(
+                    width.max(1) as u32,
+                    placement.rows.max(1) as u32 * 2,
+                    FilterType::Triangle,
+                )
+                .to_rgba8()
+        });
+
+        let color = |x: u32, y: u32| {
+            let [r, g, b, a] = scaled.get_pixel(x, y).0;
+            (a >= 128)
+                .then(|| palette.color(crate::cssom::Color::rgb(r, g, b)))
+                .flatten()
+                .unwrap_or(Color::Reset)
+        };
+        let area = placement.area.intersection(buf.area);
+        for y in area.top()..area.bottom() {
+            let row = (y - placement.area.y + placement.hidden_rows) as u32 * 2;
+            for x in area.left()..area.right() {
+                let column = (x - placement.area.x) as u32;
+                buf.get_mut(x, y)
+                    .set_char('▀')
+                    .set_fg(color(column, row))
+                    .set_bg(color(column, row + 1));
+            }
+        }
+    }
+}
+
+/// Returns the size of a terminal cell in pixels, assuming 8x16 if the terminal does not report it.
+pub fn cell_size() -> (u16, u16) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns).max(1),
+            (size.height / size.rows).max(1),
+        ),
+        _ => (8, 16),
+    }
+}
+
+/// Keeps the terminal backend from drawing over the cells of `area`, where an image is shown.
+pub fn reserve(area: Rect, buf: &mut Buffer) {
+    let area = area.intersection(buf.area);
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            buf.get_mut(x, y).set_skip(true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Graphics;
+    #[cfg(feature = "images")]
+    use super::{sixel, Placement, ScaledImages};
+    #[cfg(feature = "images")]
+    use crate::render::Palette;
+    #[cfg(feature = "images")]
+    use image::{DynamicImage, Rgba, RgbaImage};
+    #[cfg(feature = "images")]
+    use ratatui::{buffer::Buffer, layout::Rect, style::Color};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Graphics::from_env("xterm-kitty", "", ""), Graphics::Kitty);
+        assert_eq!(Graphics::from_env("xterm", "", "1"), Graphics::Kitty);
+        assert_eq!(
+            Graphics::from_env("xterm-256color", "iTerm.app", ""),
+            Graphics::Iterm2
+        );
+        assert_eq!(Graphics::from_env("foot", "", ""), Graphics::Sixel);
+        assert_eq!(
+            Graphics::from_env("xterm-256color", "", ""),
+            Graphics::HalfBlocks
+        );
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    fn test_sixel() {
+        let mut image = RgbaImage::from_pixel(3, 2, Rgba([255, 0, 0, 255]));
+        image.put_pixel(2, 1, Rgba([0, 0, 0, 0]));
+        let data = sixel(&image);
+
+        assert!(data.starts_with("\x1bPq\"1;1;3;2"));
+        assert!(data.ends_with("\x1b\\"));
+        // red is color 180 of the cube; the last column only has its top pixel
+        assert!(data.contains("#180BB@-"));
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    fn test_half_blocks() {
+        let mut image = RgbaImage::from_pixel(1, 4, Rgba([255, 255, 255, 255]));
+        image.put_pixel(0, 1, Rgba([0, 0, 0, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+        let mut buf = Buffer::empty(Rect::new(0, 0, 1, 2));
+        let placement = Placement {
+            src: "a.png".into(),
+            area: Rect::new(0, 0, 1, 2),
+            hidden_rows: 0,
+            rows: 2,
+        };
+        ScaledImages::default().draw(&image, &placement, &mut buf, Palette::TRUE_COLOR);
+
+        let cell = buf.get(0, 0);
+        assert_eq!(cell.symbol(), "▀");
+        assert_eq!(cell.fg, Color::Rgb(255, 255, 255));
+        assert_eq!(cell.bg, Color::Rgb(0, 0, 0));
+        assert_eq!(buf.get(0, 1).bg, Color::Rgb(255, 255, 255));
+    }
+}
@@ -0,0 +1,158 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+};
+
+/// Letters hint labels are made of, home row first so that the first labels are easy to type.
+const HINT_LETTERS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+/// Returns `n` distinct two-letter labels. There are at most 676 of them.
+fn labels(n: usize) -> Vec<String> {
+    let letters = HINT_LETTERS.chars().collect::<Vec<_>>();
+    (0..n.min(letters.len() * letters.len()))
+        .map(|i| {
+            [letters[i / letters.len()], letters[i % letters.len()]]
+                .iter()
+                .collect()
+        })
+        .collect()
+}
+
+/// `Hint` is a label shown over a link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hint {
+    pub label: String,
+    pub href: String,
+    /// Where the link starts on screen.
+    pub area: Rect,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HintEvent {
+    Follow(String),
+    Cancel,
+}
+
+/// `LinkHints` is the link hint mode: every visible link gets a label,
+/// and typing a label follows its link.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LinkHints {
+    hints: Vec<Hint>,
+    typed: String,
+}
+
+impl LinkHints {
+    /// Labels `links`, given as their targets and where they start on screen.
+    pub fn new(links: &[(String, Rect)]) -> Self {
+        let hints = links
+            .iter()
+            .zip(labels(links.len()))
+            .map(|((href, area), label)| Hint {
+                label,
+                href: href.clone(),
+                area: *area,
+            })
+            .collect();
+        LinkHints {
+            hints,
+            typed: String::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<HintEvent> {
+        match key.code {
+            KeyCode::Char(c) => {
+                self.typed.push(c.to_ascii_lowercase());
+                if let Some(hint) = self.hints.iter().find(|h| h.label == self.typed) {
+                    return Some(HintEvent::Follow(hint.href.clone()));
+                }
+                if self.hints.iter().any(|h| h.label.starts_with(&self.typed)) {
+                    None
+                } else {
+                    Some(HintEvent::Cancel)
+                }
+            }
+            KeyCode::Backspace => {
+                self.typed.pop();
+                None
+            }
+            _ => Some(HintEvent::Cancel),
+        }
+    }
+
+    /// Draws the labels that still match what has been typed over their links.
+    pub fn render(&self, buf: &mut Buffer) {
+        let style = Style::default()
+            .fg(Color::Black)
+            .bg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+        let typed_style = style.fg(Color::DarkGray);
+        for hint in self
+            .hints
+            .iter()
+            .filter(|h| h.label.starts_with(&self.typed))
+        {
+            for (i, c) in hint.label.chars().enumerate() {
+                let x = hint.area.x.saturating_add(i as u16);
+                let (y, area) = (hint.area.y, buf.area);
+                if area.x <= x && x < area.right() && area.y <= y && y < area.bottom() {
+                    let style = if i < self.typed.len() {
+                        typed_style
+                    } else {
+                        style
+                    };
+                    buf.get_mut(x, y).set_char(c).set_style(style);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{labels, HintEvent, LinkHints};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::{buffer::Buffer, layout::Rect};
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(labels(3), vec!["aa", "as", "ad"]);
+        let labels = labels(1000);
+        assert_eq!(labels.len(), 676);
+        assert_eq!(labels[26], "sa");
+    }
+
+    #[test]
+    fn test_link_hints() {
+        let links = vec![
+            ("/a".to_string(), Rect::new(0, 0, 5, 1)),
+            ("/b".to_string(), Rect::new(3, 2, 5, 1)),
+        ];
+        let mut hints = LinkHints::new(&links);
+        assert_eq!(hints.handle_key(key('a')), None);
+        assert_eq!(
+            hints.handle_key(key('s')),
+            Some(HintEvent::Follow("/b".into()))
+        );
+
+        let mut hints = LinkHints::new(&links);
+        assert_eq!(hints.handle_key(key('x')), Some(HintEvent::Cancel));
+
+        let hints = LinkHints::new(&links);
+        let mut buf = Buffer::empty(Rect::new(0, 0, 6, 3));
+        hints.render(&mut buf);
+        assert_eq!(buf.get(0, 0).symbol(), "a");
+        assert_eq!(buf.get(1, 0).symbol(), "a");
+        assert_eq!(buf.get(4, 2).symbol(), "s");
+    }
+}
@@ -0,0 +1,92 @@
+use crate::page::Page;
+use std::mem;
+
+/// `Entry` is a visited page together with how far it was scrolled and what was focused.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub page: Page,
+    pub scroll: u16,
+    /// The path in the DOM tree of the focused link or form control.
+    pub focus: Option<Vec<usize>>,
+    /// Whether the page is yet to be scrolled to the element named by its URL fragment.
+    pub reveal_fragment: bool,
+}
+
+/// `History` holds the pages behind and ahead of the current one.
+/// Pages are kept loaded so going back and forward does not fetch them again.
+#[derive(Debug, Default, PartialEq)]
+pub struct History {
+    back: Vec<Entry>,
+    forward: Vec<Entry>,
+}
+
+impl History {
+    /// Makes `next` the current entry, pushing the old one onto the back stack.
+    /// Visiting a new page discards the forward stack.
+    pub fn visit(&mut self, current: &mut Entry, next: Entry) {
+        self.back.push(mem::replace(current, next));
+        self.forward.clear();
+    }
+
+    /// Swaps `current` with the previous entry. Returns `false` if there is none.
+    pub fn back(&mut self, current: &mut Entry) -> bool {
+        match self.back.pop() {
+            Some(prev) => {
+                self.forward.push(mem::replace(current, prev));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Swaps `current` with the next entry. Returns `false` if there is none.
+    pub fn forward(&mut self, current: &mut Entry) -> bool {
+        match self.forward.pop() {
+            Some(next) => {
+                self.back.push(mem::replace(current, next));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, History};
+    use crate::page::{Page, Settings};
+
+    fn entry(url: &str, scroll: u16) -> Entry {
+        Entry {
+            page: Page::from_html(url, "<p>page</p>", &Settings::default()).unwrap(),
+            scroll,
+            focus: None,
+            reveal_fragment: false,
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = History::default();
+        let mut current = entry("a", 0);
+        assert!(!history.back(&mut current));
+
+        current.scroll = 3;
+        history.visit(&mut current, entry("b", 0));
+        history.visit(&mut current, entry("c", 0));
+        assert_eq!(current.page.url, "c");
+
+        assert!(history.back(&mut current));
+        assert!(history.back(&mut current));
+        assert_eq!(current, entry("a", 3));
+        assert!(!history.back(&mut current));
+
+        assert!(history.forward(&mut current));
+        assert_eq!(current.page.url, "b");
+
+        history.visit(&mut current, entry("d", 0));
+        assert!(!history.forward(&mut current));
+        assert!(history.back(&mut current));
+        assert_eq!(current.page.url, "b");
+    }
+}
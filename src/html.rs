@@ -1,28 +1,42 @@
-use crate::dom::{AttrMap, Element, Node, Text};
+use crate::{
+    dom::{self, AttrMap, Comment, Doctype, Element, Node, NodeType, Span, Text},
+    entity,
+    error::Error,
+};
 use combine::{
-    attempt, between,
+    any, attempt, between, eof,
     error::StreamError,
     many, many1, optional, parser,
-    parser::char::{self, string_cmp},
+    parser::char::string_cmp,
     parser::{
-        char::{char, letter, newline, space},
+        char::{alpha_num, char, letter, newline, space, string},
         choice::choice,
+        repeat::take_until,
     },
-    satisfy, sep_by, skip_many, ParseError, Parser, Stream,
+    position, satisfy, sep_end_by, skip_many, skip_many1,
+    stream::PointerOffset,
+    ParseError, Parser, Stream,
 };
+use std::borrow::Cow;
 
-fn cstring<Input>(s: &'static str) -> impl Parser<Input, Output = &str>
-where
-    Input: Stream<Token = char>,
-{
-    string_cmp(s, |l, r| l.eq_ignore_ascii_case(&r))
+/// `SourcePosition` is the position of the input of the parsers, which says where in the
+/// HTML it is if it knows.
+pub trait SourcePosition {
+    fn offset(&self) -> Option<usize>;
+}
+
+/// A bare `&str` only knows where it is in memory, so what is parsed from one has no span.
+impl SourcePosition for PointerOffset<str> {
+    fn offset(&self) -> Option<usize> {
+        None
+    }
 }
 
-fn ignore<Input>(p: impl Parser<Input>) -> impl Parser<Input, Output = ()>
+fn cstring<Input>(s: &'static str) -> impl Parser<Input, Output = &'static str>
 where
     Input: Stream<Token = char>,
 {
-    p.map(|_| ())
+    string_cmp(s, |l, r| l.eq_ignore_ascii_case(&r))
 }
 
 fn is_ascii_whitespace(c: char) -> bool {
@@ -76,6 +90,12 @@ where
     attribute_name().map(|key| (key, String::new()))
 }
 
+/// Makes an attribute of what `name = value` parses to, with the character references
+/// of its value decoded.
+fn name_and_value((name, _, _, _, value): (String, (), char, (), String)) -> (String, String) {
+    (name, entity::decode(&value).into_owned())
+}
+
 fn unquoted_attribute<Input>() -> impl Parser<Input, Output = (String, String)>
 where
     Input: Stream<Token = char>,
@@ -87,7 +107,7 @@ where
         skip_many(ascii_whitespace()),
         unquoted_attribute_value(),
     )
-        .map(|(key, _, _, _, value)| (key, value))
+        .map(name_and_value)
 }
 
 fn single_quoted_attribute<Input>() -> impl Parser<Input, Output = (String, String)>
@@ -101,7 +121,7 @@ where
         skip_many(ascii_whitespace()),
         between(char('\''), char('\''), many(satisfy(|c| c != '\''))),
     )
-        .map(|(key, _, _, _, value)| (key, value))
+        .map(name_and_value)
 }
 
 fn double_quoted_attribute<Input>() -> impl Parser<Input, Output = (String, String)>
@@ -115,7 +135,7 @@ where
         skip_many(ascii_whitespace()),
         between(char('"'), char('"'), many(satisfy(|c| c != '"'))),
     )
-        .map(|(key, _, _, _, value)| (key, value))
+        .map(name_and_value)
 }
 
 fn attribute<Input>() -> impl Parser<Input, Output = (String, String)>
@@ -134,118 +154,554 @@ fn attributes<Input>() -> impl Parser<Input, Output = AttrMap>
 where
     Input: Stream<Token = char>,
 {
-    (sep_by(attribute(), space().or(newline())))
+    // the whitespace after the last is left in, before the end of a tag like `<img src=a />`
+    sep_end_by(attribute(), skip_many1(space().or(newline())))
         .map(|v: Vec<(String, String)>| v.into_iter().collect())
 }
 
-fn open_tag<Input>() -> impl Parser<Input, Output = (String, AttrMap)>
+/// Parses a tag name: a letter followed by letters and digits, like `p` or `h1`.
+/// It is lowercased, as the HTML tokenizer does, so that `<SCRIPT>` is a `script`.
+fn tag_name<Input>() -> impl Parser<Input, Output = String>
+where
+    Input: Stream<Token = char>,
+{
+    (letter(), many::<String, _, _>(alpha_num()))
+        .map(|(first, rest)| format!("{}{}", first, rest).to_ascii_lowercase())
+}
+
+/// Parses a start tag into its name, its attributes and whether it closes itself with a `/`
+/// before its `>`, like `<br/>` and `<img src="a.png" />` in XHTML.
+fn open_tag<Input>() -> impl Parser<Input, Output = (String, AttrMap, bool)>
 where
     Input: Stream<Token = char>,
 {
-    let open_tag_name = many1::<String, _, _>(letter());
-    let open_tag_content = (
-        open_tag_name,
+    (
+        char('<'),
+        tag_name(),
         skip_many(space().or(newline())),
         attributes(),
+        optional(char('/')),
+        char('>'),
     )
-        .map(|(tag_name, _, attr_map)| (tag_name, attr_map));
-    between(char('<'), char('>'), open_tag_content)
+        .map(|(_, tag_name, _, attr_map, slash, _)| (tag_name, attr_map, slash.is_some()))
 }
 
 fn close_tag<Input>() -> impl Parser<Input, Output = String>
 where
     Input: Stream<Token = char>,
 {
-    (char('<'), char('/'), many1(letter()), char('>')).map(|(_, _, tag_name, _)| tag_name)
+    (char('<'), char('/'), tag_name(), char('>')).map(|(_, _, tag_name, _)| tag_name)
+}
+
+/// The elements whose start tag ends an open `<p>`, as they cannot be in one.
+const ENDS_PARAGRAPH: [&str; 29] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "table",
+    "ul",
+];
+
+/// Returns whether the start tag of `tag_name` ends an open `parent`, one of those whose end
+/// tag may be left out: `p`, `li`, `dt` and `dd`.
+fn ends(parent: &str, tag_name: &str) -> bool {
+    match parent {
+        "p" => ENDS_PARAGRAPH.contains(&tag_name),
+        "li" => tag_name == "li",
+        "dt" | "dd" => tag_name == "dt" || tag_name == "dd",
+        _ => false,
+    }
+}
+
+/// `Token` is what the tokenizer reads from markup at a time.
+#[derive(Debug)]
+enum Token {
+    /// A node that no more children come into: a comment, a doctype, a void element or one
+    /// that closes itself, or a `<script>` or `<style>` with its raw text.
+    Node(Box<Node>),
+    /// A start tag, of an element whose children follow.
+    Start(String, AttrMap),
+    /// An end tag.
+    End(String),
 }
 
-fn nodes_<Input>() -> impl Parser<Input, Output = Vec<Box<Node>>>
+fn token<Input>() -> impl Parser<Input, Output = Token>
 where
     Input: Stream<Token = char>,
 {
-    (
-        skip_many(space().or(newline())),
-        attempt(many(
-            (
-                choice((
-                    attempt(normal_element()),
-                    attempt(void_element()),
-                    attempt(text()),
-                )),
-                skip_many(space().or(newline())),
-            )
-                .map(|(node, _)| node),
-        )),
-    )
-        .map(|(_, nodes)| nodes)
+    choice((
+        attempt(doctype()).map(Token::Node),
+        attempt(comment()).map(Token::Node),
+        attempt(raw_text_element("script", "</script")).map(Token::Node),
+        attempt(raw_text_element("style", "</style")).map(Token::Node),
+        attempt(void_element()).map(Token::Node),
+        attempt(open_tag()).map(|(tag_name, attributes, _)| Token::Start(tag_name, attributes)),
+        attempt(close_tag()).map(Token::End),
+    ))
+}
+
+/// Returns where `needle`, in lowercase, first is in `haystack` from `from` on, in any case.
+fn find_ignore_case(haystack: &str, needle: &str, from: usize) -> Option<usize> {
+    (from..=haystack.len().checked_sub(needle.len())?)
+        .find(|&i| haystack.as_bytes()[i..i + needle.len()].eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Returns how long the markup that `rest` starts with, at a `<`, is: up to the `>` of a tag
+/// outside its quoted attribute values, the `-->` of a comment, or the end tag of a
+/// `<script>` or a `<style>`. A `<` that starts no markup is 1 long. `None` if the markup
+/// does not end within `rest`.
+fn markup_len(rest: &str) -> Option<usize> {
+    let after = rest[1..].chars().next()?;
+    if after == '!' {
+        if "<!--".starts_with(rest) {
+            return None;
+        }
+        return match rest.starts_with("<!--") {
+            true => rest[4..].find("-->").map(|i| 4 + i + 3),
+            false => rest.find('>').map(|i| i + 1),
+        };
+    }
+    if after == '/' {
+        return match rest[2..].chars().next()? {
+            c if c.is_ascii_alphabetic() => rest.find('>').map(|i| i + 1),
+            _ => Some(1),
+        };
+    }
+    if !after.is_ascii_alphabetic() {
+        return Some(1);
+    }
+    let (mut quote, mut after_equals) = (None, false);
+    let mut tag_end = None;
+    for (i, c) in rest.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => (quote, after_equals) = (None, false),
+            (Some(_), _) => {}
+            (None, '>') => {
+                tag_end = Some(i + 1);
+                break;
+            }
+            (None, '"' | '\'') if after_equals => quote = Some(c),
+            (None, '=') => after_equals = true,
+            (None, c) if is_ascii_whitespace(c) => {}
+            (None, _) => after_equals = false,
+        }
+    }
+    let tag_end = tag_end?;
+    let tag_name = rest[1..]
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .next()
+        .unwrap_or_default();
+    let end_tag = match tag_name.to_ascii_lowercase().as_str() {
+        "script" => "</script",
+        "style" => "</style",
+        _ => return Some(tag_end),
+    };
+    if rest[..tag_end].ends_with("/>") {
+        return Some(tag_end);
+    }
+    // the raw text goes on up to the end tag, whatever looks like markup in it
+    let mut from = tag_end;
+    loop {
+        let start = find_ignore_case(rest, end_tag, from)?;
+        let after_name = &rest[start + end_tag.len()..];
+        let name_end = rest.len() - after_name.trim_start_matches(is_ascii_whitespace).len();
+        match rest[name_end..].chars().next()? {
+            '>' => return Some(name_end + 1),
+            _ => from = start + 1,
+        }
+    }
+}
+
+/// `TreeBuilder` builds the nodes of HTML as it is given, a part at a time or all at once.
+/// The elements that are open are kept on a stack: one is closed by its end tag, by the end
+/// tag of an element it is in, by the start tag of an element that cannot be in it, like a
+/// `<li>` after a `<li>`, or at the end of the HTML. So an end tag that is left out or
+/// misplaced never makes what came before it parsed again.
+#[derive(Debug, Clone)]
+pub struct TreeBuilder {
+    /// What was given but does not make a whole token yet.
+    pending: String,
+    /// Where `pending` starts in the HTML, if the nodes keep their spans.
+    offset: Option<usize>,
+    /// The elements that are open, outermost first.
+    open: Vec<Node>,
+    /// What the top-level nodes that are closed are children of.
+    root: Node,
+    /// Where the last token that was not whitespace ended.
+    end: Option<usize>,
+    /// Whether line breaks are taken as spaces.
+    fold_line_breaks: bool,
+}
+
+/// A builder of the nodes of an HTML document, each with its span of the document.
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        TreeBuilder::starting_at(Some(0))
+    }
+}
+
+impl TreeBuilder {
+    /// Makes a builder of nodes of HTML that starts `offset` bytes into the document, or
+    /// whose nodes have no spans if `None`.
+    fn starting_at(offset: Option<usize>) -> TreeBuilder {
+        TreeBuilder {
+            pending: String::new(),
+            offset,
+            open: vec![],
+            root: *Element::new(String::new(), AttrMap::new(), vec![]),
+            end: None,
+            fold_line_breaks: false,
+        }
+    }
+
+    /// Makes a builder as `default` does that takes the line breaks of the HTML as spaces,
+    /// as pages are shown, but those in its `<script>`s, where they end `//` comments.
+    /// Every byte stays where it was, so the spans still point into the HTML.
+    pub fn folding_line_breaks() -> TreeBuilder {
+        TreeBuilder {
+            fold_line_breaks: true,
+            ..TreeBuilder::default()
+        }
+    }
+
+    /// Takes in `html`, the next part of the HTML, building what it completes.
+    pub fn push(&mut self, html: &str) {
+        self.pending.push_str(html);
+        self.tokenize(false);
+    }
+
+    /// Returns the nodes built so far, with the elements that are still open closed.
+    pub fn snapshot(&self) -> Vec<Box<Node>> {
+        let mut builder = TreeBuilder {
+            pending: String::new(),
+            offset: None,
+            open: self.open.clone(),
+            root: self.root.clone(),
+            end: self.end,
+            fold_line_breaks: false,
+        };
+        builder.close_all();
+        builder.root.children
+    }
+
+    /// Builds the rest of the HTML, closing the elements that are still open, and returns
+    /// the top-level nodes. Markup that never ended is left out.
+    pub fn finish(mut self) -> Vec<Box<Node>> {
+        self.tokenize(true);
+        self.close_all();
+        self.root.children
+    }
+
+    /// Builds the tokens that `pending` starts with, as long as they are whole, unless the
+    /// HTML ends, `at_end`.
+    fn tokenize(&mut self, at_end: bool) {
+        let pending = std::mem::take(&mut self.pending);
+        let mut at = 0;
+        while at < pending.len() {
+            let rest = &pending[at..];
+            let start = self.offset.map(|offset| offset + at);
+            let len = match rest.find('<') {
+                Some(0) => match markup_len(rest) {
+                    Some(len) => self.markup(&rest[..len], start),
+                    None => break,
+                },
+                Some(len) => {
+                    self.text(&rest[..len], start);
+                    len
+                }
+                None => {
+                    // a character reference may go on in the next part
+                    let len = match at_end {
+                        true => rest.len(),
+                        false => rest.rfind('&').unwrap_or(rest.len()),
+                    };
+                    if len == 0 {
+                        break;
+                    }
+                    self.text(&rest[..len], start);
+                    len
+                }
+            };
+            at += len;
+        }
+        self.offset = self.offset.map(|offset| offset + at);
+        self.pending = match at_end {
+            true => String::new(),
+            false => pending[at..].to_string(),
+        };
+    }
+
+    /// Builds what `markup`, a whole tag, comment or raw text element, is, and returns how
+    /// much of it was taken: all of it, or only its `<` as text if it is not markup.
+    fn markup(&mut self, markup: &str, start: Option<usize>) -> usize {
+        let end = start.map(|start| start + markup.len());
+        let is_script = markup
+            .get(..7)
+            .is_some_and(|tag| tag.eq_ignore_ascii_case("<script"));
+        let folded = match self.fold_line_breaks && !is_script {
+            true => Cow::Owned(markup.replace('\n', " ")),
+            false => Cow::Borrowed(markup),
+        };
+        let token = token().skip(eof()).parse(folded.as_ref()).ok();
+        match token.map(|(token, _)| token) {
+            Some(Token::Node(mut node)) => {
+                if let NodeType::Element(element) = &node.node_type {
+                    self.close_ended_by(&element.tag_name);
+                }
+                node.span = span(start, end);
+                self.end = end;
+                self.append(node);
+                markup.len()
+            }
+            Some(Token::Start(tag_name, attributes)) => {
+                self.close_ended_by(&tag_name);
+                let mut element = *Element::new(tag_name, attributes, vec![]);
+                element.span = span(start, end);
+                self.end = end;
+                self.open.push(element);
+                markup.len()
+            }
+            Some(Token::End(tag_name)) => {
+                // the end tag of an element that is not open, like that of a void one, is skipped
+                let open = self.open.iter().rposition(|node| {
+                    matches!(&node.node_type, NodeType::Element(e) if e.tag_name == tag_name)
+                });
+                if let Some(i) = open {
+                    while self.open.len() > i + 1 {
+                        self.close();
+                    }
+                    self.end = end;
+                    self.close();
+                }
+                markup.len()
+            }
+            None => {
+                self.text("<", start);
+                1
+            }
+        }
+    }
+
+    /// Builds `raw`, text with its character references not decoded yet. Text right after
+    /// text goes on with it; otherwise its leading whitespace is left out, and it is left
+    /// out altogether if there is nothing else.
+    fn text(&mut self, raw: &str, start: Option<usize>) {
+        let end = start.map(|start| start + raw.len());
+        let raw = match self.fold_line_breaks {
+            true => Cow::Owned(raw.replace('\n', " ")),
+            false => Cow::Borrowed(raw),
+        };
+        let children = &mut self.open.last_mut().unwrap_or(&mut self.root).children;
+        if let Some(last) = children.last_mut() {
+            if let NodeType::Text(text) = &mut last.node_type {
+                text.data.push_str(&entity::decode(&raw));
+                if let (Some(span), Some(end)) = (&mut last.span, end) {
+                    span.end = end;
+                }
+                self.end = end;
+                return;
+            }
+        }
+        let trimmed = raw.trim_start_matches(char::is_whitespace);
+        if trimmed.is_empty() {
+            return;
+        }
+        let mut node = Text::new(entity::decode(trimmed).into_owned());
+        node.span = span(start.map(|start| start + raw.len() - trimmed.len()), end);
+        self.end = end;
+        children.push(node);
+    }
+
+    /// Closes the open elements that the start tag of `tag_name` ends.
+    fn close_ended_by(&mut self, tag_name: &str) {
+        while let Some(NodeType::Element(parent)) = self.open.last().map(|node| &node.node_type) {
+            if !ends(&parent.tag_name, tag_name) {
+                break;
+            }
+            self.close();
+        }
+    }
+
+    /// Closes the innermost open element, where the last token ended.
+    fn close(&mut self) {
+        if let Some(mut element) = self.open.pop() {
+            if let (Some(span), Some(end)) = (&mut element.span, self.end) {
+                span.end = end;
+            }
+            self.append(Box::new(element));
+        }
+    }
+
+    fn close_all(&mut self) {
+        while !self.open.is_empty() {
+            self.close();
+        }
+    }
+
+    /// Appends `node` to the innermost open element, or to the top-level nodes.
+    fn append(&mut self, node: Box<Node>) {
+        self.open
+            .last_mut()
+            .unwrap_or(&mut self.root)
+            .children
+            .push(node);
+    }
+}
+
+/// Returns the span from `start` to `end`, if both are known.
+fn span(start: Option<usize>, end: Option<usize>) -> Option<Span> {
+    Some(Span {
+        start: start?,
+        end: end?,
+    })
 }
 
 parser! {
+    /// Parses the rest of the input as HTML, into its top-level nodes.
     pub fn nodes[Input]()(Input) -> Vec<Box<Node>>
-    where [Input: Stream<Token = char>]
+    where [Input: Stream<Token = char>, Input::Position: SourcePosition]
     {
-        nodes_()
+        (position(), many::<String, _, _>(any())).map(|(start, content): (Input::Position, String)| {
+            let mut builder = TreeBuilder::starting_at(start.offset());
+            builder.push(&content);
+            builder.finish()
+        })
     }
 }
 
-fn text<Input>() -> impl Parser<Input, Output = Box<Node>>
-where
-    Input: Stream<Token = char>,
-{
-    many1(satisfy(|c: char| c != '<')).map(Text::new)
-}
-
+/// Parses an element that has no children by its name, like `<br>` or `<img src="a.png">`,
+/// or as it closes itself, like `<br/>`. What follows it is never its child.
 fn void_element<Input>() -> impl Parser<Input, Output = Box<Node>>
 where
     Input: Stream<Token = char>,
 {
-    open_tag().map(|(tag_name, attributes)| Element::new(tag_name, attributes, vec![]))
+    open_tag().and_then(|(tag_name, attributes, self_closing)| {
+        if self_closing || dom::is_void(&tag_name) {
+            Ok(Element::new(tag_name, attributes, vec![]))
+        } else {
+            Err(
+                <Input::Error as ParseError<char, _, _>>::StreamError::message_static_message(
+                    "not a void element",
+                ),
+            )
+        }
+    })
 }
 
-fn normal_element<Input>() -> impl Parser<Input, Output = Box<Node>>
+/// Parses a `tag` element, a `<script>` or a `<style>`, whose content is raw text up to
+/// `end`, the start of its end tag, as the HTML tokenizer reads it: a `<` or a `&` in it is
+/// not markup.
+fn raw_text_element<Input>(
+    tag: &'static str,
+    end: &'static str,
+) -> impl Parser<Input, Output = Box<Node>>
 where
     Input: Stream<Token = char>,
 {
-    (open_tag(), nodes(), close_tag()).and_then(
-        |((open_tag_name, attributes), children, close_tag_name)| {
-            if open_tag_name == close_tag_name {
-                Ok(Element::new(open_tag_name, attributes, children))
-            } else {
-                Err(
-                    <Input::Error as ParseError<char, _, _>>::StreamError::message_static_message(
-                        "tag name of open tag and close tag mismatched",
-                    ),
-                )
-            }
-        },
+    let start_tag = open_tag().and_then(move |(tag_name, attributes, self_closing)| {
+        if tag_name == tag && !self_closing {
+            Ok((tag_name, attributes))
+        } else {
+            Err(
+                <Input::Error as ParseError<char, _, _>>::StreamError::message_static_message(
+                    "not a raw text element",
+                ),
+            )
+        }
+    });
+    (
+        start_tag,
+        take_until::<String, _, _>(attempt(cstring(end))),
+        cstring(end),
+        skip_many(ascii_whitespace()),
+        char('>'),
     )
+        .map(|((tag_name, attributes), text, _, _, _)| {
+            let children = match text.is_empty() {
+                true => vec![],
+                false => vec![Text::new(text)],
+            };
+            Element::new(tag_name, attributes, children)
+        })
 }
 
 pub fn html<Input>() -> impl Parser<Input, Output = Vec<Box<Node>>>
 where
     Input: Stream<Token = char>,
+    Input::Position: SourcePosition,
 {
-    (optional(attempt(doctype())), nodes()).map(|(_, nodes)| nodes)
+    nodes()
 }
 
-fn doctype<Input>() -> impl Parser<Input, Output = ()>
+/// Parses `content`, a whole HTML document, into its top-level nodes, each with the span of
+/// `content` it was parsed from.
+pub fn parse(content: &str) -> Result<Vec<Box<Node>>, Error> {
+    let _span = tracing::debug_span!("parse", bytes = content.len()).entered();
+    let mut builder = TreeBuilder::default();
+    builder.push(content);
+    Ok(builder.finish())
+}
+
+fn doctype<Input>() -> impl Parser<Input, Output = Box<Node>>
 where
     Input: Stream<Token = char>,
 {
-    ignore((
+    (
         cstring("<!DOCTYPE"),
-        attempt(many::<(), _, _>(ignore(satisfy(|c| c != '>')))),
+        attempt(many::<String, _, _>(satisfy(|c| c != '>'))),
         char('>'),
-    ))
+    )
+        .map(|(_, name, _)| Doctype::new(name.trim().to_string()))
+}
+
+/// Parses a `<!-- comment -->`.
+fn comment<Input>() -> impl Parser<Input, Output = Box<Node>>
+where
+    Input: Stream<Token = char>,
+{
+    (
+        string("<!--"),
+        take_until::<String, _, _>(attempt(string("-->"))),
+        string("-->"),
+    )
+        .map(|(_, data, _)| Comment::new(data))
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        dom::{AttrMap, Element, Text},
-        html::{attribute, attributes, close_tag, doctype, normal_element, open_tag, void_element},
+        dom::{AttrMap, Comment, Doctype, Element, Node, Text},
+        html::{
+            attribute, attributes, close_tag, comment, doctype, html, open_tag, parse,
+            void_element, TreeBuilder,
+        },
     };
     use combine::Parser;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_parse_attribute() {
@@ -295,7 +751,7 @@ mod test {
         {
             assert_eq!(
                 open_tag().parse("<p>aaaa"),
-                Ok((("p".to_string(), AttrMap::new()), "aaaa"))
+                Ok((("p".to_string(), AttrMap::new(), false), "aaaa"))
             );
         }
         {
@@ -303,7 +759,7 @@ mod test {
             attributes.insert("id".to_string(), "test".to_string());
             assert_eq!(
                 open_tag().parse("<p id=\"test\">"),
-                Ok((("p".to_string(), attributes), ""))
+                Ok((("p".to_string(), attributes, false), ""))
             )
         }
         {
@@ -311,7 +767,7 @@ mod test {
             let mut attributes = AttrMap::new();
             attributes.insert("id".to_string(), "test".to_string());
             attributes.insert("class".to_string(), "sample".to_string());
-            assert_eq!(result, Ok((("p".to_string(), attributes), "")));
+            assert_eq!(result, Ok((("p".to_string(), attributes, false), "")));
         }
 
         {
@@ -319,7 +775,24 @@ mod test {
             attributes.insert("disabled".to_string(), "".to_string());
             assert_eq!(
                 open_tag().parse("<input disabled>"),
-                Ok((("input".to_string(), attributes), ""))
+                Ok((("input".to_string(), attributes.clone(), false), ""))
+            );
+            assert_eq!(
+                open_tag().parse("<input disabled/>"),
+                Ok((("input".to_string(), attributes, true), ""))
+            );
+        }
+        {
+            assert_eq!(
+                open_tag().parse("<br/>"),
+                Ok((("br".to_string(), AttrMap::new(), true), ""))
+            );
+            let mut attributes = AttrMap::new();
+            attributes.insert("src".to_string(), "a.png".to_string());
+            attributes.insert("alt".to_string(), "".to_string());
+            assert_eq!(
+                open_tag().parse("<img src=\"a.png\" alt=\"\"  />"),
+                Ok((("img".to_string(), attributes, true), ""))
             );
         }
     }
@@ -327,59 +800,409 @@ mod test {
     #[test]
     fn test_parse_close_tag() {
         let result = close_tag().parse("</p>");
-        assert_eq!(result, Ok(("p".to_string(), "")))
+        assert_eq!(result, Ok(("p".to_string(), "")));
+        let result = close_tag().parse("</h2>");
+        assert_eq!(result, Ok(("h2".to_string(), "")))
+    }
+
+    #[test]
+    fn test_heading_tag_names() {
+        for level in 1..=6 {
+            let name = format!("h{}", level);
+            assert_eq!(
+                parse(&format!("<H{0}>title</h{0}><p>text</p>", level)).unwrap(),
+                vec![
+                    Element::new(name, AttrMap::new(), vec![Text::new("title".into())]),
+                    Element::new("p".into(), AttrMap::new(), vec![Text::new("text".into())]),
+                ]
+            );
+        }
+        // a tag name starts with a letter
+        assert!(open_tag().parse("<1p>").is_err());
     }
 
     #[test]
     fn test_parse_element() {
         assert_eq!(
-            normal_element().parse("<p></p>"),
-            Ok((Element::new("p".to_string(), AttrMap::new(), vec![]), ""))
+            parse("<p></p>").unwrap(),
+            vec![Element::new("p".to_string(), AttrMap::new(), vec![])]
         );
 
         assert_eq!(
-            normal_element().parse("<p>hello world</p>"),
-            Ok((
-                Element::new(
+            parse("<p>hello world</p>").unwrap(),
+            vec![Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![Text::new("hello world".to_string())]
+            )]
+        );
+
+        assert_eq!(
+            parse("<div><p>hello world</p></div>").unwrap(),
+            vec![Element::new(
+                "div".to_string(),
+                AttrMap::new(),
+                vec![Element::new(
                     "p".to_string(),
                     AttrMap::new(),
                     vec![Text::new("hello world".to_string())]
-                ),
-                ""
-            ))
+                )],
+            )]
         );
 
+        // an end tag of no open element is skipped, and an element left open is closed at
+        // the end
         assert_eq!(
-            normal_element().parse("<div><p>hello world</p></div>"),
-            Ok((
-                Element::new(
-                    "div".to_string(),
-                    AttrMap::new(),
-                    vec![Element::new(
-                        "p".to_string(),
-                        AttrMap::new(),
-                        vec![Text::new("hello world".to_string())]
-                    )],
-                ),
-                ""
-            ))
+            parse("<p>hello world</div>").unwrap(),
+            vec![Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![Text::new("hello world".to_string())]
+            )]
+        );
+        assert_eq!(
+            parse("<div/></div>").unwrap(),
+            vec![Element::new("div".to_string(), AttrMap::new(), vec![])]
+        );
+
+        assert_eq!(
+            parse("<p>a<br/>b<img src='c.png' /></p>").unwrap(),
+            vec![Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("a".to_string()),
+                    Element::new("br".to_string(), AttrMap::new(), vec![]),
+                    Text::new("b".to_string()),
+                    Element::new(
+                        "img".to_string(),
+                        [("src".to_string(), "c.png".to_string())].into(),
+                        vec![]
+                    ),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_elements() {
+        let element =
+            |tag_name: &str, children| Element::new(tag_name.into(), AttrMap::new(), children);
+        let text = |data: &str| Text::new(data.into());
+        // an end tag closes the elements left open in the one it ends
+        assert_eq!(
+            parse("<div><span>a</div>b<b><i>c</b>d</i>").unwrap(),
+            vec![
+                element("div", vec![element("span", vec![text("a")])]),
+                text("b"),
+                element("b", vec![element("i", vec![text("c")])]),
+                text("d"),
+            ]
+        );
+
+        // each element is parsed once, however many are left open
+        let started = Instant::now();
+        let nodes = parse(&"<div>".repeat(30)).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let mut depth = 0;
+        let mut node = &nodes[0];
+        while let Some(child) = node.children.first() {
+            (node, depth) = (child, depth + 1);
+        }
+        assert_eq!(depth, 29);
+    }
+
+    #[test]
+    fn test_tree_builder() {
+        let source = concat!(
+            "<!DOCTYPE html><ul><li title=\"a > b\">one<li>two &amp; three</ul>",
+            "<!-- c --><script>if (a < b) {}</script ><p>4 < 5</p>"
         );
+        // however the HTML is cut into parts, it is built into the same nodes
+        for size in [1, 2, 3, 7, 16] {
+            let mut builder = TreeBuilder::default();
+            let mut part = String::new();
+            for c in source.chars() {
+                part.push(c);
+                if part.len() >= size {
+                    builder.push(&part);
+                    part.clear();
+                }
+            }
+            builder.push(&part);
+            let nodes = builder.finish();
+            assert_eq!(nodes, parse(source).unwrap());
+            assert_eq!(nodes[1].span, parse(source).unwrap()[1].span);
+        }
 
-        assert!(normal_element().parse("<p>hello world</div>").is_err());
+        let mut builder = TreeBuilder::default();
+        builder.push("<div><p>a</p><p>b");
+        assert_eq!(
+            builder.snapshot(),
+            parse("<div><p>a</p><p>b</p></div>").unwrap()
+        );
+        builder.push("c</p><im");
+        assert_eq!(
+            builder.snapshot(),
+            parse("<div><p>a</p><p>bc</p></div>").unwrap()
+        );
+        builder.push("g src=x>");
+        assert_eq!(
+            builder.finish(),
+            parse("<div><p>a</p><p>bc</p><img src=x></div>").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fold_line_breaks() {
+        let folded = |html: &str| {
+            let mut builder = TreeBuilder::folding_line_breaks();
+            builder.push(html);
+            builder.finish()
+        };
+        assert_eq!(
+            folded("<p>a\nb</p><SCRIPT>// x\ny()</SCRIPT>\n<p>日本\n</p>"),
+            parse("<p>a b</p><SCRIPT>// x\ny()</SCRIPT> <p>日本 </p>").unwrap()
+        );
+        assert_eq!(
+            folded("<!--a\nb--><p\ntitle='c\nd'><style>\np {}</style>"),
+            parse("<!--a b--><p title='c d'><style> p {}</style>").unwrap()
+        );
     }
 
     #[test]
     fn test_parse_doctype() {
         assert_eq!(
             doctype().parse("<!DOCTYPE html><div></div>"),
-            Ok(((), "<div></div>"))
+            Ok((Doctype::new("html".into()), "<div></div>"))
         );
         assert_eq!(
             doctype().parse(r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.0 Transitional//EN">"#),
-            Ok(((), ""))
+            Ok((
+                Doctype::new(r#"HTML PUBLIC "-//W3C//DTD HTML 4.0 Transitional//EN""#.into()),
+                ""
+            ))
         )
     }
 
+    #[test]
+    fn test_comment() {
+        assert_eq!(
+            comment().parse("<!-- a <b> -- c -->d"),
+            Ok((Comment::new(" a <b> -- c ".into()), "d"))
+        );
+        assert_eq!(
+            parse("<!DOCTYPE html><!--x--><p>a<!---->b</p>").unwrap(),
+            vec![
+                Doctype::new("html".into()),
+                Comment::new("x".into()),
+                Element::new(
+                    "p".into(),
+                    AttrMap::new(),
+                    vec![
+                        Text::new("a".into()),
+                        Comment::new("".into()),
+                        Text::new("b".into())
+                    ]
+                )
+            ]
+        );
+        // between elements, and among those of the <head>
+        assert_eq!(
+            parse("<html><head><!-- meta --><title>t</title>\n<!--\nmore\n--></head><body><p>a</p> <!-- x --> <p>b</p></body></html>").unwrap(),
+            vec![Element::new(
+                "html".into(),
+                AttrMap::new(),
+                vec![
+                    Element::new(
+                        "head".into(),
+                        AttrMap::new(),
+                        vec![
+                            Comment::new(" meta ".into()),
+                            Element::new("title".into(), AttrMap::new(), vec![Text::new("t".into())]),
+                            Comment::new("\nmore\n".into()),
+                        ]
+                    ),
+                    Element::new(
+                        "body".into(),
+                        AttrMap::new(),
+                        vec![
+                            Element::new("p".into(), AttrMap::new(), vec![Text::new("a".into())]),
+                            Comment::new(" x ".into()),
+                            Element::new("p".into(), AttrMap::new(), vec![Text::new("b".into())]),
+                        ]
+                    )
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_character_references() {
+        let mut attributes = AttrMap::new();
+        attributes.insert("title".into(), "Tom & Jerry".into());
+        attributes.insert("alt".into(), "<é>".into());
+        attributes.insert("data-x".into(), "a&b".into());
+        attributes.insert("lang".into(), "©".into());
+        assert_eq!(
+            parse(r#"<p title="Tom &amp; Jerry" alt='&lt;&eacute;&gt;' data-x=a&amp;b lang="&#169;">caf&eacute;&#x21; &mdash; 1 &lt; 2 &unknown;</p>"#).unwrap(),
+            vec![Element::new(
+                "p".into(),
+                attributes,
+                vec![Text::new("café! — 1 < 2 &unknown;".into())]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_implied_end_tags() {
+        let element =
+            |tag_name: &str, children| Element::new(tag_name.into(), AttrMap::new(), children);
+        let text = |data: &str| Text::new(data.into());
+        assert_eq!(
+            parse("<ul><li>one<li>two<ul><li>nested</ul><li>three</li></ul>").unwrap(),
+            vec![element(
+                "ul",
+                vec![
+                    element("li", vec![text("one")]),
+                    element(
+                        "li",
+                        vec![
+                            text("two"),
+                            element("ul", vec![element("li", vec![text("nested")])])
+                        ]
+                    ),
+                    element("li", vec![text("three")]),
+                ]
+            )]
+        );
+        assert_eq!(
+            parse("<body><p>a<P>b <em>c</em><div>d</div><p>e</p></body>").unwrap(),
+            vec![element(
+                "body",
+                vec![
+                    element("p", vec![text("a")]),
+                    element("p", vec![text("b "), element("em", vec![text("c")])]),
+                    element("div", vec![text("d")]),
+                    element("p", vec![text("e")]),
+                ]
+            )]
+        );
+        assert_eq!(
+            parse("<dl><dt>term<dd>one<dd>two<dt>other</dl>").unwrap(),
+            vec![element(
+                "dl",
+                vec![
+                    element("dt", vec![text("term")]),
+                    element("dd", vec![text("one")]),
+                    element("dd", vec![text("two")]),
+                    element("dt", vec![text("other")]),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let source = "<!DOCTYPE html>\n<ul>\n  <li>caf\u{e9}<li><b>b</b>\n</ul><!-- c -->";
+        let nodes = parse(source).unwrap();
+        let text = |node: &Node| {
+            let span = node.span.unwrap();
+            &source[span.start..span.end]
+        };
+        assert_eq!(text(&nodes[0]), "<!DOCTYPE html>");
+        assert_eq!(text(&nodes[1]), "<ul>\n  <li>caf\u{e9}<li><b>b</b>\n</ul>");
+        assert_eq!(text(&nodes[2]), "<!-- c -->");
+        let (first, second) = (&nodes[1].children[0], &nodes[1].children[1]);
+        assert_eq!(text(first), "<li>caf\u{e9}");
+        assert_eq!(text(&first.children[0]), "caf\u{e9}");
+        assert_eq!(text(&second.children[0]), "<b>b</b>");
+        assert_eq!(first.span.unwrap().line_column(source), (3, 3));
+        assert_eq!(second.span.unwrap().line_column(source), (3, 11));
+        assert_eq!(nodes[0].span.unwrap().line_column(source), (1, 1));
+
+        // a bare `&str` does not say where it is
+        let (nodes, _) = html().parse("<p>a</p>").unwrap();
+        assert_eq!(nodes[0].span, None);
+    }
+
+    #[test]
+    fn test_raw_text_elements() {
+        let style = "p > a { color: red; } a[title=\"<\"] {}";
+        let script = "if (a < b && c > d) { s = '<p>&amp;</p>'; }";
+        assert_eq!(
+            parse(&format!(
+                "<head><style>{}</style><SCRIPT type=module>{}</script ><script src=a.js></script></head>",
+                style, script
+            ))
+            .unwrap(),
+            vec![Element::new(
+                "head".to_string(),
+                AttrMap::new(),
+                vec![
+                    Element::new(
+                        "style".to_string(),
+                        AttrMap::new(),
+                        vec![Text::new(style.to_string())]
+                    ),
+                    Element::new(
+                        "script".to_string(),
+                        [("type".to_string(), "module".to_string())].into(),
+                        vec![Text::new(script.to_string())]
+                    ),
+                    Element::new(
+                        "script".to_string(),
+                        [("src".to_string(), "a.js".to_string())].into(),
+                        vec![]
+                    ),
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_void_elements_by_name() {
+        let link = |rel: &str| {
+            Element::new(
+                "link".to_string(),
+                [("rel".to_string(), rel.to_string())].into(),
+                vec![],
+            )
+        };
+        // the second link is not a child of the first, though an end tag follows it
+        assert_eq!(
+            parse("<head><link rel=a><link rel=b></link><META charset=utf-8></head>").unwrap(),
+            vec![Element::new(
+                "head".to_string(),
+                AttrMap::new(),
+                vec![
+                    link("a"),
+                    link("b"),
+                    Element::new(
+                        "meta".to_string(),
+                        [("charset".to_string(), "utf-8".to_string())].into(),
+                        vec![]
+                    ),
+                ]
+            )]
+        );
+        assert_eq!(
+            parse("<p>a<br>b</br><input>c</p>").unwrap(),
+            vec![Element::new(
+                "p".to_string(),
+                AttrMap::new(),
+                vec![
+                    Text::new("a".to_string()),
+                    Element::new("br".to_string(), AttrMap::new(), vec![]),
+                    Text::new("b".to_string()),
+                    Element::new("input".to_string(), AttrMap::new(), vec![]),
+                    Text::new("c".to_string()),
+                ]
+            )]
+        );
+        assert!(void_element().parse("<div>").is_err());
+    }
+
     #[test]
     fn test_void_element() {
         assert_eq!(
@@ -393,7 +1216,7 @@ mod test {
         assert_eq!(
             void_element()
                 .parse(r#"<META content="text/html; charset=utf8" http-equiv=Content-Type>"#),
-            Ok((Element::new("META".to_string(), attributes, vec![]), ""))
+            Ok((Element::new("meta".to_string(), attributes, vec![]), ""))
         );
     }
 }
@@ -0,0 +1,338 @@
+use crate::{
+    cssom::CSSValue,
+    dom::{Document, Node, NodeType},
+    page::Page,
+    style::{cascade_trace, Origin},
+};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, Widget},
+};
+use std::collections::HashSet;
+use unicode_width::UnicodeWidthStr;
+
+/// How many characters of a text node its row shows.
+const TEXT_PREVIEW_LEN: usize = 40;
+
+/// `Row` is a line of the DOM tree pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    pub path: Vec<usize>,
+    pub depth: usize,
+    pub label: String,
+    pub has_children: bool,
+}
+
+/// Returns how a node is shown in the tree: an element as its start tag, a text as its quoted text.
+pub fn label(node: &Node) -> String {
+    match &node.node_type {
+        NodeType::Element(e) => {
+            let mut attributes = e.attributes.iter().collect::<Vec<_>>();
+            attributes.sort();
+            let attributes = attributes
+                .iter()
+                .map(|(name, value)| format!(" {}=\"{}\"", name, value))
+                .collect::<String>();
+            format!("<{}{}>", e.tag_name, attributes)
+        }
+        NodeType::Text(t) => {
+            let text = t.data.split_whitespace().collect::<Vec<_>>().join(" ");
+            let preview = text.chars().take(TEXT_PREVIEW_LEN).collect::<String>();
+            if preview.len() < text.len() {
+                format!("\"{}...\"", preview)
+            } else {
+                format!("\"{}\"", preview)
+            }
+        }
+        NodeType::Comment(c) => {
+            let text = c.data.chars().take(TEXT_PREVIEW_LEN).collect::<String>();
+            format!("<!--{}-->", text)
+        }
+        NodeType::Doctype(d) => format!("<!DOCTYPE {}>", d.name),
+        NodeType::Document => "#document".into(),
+    }
+}
+
+fn is_blank(node: &Node) -> bool {
+    matches!(&node.node_type, NodeType::Text(t) if t.data.trim().is_empty())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InspectorEvent {
+    Close,
+}
+
+/// `Inspector` is the inspector mode: a pane showing the DOM tree, where the node under
+/// the cursor is highlighted on the page.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Inspector {
+    /// Paths of the elements whose children are shown.
+    expanded: HashSet<Vec<usize>>,
+    cursor: usize,
+}
+
+impl Inspector {
+    /// Returns the rows of the tree of `root` that are shown, in document order.
+    /// `root` itself is not shown; its children are the top-level rows.
+    pub fn rows(&self, root: &Node) -> Vec<Row> {
+        let mut rows = vec![];
+        self.collect_rows(root, &mut vec![], &mut rows);
+        rows
+    }
+
+    fn collect_rows(&self, node: &Node, path: &mut Vec<usize>, rows: &mut Vec<Row>) {
+        for (i, child) in node.children.iter().enumerate() {
+            if is_blank(child) {
+                continue;
+            }
+            path.push(i);
+            rows.push(Row {
+                path: path.clone(),
+                depth: path.len() - 1,
+                label: label(child),
+                has_children: child.children.iter().any(|c| !is_blank(c)),
+            });
+            if self.expanded.contains(path) {
+                self.collect_rows(child, path, rows);
+            }
+            path.pop();
+        }
+    }
+
+    /// Returns the path of the node under the cursor.
+    pub fn selected(&self, root: &Node) -> Option<Vec<usize>> {
+        self.rows(root).get(self.cursor).map(|row| row.path.clone())
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent, root: &Node) -> Option<InspectorEvent> {
+        let rows = self.rows(root);
+        let Some(row) = rows.get(self.cursor) else {
+            return matches!(key.code, KeyCode::Esc | KeyCode::Char('i' | 'q'))
+                .then_some(InspectorEvent::Close);
+        };
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.cursor = (self.cursor + 1).min(rows.len() - 1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Enter if row.has_children => {
+                self.expanded.insert(row.path.clone());
+            }
+            KeyCode::Char('h') | KeyCode::Left if !self.expanded.remove(&row.path) => {
+                // a collapsed node moves the cursor to its parent
+                let parent = &row.path[..row.path.len() - 1];
+                if let Some(i) = rows.iter().position(|r| r.path == parent) {
+                    self.cursor = i;
+                }
+            }
+            KeyCode::Char(c @ ('J' | 'K')) => {
+                // moves over the descendants of the node to its next or previous sibling
+                let document = Document::new(root);
+                let step = |id| match c {
+                    'J' => document.next_sibling(id),
+                    _ => document.previous_sibling(id),
+                };
+                let sibling = document.id_at(&row.path).and_then(|id| {
+                    std::iter::successors(step(id), |&id| step(id))
+                        .find(|&id| !is_blank(document.node(id)))
+                });
+                if let Some(path) = sibling.map(|id| document.path(id)) {
+                    if let Some(i) = rows.iter().position(|r| r.path == path) {
+                        self.cursor = i;
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('i') | KeyCode::Char('q') => {
+                return Some(InspectorEvent::Close)
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Draws the DOM tree of `page` in `area`, scrolled so that the cursor is in view.
+    /// Below it are the properties of the node under the cursor and the rules they came from.
+    pub fn render(&self, page: &Page, area: Rect, buf: &mut Buffer) {
+        let block = Block::default().borders(Borders::LEFT).title(" DOM ");
+        let inner = block.inner(area);
+        block.render(area, buf);
+        for y in inner.top()..inner.bottom() {
+            for x in inner.left()..inner.right() {
+                buf.get_mut(x, y).reset();
+            }
+        }
+
+        let trace = self
+            .selected(page.dom())
+            .map(|path| cascade_trace(page.dom(), &path, &page.stylesheet))
+            .unwrap_or_default();
+        // the properties take up to half of the pane, under a heading
+        let style_height = match trace.len() {
+            0 => 0,
+            n => (n as u16 + 1).min(inner.height / 2),
+        };
+        let tree = Rect {
+            height: inner.height - style_height,
+            ..inner
+        };
+        let styles = Rect {
+            y: tree.bottom(),
+            height: style_height,
+            ..inner
+        };
+        self.render_tree(page.dom(), tree, buf);
+        render_styles(&trace, styles, buf);
+    }
+
+    fn render_tree(&self, root: &Node, inner: Rect, buf: &mut Buffer) {
+        let rows = self.rows(root);
+        let top = (self.cursor + 1).saturating_sub(inner.height as usize);
+        for (i, row) in rows
+            .iter()
+            .enumerate()
+            .skip(top)
+            .take(inner.height as usize)
+        {
+            let marker = match (row.has_children, self.expanded.contains(&row.path)) {
+                (false, _) => " ",
+                (true, true) => "▾",
+                (true, false) => "▸",
+            };
+            let line = format!("{}{} {}", "  ".repeat(row.depth), marker, row.label);
+            let style = if i == self.cursor {
+                Style::new().add_modifier(Modifier::REVERSED)
+            } else if row.label.starts_with('"') {
+                Style::new().fg(Color::Gray)
+            } else {
+                Style::new()
+            };
+            let y = inner.y + (i - top) as u16;
+            let width = (line.width() as u16).min(inner.width);
+            buf.set_stringn(inner.x, y, &line, inner.width as usize, Style::new());
+            buf.set_style(
+                Rect {
+                    x: inner.x,
+                    y,
+                    width,
+                    height: 1,
+                },
+                style,
+            );
+        }
+    }
+}
+
+/// Lists `trace` as `name: value` lines, each followed by where the value came from.
+fn render_styles(trace: &[(String, CSSValue, Origin)], area: Rect, buf: &mut Buffer) {
+    if area.height == 0 {
+        return;
+    }
+    buf.set_stringn(
+        area.x,
+        area.y,
+        "Styles",
+        area.width as usize,
+        Style::new().add_modifier(Modifier::BOLD),
+    );
+    for (y, (name, value, origin)) in (area.y + 1..area.bottom()).zip(trace) {
+        let property = format!("{}: {}; ", name, value);
+        let origin = match origin {
+            Origin::Rule(selector) => selector.to_string(),
+            Origin::UserAgent => "default".into(),
+            Origin::Inherited => "inherited".into(),
+        };
+        let (x, _) = buf.set_stringn(area.x, y, &property, area.width as usize, Style::new());
+        let rest = area.right().saturating_sub(x) as usize;
+        buf.set_stringn(x, y, origin, rest, Style::new().fg(Color::Gray));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Inspector, InspectorEvent};
+    use crate::page::{Page, Settings};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use ratatui::{buffer::Buffer, layout::Rect};
+
+    fn press(inspector: &mut Inspector, code: KeyCode, page: &Page) -> Option<InspectorEvent> {
+        inspector.handle_key(KeyEvent::new(code, KeyModifiers::NONE), page.dom())
+    }
+
+    #[test]
+    fn test_inspector() {
+        let page = Page::from_html(
+            "about:test",
+            r#"<body><p class="a">hello <b>world</b></p><p>bye</p></body>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        let mut inspector = Inspector::default();
+        let labels = |inspector: &Inspector| {
+            inspector
+                .rows(page.dom())
+                .into_iter()
+                .map(|row| row.label)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(labels(&inspector), vec!["<body>"]);
+
+        press(&mut inspector, KeyCode::Enter, &page);
+        press(&mut inspector, KeyCode::Down, &page);
+        press(&mut inspector, KeyCode::Char('l'), &page);
+        assert_eq!(
+            labels(&inspector),
+            vec!["<body>", "<p class=\"a\">", "\"hello\"", "<b>", "<p>"]
+        );
+        assert_eq!(inspector.selected(page.dom()), Some(vec![0, 0]));
+
+        // `J` and `K` move between siblings, over what is expanded
+        press(&mut inspector, KeyCode::Char('J'), &page);
+        assert_eq!(inspector.selected(page.dom()), Some(vec![0, 1]));
+        press(&mut inspector, KeyCode::Char('J'), &page);
+        assert_eq!(inspector.selected(page.dom()), Some(vec![0, 1]));
+        press(&mut inspector, KeyCode::Char('K'), &page);
+        assert_eq!(inspector.selected(page.dom()), Some(vec![0, 0]));
+
+        // from a leaf, `h` moves to the parent, and then collapses it
+        press(&mut inspector, KeyCode::Down, &page);
+        press(&mut inspector, KeyCode::Char('h'), &page);
+        assert_eq!(inspector.selected(page.dom()), Some(vec![0, 0]));
+        press(&mut inspector, KeyCode::Char('h'), &page);
+        assert_eq!(labels(&inspector), vec!["<body>", "<p class=\"a\">", "<p>"]);
+
+        assert_eq!(
+            press(&mut inspector, KeyCode::Esc, &page),
+            Some(InspectorEvent::Close)
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let page = Page::from_html(
+            "about:test",
+            "<style>p { color: red; }</style><p>text</p>",
+            &Settings::default(),
+        )
+        .unwrap();
+        let mut inspector = Inspector::default();
+        press(&mut inspector, KeyCode::Down, &page);
+        let area = Rect::new(0, 0, 25, 8);
+        let mut buf = Buffer::empty(area);
+        inspector.render(&page, area, &mut buf);
+        let row = |y| {
+            (1..25)
+                .map(|x| buf.get(x, y).symbol())
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        };
+        assert_eq!(row(2), "▸ <p>");
+        // three properties take three of the seven rows, so one is cut off
+        assert_eq!(row(5), "Styles");
+        assert_eq!(row(6), "color: red; p");
+        assert_eq!(row(7), "display: block; default");
+    }
+}
@@ -0,0 +1,402 @@
+use crate::{
+    error::{self, Error},
+    export::Format,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+/// `Action` is what the user asked for with a key press.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Action {
+    Quit,
+    ScrollLines(i32),
+    ScrollColumns(i32),
+    ScrollPages(i32),
+    ScrollToTop,
+    ScrollToBottom,
+    Back,
+    Forward,
+    AddBookmark,
+    OpenBookmarks,
+    /// Fetches the current page again. A hard reload bypasses any cached copy.
+    Reload {
+        hard: bool,
+    },
+    /// Opens the address bar.
+    OpenAddressBar,
+    /// Labels the links on screen so that they can be followed by typing the label.
+    ShowLinkHints,
+    /// Asks for a file to write the whole rendered page to.
+    Export(Format),
+    /// Moves the focus to the next or previous link or form control.
+    FocusNext,
+    FocusPrevious,
+    /// Follows the focused link, or uses the focused form control.
+    Activate,
+    /// Opens the inspector, which shows the DOM tree of the page.
+    Inspect,
+    /// Splits the screen into two panes, or changes how it is split.
+    Split {
+        vertical: bool,
+    },
+    /// Moves the focus to the other pane.
+    SwitchPane,
+    /// Closes the pane that does not have focus.
+    ClosePane,
+    /// Opens the focused link in the desktop's browser.
+    OpenExternally,
+    /// Copies the URL of the page to the clipboard.
+    CopyUrl,
+    /// Copies the URL of the focused link to the clipboard.
+    CopyLink,
+    /// Describes the element under the focus on the status line.
+    DescribeElement,
+    /// Opens the command line, where commands like `:messages` are typed.
+    OpenCommandLine,
+    /// Runs the user's script of this name with the script engine the embedding program set.
+    /// No Lua or other runtime comes with the crate, so without an engine it only says so.
+    #[cfg(feature = "scripts")]
+    Script(String),
+}
+
+/// Returns the action called `name` in the `[keys]` table of the config file.
+fn action_named(name: &str) -> Option<Action> {
+    Some(match name {
+        "quit" => Action::Quit,
+        "scroll-down" => Action::ScrollLines(1),
+        "scroll-up" => Action::ScrollLines(-1),
+        "scroll-left" => Action::ScrollColumns(-1),
+        "scroll-right" => Action::ScrollColumns(1),
+        "page-down" => Action::ScrollPages(1),
+        "page-up" => Action::ScrollPages(-1),
+        "top" => Action::ScrollToTop,
+        "bottom" => Action::ScrollToBottom,
+        "back" => Action::Back,
+        "forward" => Action::Forward,
+        "add-bookmark" => Action::AddBookmark,
+        "bookmarks" => Action::OpenBookmarks,
+        "reload" => Action::Reload { hard: false },
+        "hard-reload" => Action::Reload { hard: true },
+        "address-bar" => Action::OpenAddressBar,
+        "link-hints" => Action::ShowLinkHints,
+        "export" => Action::Export(Format::Plain),
+        "export-ansi" => Action::Export(Format::Ansi),
+        "export-linear" => Action::Export(Format::Linear),
+        "focus-next" => Action::FocusNext,
+        "focus-previous" => Action::FocusPrevious,
+        "activate" => Action::Activate,
+        "inspect" => Action::Inspect,
+        "split" => Action::Split { vertical: false },
+        "vsplit" => Action::Split { vertical: true },
+        "switch-pane" => Action::SwitchPane,
+        "close-pane" => Action::ClosePane,
+        "open-externally" => Action::OpenExternally,
+        "copy-url" => Action::CopyUrl,
+        "copy-link" => Action::CopyLink,
+        "describe-element" => Action::DescribeElement,
+        "command-line" => Action::OpenCommandLine,
+        #[cfg(feature = "scripts")]
+        _ if name.starts_with("script:") => Action::Script(name["script:".len()..].into()),
+        _ => return None,
+    })
+}
+
+/// The modifiers a key is bound with. Shift is left out, as it is part of the character typed.
+fn bound_modifiers(modifiers: KeyModifiers) -> KeyModifiers {
+    modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT)
+}
+
+/// Reads `key`, a key as it is written in the config file: a character or the name of a key
+/// like `Enter` or `PageDown`, after any of `C-` for Control and `A-` for Alt.
+fn parse_key(key: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = key;
+    loop {
+        if let Some(after) = rest.strip_prefix("C-").filter(|after| !after.is_empty()) {
+            modifiers |= KeyModifiers::CONTROL;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("A-").filter(|after| !after.is_empty()) {
+            modifiers |= KeyModifiers::ALT;
+            rest = after;
+        } else {
+            break;
+        }
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match rest {
+            "Space" => KeyCode::Char(' '),
+            "Enter" => KeyCode::Enter,
+            "Tab" => KeyCode::Tab,
+            "BackTab" => KeyCode::BackTab,
+            "Backspace" => KeyCode::Backspace,
+            "Esc" => KeyCode::Esc,
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            _ => return None,
+        },
+    };
+    Some((code, modifiers))
+}
+
+/// `Keymap` is the keys the user bound to actions in the config file, in place of what
+/// they do by default.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: HashMap<(KeyCode, KeyModifiers), Action>,
+}
+
+impl Keymap {
+    /// Reads `keys`, the `[keys]` table of the config file, which maps keys to the names
+    /// of actions, like `"C-d" = "page-down"`.
+    pub fn parse(keys: &BTreeMap<String, String>) -> error::Result<Keymap> {
+        let mut bindings = HashMap::new();
+        for (key, name) in keys {
+            let key =
+                parse_key(key).ok_or_else(|| Error::Config(format!("`{}` is not a key", key)))?;
+            let action =
+                action_named(name).ok_or_else(|| Error::Config(format!("no action `{}`", name)))?;
+            bindings.insert(key, action);
+        }
+        Ok(Keymap { bindings })
+    }
+
+    fn get(&self, key: KeyEvent) -> Option<Action> {
+        self.bindings
+            .get(&(key.code, bound_modifiers(key.modifiers)))
+            .cloned()
+    }
+}
+
+/// Translates a key press into an `Action`, as `keymap` binds it or else by default.
+/// `pending` holds the previous key of a multi-key sequence such as `gg`.
+pub fn action_for_key(
+    key: KeyEvent,
+    pending: &mut Option<char>,
+    keymap: &Keymap,
+) -> Option<Action> {
+    let prev = pending.take();
+    if prev.is_none() {
+        if let Some(action) = keymap.get(key) {
+            return Some(action);
+        }
+    }
+    match key.code {
+        KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Some(Action::OpenAddressBar)
+        }
+        KeyCode::Char('g') if prev == Some('g') => Some(Action::ScrollToTop),
+        KeyCode::Char('y') if prev == Some('y') => Some(Action::CopyUrl),
+        KeyCode::Char('l') if prev == Some('y') => Some(Action::CopyLink),
+        _ if prev == Some('y') => None,
+        KeyCode::Char('q') => Some(Action::Quit),
+        KeyCode::Char('j') | KeyCode::Down => Some(Action::ScrollLines(1)),
+        KeyCode::Char('k') | KeyCode::Up => Some(Action::ScrollLines(-1)),
+        KeyCode::Char('h') | KeyCode::Left => Some(Action::ScrollColumns(-1)),
+        KeyCode::Char('l') | KeyCode::Right => Some(Action::ScrollColumns(1)),
+        KeyCode::PageDown | KeyCode::Char(' ') => Some(Action::ScrollPages(1)),
+        KeyCode::PageUp => Some(Action::ScrollPages(-1)),
+        KeyCode::Home => Some(Action::ScrollToTop),
+        KeyCode::End | KeyCode::Char('G') => Some(Action::ScrollToBottom),
+        KeyCode::Backspace | KeyCode::Char('H') => Some(Action::Back),
+        KeyCode::Char('L') => Some(Action::Forward),
+        KeyCode::Char('b') => Some(Action::AddBookmark),
+        KeyCode::Char('B') => Some(Action::OpenBookmarks),
+        KeyCode::Char('r') => Some(Action::Reload { hard: false }),
+        KeyCode::Char('f') => Some(Action::ShowLinkHints),
+        KeyCode::Char('i') => Some(Action::Inspect),
+        KeyCode::Char('s') => Some(Action::Split { vertical: false }),
+        KeyCode::Char('v') => Some(Action::Split { vertical: true }),
+        KeyCode::Char('w') => Some(Action::SwitchPane),
+        KeyCode::Char('W') => Some(Action::ClosePane),
+        KeyCode::Char('e') => Some(Action::Export(Format::Plain)),
+        KeyCode::Char('E') => Some(Action::Export(Format::Ansi)),
+        KeyCode::Char('R') => Some(Action::Reload { hard: true }),
+        KeyCode::Char('o') => Some(Action::OpenExternally),
+        KeyCode::Char('I') => Some(Action::DescribeElement),
+        KeyCode::Char(':') => Some(Action::OpenCommandLine),
+        KeyCode::Tab => Some(Action::FocusNext),
+        KeyCode::BackTab => Some(Action::FocusPrevious),
+        KeyCode::Enter => Some(Action::Activate),
+        KeyCode::Char(c @ ('g' | 'y')) => {
+            *pending = Some(c);
+            None
+        }
+        _ => None,
+    }
+}
+
+/// How long a lone `g` waits for a second `g`, and a lone `y` for the key that says
+/// what to copy, before they are forgotten.
+pub const PENDING_KEY_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[cfg(test)]
+mod tests {
+    use super::{action_for_key, Action, Format, Keymap};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::collections::BTreeMap;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_action_for_key() {
+        let mut pending = None;
+        let keymap = Keymap::default();
+        assert_eq!(
+            action_for_key(key('j'), &mut pending, &keymap),
+            Some(Action::ScrollLines(1))
+        );
+        assert_eq!(
+            action_for_key(
+                KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE),
+                &mut pending,
+                &keymap
+            ),
+            Some(Action::ScrollPages(-1))
+        );
+        assert_eq!(action_for_key(key('g'), &mut pending, &keymap), None);
+        assert_eq!(
+            action_for_key(key('g'), &mut pending, &keymap),
+            Some(Action::ScrollToTop)
+        );
+        assert_eq!(action_for_key(key('g'), &mut pending, &keymap), None);
+        assert_eq!(
+            action_for_key(key('j'), &mut pending, &keymap),
+            Some(Action::ScrollLines(1))
+        );
+        assert_eq!(pending, None);
+        assert_eq!(
+            action_for_key(
+                KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL),
+                &mut pending,
+                &keymap
+            ),
+            Some(Action::OpenAddressBar)
+        );
+        assert_eq!(
+            action_for_key(key('G'), &mut pending, &keymap),
+            Some(Action::ScrollToBottom)
+        );
+        assert_eq!(
+            action_for_key(key('q'), &mut pending, &keymap),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            action_for_key(key('i'), &mut pending, &keymap),
+            Some(Action::Inspect)
+        );
+        assert_eq!(
+            action_for_key(key('h'), &mut pending, &keymap),
+            Some(Action::ScrollColumns(-1))
+        );
+        assert_eq!(
+            action_for_key(key('H'), &mut pending, &keymap),
+            Some(Action::Back)
+        );
+        assert_eq!(
+            action_for_key(key('L'), &mut pending, &keymap),
+            Some(Action::Forward)
+        );
+        assert_eq!(
+            action_for_key(key('b'), &mut pending, &keymap),
+            Some(Action::AddBookmark)
+        );
+        assert_eq!(
+            action_for_key(key('B'), &mut pending, &keymap),
+            Some(Action::OpenBookmarks)
+        );
+        assert_eq!(
+            action_for_key(key('R'), &mut pending, &keymap),
+            Some(Action::Reload { hard: true })
+        );
+        assert_eq!(
+            action_for_key(key('E'), &mut pending, &keymap),
+            Some(Action::Export(Format::Ansi))
+        );
+        assert_eq!(
+            action_for_key(key('o'), &mut pending, &keymap),
+            Some(Action::OpenExternally)
+        );
+        assert_eq!(action_for_key(key('y'), &mut pending, &keymap), None);
+        assert_eq!(
+            action_for_key(key('y'), &mut pending, &keymap),
+            Some(Action::CopyUrl)
+        );
+        assert_eq!(action_for_key(key('y'), &mut pending, &keymap), None);
+        assert_eq!(
+            action_for_key(key('l'), &mut pending, &keymap),
+            Some(Action::CopyLink)
+        );
+        assert_eq!(action_for_key(key('y'), &mut pending, &keymap), None);
+        assert_eq!(action_for_key(key('j'), &mut pending, &keymap), None);
+        assert_eq!(pending, None);
+        assert_eq!(
+            action_for_key(
+                KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT),
+                &mut pending,
+                &keymap
+            ),
+            Some(Action::FocusPrevious)
+        );
+        assert_eq!(
+            action_for_key(
+                KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE),
+                &mut pending,
+                &keymap
+            ),
+            Some(Action::Activate)
+        );
+
+        let keymap = Keymap::parse(&BTreeMap::from([
+            ("j".to_string(), "page-down".to_string()),
+            ("C-d".to_string(), "quit".to_string()),
+            ("A-Space".to_string(), "vsplit".to_string()),
+        ]))
+        .unwrap();
+        assert_eq!(
+            action_for_key(key('j'), &mut pending, &keymap),
+            Some(Action::ScrollPages(1))
+        );
+        assert_eq!(
+            action_for_key(
+                KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL),
+                &mut pending,
+                &keymap
+            ),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            action_for_key(
+                KeyEvent::new(KeyCode::Char(' '), KeyModifiers::ALT),
+                &mut pending,
+                &keymap
+            ),
+            Some(Action::Split { vertical: true })
+        );
+        // the keys left unbound do what they do by default
+        assert_eq!(
+            action_for_key(key('k'), &mut pending, &keymap),
+            Some(Action::ScrollLines(-1))
+        );
+        assert_eq!(action_for_key(key('g'), &mut pending, &keymap), None);
+        assert_eq!(
+            action_for_key(key('j'), &mut pending, &keymap),
+            Some(Action::ScrollLines(1))
+        );
+        for (key, name) in [("Ctrl-x", "quit"), ("C-", "quit"), ("x", "fly")] {
+            assert!(Keymap::parse(&BTreeMap::from([(key.into(), name.into())])).is_err());
+        }
+    }
+}
@@ -1,29 +1,200 @@
-use ratatui::layout::Rect;
+use crate::rect::Rect;
+use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc};
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
-    cssom::CSSValue,
-    dom::{self, NodeType},
+    cssom::{CSSValue, Color, Unit},
+    dom::{self, NodeId, NodeType},
+    form,
     style::StyledNode,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct LayoutObject<'a> {
     pub area: Rect,
     pub ty: LayoutObjectType<'a>,
+    /// The styled node this object was laid out from, which in turn refers to its DOM node.
+    pub node: Option<&'a StyledNode<'a>>,
 }
 
+impl<'a> LayoutObject<'a> {
+    /// Returns the objects containing the cell at (`x`, `y`), from the outermost to the innermost.
+    /// Text objects are hit-tested against their line fragments rather than their bounding area.
+    pub fn hit_test(&self, x: u16, y: u16) -> Vec<&LayoutObject<'a>> {
+        match &self.ty {
+            LayoutObjectType::Texts(texts) => {
+                if texts.iter().any(|t| contains(t.area, x, y)) {
+                    vec![self]
+                } else {
+                    vec![]
+                }
+            }
+            LayoutObjectType::Image { .. } => {
+                if contains(self.area, x, y) {
+                    vec![self]
+                } else {
+                    vec![]
+                }
+            }
+            LayoutObjectType::Block { children } => {
+                let inner = children
+                    .iter()
+                    .map(|child| child.hit_test(x, y))
+                    .find(|path| !path.is_empty());
+                match inner {
+                    Some(path) => std::iter::once(self).chain(path).collect(),
+                    None if contains(self.area, x, y) => vec![self],
+                    None => vec![],
+                }
+            }
+        }
+    }
+}
+
+/// `Link` is an `<a href>` element and the cells its text occupies on the page.
 #[derive(Debug, PartialEq, Eq)]
+pub struct Link<'a> {
+    pub href: &'a str,
+    pub areas: Vec<Rect>,
+}
+
+impl Link<'_> {
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        self.areas.iter().any(|area| contains(*area, x, y))
+    }
+}
+
+impl<'a> LayoutObject<'a> {
+    /// Collects the links in this tree in document order.
+    pub fn links(&self) -> Vec<Link<'a>> {
+        let mut links = vec![];
+        self.collect_links(None, &mut links);
+        links
+    }
+
+    fn collect_links(&self, current: Option<usize>, links: &mut Vec<Link<'a>>) {
+        let href = self.node.and_then(|node| match node.node_type {
+            NodeType::Element(e) if e.tag_name == "a" => e.attributes.get("href"),
+            _ => None,
+        });
+        let current = match href {
+            Some(href) => {
+                links.push(Link {
+                    href,
+                    areas: vec![],
+                });
+                Some(links.len() - 1)
+            }
+            None => current,
+        };
+        match &self.ty {
+            LayoutObjectType::Texts(texts) => {
+                if let Some(i) = current {
+                    links[i].areas.extend(texts.iter().map(|t| t.area));
+                }
+            }
+            LayoutObjectType::Image { .. } => {
+                if let Some(i) = current {
+                    links[i].areas.push(self.area);
+                }
+            }
+            LayoutObjectType::Block { children } => children
+                .iter()
+                .for_each(|child| child.collect_links(current, links)),
+        }
+    }
+}
+
+impl LayoutObject<'_> {
+    /// Returns the ID of the DOM node the object was laid out from, if any.
+    pub fn node_id(&self) -> Option<NodeId> {
+        self.node.map(|node| node.id)
+    }
+
+    /// Returns the cells taken by the object laid out from the DOM node `id`:
+    /// the line fragments of a text, or the area of anything else.
+    pub fn areas_of(&self, id: NodeId) -> Vec<Rect> {
+        if self.node_id() == Some(id) {
+            return match &self.ty {
+                LayoutObjectType::Texts(texts) => texts.iter().map(|t| t.area).collect(),
+                _ => vec![self.area],
+            };
+        }
+        match &self.ty {
+            LayoutObjectType::Block { children } => children
+                .iter()
+                .map(|child| child.areas_of(id))
+                .find(|areas| !areas.is_empty())
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+}
+
+impl LayoutObject<'_> {
+    /// Returns the column just right of the rightmost content, which lies beyond the area
+    /// of a block when its lines do not wrap.
+    pub fn content_right(&self) -> u16 {
+        match &self.ty {
+            LayoutObjectType::Texts(texts) => texts
+                .iter()
+                .map(|t| t.area.right())
+                .max()
+                .unwrap_or(self.area.x),
+            LayoutObjectType::Image { .. } => self.area.right(),
+            LayoutObjectType::Block { children } => children
+                .iter()
+                .map(|child| child.content_right())
+                .fold(self.area.right(), u16::max),
+        }
+    }
+}
+
+fn contains(area: Rect, x: u16, y: u16) -> bool {
+    area.x <= x && x < area.right() && area.y <= y && y < area.bottom()
+}
+
+#[derive(Debug, PartialEq)]
 pub enum LayoutObjectType<'a> {
-    Block { children: Vec<LayoutObject<'a>> },
+    Block {
+        children: Vec<LayoutObject<'a>>,
+    },
     Texts(Vec<Text<'a>>),
+    /// A loaded `<img>`, drawn over its whole area.
+    Image {
+        src: &'a str,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Text<'a> {
     pub area: Rect,
-    pub data: &'a str,
+    pub data: Cow<'a, str>,
+    pub style: TextStyle,
+}
+
+/// `TextRole` is what a text is part of, which decides its color when the page gives it none.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TextRole {
+    #[default]
+    Body,
+    /// A link, colored `linktext` by default.
+    Link,
+    Heading,
+}
+
+/// `TextStyle` is how a text fragment is painted, resolved from its inherited properties.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TextStyle {
+    /// The color the page gives the text. `None` leaves it to the theme, by `role`.
+    pub color: Option<Color>,
+    pub role: TextRole,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -62,142 +233,880 @@ pub fn inline_node(node: &StyledNode) -> bool {
             matches!(node.properties.get("display"), Some(CSSValue::Keyword(value)) if value == "inline")
         }
         NodeType::Text(_) => true,
+        NodeType::Document | NodeType::Comment(_) | NodeType::Doctype(_) => false,
     }
 }
 
-fn split_string_by_width(text: &str, width: usize, offset: usize) -> Vec<&str> {
+/// Byte length and display width of each grapheme cluster in a text.
+type Measurements = Rc<[(usize, usize)]>;
+
+/// Texts are measured on every layout pass (e.g. after each resize), so their
+/// grapheme segmentation is cached by content. The cache is simply flushed when
+/// it grows past this many entries.
+const MEASURE_CACHE_CAPACITY: usize = 4096;
+
+thread_local! {
+    static MEASURE_CACHE: RefCell<HashMap<String, Measurements>> = RefCell::new(HashMap::new());
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+const EMOJI_PRESENTATION_SELECTOR: char = '\u{FE0F}';
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+fn is_emoji_modifier(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+/// Returns the display width of a single extended grapheme cluster.
+///
+/// `unicode-width` measures codepoint by codepoint, which over-counts emoji sequences
+/// (ZWJ families, flags, skin tones) that terminals draw as one wide glyph.
+fn grapheme_width(grapheme: &str) -> usize {
+    let mut chars = grapheme.chars();
+    let Some(first) = chars.next() else {
+        return 0;
+    };
+    let is_wide_base = first.width() == Some(2);
+    let is_flag = is_regional_indicator(first) && chars.next().is_some_and(is_regional_indicator);
+
+    if is_flag
+        || grapheme.contains(EMOJI_PRESENTATION_SELECTOR)
+        || (is_wide_base && grapheme.contains(ZERO_WIDTH_JOINER))
+        || (is_wide_base && grapheme.chars().any(is_emoji_modifier))
+    {
+        2
+    } else {
+        grapheme.width()
+    }
+}
+
+fn measure(text: &str) -> Measurements {
+    MEASURE_CACHE.with(|cache| {
+        if let Some(measurements) = cache.borrow().get(text) {
+            return measurements.clone();
+        }
+
+        let measurements: Measurements = text
+            .graphemes(true)
+            .map(|grapheme| (grapheme.len(), grapheme_width(grapheme)))
+            .collect();
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= MEASURE_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(text.to_string(), measurements.clone());
+        measurements
+    })
+}
+
+/// Splits `text` into lines no wider than `width`, returning each line with its display width.
+/// The first line starts `offset` columns into the line.
+fn split_measured_by_width(text: &str, width: usize, offset: usize) -> Vec<(&str, usize)> {
     let mut result = Vec::new();
     let mut curr_width = offset;
+    let mut line_width = 0;
     let mut prev_index = 0;
     let mut curr_index = 0;
 
-    for grapheme in text.graphemes(true) {
-        if curr_width + grapheme.width() > width {
-            result.push(&text[prev_index..curr_index]);
+    for &(len, grapheme_width) in measure(text).iter() {
+        if curr_width + grapheme_width > width {
+            result.push((&text[prev_index..curr_index], line_width));
             prev_index = curr_index;
-            curr_width = grapheme.width();
+            curr_width = grapheme_width;
+            line_width = grapheme_width;
         } else {
-            curr_width += grapheme.width();
+            curr_width += grapheme_width;
+            line_width += grapheme_width;
         }
-        curr_index += grapheme.len();
+        curr_index += len;
     }
 
-    result.push(&text[prev_index..]);
+    result.push((&text[prev_index..], line_width));
 
     result
 }
 
-fn text_object(text: &str, x: u16, y: u16) -> TextObject<'_> {
-    TextObject {
-        area: Rect {
-            x,
-            y,
-            width: text.width() as u16,
-            height: 1,
-        },
-        content: vec![text],
-    }
+/// Splits `text` into the lines the layout wraps it into within `width` columns, the first
+/// line starting `offset` columns into the line.
+pub fn split_string_by_width(text: &str, width: usize, offset: usize) -> Vec<&str> {
+    split_measured_by_width(text, width, offset)
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect()
 }
 
-fn inline_object<'a>(node: &'a StyledNode<'a>, x: u16, y: u16) -> InlineObject<'a> {
-    let width = 0;
-    InlineObject {
-        area: Rect {
-            x,
-            y,
-            width,
-            height: 1,
-        },
-        children: vec![],
-    }
+/// Converts a length in cells into a `u16`, saturating at the largest representable length.
+fn saturate(cells: usize) -> u16 {
+    u16::try_from(cells).unwrap_or(u16::MAX)
 }
 
-fn text_to_object(text: &str, area: Rect, offset: usize) -> LayoutObject<'_> {
+/// Lays out `text` as lines within `area`. The first line starts `offset` columns to the right.
+///
+/// The area of the returned object spans the total width of its lines, so that the caller
+/// can continue the inline flow from where the text ends.
+fn text_to_object<'a>(
+    text: impl Into<Cow<'a, str>>,
+    area: Rect,
+    offset: usize,
+    style: TextStyle,
+) -> LayoutObject<'a> {
+    let text = text.into();
+    let offset = if area.width == 0 {
+        0
+    } else {
+        offset % area.width as usize
+    };
+    let lines = if area.width == 0 {
+        vec![]
+    } else {
+        split_measured_by_width(&text, area.width as usize, offset)
+    };
+
     let mut texts = vec![];
-    let mut y = area.y;
     let mut content_len = 0;
-    for d in split_string_by_width(text, area.width as usize, offset) {
-        let len = UnicodeWidthStr::width(d) as u16;
+    let mut start = 0;
+    for (i, (d, len)) in lines.into_iter().enumerate() {
+        let x = if i == 0 {
+            area.x.saturating_add(offset as u16)
+        } else {
+            area.x
+        };
         let area = Rect {
-            x: area.x,
-            y,
-            width: len,
+            x,
+            y: area.y.saturating_add(saturate(i)),
+            width: saturate(len),
             height: 1,
         };
-        y += 1;
         content_len += len;
 
-        texts.push(Text { area, data: d })
+        let range = start..start + d.len();
+        start = range.end;
+        let data = match &text {
+            Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+            Cow::Owned(text) => Cow::Owned(text[range].to_string()),
+        };
+        texts.push(Text { area, data, style })
     }
 
-    let (width, height) = (content_len, 1);
+    let (width, height) = (saturate(content_len), 1);
     LayoutObject {
         area: Rect {
-            x: area.x,
+            x: area.x.saturating_add(offset as u16),
             y: area.y,
             width,
             height,
         },
         ty: LayoutObjectType::Texts(texts),
+        node: None,
     }
 }
 
-fn children_to_object<'a>(node: &'a StyledNode<'a>, area: Rect, offset: usize) -> LayoutObject<'a> {
-    let mut y = area.y;
-    let mut height = 0;
+/// `TextTransform` is the computed value of `text-transform`.
+/// See https://www.w3.org/TR/css-text-3/#text-transform-property
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TextTransform {
+    #[default]
+    None,
+    Uppercase,
+    Lowercase,
+    Capitalize,
+}
+
+impl TextTransform {
+    fn from_value(value: &CSSValue) -> Option<Self> {
+        match value {
+            CSSValue::Keyword(keyword) => match keyword.as_str() {
+                "none" => Some(TextTransform::None),
+                "uppercase" => Some(TextTransform::Uppercase),
+                "lowercase" => Some(TextTransform::Lowercase),
+                "capitalize" => Some(TextTransform::Capitalize),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            TextTransform::None => Cow::Borrowed(text),
+            TextTransform::Uppercase => Cow::Owned(text.to_uppercase()),
+            TextTransform::Lowercase => Cow::Owned(text.to_lowercase()),
+            TextTransform::Capitalize => {
+                let mut result = String::with_capacity(text.len());
+                let mut at_word_start = true;
+                for c in text.chars() {
+                    if at_word_start {
+                        result.extend(c.to_uppercase());
+                    } else {
+                        result.push(c);
+                    }
+                    at_word_start = c.is_whitespace();
+                }
+                Cow::Owned(result)
+            }
+        }
+    }
+}
+
+/// `InheritedStyle` holds the inherited properties (https://www.w3.org/TR/css-cascade-3/#inheriting)
+/// in effect for a node, resolved while descending the styled tree.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct InheritedStyle {
+    pub text_transform: TextTransform,
+    pub color: Option<Color>,
+    pub role: TextRole,
+    pub bold: bool,
+    pub italic: bool,
+    /// `text-decoration` is not inherited, but decorations propagate to all descendant text.
+    /// See https://www.w3.org/TR/css-text-decor-3/#line-decoration
+    pub underline: bool,
+    pub strikethrough: bool,
+    /// The background of the innermost inline element, painted behind its text.
+    /// Blocks paint their own background over their whole area instead.
+    pub background: Option<Color>,
+    /// Whether lines only break where the text does, from `white-space: nowrap` or `pre`.
+    pub nowrap: bool,
+}
+
+/// Returns the color `node` paints its background with, from `background-color` or
+/// a `background` shorthand consisting of a single color.
+pub fn background_color(node: &StyledNode) -> Option<Color> {
+    node.properties
+        .get("background-color")
+        .or_else(|| node.properties.get("background"))
+        .and_then(Color::from_value)
+        .filter(|color| !color.is_transparent())
+}
+
+impl InheritedStyle {
+    /// Returns the style inherited by the children of `node`.
+    fn cascade(&self, node: &StyledNode) -> Self {
+        let text_transform = node
+            .properties
+            .get("text-transform")
+            .and_then(TextTransform::from_value)
+            .unwrap_or(self.text_transform);
+        let is_heading = matches!(
+            node.node_type,
+            NodeType::Element(e) if matches!(e.tag_name.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+        );
+        let (color, role) = match node.properties.get("color") {
+            Some(CSSValue::Keyword(keyword)) if keyword == "linktext" => (None, TextRole::Link),
+            Some(value) => (Color::from_value(value).or(self.color), self.role),
+            // headings take the theme's color over the color of the text around them
+            None if is_heading => (None, TextRole::Heading),
+            None => (self.color, self.role),
+        };
+        let bold = match node.properties.get("font-weight") {
+            Some(CSSValue::Keyword(weight)) => weight == "bold" || weight == "bolder",
+            // numeric weights are parsed as unitless lengths
+            Some(CSSValue::Length(weight, _)) => *weight >= 600.0,
+            _ => self.bold,
+        };
+        let italic = match node.properties.get("font-style") {
+            Some(CSSValue::Keyword(style)) => style == "italic" || style == "oblique",
+            _ => self.italic,
+        };
+        let nowrap = match node.properties.get("white-space") {
+            Some(CSSValue::Keyword(white_space)) => white_space == "nowrap" || white_space == "pre",
+            _ => self.nowrap,
+        };
+        let decoration = match node.properties.get("text-decoration") {
+            Some(CSSValue::Keyword(decoration)) => decoration.as_str(),
+            _ => "",
+        };
+        InheritedStyle {
+            text_transform,
+            color,
+            role,
+            bold,
+            italic,
+            underline: self.underline || decoration == "underline",
+            strikethrough: self.strikethrough || decoration == "line-through",
+            background: if inline_node(node) {
+                background_color(node).or(self.background)
+            } else {
+                None
+            },
+            nowrap,
+        }
+    }
+
+    fn text_style(&self) -> TextStyle {
+        TextStyle {
+            color: self.color,
+            role: self.role,
+            bold: self.bold,
+            italic: self.italic,
+            underline: self.underline,
+            strikethrough: self.strikethrough,
+            background: self.background,
+        }
+    }
+}
+
+/// `LayoutContext` is what layout needs to know about a page besides its styled tree.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutContext {
+    /// Natural sizes in pixels of the images the page has loaded, keyed by `src`.
+    pub image_sizes: HashMap<String, (u32, u32)>,
+    /// The tallest an image may be, in cells.
+    pub max_image_height: Option<u16>,
+}
+
+/// Lays out the children of `node` in `area`, stacking blocks vertically and flowing
+/// consecutive inline children into lines. The first line starts `offset` columns to the right.
+fn children_to_object<'a>(
+    node: &'a StyledNode<'a>,
+    area: Rect,
+    offset: usize,
+    inherited: &InheritedStyle,
+    context: &LayoutContext,
+) -> LayoutObject<'a> {
+    let inherited = inherited.cascade(node);
+    let line_width = area.width.max(1) as usize;
+    let offset = offset % line_width;
+
     let mut objects = vec![];
-    let mut content_len = offset as u16;
     let mut width = 0;
+    // the top of the current run of inline content
+    let mut y = area.y;
+    // columns consumed by the current run of inline content, starting from `run_start`
+    let mut content_len = offset;
+    let mut run_start = offset;
+    // the number of the next list item
+    let mut counter = list_start(node);
     for child in node.children.iter() {
-        let area = Rect {
-            x: area.x + (content_len % area.width),
-            y,
-            width: area.width,
-            height: area.height,
-        };
-        let object = node_to_object(child, area, offset);
-        content_len += object.area.width;
-        if !inline_node(child) {
-            y += object.area.height;
-            height += object.area.height;
-            if width < content_len {
-                width = content_len;
+        // a loaded image takes whole lines even when it is an inline element
+        if inline_node(child) && loaded_image_src(child, context).is_none() {
+            let area = Rect {
+                y: y.saturating_add(saturate(content_len / line_width)),
+                ..area
+            };
+            let object = layout_node(child, area, content_len % line_width, &inherited, context);
+            content_len += object.area.width as usize;
+            objects.push(object);
+        } else {
+            if content_len > run_start {
+                y = y.saturating_add(saturate(content_len.div_ceil(line_width)));
+                width = width.max(content_len - run_start);
             }
+            let area = Rect { y, ..area };
+            let marker = details_marker(node, child)
+                .map(Cow::Borrowed)
+                .or_else(|| list_marker(child, &mut counter).map(Cow::Owned));
+            let object = match marker {
+                Some(marker) => marked_to_object(child, marker, area, &inherited, context),
+                None => layout_node(child, area, 0, &inherited, context),
+            };
+            y = y.saturating_add(object.area.height);
+            width = width.max(object.area.width as usize);
             content_len = 0;
-        } else {
-            y = area.y + content_len / area.width;
-            height = (content_len + area.width - 1) / area.width;
+            run_start = 0;
+            objects.push(object);
         }
-        objects.push(object);
     }
-    if width < content_len {
-        width = content_len;
+    if content_len > run_start {
+        y = y.saturating_add(saturate(content_len.div_ceil(line_width)));
+        width = width.max(content_len - run_start);
     }
 
     LayoutObject {
         area: Rect {
-            x: area.x,
+            x: area.x.saturating_add(offset as u16),
             y: area.y,
-            width,
-            height,
+            width: saturate(width),
+            height: y - area.y,
         },
         ty: LayoutObjectType::Block { children: objects },
+        node: Some(node),
+    }
+}
+
+/// Returns the marker drawn before `child` if it is the summary of `parent`, a `<details>` element.
+/// The marker shows whether the details are open.
+fn details_marker(parent: &StyledNode, child: &StyledNode) -> Option<&'static str> {
+    let NodeType::Element(details) = parent.node_type else {
+        return None;
+    };
+    let is_summary = matches!(child.node_type, NodeType::Element(e) if e.tag_name == "summary");
+    if details.tag_name != "details" || !is_summary {
+        return None;
     }
+    Some(if details.attributes.contains_key("open") {
+        "▾ "
+    } else {
+        "▸ "
+    })
+}
+
+/// Returns the number of the first item of the list `node`, which `<ol start>` sets.
+fn list_start(node: &StyledNode) -> i64 {
+    match node.node_type {
+        NodeType::Element(e) if e.tag_name == "ol" => e
+            .attributes
+            .get("start")
+            .and_then(|start| start.trim().parse().ok())
+            .unwrap_or(1),
+        _ => 1,
+    }
+}
+
+/// Returns the marker drawn before `item` if it is a list item, numbered by `counter`
+/// unless `<li value>` renumbers it, and counts the item.
+fn list_marker(item: &StyledNode, counter: &mut i64) -> Option<String> {
+    let NodeType::Element(element) = item.node_type else {
+        return None;
+    };
+    if item.properties.get("display") != Some(&CSSValue::Keyword("list-item".into())) {
+        return None;
+    }
+    if let Some(value) = element
+        .attributes
+        .get("value")
+        .and_then(|value| value.trim().parse().ok())
+    {
+        *counter = value;
+    }
+    let number = *counter;
+    *counter += 1;
+    let style = match item.properties.get("list-style-type") {
+        Some(CSSValue::Keyword(style)) => style.as_str(),
+        _ => "disc",
+    };
+    marker_text(style, number)
+}
+
+/// Returns the marker of list item `number` in `list-style-type` `style`, or `None` for `none`.
+/// Numbers that a style cannot show, like 0 in letters, are shown in decimal.
+fn marker_text(style: &str, number: i64) -> Option<String> {
+    let bullet = match style {
+        "none" => return None,
+        "circle" => "◦",
+        "square" => "▪",
+        "decimal"
+        | "decimal-leading-zero"
+        | "lower-alpha"
+        | "lower-latin"
+        | "upper-alpha"
+        | "upper-latin"
+        | "lower-roman"
+        | "upper-roman" => "",
+        // an unknown style is ignored, leaving the initial `disc`
+        _ => "•",
+    };
+    if !bullet.is_empty() {
+        return Some(format!("{} ", bullet));
+    }
+    let roman = (1..4000).contains(&number);
+    let numeral = match style {
+        "decimal-leading-zero" => format!("{:02}", number),
+        "lower-alpha" | "lower-latin" if number > 0 => alphabetic_numeral(number),
+        "upper-alpha" | "upper-latin" if number > 0 => {
+            alphabetic_numeral(number).to_ascii_uppercase()
+        }
+        "lower-roman" if roman => roman_numeral(number as u16),
+        "upper-roman" if roman => roman_numeral(number as u16).to_ascii_uppercase(),
+        _ => number.to_string(),
+    };
+    Some(format!("{}. ", numeral))
+}
+
+/// Writes `number`, at least 1, in lowercase letters: a to z, then aa, ab, and so on.
+fn alphabetic_numeral(mut number: i64) -> String {
+    let mut letters = vec![];
+    while number > 0 {
+        number -= 1;
+        letters.push(b'a' + (number % 26) as u8);
+        number /= 26;
+    }
+    letters.iter().rev().map(|&b| b as char).collect()
+}
+
+/// Writes `number`, between 1 and 3999, in lowercase Roman numerals.
+fn roman_numeral(mut number: u16) -> String {
+    const NUMERALS: [(u16, &str); 13] = [
+        (1000, "m"),
+        (900, "cm"),
+        (500, "d"),
+        (400, "cd"),
+        (100, "c"),
+        (90, "xc"),
+        (50, "l"),
+        (40, "xl"),
+        (10, "x"),
+        (9, "ix"),
+        (5, "v"),
+        (4, "iv"),
+        (1, "i"),
+    ];
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman += numeral;
+            number -= value;
+        }
+    }
+    roman
+}
+
+/// Lays out `item`, the summary of a `<details>` element or a list item, after its marker,
+/// indenting the lines that wrap.
+fn marked_to_object<'a>(
+    item: &'a StyledNode<'a>,
+    marker: Cow<'a, str>,
+    area: Rect,
+    inherited: &InheritedStyle,
+    context: &LayoutContext,
+) -> LayoutObject<'a> {
+    let marker = text_to_object(marker, area, 0, inherited.cascade(item).text_style());
+    let indent = marker.area.width.min(area.width.saturating_sub(1));
+    let object = layout_node(
+        item,
+        Rect {
+            x: area.x + indent,
+            width: area.width - indent,
+            ..area
+        },
+        0,
+        inherited,
+        context,
+    );
+    LayoutObject {
+        area: Rect {
+            x: area.x,
+            y: area.y,
+            width: object.area.width + indent,
+            height: object.area.height.max(1),
+        },
+        ty: LayoutObjectType::Block {
+            children: vec![marker, object],
+        },
+        node: None,
+    }
+}
+
+/// Narrows `viewport` to at most `max_width` columns and centers it horizontally,
+/// so that lines stay readable in very wide terminals.
+pub fn content_area(viewport: Rect, max_width: Option<u16>) -> Rect {
+    match max_width {
+        Some(max_width) if max_width < viewport.width => Rect {
+            x: viewport.x + (viewport.width - max_width) / 2,
+            width: max_width,
+            ..viewport
+        },
+        _ => viewport,
+    }
+}
+
+/// Size of a terminal cell in CSS pixels, used to convert lengths into cells.
+const CELL_WIDTH_PX: f32 = 8.0;
+const CELL_HEIGHT_PX: f32 = 16.0;
+const EM_PX: f32 = 16.0;
+
+#[derive(Debug, Clone, Copy)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+fn length_to_cells(value: &CSSValue, axis: Axis) -> Option<u16> {
+    let CSSValue::Length(n, unit) = value else {
+        return None;
+    };
+    let px = match unit {
+        Unit::Px => *n,
+        Unit::Em => n * EM_PX,
+        Unit::Ch => n * CELL_WIDTH_PX,
+    };
+    let cells = match axis {
+        Axis::Horizontal => px / CELL_WIDTH_PX,
+        Axis::Vertical => px / CELL_HEIGHT_PX,
+    };
+    Some(cells.max(0.0).ceil().min(u16::MAX as f32) as u16)
+}
+
+/// Grows `object` to honor `min-width` and `min-height`.
+/// `min-width` never grows a block beyond the width available in `area`.
+fn apply_min_size(node: &StyledNode, object: &mut LayoutObject, area: Rect) {
+    if inline_node(node) {
+        return;
+    }
+    if let Some(min_width) = node
+        .properties
+        .get("min-width")
+        .and_then(|v| length_to_cells(v, Axis::Horizontal))
+    {
+        object.area.width = object.area.width.max(min_width.min(area.width));
+    }
+    if let Some(min_height) = node
+        .properties
+        .get("min-height")
+        .and_then(|v| length_to_cells(v, Axis::Vertical))
+    {
+        object.area.height = object.area.height.max(min_height);
+    }
+}
+
+/// `BorderStyle` is the line a border is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderStyle {
+    Solid,
+    Double,
+    Dashed,
+    Dotted,
+}
+
+/// `Border` is the border around a block. Any `border-width` above zero takes one cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Border {
+    pub style: BorderStyle,
+    pub color: Option<Color>,
+}
+
+/// Returns the border of `node` from `border-width`, `border-style` and `border-color`.
+/// A border without a `border-style` is drawn solid. Inline elements have no border.
+pub fn border(node: &StyledNode) -> Option<Border> {
+    if inline_node(node) {
+        return None;
+    }
+    let width = match node.properties.get("border-width")? {
+        CSSValue::Length(width, _) => *width,
+        CSSValue::Keyword(keyword) if matches!(keyword.as_str(), "thin" | "medium" | "thick") => {
+            1.0
+        }
+        _ => 0.0,
+    };
+    if width <= 0.0 {
+        return None;
+    }
+    let style = match node.properties.get("border-style") {
+        Some(CSSValue::Keyword(style)) => match style.as_str() {
+            "none" | "hidden" => return None,
+            "double" => BorderStyle::Double,
+            "dashed" => BorderStyle::Dashed,
+            "dotted" => BorderStyle::Dotted,
+            _ => BorderStyle::Solid,
+        },
+        _ => BorderStyle::Solid,
+    };
+    let color = node
+        .properties
+        .get("border-color")
+        .or_else(|| node.properties.get("color"))
+        .and_then(Color::from_value);
+    Some(Border { style, color })
+}
+
+/// Returns the `src` of `node` if it is an `<img>` whose image has been loaded.
+fn loaded_image_src<'a>(node: &'a StyledNode<'a>, context: &LayoutContext) -> Option<&'a str> {
+    let NodeType::Element(element) = node.node_type else {
+        return None;
+    };
+    if element.tag_name != "img" {
+        return None;
+    }
+    element
+        .attributes
+        .get("src")
+        .map(|src| src.as_str())
+        .filter(|src| context.image_sizes.contains_key(*src))
+}
+
+/// The color of the `[alt text]` shown in place of images that are not drawn.
+pub const IMAGE_PLACEHOLDER_COLOR: Color = Color::rgb(0x80, 0x80, 0x80);
+
+/// Returns the text shown in place of `node` if it is an `<img>`: its alt text, or `[IMG]`.
+fn image_placeholder(node: &StyledNode) -> Option<String> {
+    match node.node_type {
+        NodeType::Element(element) if element.tag_name == "img" => {
+            match element.attributes.get("alt").map(|alt| alt.trim()) {
+                Some(alt) if !alt.is_empty() => Some(format!("[{}]", alt)),
+                _ => Some("[IMG]".into()),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Sizes an `<img>` whose image has been loaded, from its `width`/`height` attributes
+/// or its natural size, shrinking it to fit `area` and the maximum image height.
+fn image_to_object<'a>(
+    node: &'a StyledNode<'a>,
+    area: Rect,
+    context: &LayoutContext,
+) -> Option<LayoutObject<'a>> {
+    let NodeType::Element(element) = node.node_type else {
+        return None;
+    };
+    let src = loaded_image_src(node, context)?;
+    if area.width == 0 {
+        return None;
+    }
+    let (natural_width, natural_height) = match context.image_sizes.get(src) {
+        Some(&(w, h)) if w > 0 && h > 0 => (w as f32, h as f32),
+        _ => return None,
+    };
+    let attribute = |name: &str| {
+        element
+            .attributes
+            .get(name)
+            .and_then(|v| v.trim().trim_end_matches("px").parse::<f32>().ok())
+            .filter(|v| *v > 0.0)
+    };
+    let (width, height) = match (attribute("width"), attribute("height")) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, w * natural_height / natural_width),
+        (None, Some(h)) => (h * natural_width / natural_height, h),
+        (None, None) => (natural_width, natural_height),
+    };
+
+    let mut columns = (width / CELL_WIDTH_PX).max(1.0);
+    let mut rows = (height / CELL_HEIGHT_PX).max(1.0);
+    let max_width = area.width as f32;
+    if columns > max_width {
+        rows *= max_width / columns;
+        columns = max_width;
+    }
+    if let Some(max_height) = context.max_image_height.map(|h| h.max(1) as f32) {
+        if rows > max_height {
+            columns *= max_height / rows;
+            rows = max_height;
+        }
+    }
+    let cells = |n: f32| n.round().clamp(1.0, u16::MAX as f32) as u16;
+
+    Some(LayoutObject {
+        area: Rect {
+            x: area.x,
+            y: area.y,
+            width: cells(columns),
+            height: cells(rows),
+        },
+        ty: LayoutObjectType::Image { src },
+        node: Some(node),
+    })
 }
 
 pub fn node_to_object<'a>(node: &'a StyledNode<'a>, area: Rect, offset: usize) -> LayoutObject<'a> {
+    node_to_object_with_context(node, area, offset, &LayoutContext::default())
+}
+
+pub fn node_to_object_with_context<'a>(
+    node: &'a StyledNode<'a>,
+    area: Rect,
+    offset: usize,
+    context: &LayoutContext,
+) -> LayoutObject<'a> {
+    let _span = tracing::debug_span!("layout", width = area.width).entered();
+    layout_node(node, area, offset, &InheritedStyle::default(), context)
+}
+
+fn layout_node<'a>(
+    node: &'a StyledNode<'a>,
+    area: Rect,
+    offset: usize,
+    inherited: &InheritedStyle,
+    context: &LayoutContext,
+) -> LayoutObject<'a> {
     match node.node_type {
-        NodeType::Text(dom::Text { data }) => text_to_object(data, area, offset),
-        NodeType::Element(_) => children_to_object(node, area, offset),
+        NodeType::Text(dom::Text { data }) => LayoutObject {
+            node: Some(node),
+            ..text_to_object(
+                inherited.text_transform.apply(data),
+                area,
+                offset,
+                inherited.text_style(),
+            )
+        },
+        // comments and doctypes are never styled, so they take no room if laid out
+        NodeType::Comment(_) | NodeType::Doctype(_) => LayoutObject {
+            area: Rect { height: 0, ..area },
+            ty: LayoutObjectType::Block { children: vec![] },
+            node: Some(node),
+        },
+        NodeType::Element(_) | NodeType::Document => {
+            if let Some(object) = image_to_object(node, area, context) {
+                return object;
+            }
+            if let Some(placeholder) = image_placeholder(node) {
+                let style = TextStyle {
+                    color: Some(IMAGE_PLACEHOLDER_COLOR),
+                    italic: true,
+                    ..inherited.cascade(node).text_style()
+                };
+                return LayoutObject {
+                    node: Some(node),
+                    ..text_to_object(placeholder, area, offset, style)
+                };
+            }
+            // form controls are drawn as text in place of their children
+            if let Some(widget) = form::widget_text(node) {
+                return LayoutObject {
+                    node: Some(node),
+                    ..text_to_object(widget, area, offset, inherited.cascade(node).text_style())
+                };
+            }
+            // a block always starts on its own line
+            let offset = if inline_node(node) { offset } else { 0 };
+            // the border takes a cell on each side, when there is room for it
+            let border = border(node).filter(|_| area.width > 2);
+            let inner = match border {
+                Some(_) => Rect {
+                    x: area.x.saturating_add(1),
+                    y: area.y.saturating_add(1),
+                    width: area.width - 2,
+                    height: area.height.saturating_sub(2),
+                },
+                None => area,
+            };
+            // lines that do not wrap run as far right as they need to
+            let flow = if inherited.cascade(node).nowrap && !inline_node(node) {
+                Rect {
+                    width: u16::MAX - inner.x,
+                    ..inner
+                }
+            } else {
+                inner
+            };
+            let mut object = children_to_object(node, flow, offset, inherited, context);
+            apply_min_size(node, &mut object, inner);
+            if border.is_some() {
+                object.area = Rect {
+                    x: area.x,
+                    y: area.y,
+                    width: object.area.width.saturating_add(2),
+                    height: object.area.height.saturating_add(2),
+                };
+            }
+            if !inline_node(node) {
+                // a block never grows wider than its containing block
+                object.area.width = object.area.width.min(area.width);
+            }
+            object
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::split_string_by_width;
-    use crate::layout::{children_to_object, text_to_object, LayoutObject, LayoutObjectType, Text};
+    use super::{
+        border, content_area, grapheme_width, inline_node, marker_text, node_to_object,
+        split_measured_by_width, split_string_by_width, Border, BorderStyle,
+    };
+    use crate::cssom::Color;
+    use crate::layout::{
+        children_to_object, node_to_object_with_context, text_to_object, InheritedStyle,
+        LayoutContext, LayoutObject, LayoutObjectType, Link, Text, TextStyle, TextTransform,
+        IMAGE_PLACEHOLDER_COLOR,
+    };
+    use crate::rect::Rect;
     use combine::Parser;
-    use ratatui::layout::Rect;
+    use proptest::prelude::*;
+    use unicode_segmentation::UnicodeSegmentation;
 
     #[test]
     fn test_split_string_by_width() {
@@ -224,81 +1133,233 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_split_measured_by_width() {
+        assert_eq!(
+            split_measured_by_width("hello world", 3, 0),
+            vec![("hel", 3), ("lo ", 3), ("wor", 3), ("ld", 2)]
+        );
+
+        assert_eq!(
+            split_measured_by_width("こんにちは、今日はいい天気ですね。", 4, 0),
+            vec![
+                ("こん", 4),
+                ("にち", 4),
+                ("は、", 4),
+                ("今日", 4),
+                ("はい", 4),
+                ("い天", 4),
+                ("気で", 4),
+                ("すね", 4),
+                ("。", 2)
+            ]
+        );
+
+        assert_eq!(
+            split_measured_by_width("こんにちは、今日はいい天気ですね。", 4, 0),
+            split_measured_by_width("こんにちは、今日はいい天気ですね。", 5, 0),
+        );
+
+        // the offset is not part of the width of the first line
+        assert_eq!(
+            split_measured_by_width("こんにちは、今日はいい天気ですね。", 6, 2),
+            vec![
+                ("こん", 4),
+                ("にちは", 6),
+                ("、今日", 6),
+                ("はいい", 6),
+                ("天気で", 6),
+                ("すね。", 6)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_grapheme_width() {
+        assert_eq!(grapheme_width("a"), 1);
+        assert_eq!(grapheme_width("あ"), 2);
+        assert_eq!(grapheme_width("e\u{301}"), 1);
+        assert_eq!(grapheme_width("😀"), 2);
+        // family: man, woman, girl, boy joined by ZWJ
+        assert_eq!(grapheme_width("👨\u{200D}👩\u{200D}👧\u{200D}👦"), 2);
+        // flag of Japan
+        assert_eq!(grapheme_width("🇯🇵"), 2);
+        // thumbs up with skin tone modifier
+        assert_eq!(grapheme_width("👍\u{1F3FD}"), 2);
+        // heavy black heart with emoji presentation selector
+        assert_eq!(grapheme_width("❤\u{FE0F}"), 2);
+        // keycap sequence
+        assert_eq!(grapheme_width("1\u{FE0F}\u{20E3}"), 2);
+    }
+
+    #[test]
+    fn test_split_emoji_by_width() {
+        assert_eq!(
+            split_measured_by_width("👨\u{200D}👩\u{200D}👧\u{200D}👦🇯🇵🇺🇸ab", 4, 0),
+            vec![("👨\u{200D}👩\u{200D}👧\u{200D}👦🇯🇵", 4), ("🇺🇸ab", 4)]
+        );
+
+        assert_eq!(
+            split_measured_by_width("I ❤\u{FE0F} 👍\u{1F3FD}!", 4, 0),
+            vec![("I ❤\u{FE0F}", 4), (" 👍\u{1F3FD}!", 4)]
+        );
+    }
+
+    #[test]
+    fn test_content_area() {
+        assert_eq!(
+            content_area(Rect::new(0, 0, 300, 40), Some(80)),
+            Rect::new(110, 0, 80, 40)
+        );
+        assert_eq!(
+            content_area(Rect::new(0, 0, 81, 40), Some(80)),
+            Rect::new(0, 0, 80, 40)
+        );
+        assert_eq!(
+            content_area(Rect::new(0, 0, 60, 40), Some(80)),
+            Rect::new(0, 0, 60, 40)
+        );
+        assert_eq!(
+            content_area(Rect::new(0, 0, 300, 40), None),
+            Rect::new(0, 0, 300, 40)
+        );
+    }
+
     #[test]
     fn test_text_to_object() {
         assert_eq!(
-            text_to_object("hello world", Rect::new(0, 0, 20, 3), 0),
+            text_to_object(
+                "hello world",
+                Rect::new(0, 0, 20, 3),
+                0,
+                TextStyle::default()
+            ),
             LayoutObject {
                 area: Rect::new(0, 0, 11, 1),
+                node: None,
                 ty: LayoutObjectType::Texts(vec![Text {
                     area: Rect::new(0, 0, 11, 1),
-                    data: "hello world"
+                    data: "hello world".into(),
+                    style: TextStyle::default()
                 }])
             }
         );
 
         assert_eq!(
-            text_to_object("hello world", Rect::new(0, 0, 3, 10), 0),
+            text_to_object(
+                "hello world",
+                Rect::new(0, 0, 3, 10),
+                0,
+                TextStyle::default()
+            ),
             LayoutObject {
                 area: Rect::new(0, 0, 11, 1),
+                node: None,
                 ty: LayoutObjectType::Texts(vec![
                     Text {
                         area: Rect::new(0, 0, 3, 1),
-                        data: "hel"
+                        data: "hel".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(0, 1, 3, 1),
-                        data: "lo "
+                        data: "lo ".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(0, 2, 3, 1),
-                        data: "wor"
+                        data: "wor".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(0, 3, 2, 1),
-                        data: "ld"
+                        data: "ld".into(),
+                        style: TextStyle::default()
                     }
                 ])
             }
         );
 
         assert_eq!(
-            text_to_object("hello world", Rect::new(3, 6, 5, 10), 0),
+            text_to_object(
+                "hello world",
+                Rect::new(3, 6, 5, 10),
+                0,
+                TextStyle::default()
+            ),
             LayoutObject {
                 area: Rect::new(3, 6, 11, 1),
+                node: None,
                 ty: LayoutObjectType::Texts(vec![
                     Text {
                         area: Rect::new(3, 6, 5, 1),
-                        data: "hello"
+                        data: "hello".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(3, 7, 5, 1),
-                        data: " worl"
+                        data: " worl".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(3, 8, 1, 1),
-                        data: "d"
+                        data: "d".into(),
+                        style: TextStyle::default()
                     },
                 ])
             }
         );
 
         assert_eq!(
-            text_to_object("hello world", Rect::new(3, 6, 5, 10), 4),
+            text_to_object(
+                "🇯🇵👨\u{200D}👩\u{200D}👧x",
+                Rect::new(0, 0, 4, 10),
+                0,
+                TextStyle::default()
+            ),
             LayoutObject {
-                area: Rect::new(3, 6, 11, 1),
+                area: Rect::new(0, 0, 5, 1),
+                node: None,
                 ty: LayoutObjectType::Texts(vec![
                     Text {
-                        area: Rect::new(3, 6, 1, 1),
-                        data: "h"
+                        area: Rect::new(0, 0, 4, 1),
+                        data: "🇯🇵👨\u{200D}👩\u{200D}👧".into(),
+                        style: TextStyle::default()
+                    },
+                    Text {
+                        area: Rect::new(0, 1, 1, 1),
+                        data: "x".into(),
+                        style: TextStyle::default()
+                    },
+                ])
+            }
+        );
+
+        assert_eq!(
+            text_to_object(
+                "hello world",
+                Rect::new(3, 6, 5, 10),
+                4,
+                TextStyle::default()
+            ),
+            LayoutObject {
+                area: Rect::new(7, 6, 11, 1),
+                node: None,
+                ty: LayoutObjectType::Texts(vec![
+                    Text {
+                        area: Rect::new(7, 6, 1, 1),
+                        data: "h".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(3, 7, 5, 1),
-                        data: "ello "
+                        data: "ello ".into(),
+                        style: TextStyle::default()
                     },
                     Text {
                         area: Rect::new(3, 8, 5, 1),
-                        data: "world"
+                        data: "world".into(),
+                        style: TextStyle::default()
                     },
                 ])
             }
@@ -315,35 +1376,48 @@ mod tests {
             "#;
         let css = r#""#;
         let node = &crate::html::html().parse(html).unwrap().0[0];
-        let stylesheet = crate::css::stylesheet(css);
+        let stylesheet = crate::css::stylesheet(css).unwrap();
 
         let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
         assert_eq!(
-            children_to_object(&node, Rect::new(0, 0, 80, 40), 0),
+            children_to_object(
+                &node,
+                Rect::new(0, 0, 80, 40),
+                0,
+                &InheritedStyle::default(),
+                &LayoutContext::default()
+            ),
             LayoutObject {
                 area: Rect::new(0, 0, 5, 2),
+                node: Some(&node),
                 ty: LayoutObjectType::Block {
                     children: vec![
                         LayoutObject {
                             area: Rect::new(0, 0, 3, 1),
+                            node: Some(&node.children[0]),
                             ty: LayoutObjectType::Block {
                                 children: vec![LayoutObject {
                                     area: Rect::new(0, 0, 3, 1),
+                                    node: Some(&node.children[0].children[0]),
                                     ty: LayoutObjectType::Texts(vec![Text {
                                         area: Rect::new(0, 0, 3, 1),
-                                        data: "aaa"
+                                        data: "aaa".into(),
+                                        style: TextStyle::default()
                                     }])
                                 },]
                             }
                         },
                         LayoutObject {
                             area: Rect::new(0, 1, 5, 1),
+                            node: Some(&node.children[1]),
                             ty: LayoutObjectType::Block {
                                 children: vec![LayoutObject {
                                     area: Rect::new(0, 1, 5, 1),
+                                    node: Some(&node.children[1].children[0]),
                                     ty: LayoutObjectType::Texts(vec![Text {
                                         area: Rect::new(0, 1, 5, 1),
-                                        data: "bbbbb"
+                                        data: "bbbbb".into(),
+                                        style: TextStyle::default()
                                     }])
                                 }]
                             }
@@ -357,30 +1431,45 @@ mod tests {
                 "#;
         let css = r#"strong { display: inline; }"#;
         let node = &crate::html::html().parse(html).unwrap().0[0];
-        let stylesheet = crate::css::stylesheet(css);
+        let stylesheet = crate::css::stylesheet(css).unwrap();
 
         let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
         assert_eq!(
-            children_to_object(&node, Rect::new(0, 0, 80, 40), 0),
+            children_to_object(
+                &node,
+                Rect::new(0, 0, 80, 40),
+                0,
+                &InheritedStyle::default(),
+                &LayoutContext::default()
+            ),
             LayoutObject {
                 area: Rect::new(0, 0, 10, 1),
+                node: Some(&node),
                 ty: LayoutObjectType::Block {
                     children: vec![
                         LayoutObject {
                             area: Rect::new(0, 0, 6, 1),
+                            node: Some(&node.children[0]),
                             ty: LayoutObjectType::Texts(vec![Text {
                                 area: Rect::new(0, 0, 6, 1),
-                                data: "とても"
+                                data: "とても".into(),
+                                style: TextStyle::default()
                             }])
                         },
                         LayoutObject {
                             area: Rect::new(6, 0, 4, 1),
+                            node: Some(&node.children[1]),
                             ty: LayoutObjectType::Block {
                                 children: vec![LayoutObject {
                                     area: Rect::new(6, 0, 4, 1),
+                                    node: Some(&node.children[1].children[0]),
                                     ty: LayoutObjectType::Texts(vec![Text {
                                         area: Rect::new(6, 0, 4, 1),
-                                        data: "強い"
+                                        data: "強い".into(),
+                                        style: TextStyle {
+                                            bold: true,
+                                            ..Default::default()
+                                        }
                                     }])
                                 }]
                             }
@@ -390,4 +1479,420 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_min_size() {
+        let html = r#"
+        <div>
+            <div class="badge">ab</div>
+            <div>c</div>
+        </div>
+            "#;
+        let css = r#".badge { min-width: 6ch; min-height: 2em; }"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+
+        let object = node_to_object(&node, Rect::new(0, 0, 80, 40), 0);
+        let LayoutObjectType::Block { children } = &object.ty else {
+            panic!("expected a block");
+        };
+        assert_eq!(children[0].area, Rect::new(0, 0, 6, 2));
+        assert_eq!(children[1].area, Rect::new(0, 2, 1, 1));
+        assert_eq!(object.area, Rect::new(0, 0, 6, 3));
+
+        let object = node_to_object(&node, Rect::new(0, 0, 4, 40), 0);
+        let LayoutObjectType::Block { children } = &object.ty else {
+            panic!("expected a block");
+        };
+        assert_eq!(children[0].area, Rect::new(0, 0, 4, 2));
+    }
+
+    #[test]
+    fn test_hit_test() {
+        let html = r#"<div><div>aaa</div><div>bb<a href="x">cc</a></div></div>"#;
+        let css = r#"a { display: inline; }"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 80, 40), 0);
+
+        let path = object.hit_test(3, 1);
+        let link = &node.children[1].children[1];
+        assert_eq!(path.len(), 4);
+        assert_eq!(path[2].node, Some(link));
+        assert_eq!(path[3].node, Some(&link.children[0]));
+
+        assert_eq!(object.hit_test(1, 0)[1].node, Some(&node.children[0]));
+        assert!(object.hit_test(10, 10).is_empty());
+    }
+
+    #[test]
+    fn test_text_transform() {
+        assert_eq!(TextTransform::None.apply("hello World"), "hello World");
+        assert_eq!(TextTransform::Uppercase.apply("hello World"), "HELLO WORLD");
+        assert_eq!(TextTransform::Lowercase.apply("hello World"), "hello world");
+        assert_eq!(
+            TextTransform::Capitalize.apply("hello wide  world"),
+            "Hello Wide  World"
+        );
+        assert_eq!(TextTransform::Uppercase.apply("straße"), "STRASSE");
+    }
+
+    #[test]
+    fn test_text_transform_layout() {
+        let html = r#"<div class="loud"><p>straße</p><p class="quiet">Hi</p></div>"#;
+        let css = r#".loud { text-transform: uppercase; } .quiet { text-transform: none; }"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 80, 40), 0);
+
+        let texts = |object: &LayoutObject| match &object.ty {
+            LayoutObjectType::Block { children } => match &children[0].ty {
+                LayoutObjectType::Texts(texts) => texts
+                    .iter()
+                    .map(|t| (t.data.to_string(), t.area.width))
+                    .collect::<Vec<_>>(),
+                _ => panic!("expected texts"),
+            },
+            _ => panic!("expected a block"),
+        };
+        let LayoutObjectType::Block { children } = &object.ty else {
+            panic!("expected a block");
+        };
+        assert_eq!(texts(&children[0]), vec![("STRASSE".to_string(), 7)]);
+        assert_eq!(texts(&children[1]), vec![("Hi".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_mixed_inline_and_block() {
+        let html = r#"<div>aaaa<span>bbbb</span><div>cc</div>dd</div>"#;
+        let css = r#"span { display: inline; }"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 6, 40), 0);
+
+        let LayoutObjectType::Block { children } = &object.ty else {
+            panic!("expected a block");
+        };
+        assert_eq!(children[0].area, Rect::new(0, 0, 4, 1));
+        assert_eq!(children[1].area, Rect::new(4, 0, 4, 2));
+        assert_eq!(children[2].area, Rect::new(0, 2, 2, 1));
+        assert_eq!(children[3].area, Rect::new(0, 3, 2, 1));
+        assert_eq!(object.area, Rect::new(0, 0, 6, 4));
+
+        assert!(node_to_object(&node, Rect::new(0, 0, 0, 0), 0)
+            .hit_test(0, 0)
+            .is_empty());
+    }
+
+    fn fragments<'a, 'b>(object: &'b LayoutObject<'a>, out: &mut Vec<&'b Text<'a>>) {
+        match &object.ty {
+            LayoutObjectType::Texts(texts) => out.extend(texts.iter()),
+            LayoutObjectType::Image { .. } => {}
+            LayoutObjectType::Block { children } => {
+                children.iter().for_each(|child| fragments(child, out))
+            }
+        }
+    }
+
+    fn blocks<'a, 'b>(object: &'b LayoutObject<'a>, out: &mut Vec<&'b LayoutObject<'a>>) {
+        if let LayoutObjectType::Block { children } = &object.ty {
+            if object.node.is_some_and(|n| !inline_node(n)) {
+                out.push(object);
+            }
+            children.iter().for_each(|child| blocks(child, out))
+        }
+    }
+
+    fn arbitrary_html() -> impl Strategy<Value = String> {
+        let word = prop_oneof![
+            "[a-z]{1,12}",
+            "[あ-ん]{1,6}",
+            Just("👨\u{200D}👩\u{200D}👧".to_string()),
+            Just("🇯🇵".to_string()),
+        ];
+        let text = prop::collection::vec(word, 1..20).prop_map(|words| words.join(" "));
+        text.prop_recursive(4, 64, 8, |inner| {
+            (
+                prop_oneof![Just("div"), Just("span"), Just("p"), Just("b")],
+                prop::collection::vec(inner, 0..6),
+            )
+                .prop_map(|(tag, children)| format!("<{tag}>{}</{tag}>", children.concat()))
+        })
+        .prop_map(|html| format!("<div>{}</div>", html))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_layout_stays_within_viewport(
+            html in arbitrary_html(),
+            x in 0u16..100,
+            y in 0u16..100,
+            width in 0u16..300,
+            height in 0u16..100,
+            offset in 0usize..400,
+        ) {
+            let css = "span, b { display: inline; } p { min-width: 100000px; min-height: 2em; }";
+            let node = &crate::html::html().parse(html.as_str()).unwrap().0[0];
+            let stylesheet = crate::css::stylesheet(css).unwrap();
+            let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+            let viewport = Rect { x, y, width, height };
+            let object = node_to_object(&node, viewport, offset);
+
+            let mut texts = vec![];
+            fragments(&object, &mut texts);
+            for text in texts {
+                prop_assert!(text.area.x >= viewport.x && text.area.y >= viewport.y);
+                prop_assert!(
+                    text.area.right() <= viewport.right()
+                        || text.data.graphemes(true).count() == 1,
+                    "{:?} exceeds {:?}", text, viewport
+                );
+            }
+
+            let mut objects = vec![];
+            blocks(&object, &mut objects);
+            for object in objects {
+                prop_assert!(object.area.x >= viewport.x && object.area.y >= viewport.y);
+                prop_assert!(
+                    object.area.right() <= viewport.right(),
+                    "{:?} exceeds {:?}", object.area, viewport
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_marker_text() {
+        let markers = |style| [1, 4, 28].map(|n| marker_text(style, n).unwrap()).join("");
+        assert_eq!(markers("decimal"), "1. 4. 28. ");
+        assert_eq!(markers("decimal-leading-zero"), "01. 04. 28. ");
+        assert_eq!(markers("lower-alpha"), "a. d. ab. ");
+        assert_eq!(markers("upper-latin"), "A. D. AB. ");
+        assert_eq!(markers("lower-roman"), "i. iv. xxviii. ");
+        assert_eq!(markers("upper-roman"), "I. IV. XXVIII. ");
+        assert_eq!(markers("square"), "▪ ▪ ▪ ");
+        assert_eq!(markers("unknown"), "• • • ");
+        assert_eq!(marker_text("lower-alpha", 0), Some("0. ".into()));
+        assert_eq!(marker_text("upper-roman", 4000), Some("4000. ".into()));
+        assert_eq!(marker_text("none", 1), None);
+    }
+
+    #[test]
+    fn test_list_markers() {
+        let html = r#"<div><ol start="3"><li>a</li><li value="7">b</li><li>c</li></ol><ul><li>dddd</li></ul><ol style="x"><li>e</li></ol></div>"#;
+        let css = r#"ol[style=x] { list-style-type: upper-alpha; color: red; }"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 4, 40), 0);
+
+        let mut texts = vec![];
+        fragments(&object, &mut texts);
+        // the marker is colored like the text of its item
+        let (marker, item) = (&texts[texts.len() - 2], &texts[texts.len() - 1]);
+        assert!(marker.style.color.is_some());
+        assert_eq!(marker.style, item.style);
+        let texts = texts
+            .iter()
+            .map(|t| (t.data.as_ref(), t.area.x, t.area.y))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                ("3. ", 0, 0),
+                ("a", 3, 0),
+                ("7. ", 0, 1),
+                ("b", 3, 1),
+                ("8. ", 0, 2),
+                ("c", 3, 2),
+                ("• ", 0, 3),
+                // wrapped lines are indented past the marker
+                ("dd", 2, 3),
+                ("dd", 2, 4),
+                ("A. ", 0, 5),
+                ("e", 3, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_style() {
+        let html = r#"<div><b>a<i>b</i></b><u>c<s>d</s></u><b style="x">e</b></div>"#;
+        let css = r#"b, i, u, s { display: inline; } b[style=x] { font-weight: normal; }"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 80, 40), 0);
+
+        let mut texts = vec![];
+        fragments(&object, &mut texts);
+        let styles = texts
+            .iter()
+            .map(|t| (t.data.as_ref(), t.style))
+            .collect::<Vec<_>>();
+        let bold = TextStyle {
+            bold: true,
+            ..Default::default()
+        };
+        let underline = TextStyle {
+            underline: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            styles,
+            vec![
+                ("a", bold),
+                (
+                    "b",
+                    TextStyle {
+                        italic: true,
+                        ..bold
+                    }
+                ),
+                ("c", underline),
+                (
+                    "d",
+                    TextStyle {
+                        strikethrough: true,
+                        ..underline
+                    }
+                ),
+                ("e", TextStyle::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_image_size() {
+        let html = r#"<div><img src="a.png"><img src="a.png" width="80"><img src="b.png" alt="b"><p>after</p></div>"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let context = LayoutContext {
+            image_sizes: vec![("a.png".into(), (320, 320))].into_iter().collect(),
+            max_image_height: Some(10),
+        };
+        let object = node_to_object_with_context(&node, Rect::new(0, 0, 30, 40), 0, &context);
+
+        let LayoutObjectType::Block { children } = &object.ty else {
+            panic!("expected a block");
+        };
+        // 320px is 40 columns by 20 rows, shrunk to the width and then to the maximum height
+        assert_eq!(children[0].area, Rect::new(0, 0, 20, 10));
+        assert_eq!(children[0].ty, LayoutObjectType::Image { src: "a.png" });
+        // the height follows the width attribute to keep the aspect ratio
+        assert_eq!(children[1].area, Rect::new(0, 10, 10, 5));
+        // an image that has not been loaded is replaced by its alt text
+        let LayoutObjectType::Texts(texts) = &children[2].ty else {
+            panic!("expected texts");
+        };
+        assert_eq!(texts[0].data, "[b]");
+        assert_eq!(texts[0].area, Rect::new(0, 15, 3, 1));
+        assert_eq!(texts[0].style.color, Some(IMAGE_PLACEHOLDER_COLOR));
+        assert_eq!(children[3].area.y, 16);
+    }
+
+    #[test]
+    fn test_image_placeholder() {
+        let html = r#"<p>see <img src="a.png"> and <img src="b.png" alt=" chart "></p>"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 80, 40), 0);
+
+        let mut texts = vec![];
+        fragments(&object, &mut texts);
+        let texts = texts.iter().map(|t| t.data.as_ref()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["see ", "[IMG]", "and ", "[chart]"]);
+        assert_eq!(object.area.height, 1);
+    }
+
+    #[test]
+    fn test_border() {
+        let html = r#"<div><div class="box">hello</div><p>after</p></div>"#;
+        let css = ".box { border-width: 1px; border-style: double; border-color: red; }";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 80, 40), 0);
+
+        let LayoutObjectType::Block { children } = &object.ty else {
+            panic!("expected a block");
+        };
+        assert_eq!(children[0].area, Rect::new(0, 0, 7, 3));
+        assert_eq!(
+            border(children[0].node.unwrap()),
+            Some(Border {
+                style: BorderStyle::Double,
+                color: Some(Color::rgb(255, 0, 0))
+            })
+        );
+        let mut texts = vec![];
+        fragments(&children[0], &mut texts);
+        assert_eq!(texts[0].area, Rect::new(1, 1, 5, 1));
+        assert_eq!(children[1].area.y, 3);
+    }
+
+    #[test]
+    fn test_links() {
+        let html = r#"<p>see <a href="/a">the <b>first</b></a> and <a href="/b">second link</a><a>x</a></p>"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 20, 40), 0);
+
+        let links = object.links();
+        assert_eq!(
+            links,
+            vec![
+                Link {
+                    href: "/a",
+                    areas: vec![Rect::new(4, 0, 4, 1), Rect::new(8, 0, 5, 1)]
+                },
+                Link {
+                    href: "/b",
+                    areas: vec![Rect::new(17, 0, 3, 1), Rect::new(0, 1, 8, 1)]
+                },
+            ]
+        );
+        assert!(links[1].contains(3, 1));
+        assert!(!links[1].contains(3, 0));
+    }
+
+    #[test]
+    fn test_anchors() {
+        let html = r#"<div><p id="intro">intro</p><p>text <a name="more">more</a></p><p id="intro">again</p></div>"#;
+        let dom = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(dom, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 20, 40), 0);
+
+        let document = crate::dom::Document::new(dom);
+        let row = |name| Some(object.areas_of(document.anchor(name)?).first()?.y);
+        assert_eq!(row("intro"), Some(0));
+        assert_eq!(row("more"), Some(1));
+        assert_eq!(row("none"), None);
+    }
+
+    #[test]
+    fn test_nowrap() {
+        let html =
+            r#"<div><pre>a long line that does not wrap</pre><p>but this one does</p></div>"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 10, 40), 0);
+
+        let mut texts = vec![];
+        fragments(&object, &mut texts);
+        assert_eq!(texts[0].area, Rect::new(0, 0, 30, 1));
+        assert!(texts[1].area.y == 1 && texts[1].area.right() <= 10);
+        assert!(texts.len() > 2);
+        assert_eq!(object.area.width, 10);
+        assert_eq!(object.content_right(), 30);
+    }
 }
@@ -1,10 +1,76 @@
+#[cfg(all(feature = "tui", feature = "net"))]
+pub mod app;
+#[cfg(feature = "net")]
+pub mod archive;
+#[cfg(feature = "net")]
+pub mod auth;
+#[cfg(all(feature = "net", feature = "render"))]
+pub mod batch;
+pub mod bookmark;
+#[cfg(feature = "net")]
+pub mod browser;
+#[cfg(feature = "net")]
+pub mod cache;
+pub mod charset;
+#[cfg(feature = "net")]
+pub mod compression;
+pub mod config;
 pub mod css;
 pub mod cssom;
+pub mod diff;
 pub mod dom;
+#[cfg(feature = "net")]
+pub mod download;
+pub mod entity;
+pub mod error;
+#[cfg(feature = "render")]
+pub mod export;
+pub mod filter;
+pub mod form;
+#[cfg(feature = "tui")]
+pub mod graphics;
+#[cfg(feature = "tui")]
+pub mod hint;
+pub mod history;
 pub mod html;
+#[cfg(feature = "tui")]
+pub mod inspector;
+#[cfg(feature = "tui")]
+pub mod keymap;
 pub mod layout;
+pub mod linear;
+pub mod loader;
+pub mod logging;
+pub mod message;
+pub mod page;
+#[cfg(all(feature = "tui", feature = "net"))]
+pub mod pane;
+#[cfg(feature = "tui")]
+pub mod prompt;
+#[cfg(feature = "net")]
+pub mod queue;
+pub mod rect;
+#[cfg(feature = "render")]
 pub mod render;
+#[cfg(feature = "tui")]
+pub mod repl;
+#[cfg(feature = "net")]
 pub mod request;
+#[cfg(feature = "net")]
+pub mod resources;
+pub mod robots;
+#[cfg(feature = "scripts")]
+pub mod script;
 pub mod style;
+#[cfg(feature = "tui")]
+pub mod terminal;
+pub mod theme;
 
-pub use render::start;
+#[cfg(all(feature = "tui", feature = "net"))]
+pub use app::App;
+#[cfg(feature = "net")]
+pub use browser::Browser;
+pub use error::{Error, Result};
+#[cfg(feature = "render")]
+pub use export::{render_to_buffer, render_to_string};
+pub use page::Page;
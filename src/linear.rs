@@ -0,0 +1,333 @@
+use crate::{
+    dom::{Element, NodeType, Text},
+    layout::inline_node,
+    page::Page,
+    style::{to_styled_node, StyledNode},
+};
+
+/// Writes `page` out for screen readers and speech tools: a line for each block, read in
+/// document order, with what a sighted reader takes from the layout said in words.
+/// Headings say their level, links where they go and images their alt text, and lists and
+/// tables say how many items, rows and columns they have. What the page hides is left out.
+///
+/// ```text
+/// Page: Example
+/// Heading level 1: Welcome
+/// See link: the docs (https://example.com/docs) for more.
+/// List, 2 items:
+///   - one
+///   - two
+/// End of list.
+/// ```
+pub fn linearize(page: &Page) -> String {
+    let _span = tracing::debug_span!("linearize", url = page.url).entered();
+    let mut linearizer = Linearizer::new(page);
+    if let Some(title) = page.title() {
+        linearizer.lines.push(format!("Page: {}", title));
+    }
+    if let Some(styled) = to_styled_node(page.dom(), &page.stylesheet) {
+        linearizer.node(&styled);
+    }
+    linearizer.flush();
+    linearizer
+        .lines
+        .into_iter()
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// `Linearizer` gathers the lines of a page as it walks its styled tree.
+struct Linearizer<'p> {
+    page: &'p Page,
+    lines: Vec<String>,
+    /// The inline content of the block being read, which makes a line when the block ends.
+    line: String,
+    /// How deep in lists the block being read is, for the lines to be indented.
+    depth: usize,
+}
+
+fn element<'a>(node: &StyledNode<'a>) -> Option<&'a Element> {
+    match node.node_type {
+        NodeType::Element(e) => Some(e),
+        _ => None,
+    }
+}
+
+impl<'p> Linearizer<'p> {
+    fn new(page: &'p Page) -> Linearizer<'p> {
+        Linearizer {
+            page,
+            lines: vec![],
+            line: String::new(),
+            depth: 0,
+        }
+    }
+
+    /// Reads `nodes` on their own, as the text of a single line.
+    fn inline_text(&self, nodes: &[StyledNode]) -> String {
+        let mut linearizer = Linearizer::new(self.page);
+        nodes.iter().for_each(|node| linearizer.node(node));
+        linearizer.flush();
+        linearizer.lines.join(" ")
+    }
+
+    /// Adds `text` to the line, collapsing whitespace.
+    fn text(&mut self, text: &str) {
+        for (i, word) in text.split_whitespace().enumerate() {
+            let space = i > 0 || text.starts_with(char::is_whitespace);
+            if space && !self.line.is_empty() && !self.line.ends_with(' ') {
+                self.line.push(' ');
+            }
+            self.line.push_str(word);
+        }
+        if text.ends_with(char::is_whitespace) && !self.line.is_empty() {
+            self.line.push(' ');
+        }
+    }
+
+    /// Ends the line of the block being read, unless it has nothing to say.
+    fn flush(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        if !line.trim().is_empty() {
+            self.lines
+                .push(format!("{}{}", "  ".repeat(self.depth), line.trim()));
+        }
+    }
+
+    /// Adds `line` as a line of its own.
+    fn line(&mut self, line: String) {
+        self.flush();
+        self.lines
+            .push(format!("{}{}", "  ".repeat(self.depth), line));
+    }
+
+    fn children(&mut self, node: &StyledNode) {
+        node.children.iter().for_each(|child| self.node(child));
+    }
+
+    fn node(&mut self, node: &StyledNode) {
+        let e = match node.node_type {
+            NodeType::Text(Text { data }) => return self.text(data),
+            NodeType::Element(e) => e,
+            _ => return self.children(node),
+        };
+        let attribute = |name: &str| e.attributes.get(name).map(|value| value.trim());
+        match e.tag_name.as_str() {
+            tag @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6") => {
+                let text = self.inline_text(&node.children);
+                self.line(format!("Heading level {}: {}", &tag[1..], text));
+            }
+            "a" => match attribute("href") {
+                Some(href) => {
+                    let text = self.inline_text(&node.children);
+                    let url = self.page.resolve(href);
+                    self.text(&format!(" link: {} ({}) ", text, url));
+                }
+                None => self.children(node),
+            },
+            "img" => match attribute("alt") {
+                // an empty alt says the image is only decoration
+                Some("") => {}
+                Some(alt) => self.text(&format!(" image: {} ", alt)),
+                None => self.text(" image without a description "),
+            },
+            "br" => self.flush(),
+            "hr" => self.line("Separator.".into()),
+            "ul" | "ol" | "menu" => self.list(node, e),
+            "table" => self.table(node),
+            "blockquote" => {
+                self.line("Quote:".into());
+                self.children(node);
+                self.line("End of quote.".into());
+            }
+            "input" => {
+                let ty = attribute("type").unwrap_or("text").to_ascii_lowercase();
+                let value = attribute("value").unwrap_or_default();
+                let control = match ty.as_str() {
+                    "submit" | "button" | "reset" => format!("button: {}", value),
+                    "checkbox" | "radio" => format!(
+                        "{}, {}",
+                        ty,
+                        if e.attributes.contains_key("checked") {
+                            "checked"
+                        } else {
+                            "not checked"
+                        }
+                    ),
+                    _ => {
+                        let placeholder = attribute("placeholder").unwrap_or_default();
+                        format!(
+                            "text field: {}",
+                            if value.is_empty() { placeholder } else { value }
+                        )
+                    }
+                };
+                self.text(&format!(" {} ", control.trim_end_matches([' ', ':'])));
+            }
+            "button" => {
+                let text = self.inline_text(&node.children);
+                self.text(&format!(" button: {} ", text));
+            }
+            "textarea" => self.text(" text area "),
+            "select" => self.text(" combo box "),
+            _ if inline_node(node) => self.children(node),
+            _ => {
+                self.flush();
+                self.children(node);
+                self.flush();
+            }
+        }
+    }
+
+    fn list(&mut self, node: &StyledNode, e: &Element) {
+        let items = node
+            .children
+            .iter()
+            .filter(|child| element(child).is_some_and(|e| e.tag_name == "li"))
+            .collect::<Vec<_>>();
+        let ordered = e.tag_name == "ol";
+        let start = e
+            .attributes
+            .get("start")
+            .and_then(|start| start.trim().parse::<i64>().ok())
+            .unwrap_or(1);
+        let count = items.len();
+        self.line(format!(
+            "List, {} item{}:",
+            count,
+            if count == 1 { "" } else { "s" }
+        ));
+        self.depth += 1;
+        for (i, item) in items.into_iter().enumerate() {
+            self.flush();
+            self.line = match ordered {
+                true => format!("{}. ", start + i as i64),
+                false => "- ".into(),
+            };
+            self.children(item);
+            self.flush();
+        }
+        self.depth -= 1;
+        self.line("End of list.".into());
+    }
+
+    fn table(&mut self, node: &StyledNode) {
+        // the rows of the table, and of its `<thead>`, `<tbody>` and `<tfoot>`
+        let mut rows = vec![];
+        for child in &node.children {
+            match element(child).map(|e| e.tag_name.as_str()) {
+                Some("tr") => rows.push(child),
+                Some("thead" | "tbody" | "tfoot") => rows.extend(
+                    child
+                        .children
+                        .iter()
+                        .filter(|row| element(row).is_some_and(|e| e.tag_name == "tr")),
+                ),
+                Some("caption") => {
+                    let caption = self.inline_text(&child.children);
+                    self.line(format!("Caption: {}", caption));
+                }
+                _ => {}
+            }
+        }
+        let cells = |row: &StyledNode| {
+            row.children
+                .iter()
+                .filter_map(|cell| {
+                    let e = element(cell).filter(|e| matches!(e.tag_name.as_str(), "td" | "th"))?;
+                    Some((e.tag_name == "th", self.inline_text(&cell.children)))
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut rows = rows.into_iter().map(cells).collect::<Vec<_>>();
+        // a first row of header cells names the columns
+        let headers = match rows.first() {
+            Some(first) if !first.is_empty() && first.iter().all(|(header, _)| *header) => {
+                Some(rows.remove(0))
+            }
+            _ => None,
+        };
+        let columns = rows.iter().chain(&headers).map(Vec::len).max().unwrap_or(0);
+        self.line(format!(
+            "Table, {} row{}, {} column{}:",
+            rows.len(),
+            if rows.len() == 1 { "" } else { "s" },
+            columns,
+            if columns == 1 { "" } else { "s" }
+        ));
+        self.depth += 1;
+        if let Some(headers) = &headers {
+            let names = headers.iter().map(|(_, text)| text.as_str());
+            self.line(format!(
+                "Columns: {}",
+                names.collect::<Vec<_>>().join(" | ")
+            ));
+        }
+        for (i, row) in rows.iter().enumerate() {
+            let row = row.iter().enumerate().map(|(j, (_, text))| {
+                match headers.as_ref().and_then(|h| h.get(j)) {
+                    Some((_, header)) => format!("{}: {}", header, text),
+                    None => text.clone(),
+                }
+            });
+            let separator = if headers.is_some() { "; " } else { " | " };
+            self.line(format!(
+                "Row {}: {}",
+                i + 1,
+                row.collect::<Vec<_>>().join(separator)
+            ));
+        }
+        self.depth -= 1;
+        self.line("End of table.".into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::linearize;
+    use crate::page::{Page, Settings};
+
+    #[test]
+    fn test_linearize() {
+        let page = Page::from_html(
+            "https://a.test/dir/",
+            r#"<html><head><title>Example</title><style>.hidden { display: none; }</style></head><body>
+            <h1>Welcome <em>home</em></h1>
+            <p>See <a href="docs">the
+                docs</a> for more.<img src="a.png" alt="A cat"><img src="b.png" alt=""><img src="c.png"></p>
+            <p class="hidden">hidden</p>
+            <ol start="3"><li>three</li><li>four<ul><li>nested</li></ul></li></ol>
+            <table><tr><th>Name</th><th>Age</th></tr><tr><td>Alice</td><td>30</td></tr></table>
+            <table><tr><td>a</td><td>b</td></tr></table>
+            <form><input name="q" placeholder="Search"><input type="checkbox" checked><input type="submit" value="Go"></form>
+            <hr><p>the end</p>
+            </body></html>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            linearize(&page),
+            "Page: Example
+Heading level 1: Welcome home
+See link: the docs (https://a.test/dir/docs) for more. image: A cat image without a description
+List, 2 items:
+  3. three
+  4. four
+  List, 1 item:
+    - nested
+  End of list.
+End of list.
+Table, 1 row, 2 columns:
+  Columns: Name | Age
+  Row 1: Name: Alice; Age: 30
+End of table.
+Table, 1 row, 2 columns:
+  Row 1: a | b
+End of table.
+text field: Search checkbox, checked button: Go
+Separator.
+the end
+"
+        );
+    }
+}
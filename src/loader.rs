@@ -0,0 +1,483 @@
+#[cfg(feature = "net")]
+use crate::{
+    cache::{CacheMode, Entry},
+    compression::Coding,
+    download::Download,
+    error::{Error, Result},
+    form::{FormData, Method},
+    page::Settings,
+    queue::{self, Priority, Slot},
+    request::{self, Fetched, ResponseBody, Transport},
+};
+#[cfg(feature = "net")]
+use reqwest::Response;
+use std::path::Path;
+#[cfg(feature = "net")]
+use std::sync::Arc;
+#[cfg(feature = "net")]
+use tokio::{fs::File, io::AsyncReadExt};
+use url::Url;
+
+pub fn is_web_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Returns `url` in the form equivalent URLs share, so that they are taken as the same page:
+/// the scheme and host lowercase, an internationalized host in punycode, without the default
+/// port or `.` and `..` segments, and percent-encoded only where it has to be, in uppercase.
+/// URLs that are not on the web are kept as they are.
+pub fn normalize_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+            normalize_percent_encoding(parsed.as_str())
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Decodes the percent-encoded characters of `url` that need not be encoded, the unreserved
+/// ones of RFC 3986, and writes the hex digits of the rest in uppercase.
+fn normalize_percent_encoding(url: &str) -> String {
+    let mut normalized = String::with_capacity(url.len());
+    let mut rest = url;
+    while let Some(i) = rest.find('%') {
+        normalized.push_str(&rest[..i]);
+        let hex = rest
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(b) if b.is_ascii_alphanumeric() || b"-._~".contains(&b) => {
+                normalized.push(b as char)
+            }
+            Some(_) => {
+                normalized.push('%');
+                normalized.push_str(&rest[i + 1..i + 3].to_ascii_uppercase());
+            }
+            None => {
+                normalized.push('%');
+                rest = &rest[i + 1..];
+                continue;
+            }
+        }
+        rest = &rest[i + 3..];
+    }
+    normalized.push_str(rest);
+    normalized
+}
+
+/// Schemes of pages that are neither on the web nor in files, which are left as they are.
+const OTHER_SCHEMES: [&str; 2] = ["wev:", "about:"];
+
+/// Returns the URL of the page `input` names, as typed on the command line or in the address bar:
+/// `http(s)://` URLs are kept, `file://` URLs become the paths they name, paths are kept,
+/// and anything else that looks like a domain, like `example.com/a`, gets `https://` in front.
+pub fn url_from_input(input: &str) -> String {
+    let input = input.trim();
+    if is_web_url(&input.to_ascii_lowercase()) {
+        return normalize_url(input);
+    }
+    if OTHER_SCHEMES.iter().any(|scheme| input.starts_with(scheme)) {
+        return input.to_string();
+    }
+    if let Some(url) = Url::parse(input).ok().filter(|url| url.scheme() == "file") {
+        if let Ok(path) = url.to_file_path() {
+            let path = path.to_string_lossy();
+            return match url.fragment() {
+                Some(fragment) => format!("{}#{}", path, fragment),
+                None => path.into_owned(),
+            };
+        }
+    }
+    let location = input.split('#').next().unwrap_or_default();
+    let looks_like_path = location.starts_with(['/', '.', '~']) || location.contains('\\');
+    if looks_like_path || Path::new(location).exists() {
+        return input.to_string();
+    }
+    let host = input.split(['/', '?', '#']).next().unwrap_or_default();
+    let name = host.split(':').next().unwrap_or_default();
+    if name == "localhost" {
+        // local servers seldom have certificates
+        return normalize_url(&format!("http://{}", input));
+    }
+    let is_domain = name.contains('.')
+        && !name.contains(char::is_whitespace)
+        && name.split('.').all(|label| !label.is_empty());
+    if is_domain {
+        normalize_url(&format!("https://{}", input))
+    } else {
+        input.to_string()
+    }
+}
+
+/// `ContentKind` is how a document is shown, by its content type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    /// Parsed as HTML. Documents without a content type are taken to be HTML.
+    Html,
+    /// Shown as it is, like `text/plain`.
+    Text,
+    /// Shown as an image.
+    Image,
+    /// Not shown, but saved.
+    Other,
+}
+
+impl ContentKind {
+    pub fn of(content_type: Option<&str>) -> ContentKind {
+        let mime = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            "" | "text/html" | "application/xhtml+xml" | "application/xml" | "text/xml" => {
+                ContentKind::Html
+            }
+            _ if mime.starts_with("text/") => ContentKind::Text,
+            "image/png" | "image/jpeg" | "image/gif" | "image/webp" => ContentKind::Image,
+            _ => ContentKind::Other,
+        }
+    }
+}
+
+/// Returns the content type of a file at `path` by its extension, for the kinds that are
+/// shown differently from HTML.
+pub fn guess_content_type(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "txt" | "text" | "md" | "log" => Some("text/plain"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// `Resource` is a whole fetched document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    /// What references in the document are resolved against: where it was finally
+    /// fetched from, after any redirects.
+    pub base_url: String,
+    pub body: Vec<u8>,
+    /// The content type the server gave, or that the extension of a file tells.
+    pub content_type: Option<String>,
+}
+
+#[cfg(feature = "net")]
+/// `Body` is where the rest of a body is read from.
+#[derive(Debug)]
+enum Body {
+    /// A cached body, until it is read.
+    Cached(Option<Vec<u8>>),
+    /// A body from the server, and what has been read of it, until it is stored in the cache.
+    /// Responses that are not cached keep nothing.
+    Web(ResponseBody, Option<Vec<u8>>),
+    Local(File),
+}
+
+#[cfg(feature = "net")]
+/// `Opened` is a document whose body is still to be read, a chunk at a time.
+#[derive(Debug)]
+pub struct Opened {
+    /// The URL that was asked for, which the document is cached as.
+    url: String,
+    pub base_url: String,
+    pub content_type: Option<String>,
+    body: Body,
+    /// Whether the server said the cached body has not changed.
+    not_modified: bool,
+    /// The turn to fetch from the host, held until the body has been read.
+    slot: Option<Slot>,
+}
+
+#[cfg(feature = "net")]
+impl Opened {
+    /// Returns whether the body is being read from the server.
+    pub fn is_from_network(&self) -> bool {
+        matches!(self.body, Body::Web(..))
+    }
+
+    /// Returns the coding the body was sent in, once the first of it has been read.
+    pub fn coding(&self) -> Coding {
+        match &self.body {
+            Body::Web(response, _) => response.coding(),
+            _ => Coding::Identity,
+        }
+    }
+
+    /// Returns whether the body is from the cache.
+    pub fn is_cached(&self) -> bool {
+        matches!(self.body, Body::Cached(_))
+    }
+
+    /// Returns whether the body is from the cache because the server said it has not changed
+    /// since it was cached.
+    pub fn is_not_modified(&self) -> bool {
+        self.not_modified
+    }
+
+    /// Reads the next chunk of the body. A body from the server is cached once it has all been read.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        let chunk = self.read_chunk().await;
+        if !matches!(chunk, Ok(Some(_))) {
+            // the next fetch from the host need not wait for this one to be dropped
+            self.slot = None;
+        }
+        chunk
+    }
+
+    async fn read_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        match &mut self.body {
+            Body::Cached(body) => Ok(body.take()),
+            Body::Web(response, received) => {
+                let chunk = response.chunk().await?;
+                match (&chunk, received.as_mut()) {
+                    (Some(chunk), Some(received)) => received.extend_from_slice(chunk),
+                    (None, Some(_)) => {
+                        let body = received.take().unwrap_or_default();
+                        request::store(&self.url, response.response().headers(), &body);
+                    }
+                    _ => {}
+                }
+                Ok(chunk)
+            }
+            Body::Local(file) => {
+                let mut chunk = vec![0; 16 * 1024];
+                let n = file.read(&mut chunk).await?;
+                chunk.truncate(n);
+                Ok((n > 0).then_some(chunk))
+            }
+        }
+    }
+
+    /// Reads the rest of the body.
+    pub async fn read_all(mut self) -> Result<Resource> {
+        let mut body = vec![];
+        while let Some(chunk) = self.chunk().await? {
+            body.extend(chunk);
+        }
+        Ok(Resource {
+            base_url: self.base_url,
+            body,
+            content_type: self.content_type,
+        })
+    }
+
+    /// Turns a response from the server into a download, leaving the body unread.
+    /// A download does not count against the fetches from its host.
+    pub fn into_download(self) -> Option<Download> {
+        match self.body {
+            Body::Web(response, _) => Some(Download::new(&self.url, response)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+/// Starts fetching `url` as a page, from the web, using the cache as `mode` says, if it has
+/// an http(s) scheme, or from the local file system otherwise, as `settings` say.
+pub async fn open(url: &str, mode: CacheMode, settings: &Settings) -> Result<Opened> {
+    open_with(url, mode, Priority::Document, settings).await
+}
+
+#[cfg(feature = "net")]
+/// Starts fetching `url` as `open` does, once it is its turn among the fetches from its host
+/// by `priority`. A fetch that is dropped while it waits is not made.
+pub async fn open_with(
+    url: &str,
+    mode: CacheMode,
+    priority: Priority,
+    settings: &Settings,
+) -> Result<Opened> {
+    if !is_web_url(url) {
+        return Ok(Opened {
+            url: url.to_string(),
+            base_url: url.to_string(),
+            content_type: guess_content_type(url).map(str::to_string),
+            body: Body::Local(File::open(url).await?),
+            not_modified: false,
+            slot: None,
+        });
+    }
+    if settings.filter.blocks(url) {
+        tracing::debug!(url, "blocked");
+        return Err(Error::Blocked(url.to_string()));
+    }
+    let slot = queue::acquire(url, priority).await;
+    let transport = &settings.transport;
+    let mut opened = match request::get(transport, url, mode).await? {
+        Fetched::Cached(entry) => from_entry(url, entry, false),
+        Fetched::NotModified(entry) => from_entry(url, entry, true),
+        Fetched::Network(response) => from_response(url, response, transport, Some(vec![])),
+    };
+    if opened.is_from_network() {
+        opened.slot = slot;
+    }
+    Ok(opened)
+}
+
+#[cfg(feature = "net")]
+/// Makes a document of `entry`, the cached response to a request for `url`.
+fn from_entry(url: &str, entry: Entry, not_modified: bool) -> Opened {
+    Opened {
+        url: url.to_string(),
+        base_url: url.to_string(),
+        content_type: entry.content_type().map(str::to_string),
+        body: Body::Cached(Some(entry.body)),
+        not_modified,
+        slot: None,
+    }
+}
+
+#[cfg(feature = "net")]
+/// Makes a document of `response`, the response to a request for `url` made with `transport`.
+/// It is cached once it has been read if `received` is `Some`.
+fn from_response(
+    url: &str,
+    response: Response,
+    transport: &Arc<Transport>,
+    received: Option<Vec<u8>>,
+) -> Opened {
+    Opened {
+        url: url.to_string(),
+        base_url: response.url().to_string(),
+        content_type: response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        body: Body::Web(ResponseBody::new(response, transport.clone()), received),
+        not_modified: false,
+        slot: None,
+    }
+}
+
+#[cfg(feature = "net")]
+/// Fetches all of `url` as `open_with` does.
+pub async fn load(
+    url: &str,
+    mode: CacheMode,
+    priority: Priority,
+    settings: &Settings,
+) -> Result<Resource> {
+    open_with(url, mode, priority, settings)
+        .await?
+        .read_all()
+        .await
+}
+
+#[cfg(feature = "net")]
+/// Submits `form` as `request::submit` does, and starts reading the response as `open` does.
+pub async fn submit(
+    form: &FormData,
+    method: Method,
+    action: &str,
+    base: &str,
+    settings: &Settings,
+) -> Result<Opened> {
+    let transport = &settings.transport;
+    let response = request::submit(transport, form, method, action, base).await?;
+    let url = response.url().to_string();
+    Ok(from_response(&url, response, transport, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{guess_content_type, normalize_url, url_from_input, ContentKind};
+
+    #[test]
+    fn test_content_kind() {
+        assert_eq!(ContentKind::of(None), ContentKind::Html);
+        assert_eq!(
+            ContentKind::of(Some("text/html; charset=utf-8")),
+            ContentKind::Html
+        );
+        assert_eq!(
+            ContentKind::of(Some("application/xhtml+xml")),
+            ContentKind::Html
+        );
+        assert_eq!(ContentKind::of(Some("Text/Plain")), ContentKind::Text);
+        assert_eq!(ContentKind::of(Some("text/css")), ContentKind::Text);
+        assert_eq!(ContentKind::of(Some("image/png")), ContentKind::Image);
+        assert_eq!(ContentKind::of(Some("image/tiff")), ContentKind::Other);
+        assert_eq!(ContentKind::of(Some("application/pdf")), ContentKind::Other);
+
+        assert_eq!(guess_content_type("notes/README.TXT"), Some("text/plain"));
+        assert_eq!(guess_content_type("a.jpeg"), Some("image/jpeg"));
+        assert_eq!(guess_content_type("index.html"), None);
+    }
+
+    #[test]
+    fn test_url_from_input() {
+        assert_eq!(url_from_input("https://a.com/b"), "https://a.com/b");
+        assert_eq!(
+            url_from_input(" example.com/a?q#f "),
+            "https://example.com/a?q#f"
+        );
+        assert_eq!(url_from_input("localhost:8080/"), "http://localhost:8080/");
+        assert_eq!(url_from_input("file:///tmp/a.html#top"), "/tmp/a.html#top");
+        assert_eq!(url_from_input("./page.html"), "./page.html");
+        assert_eq!(url_from_input("/no/such.html"), "/no/such.html");
+        assert_eq!(url_from_input("wev:messages"), "wev:messages");
+        assert_eq!(url_from_input("notes"), "notes");
+        assert_eq!(url_from_input("Example.COM"), "https://example.com/");
+        assert_eq!(url_from_input("HTTPS://A.com/%7e"), "https://a.com/~");
+        let dir = std::env::temp_dir();
+        let path = dir.to_string_lossy();
+        assert_eq!(url_from_input(&path), path);
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(
+            normalize_url("HTTP://Example.COM:80/a/./b/../c?q#f"),
+            "http://example.com/a/c?q#f"
+        );
+        assert_eq!(
+            normalize_url("https://a.com:443/%7euser/%e3%81%82%2f?x=%41%3d"),
+            "https://a.com/~user/%E3%81%82%2F?x=A%3D"
+        );
+        assert_eq!(normalize_url("https://a.com/a b"), "https://a.com/a%20b");
+        assert_eq!(normalize_url("https://a.com/100%"), "https://a.com/100%");
+        assert_eq!(
+            normalize_url("https://日本語.jp/"),
+            "https://xn--wgv71a119e.jp/"
+        );
+        assert_eq!(
+            normalize_url("https://a.com/x"),
+            normalize_url("https://A.com:443/y/../x")
+        );
+        assert_eq!(normalize_url("./a/../b.html"), "./a/../b.html");
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_load() {
+        use super::{load, Resource};
+        use crate::{cache::CacheMode, page::Settings, queue::Priority, request};
+
+        let path = std::env::temp_dir().join(format!("wev-loader-{}.html", std::process::id()));
+        let content = "<p>loaded</p>".repeat(4096);
+        std::fs::write(&path, &content).unwrap();
+        let url = path.to_string_lossy().into_owned();
+
+        let settings = Settings::default();
+        let resource = request::runtime()
+            .block_on(load(&url, CacheMode::Normal, Priority::Document, &settings))
+            .unwrap();
+        assert_eq!(
+            resource,
+            Resource {
+                base_url: url.clone(),
+                body: content.into_bytes(),
+                content_type: None,
+            }
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(request::runtime()
+            .block_on(load(&url, CacheMode::Normal, Priority::Document, &settings))
+            .is_err());
+    }
+}
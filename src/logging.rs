@@ -0,0 +1,317 @@
+use crate::error::{Error, Result};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Write as _},
+    io::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    span, Event, Level, Metadata, Subscriber,
+};
+
+/// `Output` is where a `Logger` writes what is as severe as `level` or more.
+struct Output {
+    level: Level,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+/// `SpanData` is a span that is still open: a phase like a request or a layout.
+#[derive(Debug)]
+struct SpanData {
+    name: &'static str,
+    level: Level,
+    /// Its fields, written out as ` name=value`.
+    fields: String,
+    parent: Option<u64>,
+    start: Instant,
+    /// How many handles to it there are. It closes when the last is dropped.
+    handles: usize,
+}
+
+thread_local! {
+    /// The spans entered on this thread, innermost last.
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(vec![]) };
+}
+
+/// `Logger` writes the events and spans of `tracing` as lines of text, each with the time
+/// since it started and the spans it happened in. When a span closes, how long it was open
+/// is written, so that the time each phase of loading a page takes can be seen.
+///
+/// ```text
+///   0.412s  INFO request{method=GET url=https://example.com/}: 200 OK
+///   0.415s DEBUG parse{url=https://example.com/}: done in 2.1ms
+/// ```
+pub struct Logger {
+    outputs: Vec<Output>,
+    spans: Mutex<HashMap<u64, SpanData>>,
+    next_id: AtomicU64,
+    start: Instant,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger {
+            outputs: vec![],
+            spans: Mutex::default(),
+            next_id: AtomicU64::new(1),
+            start: Instant::now(),
+        }
+    }
+}
+
+/// `Fields` writes the fields of an event or a span, apart from its message.
+struct Fields<'a> {
+    message: Option<String>,
+    out: &'a mut String,
+}
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            let _ = write!(self.out, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            let _ = write!(self.out, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Returns how long `duration` is, in the unit that suits it.
+fn format_duration(duration: Duration) -> String {
+    if duration < Duration::from_millis(1) {
+        format!("{}µs", duration.as_micros())
+    } else if duration < Duration::from_secs(1) {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+impl Logger {
+    pub fn new() -> Logger {
+        Logger::default()
+    }
+
+    /// Writes what is as severe as `level` or more to `writer`, like standard error or a file.
+    pub fn with_output(mut self, level: Level, writer: impl Write + Send + 'static) -> Logger {
+        self.outputs.push(Output {
+            level,
+            writer: Mutex::new(Box::new(writer)),
+        });
+        self
+    }
+
+    /// Makes this the logger of the whole program.
+    pub fn install(self) -> Result<()> {
+        tracing::subscriber::set_global_default(self)
+            .map_err(|_| Error::Config("a logger was already installed".into()))
+    }
+
+    /// Returns the spans `id` is in, outermost first, as `name{fields}:name{fields}`.
+    fn context(&self, mut id: Option<u64>) -> String {
+        let spans = self.spans.lock().unwrap();
+        let mut names = vec![];
+        while let Some(span) = id.and_then(|id| spans.get(&id)) {
+            names.push(format!("{}{{{}}}", span.name, span.fields.trim_start()));
+            id = span.parent;
+        }
+        names.reverse();
+        names.join(":")
+    }
+
+    /// Writes `message`, as severe as `level`, in the spans of `context`.
+    fn write(&self, level: Level, context: Option<u64>, message: &str) {
+        let context = self.context(context);
+        let line = format!(
+            "{:>8.3}s {:>5} {}{}{}\n",
+            self.start.elapsed().as_secs_f64(),
+            level,
+            context,
+            if context.is_empty() { "" } else { ": " },
+            message
+        );
+        for output in self.outputs.iter().filter(|output| level <= output.level) {
+            // a log that cannot be written is not worth failing for
+            let _ = output.writer.lock().unwrap().write_all(line.as_bytes());
+        }
+    }
+
+    fn current() -> Option<u64> {
+        ENTERED.with(|entered| entered.borrow().last().copied())
+    }
+}
+
+impl Subscriber for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.outputs
+            .iter()
+            .any(|output| metadata.level() <= &output.level)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        let level = self.outputs.iter().map(|output| output.level).max();
+        Some(level.map_or(LevelFilter::OFF, LevelFilter::from_level))
+    }
+
+    fn new_span(&self, attributes: &span::Attributes<'_>) -> span::Id {
+        let mut fields = String::new();
+        attributes.record(&mut Fields {
+            message: None,
+            out: &mut fields,
+        });
+        let parent = if attributes.is_contextual() {
+            Logger::current()
+        } else {
+            attributes.parent().map(span::Id::into_u64)
+        };
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.spans.lock().unwrap().insert(
+            id,
+            SpanData {
+                name: attributes.metadata().name(),
+                level: *attributes.metadata().level(),
+                fields,
+                parent,
+                start: Instant::now(),
+                handles: 1,
+            },
+        );
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            values.record(&mut Fields {
+                message: None,
+                out: &mut span.fields,
+            });
+        }
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = String::new();
+        let mut visitor = Fields {
+            message: None,
+            out: &mut fields,
+        };
+        event.record(&mut visitor);
+        let message = visitor.message.unwrap_or_default() + &fields;
+        let context = if event.is_contextual() {
+            Logger::current()
+        } else {
+            event.parent().map(span::Id::into_u64)
+        };
+        self.write(*event.metadata().level(), context, message.trim_start());
+    }
+
+    fn enter(&self, span: &span::Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &span::Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(i) = entered.iter().rposition(|&id| id == span.into_u64()) {
+                entered.remove(i);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &span::Id) -> span::Id {
+        if let Some(span) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            span.handles += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: span::Id) -> bool {
+        let id = span.into_u64();
+        let (level, start) = {
+            let mut spans = self.spans.lock().unwrap();
+            let Some(span) = spans.get_mut(&id) else {
+                return false;
+            };
+            span.handles -= 1;
+            if span.handles > 0 {
+                return false;
+            }
+            (span.level, span.start)
+        };
+        let message = format!("done in {}", format_duration(start.elapsed()));
+        self.write(level, Some(id), &message);
+        self.spans.lock().unwrap().remove(&id);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, Logger};
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tracing::Level;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_logger() {
+        let (info, debug) = (Buffer::default(), Buffer::default());
+        let logger = Logger::new()
+            .with_output(Level::INFO, info.clone())
+            .with_output(Level::DEBUG, debug.clone());
+        tracing::subscriber::with_default(logger, || {
+            let request = tracing::info_span!("request", url = "https://a.test/");
+            let _request = request.enter();
+            tracing::info!(status = 200, "answered");
+            tracing::debug_span!("parse").in_scope(|| tracing::warn!("no <body>"));
+            tracing::trace!("left out");
+        });
+        let info = String::from_utf8(info.0.lock().unwrap().clone()).unwrap();
+        let lines = info.lines().map(|line| &line[16..]).collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "request{url=https://a.test/}: answered status=200"
+        );
+        assert_eq!(lines[1], "request{url=https://a.test/}:parse{}: no <body>");
+        assert!(lines[2].starts_with("request{url=https://a.test/}: done in "));
+        assert_eq!(lines.len(), 3);
+
+        let debug = String::from_utf8(debug.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(debug.lines().count(), 4);
+        assert!(debug.lines().nth(2).unwrap().contains("parse{}: done in "));
+
+        assert_eq!(format_duration(Duration::from_micros(20)), "20µs");
+        assert_eq!(format_duration(Duration::from_micros(2500)), "2.5ms");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.50s");
+    }
+}
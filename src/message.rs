@@ -0,0 +1,125 @@
+use crate::{
+    cssom::Stylesheet,
+    dom::{Element, Node, Text},
+    page::Page,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How long a message stays on the status line, unless a key press dismisses it first.
+pub const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The URL of the page listing the messages.
+pub const MESSAGES_URL: &str = "wev:messages";
+
+/// How many messages are kept for `:messages`.
+const HISTORY_CAPACITY: usize = 200;
+
+/// `Messages` is the message shown on the status line, and the messages shown before.
+#[derive(Debug, Default)]
+pub struct Messages {
+    history: VecDeque<String>,
+    /// When the last message was shown, while it is still on the status line.
+    shown_since: Option<Instant>,
+}
+
+impl Messages {
+    /// Shows `text` on the status line and keeps it in the history.
+    pub fn push(&mut self, text: impl Into<String>) {
+        if self.history.len() == HISTORY_CAPACITY {
+            self.history.pop_front();
+        }
+        self.history.push_back(text.into());
+        self.shown_since = Some(Instant::now());
+    }
+
+    /// Returns the message on the status line, if any.
+    pub fn current(&self) -> Option<&str> {
+        self.shown_since?;
+        self.history.back().map(String::as_str)
+    }
+
+    /// Takes the message off the status line. It stays in the history.
+    pub fn dismiss(&mut self) {
+        self.shown_since = None;
+    }
+
+    /// Takes the message off the status line once it has been shown for `MESSAGE_TIMEOUT`.
+    /// Returns whether it did.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let expired = self
+            .shown_since
+            .is_some_and(|since| now.duration_since(since) >= MESSAGE_TIMEOUT);
+        if expired {
+            self.dismiss();
+        }
+        expired
+    }
+
+    /// Builds a page listing the messages, the newest last.
+    pub fn to_page(&self) -> Page {
+        let paragraph =
+            |text: String| Element::new("p".into(), Default::default(), vec![Text::new(text)]);
+        let entries = if self.history.is_empty() {
+            vec![paragraph("No messages yet.".into())]
+        } else {
+            self.history.iter().cloned().map(paragraph).collect()
+        };
+        let heading = Element::new(
+            "p".into(),
+            Default::default(),
+            vec![Element::new(
+                "b".into(),
+                Default::default(),
+                vec![Text::new("Messages".into())],
+            )],
+        );
+        let dom = Element::new(
+            "".into(),
+            Default::default(),
+            std::iter::once(heading)
+                .chain(entries)
+                .collect::<Vec<Box<Node>>>(),
+        );
+
+        Page::new(MESSAGES_URL, dom, Stylesheet::new(vec![]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Messages, HISTORY_CAPACITY, MESSAGE_TIMEOUT};
+    use std::time::Instant;
+
+    #[test]
+    fn test_messages() {
+        let mut messages = Messages::default();
+        assert_eq!(messages.current(), None);
+        assert_eq!(
+            messages.to_page().dom().text_content(),
+            "MessagesNo messages yet."
+        );
+
+        messages.push("saved");
+        assert_eq!(messages.current(), Some("saved"));
+        assert!(!messages.expire(Instant::now()));
+        assert!(messages.expire(Instant::now() + MESSAGE_TIMEOUT));
+        assert_eq!(messages.current(), None);
+
+        messages.push("failed");
+        messages.dismiss();
+        assert_eq!(messages.current(), None);
+        assert_eq!(
+            messages.to_page().dom().text_content(),
+            "Messagessavedfailed"
+        );
+
+        for i in 0..HISTORY_CAPACITY {
+            messages.push(i.to_string());
+        }
+        assert_eq!(messages.history.len(), HISTORY_CAPACITY);
+        assert_eq!(messages.history.front().map(String::as_str), Some("0"));
+    }
+}
@@ -0,0 +1,1086 @@
+#[cfg(feature = "render")]
+use crate::export::{export, Format};
+#[cfg(feature = "net")]
+use crate::{
+    cache::CacheMode,
+    charset::StreamDecoder,
+    config::Features,
+    download::Download,
+    form::{Method, Submission},
+    queue::Priority,
+    request::{self, Transport},
+    resources,
+};
+use crate::{
+    charset, css,
+    cssom::Stylesheet,
+    dom::{Document, Element, Index, Node, Text},
+    error::{Error, Result},
+    filter::Filter,
+    html::TreeBuilder,
+    layout::LayoutContext,
+    loader::{self, is_web_url, ContentKind, Resource},
+};
+#[cfg(feature = "images")]
+use image::DynamicImage;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+#[cfg(feature = "net")]
+use std::{
+    future::Future,
+    io,
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    time::{Duration, Instant},
+};
+#[cfg(feature = "net")]
+use tokio::task::JoinHandle;
+#[cfg(feature = "net")]
+use tracing::Instrument;
+use url::Url;
+
+/// `Image` is a decoded image.
+#[cfg(feature = "images")]
+pub type Image = DynamicImage;
+
+/// `Image` is a decoded image, of which there are none without the `images` feature.
+#[cfg(not(feature = "images"))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Image {}
+
+/// Returns the width and height of `image` in pixels.
+fn image_size(image: &Image) -> (u32, u32) {
+    #[cfg(feature = "images")]
+    return (image.width(), image.height());
+    #[cfg(not(feature = "images"))]
+    match *image {}
+}
+
+/// `Page` is a loaded document: where it came from, its DOM tree, its stylesheet
+/// and the images it refers to.
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub url: String,
+    /// Its tree, which is changed through `dom_mut` so that its index is made again.
+    dom: Box<Node>,
+    /// The index of `dom`, made when it is first needed after `dom` changes.
+    index: OnceLock<Index>,
+    pub stylesheet: Stylesheet,
+    /// Decoded images keyed by the `src` attribute that refers to them.
+    pub images: HashMap<String, Image>,
+    /// Whether the page is an error page shown in place of one that failed to load.
+    pub failed: bool,
+}
+
+impl PartialEq for Page {
+    fn eq(&self, other: &Page) -> bool {
+        self.url == other.url
+            && self.dom == other.dom
+            && self.stylesheet == other.stylesheet
+            && self.images == other.images
+            && self.failed == other.failed
+    }
+}
+
+/// `Link` is a link of a page, `<a href>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Its text, with runs of whitespace collapsed to a space.
+    pub text: String,
+    /// Its `href`, resolved against the URL of the page.
+    pub url: String,
+}
+
+/// `Settings` is what pages are loaded and made with, as a config says. Cloning it is cheap,
+/// and the clones share the transport, so that its connections and sign-ins are reused.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// What requests are made with.
+    #[cfg(feature = "net")]
+    pub transport: Arc<Transport>,
+    /// Which subresources are fetched.
+    #[cfg(feature = "net")]
+    pub features: Features,
+    /// What every request and page goes through.
+    pub filter: Arc<Filter>,
+    /// The user's rules, which every page is styled with under its own.
+    pub user_stylesheet: Arc<Stylesheet>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            #[cfg(feature = "net")]
+            transport: Arc::default(),
+            #[cfg(feature = "net")]
+            features: Features::default(),
+            filter: Arc::default(),
+            user_stylesheet: Arc::new(Stylesheet::new(vec![])),
+        }
+    }
+}
+
+impl Settings {
+    pub fn with_filter(mut self, filter: Filter) -> Settings {
+        self.filter = Arc::new(filter);
+        self
+    }
+
+    pub fn with_user_stylesheet(mut self, stylesheet: Stylesheet) -> Settings {
+        self.user_stylesheet = Arc::new(stylesheet);
+        self
+    }
+}
+
+/// Splits `url` into the part before `#` and the fragment after it, if any.
+pub fn split_fragment(url: &str) -> (&str, Option<&str>) {
+    match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    }
+}
+
+/// Puts `fragment` back after `url`, undoing `split_fragment`.
+fn join_fragment(url: &str, fragment: Option<&str>) -> String {
+    match fragment {
+        Some(fragment) => format!("{}#{}", url, fragment),
+        None => url.to_string(),
+    }
+}
+
+impl Page {
+    /// Makes a page of `dom` styled with `stylesheet`, without images.
+    pub fn new(url: &str, dom: Box<Node>, stylesheet: Stylesheet) -> Page {
+        Page {
+            url: url.to_string(),
+            dom,
+            index: OnceLock::new(),
+            stylesheet,
+            images: HashMap::new(),
+            failed: false,
+        }
+    }
+
+    pub fn dom(&self) -> &Node {
+        &self.dom
+    }
+
+    /// Returns the tree to change, after which it is indexed again when next needed.
+    pub fn dom_mut(&mut self) -> &mut Node {
+        self.index.take();
+        &mut self.dom
+    }
+
+    /// Returns the document of the page's tree, with the index kept for it.
+    pub fn document(&self) -> Document<'_> {
+        let index = self.index.get_or_init(|| Index::new(&self.dom));
+        Document::with_index(&self.dom, index)
+    }
+
+    #[cfg(feature = "net")]
+    /// Fetches `url` from the web if it has an http(s) scheme, or from the local file system otherwise.
+    /// The page keeps the fragment of `url`, which is not part of what is fetched.
+    pub fn load(url: &str, settings: &Settings) -> Result<Page> {
+        request::runtime().block_on(Page::fetch(url, settings))
+    }
+
+    #[cfg(feature = "net")]
+    /// Fetches `url` as `load` does, on the network runtime.
+    pub async fn fetch(url: &str, settings: &Settings) -> Result<Page> {
+        let (location, fragment) = split_fragment(url);
+        async {
+            let mode = CacheMode::Normal;
+            let resource = loader::load(location, mode, Priority::Document, settings).await?;
+            let mut page = Page::from_resource(&resource, fragment, settings)?;
+            page.load_subresources(mode, settings).await;
+            Ok(page)
+        }
+        .instrument(tracing::info_span!("page", url))
+        .await
+    }
+
+    /// Builds a page explaining why `url` failed to load. Its URL is `url`, so reloading it retries.
+    pub fn error(url: &str, error: &Error) -> Page {
+        let paragraph =
+            |children: Vec<Box<Node>>| Element::new("p".into(), Default::default(), children);
+        let text = |text: String| Text::new(text);
+        let mut lines = vec![
+            paragraph(vec![Element::new(
+                "b".into(),
+                vec![("class".into(), "error".into())].into_iter().collect(),
+                vec![text("Failed to load this page".into())],
+            )]),
+            paragraph(vec![text(url.to_string())]),
+        ];
+        #[cfg(feature = "net")]
+        if let Some(status) = error.status() {
+            lines.push(paragraph(vec![text(format!("Status: {}", status))]));
+        }
+        lines.push(paragraph(vec![text(error.to_string())]));
+        lines.push(paragraph(vec![text(
+            "Press `r` to retry or `H` to go back.".into(),
+        )]));
+
+        let stylesheet = css::stylesheet(".error { color: #cc0000; }")
+            .expect("the stylesheet of error pages is valid");
+        Page {
+            failed: true,
+            ..Page::new(
+                url,
+                Element::new("".into(), Default::default(), lines),
+                stylesheet,
+            )
+        }
+    }
+
+    /// Resolves `reference`, such as an `<img src>`, against the URL of this page.
+    /// References on a local page are paths relative to its directory.
+    pub fn resolve(&self, reference: &str) -> String {
+        if reference.starts_with('#') {
+            return format!("{}{}", split_fragment(&self.url).0, reference);
+        }
+        if let Some(base) = Url::parse(&self.url).ok().filter(|_| is_web_url(&self.url)) {
+            return base
+                .join(reference)
+                .map(|url| loader::normalize_url(url.as_str()))
+                .unwrap_or_else(|_| reference.to_string());
+        }
+        if Url::parse(reference).is_ok() || Path::new(reference).is_absolute() {
+            return reference.to_string();
+        }
+        match Path::new(&self.url).parent() {
+            Some(dir) => dir.join(reference).to_string_lossy().into_owned(),
+            None => reference.to_string(),
+        }
+    }
+
+    #[cfg(feature = "net")]
+    /// Fetches the stylesheets and images the page refers to, unless their features are
+    /// turned off, and adds them to it. Linked stylesheets come after the user's rules and before the page's `<style>`s
+    /// in the cascade.
+    pub async fn load_subresources(&mut self, mode: CacheMode, settings: &Settings) {
+        let features = settings.features;
+        let mut references = resources::collect(&self.dom);
+        references.images.retain(|src| {
+            cfg!(feature = "images") && features.images && !self.images.contains_key(src)
+        });
+        if !features.stylesheets {
+            references.stylesheets.clear();
+        }
+        let resolve = |reference: &str| self.resolve(reference);
+        let subresources = resources::fetch(&references, resolve, mode, settings).await;
+        self.images.extend(subresources.images);
+        let rules = subresources
+            .stylesheets
+            .into_iter()
+            .flat_map(|stylesheet| stylesheet.rules)
+            .collect::<Vec<_>>();
+        let user = settings.user_stylesheet.rules.len();
+        let user = user.min(self.stylesheet.rules.len());
+        self.stylesheet.rules.splice(user..user, rules);
+    }
+
+    /// Returns the fragment of the page's URL, which names the element to scroll to.
+    pub fn fragment(&self) -> Option<&str> {
+        split_fragment(&self.url).1.filter(|f| !f.is_empty())
+    }
+
+    /// What layout needs to know about this page's images.
+    pub fn layout_context(&self, max_image_height: Option<u16>) -> LayoutContext {
+        LayoutContext {
+            image_sizes: self
+                .images
+                .iter()
+                .map(|(src, image)| (src.clone(), image_size(image)))
+                .collect(),
+            max_image_height,
+        }
+    }
+
+    /// Returns the text of the document's `<title>` element, if it has a non-empty one.
+    pub fn title(&self) -> Option<String> {
+        self.document().title()
+    }
+
+    /// Returns the links of the page in document order.
+    pub fn links(&self) -> Vec<Link> {
+        self.document()
+            .query("a[href]")
+            .into_iter()
+            .filter_map(|node| {
+                let href = node.element()?.attributes.get("href")?;
+                Some(Link {
+                    text: node
+                        .text_content()
+                        .split_ascii_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    url: self.resolve(href),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the elements of the page that `selector`, a selector list like `div.note > a`,
+    /// matches, in document order. A selector that cannot be parsed matches nothing.
+    pub fn query(&self, selector: &str) -> Vec<&Node> {
+        self.document().query(selector)
+    }
+
+    /// Renders the whole page as plain text, `width` columns wide.
+    #[cfg(feature = "render")]
+    pub fn render_to_string(&self, width: u16) -> String {
+        export(self, width, Format::Plain)
+    }
+
+    /// Builds a page of `resource` as its content type says, keeping `fragment` of the URL
+    /// it was asked for. Text is decoded in the charset it declares.
+    pub fn from_resource(
+        resource: &Resource,
+        fragment: Option<&str>,
+        settings: &Settings,
+    ) -> Result<Page> {
+        let url = join_fragment(&resource.base_url, fragment);
+        let content_type = resource.content_type.as_deref();
+        let text = || charset::decode(&resource.body, content_type);
+        match ContentKind::of(content_type) {
+            ContentKind::Html => Page::from_html(&url, &text(), settings),
+            ContentKind::Text => Ok(Page::from_text(&url, &text())),
+            #[cfg(feature = "images")]
+            ContentKind::Image => image::load_from_memory(&resource.body)
+                .map(|image| Page::from_image(&url, image))
+                .map_err(|e| Error::Decode(e.to_string())),
+            #[cfg(not(feature = "images"))]
+            ContentKind::Image => Err(Error::Unsupported(
+                content_type.unwrap_or_default().to_string(),
+            )),
+            ContentKind::Other => Err(Error::Unsupported(
+                content_type.unwrap_or_default().to_string(),
+            )),
+        }
+    }
+
+    /// Builds a page showing `text`, a plain text document, as it is: line by line,
+    /// without wrapping.
+    pub fn from_text(url: &str, text: &str) -> Page {
+        let lines = text
+            .lines()
+            .map(|line| {
+                // an empty block would take no line at all
+                let line = if line.is_empty() { " " } else { line };
+                Element::new(
+                    "div".into(),
+                    Default::default(),
+                    vec![Text::new(line.replace('\t', "    "))],
+                )
+            })
+            .collect();
+        let dom = Element::new(
+            "".into(),
+            Default::default(),
+            vec![Element::new("pre".into(), Default::default(), lines)],
+        );
+        Page::new(url, dom, Stylesheet::new(vec![]))
+    }
+
+    /// Builds a page showing `image`, an image from `url`, alone.
+    #[cfg(feature = "images")]
+    pub fn from_image(url: &str, image: DynamicImage) -> Page {
+        let src = split_fragment(url).0.to_string();
+        let img = Element::new(
+            "img".into(),
+            vec![("src".into(), src.clone())].into_iter().collect(),
+            vec![],
+        );
+        let dom = Element::new("".into(), Default::default(), vec![img]);
+        Page {
+            images: HashMap::from([(src, image)]),
+            ..Page::new(url, dom, Stylesheet::new(vec![]))
+        }
+    }
+
+    /// Builds a page of `content`, HTML from `url`, filtered and styled as `settings` say.
+    pub fn from_html(url: &str, content: &str, settings: &Settings) -> Result<Page> {
+        let mut builder = TreeBuilder::folding_line_breaks();
+        builder.push(content);
+        let dom = Node::document(builder.finish());
+        Ok(Page::from_document(url, dom, settings))
+    }
+
+    /// Builds a page of `dom`, the document node of HTML.
+    fn from_document(url: &str, mut dom: Box<Node>, settings: &Settings) -> Page {
+        settings.filter.apply(&mut dom);
+        #[cfg(feature = "scripts")]
+        crate::script::run_scripts(&mut dom);
+
+        // the page is indexed once its filter and scripts are done with the tree, and keeps
+        // the index for its `<style>`s and whatever else asks for its document
+        let mut page = Page::new(url, dom, Stylesheet::new(vec![]));
+        // the `<style>`s are applied in document order after the user's rules, leaving out
+        // those that cannot be parsed, as browsers do
+        let page_rules = page
+            .document()
+            .stylesheets()
+            .into_iter()
+            .filter_map(|css| {
+                css::stylesheet(&css)
+                    .inspect_err(|e| tracing::warn!("skipping a <style>: {}", e))
+                    .ok()
+            })
+            .flat_map(|stylesheet| stylesheet.rules);
+        let user_rules = settings.user_stylesheet.rules.iter().cloned();
+        let rules = user_rules.chain(page_rules).collect();
+        page.stylesheet = Stylesheet::new(rules);
+        page
+    }
+}
+
+#[cfg(feature = "net")]
+/// `Progress` is what has become of a page being loaded since it was last polled.
+#[derive(Debug)]
+pub enum Progress {
+    /// Nothing new can be shown yet.
+    Pending,
+    /// More of the page arrived. The page holds everything received so far.
+    Partial(Page),
+    /// The server said the page has not changed since it was cached, so a page shown from it
+    /// can be kept. The page is still loaded, in case it cannot.
+    NotModified,
+    /// The page finished loading, or failed to.
+    Done(Result<Page>),
+    /// The response is not a page, and is to be saved instead.
+    Download(Download),
+}
+
+#[cfg(feature = "net")]
+/// `Chunk` is what the task fetching a page sends at a time.
+enum Chunk {
+    /// The page is the cached one, which the server said has not changed.
+    NotModified,
+    /// A page of what has arrived so far.
+    Partial(Page),
+    /// The response turned out not to be a page, so nothing more is read from it.
+    Download(Download),
+    /// The whole page, with its subresources.
+    Page(Page),
+}
+
+#[cfg(feature = "net")]
+/// How long a partial page is kept before another is built, unless its text has doubled.
+const PARTIAL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[cfg(feature = "net")]
+/// `Streamed` is the content of a page of HTML or text as it arrives, decoded and built as far
+/// as it can be, for partial pages to be made of it.
+struct Streamed {
+    url: String,
+    settings: Settings,
+    decoder: StreamDecoder,
+    /// Builds the nodes of the content as it is decoded, if it is HTML.
+    builder: Option<TreeBuilder>,
+    /// The content decoded so far, if it is plain text.
+    text: String,
+    /// How much of the content has been decoded.
+    decoded: usize,
+    /// How much had been decoded when the last partial page was built, and when that was.
+    shown: (usize, Instant),
+}
+
+#[cfg(feature = "net")]
+impl Streamed {
+    /// Starts on the content of the page at `url`, if `content_type` is HTML or text. Half an
+    /// image is not shown, so it is not decoded either.
+    fn new(url: &str, content_type: Option<&str>, settings: &Settings) -> Option<Streamed> {
+        let builder = match ContentKind::of(content_type) {
+            ContentKind::Html => Some(TreeBuilder::folding_line_breaks()),
+            ContentKind::Text => None,
+            ContentKind::Image | ContentKind::Other => return None,
+        };
+        Some(Streamed {
+            url: url.to_string(),
+            settings: settings.clone(),
+            decoder: StreamDecoder::new(content_type),
+            builder,
+            text: String::new(),
+            decoded: 0,
+            shown: (0, Instant::now()),
+        })
+    }
+
+    /// Takes in `bytes`, the next part of the content.
+    fn push(&mut self, bytes: &[u8]) {
+        let text = self.decoder.push(bytes);
+        self.take(&text);
+    }
+
+    fn take(&mut self, text: &str) {
+        self.decoded += text.len();
+        match &mut self.builder {
+            Some(builder) => builder.push(text),
+            None => self.text += text,
+        }
+    }
+
+    /// Builds a page of what has arrived, if there is enough more of it since the last one.
+    fn partial(&mut self) -> Option<Page> {
+        // each partial page is built of everything so far, so another is built only once the
+        // content has doubled, or a while after, which keeps the building in proportion to it
+        let (shown, shown_at) = self.shown;
+        let is_due = self.decoded >= shown * 2 || shown_at.elapsed() >= PARTIAL_INTERVAL;
+        if self.decoded == shown || !is_due {
+            return None;
+        }
+        self.shown = (self.decoded, Instant::now());
+        Some(match &self.builder {
+            Some(builder) => {
+                let dom = Node::document(builder.snapshot());
+                Page::from_document(&self.url, dom, &self.settings)
+            }
+            None => Page::from_text(&self.url, &self.text),
+        })
+    }
+
+    /// Builds the page of the whole content, as it is at `url`.
+    fn finish(mut self, url: &str) -> Page {
+        let rest = self.decoder.finish();
+        self.take(&rest);
+        match self.builder {
+            Some(builder) => {
+                Page::from_document(url, Node::document(builder.finish()), &self.settings)
+            }
+            None => Page::from_text(url, &self.text),
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+/// `Loading` is a page being fetched in the background, so that it can be shown while it arrives.
+/// The page is built on the network runtime as it arrives, so only whole pages are polled.
+#[derive(Debug)]
+pub struct Loading {
+    pub url: String,
+    chunks: Receiver<Result<Chunk>>,
+    /// Whether a partial page has been put into history, so that later ones replace it.
+    pub in_history: bool,
+    /// The DOM tree of the page a reload replaces and the path of what was focused on it,
+    /// to be found again on the new page.
+    pub replaced: Option<(Box<Node>, Vec<usize>)>,
+    task: JoinHandle<()>,
+}
+
+#[cfg(feature = "net")]
+/// A load that is abandoned stops fetching right away.
+impl Drop for Loading {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(feature = "net")]
+impl Loading {
+    /// Starts fetching `url`, and then the subresources of the page, on the network runtime.
+    pub fn start(url: &str, settings: &Settings) -> Loading {
+        Loading::start_with(url, CacheMode::Normal, settings)
+    }
+
+    /// Starts fetching `url` as `start` does, using the cache as `mode` says.
+    pub fn start_with(url: &str, mode: CacheMode, settings: &Settings) -> Loading {
+        let page_url = url.to_string();
+        Loading::spawn(url, mode, settings, move |settings, sender| async move {
+            stream(&page_url, mode, &settings, &sender).await
+        })
+    }
+
+    /// Starts submitting `submission`, a form of `page`, and loading the response as `start`
+    /// does. Forms on local pages can only be submitted with GET, which loads the action
+    /// without a query.
+    pub fn submit(page: &Page, submission: &Submission, settings: &Settings) -> Loading {
+        let resolved = match submission.action.is_empty() {
+            true => page.url.clone(),
+            false => page.resolve(&submission.action),
+        };
+        if !is_web_url(&resolved) {
+            let action = resolved.split('?').next().unwrap_or_default();
+            return match submission.method {
+                Method::Get => Loading::start(action, settings),
+                Method::Post => Loading::spawn(action, CacheMode::Normal, settings, |_, _| async {
+                    let unsupported = io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "forms on local pages cannot be posted",
+                    );
+                    Err(unsupported.into())
+                }),
+            };
+        }
+        let (submission, base) = (submission.clone(), page.url.clone());
+        let mode = CacheMode::Normal;
+        Loading::spawn(
+            &resolved,
+            mode,
+            settings,
+            move |settings, sender| async move {
+                let Submission {
+                    method,
+                    action,
+                    data,
+                } = submission;
+                let opened = loader::submit(&data, method, &action, &base, &settings).await?;
+                let url = opened.base_url.clone();
+                stream_opened(opened, &url, None, &settings, &sender).await
+            },
+        )
+    }
+
+    /// Starts `fetch` of the page at `url` on the network runtime, and then the subresources of
+    /// the page it returns, using the cache as `mode` says. `fetch` is given `settings` and
+    /// where to send what becomes of the page while it arrives.
+    fn spawn<F, Fetch>(url: &str, mode: CacheMode, settings: &Settings, fetch: F) -> Loading
+    where
+        F: FnOnce(Settings, Sender<Result<Chunk>>) -> Fetch,
+        Fetch: Future<Output = Result<Option<Page>>> + Send + 'static,
+    {
+        let (sender, chunks) = mpsc::channel();
+        let fetch = fetch(settings.clone(), sender.clone());
+        let settings = settings.clone();
+        let span = tracing::info_span!("page", url);
+        let task = request::runtime().spawn(
+            async move {
+                let page = match fetch.await {
+                    Ok(Some(mut page)) => {
+                        page.load_subresources(mode, &settings).await;
+                        Ok(Chunk::Page(page))
+                    }
+                    Ok(None) => return,
+                    Err(e) => Err(e),
+                };
+                let _ = sender.send(page);
+            }
+            .instrument(span),
+        );
+        Loading {
+            url: url.to_string(),
+            chunks,
+            in_history: false,
+            replaced: None,
+            task,
+        }
+    }
+
+    /// Takes in what became of the page since the last poll. Of the partial pages built
+    /// since, only the last is shown.
+    pub fn poll(&mut self) -> Progress {
+        let mut partial = None;
+        loop {
+            match self.chunks.try_recv() {
+                Ok(Ok(Chunk::NotModified)) => return Progress::NotModified,
+                Ok(Ok(Chunk::Partial(page))) => partial = Some(page),
+                Ok(Ok(Chunk::Download(download))) => return Progress::Download(download),
+                Ok(Ok(Chunk::Page(page))) => return Progress::Done(Ok(page)),
+                Ok(Err(e)) => return Progress::Done(Err(e)),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    let stopped = io::Error::other("the page stopped loading");
+                    return Progress::Done(Err(stopped.into()));
+                }
+            }
+        }
+        partial.map_or(Progress::Pending, Progress::Partial)
+    }
+}
+
+#[cfg(feature = "net")]
+/// Fetches `url`, sending partial pages of it to `sender` as it arrives, and returns the
+/// page of all of it, without its subresources. Returns `None` instead if it is not a page,
+/// or nobody waits for it anymore.
+async fn stream(
+    url: &str,
+    mode: CacheMode,
+    settings: &Settings,
+    sender: &Sender<Result<Chunk>>,
+) -> Result<Option<Page>> {
+    let (location, fragment) = split_fragment(url);
+    let mut opened = loader::open(location, mode, settings).await?;
+    let kind = |opened: &loader::Opened| ContentKind::of(opened.content_type.as_deref());
+    if opened.is_cached() && kind(&opened) == ContentKind::Other {
+        // a file cached as a subresource is fetched again to be saved
+        opened = loader::open(location, CacheMode::Reload, settings).await?;
+    }
+    if opened.is_from_network() && kind(&opened) == ContentKind::Other {
+        if let Some(download) = opened.into_download() {
+            let _ = sender.send(Ok(Chunk::Download(download)));
+        }
+        return Ok(None);
+    }
+    if mode == CacheMode::Revalidate && opened.is_not_modified() {
+        let _ = sender.send(Ok(Chunk::NotModified));
+    }
+    stream_opened(opened, url, fragment, settings, sender).await
+}
+
+#[cfg(feature = "net")]
+/// Reads `opened`, the page at `url` without `fragment`, as `stream` does.
+async fn stream_opened(
+    mut opened: loader::Opened,
+    url: &str,
+    fragment: Option<&str>,
+    settings: &Settings,
+    sender: &Sender<Result<Chunk>>,
+) -> Result<Option<Page>> {
+    let content_type = opened.content_type.as_deref();
+    let mut streamed = Streamed::new(url, content_type, settings);
+    let mut content = vec![];
+    while let Some(chunk) = opened.chunk().await? {
+        let Some(streamed) = &mut streamed else {
+            content.extend_from_slice(&chunk);
+            continue;
+        };
+        streamed.push(&chunk);
+        if let Some(page) = streamed.partial() {
+            if sender.send(Ok(Chunk::Partial(page))).is_err() {
+                return Ok(None);
+            }
+        }
+    }
+    let page = match streamed {
+        Some(streamed) => streamed.finish(&join_fragment(&opened.base_url, fragment)),
+        None => Page::from_resource(
+            &Resource {
+                base_url: opened.base_url,
+                body: content,
+                content_type: opened.content_type,
+            },
+            fragment,
+            settings,
+        )?,
+    };
+    Ok(Some(page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Link, Page, Resource, Settings};
+    #[cfg(feature = "net")]
+    use super::{Loading, Progress, Streamed};
+    #[cfg(feature = "render")]
+    use crate::export::{export, Format};
+    use crate::{
+        cssom::{CSSValue, Declaration, Rule, SimpleSelector, Stylesheet},
+        dom::NodeType,
+        error::Error,
+        filter::{Filter, Hide},
+    };
+    #[cfg(feature = "net")]
+    use crate::{
+        form::{FormData, Method, Submission},
+        request::RequestError,
+    };
+    use std::io;
+    #[cfg(feature = "net")]
+    use std::thread;
+
+    #[test]
+    fn test_from_html() {
+        let page = Page::from_html(
+            "about:test",
+            "<html>\n<head><style>p { color: red; }</style></head>\n<body><p>hi</p></body></html>",
+            &Settings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(page.url, "about:test");
+        assert_eq!(
+            page.stylesheet,
+            Stylesheet::new(vec![Rule {
+                selectors: vec![SimpleSelector::TypeSelector {
+                    tag_name: "p".into()
+                }
+                .into()],
+                declarations: vec![Declaration {
+                    name: "color".into(),
+                    value: CSSValue::Keyword("red".into())
+                }]
+            }])
+        );
+        assert!(matches!(
+            &page.dom().children[0].node_type,
+            NodeType::Element(e) if e.tag_name == "html"
+        ));
+
+        // a page is filtered and styled by the settings it is made with, and no others
+        let user = crate::css::stylesheet("a { color: blue; }").unwrap();
+        let settings = Settings::default()
+            .with_filter(Filter::new().with_rule(Hide::new(".ad").unwrap()))
+            .with_user_stylesheet(user.clone());
+        let html = r#"<p class="ad">ad</p><p>text</p><style>p { color: red; }</style>"#;
+        let page = Page::from_html("about:test", html, &settings).unwrap();
+        assert!(page.query(".ad").is_empty());
+        assert_eq!(page.stylesheet.rules.len(), 2);
+        assert_eq!(page.stylesheet.rules[0], user.rules[0]);
+        let page = Page::from_html("about:test", html, &Settings::default()).unwrap();
+        assert_eq!(page.query(".ad").len(), 1);
+        assert_eq!(page.stylesheet.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let page =
+            Page::from_html("https://example.com/a/b.html", "", &Settings::default()).unwrap();
+        assert_eq!(page.resolve("c.png"), "https://example.com/a/c.png");
+        assert_eq!(page.resolve("/c.png"), "https://example.com/c.png");
+        assert_eq!(
+            page.resolve("https://example.org/c.png"),
+            "https://example.org/c.png"
+        );
+
+        assert_eq!(page.resolve("#top"), "https://example.com/a/b.html#top");
+
+        let page = Page::from_html("docs/index.html#intro", "", &Settings::default()).unwrap();
+        assert_eq!(page.fragment(), Some("intro"));
+        assert_eq!(page.resolve("#usage"), "docs/index.html#usage");
+        assert_eq!(page.resolve("img/c.png"), "docs/img/c.png");
+        assert_eq!(page.resolve("/tmp/c.png"), "/tmp/c.png");
+    }
+
+    #[test]
+    fn test_error() {
+        let error = Error::Io(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        let page = Page::error("missing.html", &error);
+        assert_eq!(page.url, "missing.html");
+        assert!(page.failed);
+        let text = page.dom().text_content();
+        assert!(text.contains("Failed to load this page"));
+        assert!(text.contains("missing.html"));
+        assert!(text.contains("no such file"));
+        assert!(text.contains("retry"));
+
+        #[cfg(feature = "net")]
+        {
+            let error = Error::Network(RequestError::Status(reqwest::StatusCode::NOT_FOUND));
+            let text = Page::error("https://a.com/b", &error).dom().text_content();
+            assert!(text.contains("Status: 404 Not Found"));
+        }
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_streamed() {
+        // the charset is told from the first KB, which comes before anything is decoded
+        let html = format!(
+            "<!--{}--><ul><li>one\n<li>two</ul><p>café</p>",
+            " ".repeat(1024)
+        );
+        let (head, rest) = html.as_bytes().split_at(1031);
+        let mut streamed =
+            Streamed::new("https://a.com/#x", Some("text/html"), &Settings::default()).unwrap();
+        streamed.push(head);
+        streamed.push(&rest[..11]);
+        let partial = streamed.partial().unwrap();
+        assert_eq!(partial.url, "https://a.com/#x");
+        assert_eq!(partial.dom.text_content(), "one");
+        // a partial page is built again only once there is enough more
+        streamed.push(&rest[11..13]);
+        assert_eq!(streamed.partial(), None);
+        // a character split between parts is decoded once it is whole
+        streamed.push(&rest[13..rest.len() - 6]);
+        streamed.push(&rest[rest.len() - 6..]);
+        assert_eq!(
+            streamed.finish("https://a.com/#x"),
+            Page::from_html("https://a.com/#x", &html, &Settings::default()).unwrap()
+        );
+
+        let mut streamed =
+            Streamed::new("a.txt", Some("text/plain"), &Settings::default()).unwrap();
+        streamed.push(b"a\nb");
+        assert_eq!(streamed.finish("a.txt"), Page::from_text("a.txt", "a\nb"));
+        assert!(Streamed::new("a.png", Some("image/png"), &Settings::default()).is_none());
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_loading() {
+        let path = std::env::temp_dir().join(format!("wev-loading-{}.html", std::process::id()));
+        std::fs::write(&path, "<p>streamed</p>").unwrap();
+        let url = path.to_string_lossy().into_owned();
+
+        let mut loading = Loading::start(&url, &Settings::default());
+        let page = loop {
+            match loading.poll() {
+                Progress::Done(page) => break page.unwrap(),
+                Progress::Download(_) => panic!("a local file is always a page"),
+                Progress::NotModified => panic!("a local file is never revalidated"),
+                Progress::Pending | Progress::Partial(_) => thread::yield_now(),
+            }
+        };
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(page.url, url);
+        assert_eq!(page.dom().text_content(), "streamed");
+
+        let mut loading = Loading::start("/nonexistent/wev.html", &Settings::default());
+        loop {
+            match loading.poll() {
+                Progress::Done(page) => break assert!(page.is_err()),
+                _ => thread::yield_now(),
+            }
+        }
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_submit() {
+        let dir = std::env::temp_dir().join(format!("wev-submit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("search.html"), "<p>results</p>").unwrap();
+        let page = Page::from_html(
+            &dir.join("form.html").to_string_lossy(),
+            "",
+            &Settings::default(),
+        )
+        .unwrap();
+        let done = |mut loading: Loading| loop {
+            match loading.poll() {
+                Progress::Done(page) => break page,
+                _ => thread::yield_now(),
+            }
+        };
+
+        let mut submission = Submission {
+            method: Method::Get,
+            action: "search.html".into(),
+            data: FormData::default(),
+        };
+        submission.data.append("q", "wev");
+        let loading = Loading::submit(&page, &submission, &Settings::default());
+        assert_eq!(loading.url, dir.join("search.html").to_string_lossy());
+        assert_eq!(done(loading).unwrap().dom.text_content(), "results");
+        submission.method = Method::Post;
+        assert!(done(Loading::submit(&page, &submission, &Settings::default())).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(feature = "net", feature = "images"))]
+    #[test]
+    fn test_load_subresources() {
+        let dir = std::env::temp_dir().join(format!("wev-subresources-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.css"), "p { color: red; }").unwrap();
+        std::fs::write(dir.join("b.css"), "p { color: blue; }").unwrap();
+        image::DynamicImage::new_rgb8(2, 1)
+            .save(dir.join("c.png"))
+            .unwrap();
+        let html = r#"<link rel="stylesheet" href="a.css"><link rel="stylesheet" href="b.css"><link rel="icon" href="c.png"><style>div { color: green; }</style><img src="c.png"><img src="missing.png">"#;
+        std::fs::write(dir.join("page.html"), html).unwrap();
+
+        let page = Page::load(
+            &dir.join("page.html").to_string_lossy(),
+            &Settings::default(),
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let stylesheet =
+            crate::css::stylesheet("p { color: red; } p { color: blue; } div { color: green; }")
+                .unwrap();
+        assert_eq!(page.stylesheet, stylesheet);
+        assert_eq!(page.images.keys().collect::<Vec<_>>(), vec!["c.png"]);
+        assert_eq!(page.images["c.png"].width(), 2);
+    }
+
+    #[test]
+    fn test_from_resource() {
+        let resource = |body: &[u8], content_type: &str| Resource {
+            base_url: "https://a.com/f".into(),
+            body: body.to_vec(),
+            content_type: Some(content_type.into()),
+        };
+        let page = Page::from_resource(
+            &resource(b"<b>not bold</b>\n\n\tindented", "text/plain"),
+            None,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert!(page.dom().text_content().contains("<b>not bold</b>"));
+        #[cfg(feature = "render")]
+        assert_eq!(
+            export(&page, 20, Format::Plain),
+            "<b>not bold</b>\n\n    indented\n"
+        );
+
+        #[cfg(feature = "images")]
+        {
+            let mut png = std::io::Cursor::new(vec![]);
+            image::DynamicImage::new_rgb8(3, 2)
+                .write_to(&mut png, image::ImageFormat::Png)
+                .unwrap();
+            let page = Page::from_resource(
+                &resource(png.get_ref(), "image/png"),
+                Some("x"),
+                &Settings::default(),
+            )
+            .unwrap();
+            assert_eq!(page.url, "https://a.com/f#x");
+            assert_eq!(page.images["https://a.com/f"].width(), 3);
+        }
+        assert!(
+            Page::from_resource(&resource(b"", "image/png"), None, &Settings::default()).is_err()
+        );
+        assert!(Page::from_resource(
+            &resource(b"%PDF", "application/pdf"),
+            None,
+            &Settings::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_title() {
+        let page = Page::from_html(
+            "about:test",
+            "<html><head><title>  Hello\n   world </title></head><body></body></html>",
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(page.title(), Some("Hello world".into()));
+
+        let page = Page::from_html("about:test", "<p>untitled</p>", &Settings::default()).unwrap();
+        assert_eq!(page.title(), None);
+    }
+
+    #[test]
+    fn test_links_and_query() {
+        let mut page = Page::from_html(
+            "https://a.test/dir/index.html",
+            r#"<p class="note">See <a href="b.html">the
+                next   page</a> or <a name="top">here</a>.</p><p><a href="/c">c</a></p>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            page.links(),
+            [
+                Link {
+                    text: "the next page".into(),
+                    url: "https://a.test/dir/b.html".into()
+                },
+                Link {
+                    text: "c".into(),
+                    url: "https://a.test/c".into()
+                }
+            ]
+        );
+        assert_eq!(page.query("p.note a").len(), 2);
+        assert_eq!(page.query("p > a[href]").len(), 2);
+        assert!(page.query("p >").is_empty());
+        #[cfg(feature = "render")]
+        assert_eq!(page.render_to_string(40).lines().last(), Some("c"));
+
+        // the kept index is made again once the tree is changed
+        assert!(page.query("#top").is_empty());
+        let path = page.document().query_paths("a[name]").remove(0);
+        let top = page.dom_mut().node_at_mut(&path).unwrap();
+        if let NodeType::Element(e) = &mut top.node_type {
+            e.attributes.insert("id".into(), "top".into());
+        }
+        assert_eq!(page.query("#top").len(), 1);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    app::Options,
+    bookmark::Bookmarks,
+    history::{Entry, History},
+    layout::{content_area, node_to_object_with_context},
+    page::Page,
+    render::{PageView, Palette},
+    style::to_styled_node,
+};
+use ratatui::prelude::*;
+use std::mem;
+
+/// `BookmarkList` is the bookmark view opened with `B`.
+pub struct BookmarkList {
+    pub selected: usize,
+    pub page: Page,
+}
+
+impl BookmarkList {
+    pub fn new(bookmarks: &Bookmarks) -> Self {
+        BookmarkList {
+            selected: 0,
+            page: bookmarks.to_page(0),
+        }
+    }
+
+    pub fn select(&mut self, bookmarks: &Bookmarks, lines: i32) {
+        let last = bookmarks.bookmarks.len().saturating_sub(1) as i32;
+        self.selected = (self.selected as i32 + lines).clamp(0, last) as usize;
+        self.page = bookmarks.to_page(self.selected);
+    }
+
+    /// Scrolls so that the selected entry is on screen, below the heading line.
+    pub fn scroll(&self, viewport_height: u16) -> u16 {
+        (self.selected as u16 + 2).saturating_sub(viewport_height)
+    }
+}
+
+/// `Split` is the screen divided into two panes, each showing a page with its own history.
+pub struct Split {
+    /// Whether the panes are side by side rather than one above the other.
+    pub vertical: bool,
+    /// The page in the pane that does not have focus, and its history.
+    pub other: Entry,
+    pub other_history: History,
+    pub other_view: Option<PageView>,
+    /// Whether the focused pane is the second one, on the right or at the bottom.
+    pub focus_second: bool,
+}
+
+impl Split {
+    /// Divides `area` into the areas of the focused pane and of the other one,
+    /// leaving a row or column between them for a separator.
+    pub fn areas(&self, area: Rect) -> (Rect, Rect) {
+        let (first, second) = if self.vertical {
+            let width = area.width.saturating_sub(1) / 2;
+            (
+                Rect { width, ..area },
+                Rect {
+                    x: area.x + width + 1,
+                    width: area.width.saturating_sub(width + 1),
+                    ..area
+                },
+            )
+        } else {
+            let height = area.height.saturating_sub(1) / 2;
+            (
+                Rect { height, ..area },
+                Rect {
+                    y: area.y + height + 1,
+                    height: area.height.saturating_sub(height + 1),
+                    ..area
+                },
+            )
+        };
+        if self.focus_second {
+            (second, first)
+        } else {
+            (first, second)
+        }
+    }
+
+    /// Gives the focus to the other pane, swapping its page, history and layout with the
+    /// focused ones.
+    pub fn switch(
+        &mut self,
+        current: &mut Entry,
+        history: &mut History,
+        view: &mut Option<PageView>,
+    ) {
+        mem::swap(current, &mut self.other);
+        mem::swap(history, &mut self.other_history);
+        mem::swap(view, &mut self.other_view);
+        self.focus_second = !self.focus_second;
+    }
+}
+
+/// Draws the line between the panes of `split`, brighter along the focused pane.
+pub fn render_separator(split: &Split, area: Rect, other_area: Rect, buf: &mut Buffer) {
+    let (symbol, line) = if split.vertical {
+        let x = area.right().min(other_area.right());
+        (
+            "│",
+            Rect {
+                x,
+                width: 1,
+                ..area
+            },
+        )
+    } else {
+        let y = area.bottom().min(other_area.bottom());
+        (
+            "─",
+            Rect {
+                y,
+                height: 1,
+                ..area
+            },
+        )
+    };
+    let line = line.intersection(buf.area);
+    for y in line.top()..line.bottom() {
+        for x in line.left()..line.right() {
+            buf.get_mut(x, y).set_symbol(symbol).set_fg(Color::Gray);
+        }
+    }
+}
+
+/// Returns the layout of `page` in `content`, which is kept in `view` until the page is laid
+/// out in another area, or `view` is emptied because the page changed.
+pub fn view_of<'v>(
+    view: &'v mut Option<PageView>,
+    page: &Page,
+    content: Rect,
+    options: Options,
+    palette: Palette,
+) -> Option<&'v PageView> {
+    if view.as_ref().map(|view| view.content) != Some(content) {
+        *view = to_styled_node(page.dom(), &page.stylesheet).map(|styled| {
+            let context = page.layout_context(options.max_image_height);
+            let object = node_to_object_with_context(&styled, content, 0, &context);
+            PageView::new(&object, content, palette)
+        });
+    }
+    view.as_ref()
+}
+
+/// Draws `entry` in `area` of `buf`, without the overlays of the focused pane.
+pub fn render_entry(
+    entry: &mut Entry,
+    view: &mut Option<PageView>,
+    area: Rect,
+    options: Options,
+    palette: Palette,
+    buf: &mut Buffer,
+) {
+    let content = Rect {
+        y: 0,
+        ..content_area(area, options.max_width)
+    };
+    let Some(view) = view_of(view, &entry.page, content, options, palette) else {
+        return;
+    };
+    entry.scroll = entry.scroll.min(view.height.saturating_sub(area.height));
+    let mut pane_buf = Buffer::empty(Rect { y: 0, ..area });
+    pane_buf.set_style(pane_buf.area, palette.base_style());
+    view.paint(&mut pane_buf, entry.scroll);
+    pane_buf.area.y = area.y;
+    buf.merge(&pane_buf);
+}
+
+pub fn shown_page<'p>(current: &'p Entry, bookmark_list: &'p Option<BookmarkList>) -> &'p Page {
+    match bookmark_list {
+        Some(list) => &list.page,
+        None => &current.page,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Split;
+    use crate::{
+        history::{Entry, History},
+        page::{Page, Settings},
+    };
+    use ratatui::layout::Rect;
+
+    #[test]
+    fn test_split() {
+        let entry = |url: &str| Entry {
+            page: Page::from_html(url, "<p>page</p>", &Settings::default()).unwrap(),
+            scroll: 0,
+            focus: None,
+            reveal_fragment: false,
+        };
+        let mut split = Split {
+            vertical: true,
+            other: entry("b.html"),
+            other_history: History::default(),
+            other_view: None,
+            focus_second: false,
+        };
+        let area = Rect::new(0, 0, 81, 24);
+        assert_eq!(
+            split.areas(area),
+            (Rect::new(0, 0, 40, 24), Rect::new(41, 0, 40, 24))
+        );
+
+        let mut current = entry("a.html");
+        let mut history = History::default();
+        split.switch(&mut current, &mut history, &mut None);
+        assert_eq!(current.page.url, "b.html");
+        assert_eq!(split.other.page.url, "a.html");
+        assert_eq!(
+            split.areas(area),
+            (Rect::new(41, 0, 40, 24), Rect::new(0, 0, 40, 24))
+        );
+
+        split.vertical = false;
+        assert_eq!(
+            split.areas(area),
+            (Rect::new(0, 12, 81, 12), Rect::new(0, 0, 81, 11))
+        );
+    }
+}
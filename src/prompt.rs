@@ -0,0 +1,138 @@
+use crate::export::Format;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    prelude::*,
+    widgets::{Clear, Paragraph},
+};
+use unicode_width::UnicodeWidthStr;
+
+/// `PromptKind` is what the text typed into a `Prompt` is for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    /// The address bar, where the user types a URL to go to.
+    #[default]
+    Url,
+    /// The path of the file to export the page to.
+    Export(Format),
+    /// The path to save a response that is not a page to.
+    Download,
+    /// A command, typed after `:`.
+    Command,
+    /// The user name to sign in to a host with.
+    User,
+    /// The password to sign in to a host with, which is not shown as it is typed.
+    Password,
+}
+
+/// `Prompt` is the input line at the bottom of the screen.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub input: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PromptEvent {
+    Submit(String),
+    Cancel,
+}
+
+impl Prompt {
+    fn label(&self) -> &'static str {
+        match self.kind {
+            PromptKind::Url => "Go to: ",
+            PromptKind::Export(Format::Plain) => "Export text to: ",
+            PromptKind::Export(Format::Ansi) => "Export ANSI text to: ",
+            PromptKind::Export(Format::Json) => "Export JSON to: ",
+            PromptKind::Export(Format::Linear) => "Export the page read out to: ",
+            PromptKind::Download => "Save to: ",
+            PromptKind::Command => ":",
+            PromptKind::User => "User name: ",
+            PromptKind::Password => "Password: ",
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<PromptEvent> {
+        match key.code {
+            // spaces around a password are a part of it
+            KeyCode::Enter if self.kind == PromptKind::Password => {
+                Some(PromptEvent::Submit(self.input.clone()))
+            }
+            KeyCode::Enter => Some(PromptEvent::Submit(self.input.trim().to_string())),
+            KeyCode::Esc => Some(PromptEvent::Cancel),
+            KeyCode::Backspace => {
+                self.input.pop();
+                None
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let area = bottom_line(frame.size());
+        let input = match self.kind {
+            PromptKind::Password => "*".repeat(self.input.chars().count()),
+            _ => self.input.clone(),
+        };
+        let line = format!("{}{}", self.label(), input);
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(line.as_str()), area);
+        frame.set_cursor(
+            area.x
+                .saturating_add(line.width() as u16)
+                .min(area.right().saturating_sub(1)),
+            area.y,
+        );
+    }
+}
+
+pub fn bottom_line(area: Rect) -> Rect {
+    Rect {
+        y: area.bottom().saturating_sub(1),
+        height: area.height.min(1),
+        ..area
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Prompt, PromptEvent, PromptKind};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut bar = Prompt::default();
+        for c in "https://exampel".chars() {
+            assert_eq!(bar.handle_key(key(c)), None);
+        }
+        bar.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        bar.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        bar.handle_key(key('l'));
+        bar.handle_key(key('e'));
+        assert_eq!(
+            bar.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+            Some(PromptEvent::Submit("https://example".into()))
+        );
+        assert_eq!(
+            bar.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)),
+            Some(PromptEvent::Cancel)
+        );
+
+        let mut password = Prompt {
+            kind: PromptKind::Password,
+            input: " secret ".into(),
+        };
+        assert_eq!(
+            password.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+            Some(PromptEvent::Submit(" secret ".into()))
+        );
+    }
+}
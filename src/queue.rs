@@ -0,0 +1,228 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    sync::{Mutex, OnceLock},
+};
+use tokio::sync::oneshot;
+use url::Url;
+
+/// How many fetches are made from a host at once, as browsers allow. The rest wait their turn.
+pub const MAX_FETCHES_PER_HOST: usize = 6;
+
+/// `Priority` is how soon a fetch is made among those waiting for the same host:
+/// the page itself, then what it is styled with, then its images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Image,
+    Stylesheet,
+    Document,
+}
+
+/// `Waiter` is a fetch waiting for a host, woken when it gets its turn.
+#[derive(Debug)]
+struct Waiter {
+    priority: Priority,
+    /// When it started waiting, so that of the same priority the first is woken first.
+    order: u64,
+    wake: oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// `Host` is what is being fetched from a host, and what waits for it.
+#[derive(Debug, Default)]
+struct Host {
+    fetching: usize,
+    waiting: BinaryHeap<Waiter>,
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    hosts: HashMap<String, Host>,
+    waited: u64,
+}
+
+fn queue() -> &'static Mutex<Queue> {
+    static QUEUE: OnceLock<Mutex<Queue>> = OnceLock::new();
+    QUEUE.get_or_init(Default::default)
+}
+
+/// `Slot` is a turn to fetch from a host, which passes to the next waiting fetch when dropped.
+#[derive(Debug)]
+pub struct Slot {
+    host: String,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        release(&self.host);
+    }
+}
+
+/// `Waiting` is a fetch waiting for its turn. A fetch that is cancelled while it waits
+/// gives back a turn it was given but did not take.
+struct Waiting {
+    host: String,
+    turn: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        if let Some(mut turn) = self.turn.take() {
+            turn.close();
+            if turn.try_recv().is_ok() {
+                release(&self.host);
+            }
+        }
+    }
+}
+
+/// Returns the host and port fetches from `url` are counted against, if it has any.
+fn host_of(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(match url.port_or_known_default() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// Waits until `url` can be fetched: until fewer than `MAX_FETCHES_PER_HOST` fetches from its
+/// host are being made and no fetch of a higher `priority` waits for it.
+/// URLs without a host, like those of files, do not wait.
+pub async fn acquire(url: &str, priority: Priority) -> Option<Slot> {
+    let host = host_of(url)?;
+    let turn = {
+        let mut queue = queue().lock().unwrap();
+        let order = queue.waited;
+        queue.waited += 1;
+        let entry = queue.hosts.entry(host.clone()).or_default();
+        if entry.fetching < MAX_FETCHES_PER_HOST {
+            entry.fetching += 1;
+            return Some(Slot { host });
+        }
+        let (wake, turn) = oneshot::channel();
+        entry.waiting.push(Waiter {
+            priority,
+            order,
+            wake,
+        });
+        turn
+    };
+    let mut waiting = Waiting {
+        host,
+        turn: Some(turn),
+    };
+    if let Some(turn) = &mut waiting.turn {
+        let _ = turn.await;
+    }
+    waiting.turn = None;
+    Some(Slot {
+        host: waiting.host.clone(),
+    })
+}
+
+/// Passes a turn to fetch from `host` to the first waiting fetch that was not cancelled.
+fn release(host: &str) {
+    let mut queue = queue().lock().unwrap();
+    let Some(entry) = queue.hosts.get_mut(host) else {
+        return;
+    };
+    while let Some(waiter) = entry.waiting.pop() {
+        if waiter.wake.send(()).is_ok() {
+            return;
+        }
+    }
+    entry.fetching -= 1;
+    if entry.fetching == 0 {
+        queue.hosts.remove(host);
+    }
+}
+
+#[cfg(test)]
+fn waiting(host: &str) -> usize {
+    let queue = queue().lock().unwrap();
+    queue.hosts.get(host).map_or(0, |entry| entry.waiting.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{acquire, host_of, waiting, Priority, MAX_FETCHES_PER_HOST};
+    use crate::request;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(host_of("https://a.com/b"), Some("a.com:443".into()));
+        assert_eq!(host_of("http://a.com:8080/"), Some("a.com:8080".into()));
+        assert_eq!(host_of("/tmp/a.html"), None);
+    }
+
+    #[test]
+    fn test_acquire() {
+        let url = "https://queue.test/";
+        request::runtime().block_on(async {
+            let mut slots = vec![];
+            for _ in 0..MAX_FETCHES_PER_HOST {
+                slots.push(acquire(url, Priority::Image).await.unwrap());
+            }
+            // other hosts and files do not wait
+            assert!(acquire("https://other.test/", Priority::Image)
+                .await
+                .is_some());
+            assert!(acquire("/tmp/a.html", Priority::Image).await.is_none());
+
+            let fetched = Arc::new(Mutex::new(vec![]));
+            let mut tasks = vec![];
+            for priority in [
+                Priority::Image,
+                Priority::Document,
+                Priority::Stylesheet,
+                Priority::Document,
+            ] {
+                let fetched = fetched.clone();
+                tasks.push(tokio::spawn(async move {
+                    let _slot = acquire(url, priority).await;
+                    fetched.lock().unwrap().push(priority);
+                }));
+                while waiting("queue.test:443") < tasks.len() {
+                    tokio::task::yield_now().await;
+                }
+            }
+            // a fetch cancelled while it waits gives up its turn
+            let cancelled = tasks.pop().unwrap();
+            cancelled.abort();
+            let _ = cancelled.await;
+
+            // each fetch passes its turn to the next when it is done
+            slots.pop();
+            for task in tasks {
+                task.await.unwrap();
+            }
+            assert_eq!(
+                *fetched.lock().unwrap(),
+                [Priority::Document, Priority::Stylesheet, Priority::Image]
+            );
+        });
+    }
+}
@@ -0,0 +1,62 @@
+// what is laid out is drawn as it is with ratatui
+#[cfg(feature = "render")]
+pub use ratatui::layout::Rect;
+
+/// `Rect` is an area of the screen, in cells. It has the fields and methods of ratatui's,
+/// for the layout engine to be used without a terminal.
+#[cfg(not(feature = "render"))]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[cfg(not(feature = "render"))]
+impl Rect {
+    /// Makes a `Rect`, as small as it needs to be, keeping its aspect ratio, for its area
+    /// to fit in a `u16`.
+    pub fn new(x: u16, y: u16, width: u16, height: u16) -> Rect {
+        let max_area = u16::MAX;
+        let (width, height) = if u32::from(width) * u32::from(height) > u32::from(max_area) {
+            let aspect_ratio = f64::from(width) / f64::from(height);
+            let height = (f64::from(max_area) / aspect_ratio).sqrt();
+            ((height * aspect_ratio) as u16, height as u16)
+        } else {
+            (width, height)
+        };
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub const fn area(self) -> u16 {
+        self.width.saturating_mul(self.height)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub const fn left(self) -> u16 {
+        self.x
+    }
+
+    /// The first column to the right of it.
+    pub const fn right(self) -> u16 {
+        self.x.saturating_add(self.width)
+    }
+
+    pub const fn top(self) -> u16 {
+        self.y
+    }
+
+    /// The first row below it.
+    pub const fn bottom(self) -> u16 {
+        self.y.saturating_add(self.height)
+    }
+}
@@ -1,47 +1,746 @@
-use crate::layout::{LayoutObject, LayoutObjectType};
-use crossterm::{
-    event::{self, KeyCode, KeyEventKind},
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
+use crate::{
+    cssom,
+    layout::{
+        background_color, border, inline_node, Border, BorderStyle, LayoutObject, LayoutObjectType,
+        TextRole, TextStyle,
+    },
+    theme::Theme,
 };
+#[cfg(feature = "tui")]
+use crate::{dom::NodeId, graphics::Placement};
 use ratatui::{
     prelude::*,
     widgets::{Paragraph, Widget},
 };
-use std::io::{stdout, Result};
+#[cfg(feature = "tui")]
+use std::collections::HashMap;
+use std::env;
 
-pub fn render(object: &LayoutObject, buf: &mut Buffer) {
+/// `ColorSupport` is the set of colors a terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    /// The 6×6×6 color cube and the gray ramp of 256-color terminals.
+    Ansi256,
+    Ansi16,
+    /// No colors at all, as asked for by `NO_COLOR` (https://no-color.org/).
+    NoColor,
+}
+
+impl ColorSupport {
+    /// Detects the colors of the terminal from the environment.
+    pub fn detect() -> Self {
+        let var = |name| env::var(name).ok();
+        ColorSupport::from_env(
+            var("NO_COLOR").as_deref(),
+            var("COLORTERM").as_deref(),
+            var("TERM").as_deref(),
+        )
+    }
+
+    /// Decides the colors of the terminal from the `NO_COLOR`, `COLORTERM` and `TERM` variables.
+    /// Terminals that do not announce more, by `COLORTERM` or a `TERM` like `xterm-256color`,
+    /// get the 16 ANSI colors.
+    fn from_env(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if no_color.is_some_and(|value| !value.is_empty()) || term == Some("dumb") {
+            return ColorSupport::NoColor;
+        }
+        match (colorterm, term) {
+            (Some("truecolor" | "24bit"), _) => ColorSupport::TrueColor,
+            (_, Some(term)) if term.ends_with("-direct") => ColorSupport::TrueColor,
+            (_, Some(term)) if term.contains("256color") => ColorSupport::Ansi256,
+            _ => ColorSupport::Ansi16,
+        }
+    }
+}
+
+/// `Palette` is how page colors become terminal colors: made readable by the theme,
+/// then mapped onto the colors the terminal supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub support: ColorSupport,
+    pub theme: Theme,
+}
+
+/// The default xterm colors of the 16 ANSI colors.
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each channel in the color cube of 256-color terminals.
+const CUBE_LEVELS: [u8; 6] = [0, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> i32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+    d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+/// Returns the index of the nearest color in the 256-color palette. The first 16 colors
+/// are left out, since terminals differ in what they show for them.
+fn ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let cube = (0..216).map(|i| {
+        let level = |n: usize| CUBE_LEVELS[n % 6];
+        (16 + i, (level(i / 36), level(i / 6), level(i)))
+    });
+    let grays = (0..24).map(|i| {
+        let level = 8 + 10 * i as u8;
+        (232 + i, (level, level, level))
+    });
+    cube.chain(grays)
+        .min_by_key(|(_, color)| distance(rgb, *color))
+        .map_or(0, |(i, _)| i as u8)
+}
+
+/// Removes all colors from `buf`, for terminals that show none. Cells that stood out by
+/// their background, like link hints and highlights, are shown in reverse video instead.
+pub fn strip_colors(buf: &mut Buffer) {
+    let area = buf.area;
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            let cell = buf.get_mut(x, y);
+            if cell.bg != Color::Reset {
+                cell.modifier.insert(Modifier::REVERSED);
+            }
+            cell.set_fg(Color::Reset).set_bg(Color::Reset);
+        }
+    }
+}
+
+impl Palette {
+    /// Shows page colors as they are, with all the colors of a truecolor terminal.
+    pub const TRUE_COLOR: Palette = Palette {
+        support: ColorSupport::TrueColor,
+        theme: Theme::DEFAULT,
+    };
+
+    pub fn detect(theme: Theme) -> Self {
+        Palette {
+            support: ColorSupport::detect(),
+            theme,
+        }
+    }
+
+    /// Maps a CSS color onto the nearest color of this palette.
+    pub fn color(&self, color: cssom::Color) -> Option<Color> {
+        if color.is_transparent() {
+            return None;
+        }
+        let rgb = (color.r, color.g, color.b);
+        match self.support {
+            ColorSupport::TrueColor => Some(Color::Rgb(color.r, color.g, color.b)),
+            ColorSupport::Ansi256 => Some(Color::Indexed(ansi256(rgb))),
+            ColorSupport::Ansi16 => ANSI16
+                .iter()
+                .min_by_key(|(_, ansi)| distance(rgb, *ansi))
+                .map(|(c, _)| *c),
+            ColorSupport::NoColor => None,
+        }
+    }
+
+    /// Returns the style of the screen behind the page, in the colors of the theme.
+    pub fn base_style(&self) -> Style {
+        let mut result = Style::default();
+        if let Some(fg) = self.theme.foreground.and_then(|c| self.color(c)) {
+            result = result.fg(fg);
+        }
+        if let Some(bg) = self.theme.background.and_then(|c| self.color(c)) {
+            result = result.bg(bg);
+        }
+        result
+    }
+
+    fn style(&self, style: &TextStyle) -> Style {
+        let mut result = Style::default();
+        let color = match (style.color, style.role) {
+            // text on a background of its own is left as the page colored it
+            (Some(color), _) if style.background.is_some() => Some(color),
+            (Some(color), _) => Some(self.theme.readable(color)),
+            (None, TextRole::Link) => Some(self.theme.link),
+            (None, TextRole::Heading) => self.theme.heading,
+            (None, TextRole::Body) => None,
+        };
+        if let Some(fg) = color.and_then(|c| self.color(c)) {
+            result = result.fg(fg);
+        }
+        if let Some(bg) = style.background.and_then(|c| self.color(c)) {
+            result = result.bg(bg);
+        }
+        let modifiers = [
+            (style.bold, Modifier::BOLD),
+            (style.italic, Modifier::ITALIC),
+            (style.underline, Modifier::UNDERLINED),
+            (style.strikethrough, Modifier::CROSSED_OUT),
+        ];
+        for (enabled, modifier) in modifiers {
+            if enabled {
+                result = result.add_modifier(modifier);
+            }
+        }
+        result
+    }
+}
+
+/// Translates `area` by the scroll position and clips it to the buffer,
+/// since layout places content beyond the viewport.
+pub fn visible_area(area: Rect, scroll: u16, buf: &Buffer) -> Option<Rect> {
+    let top = area.y.max(scroll);
+    let bottom = area.bottom();
+    if bottom <= top {
+        return None;
+    }
+    let area = Rect {
+        y: top - scroll,
+        height: bottom - top,
+        ..area
+    }
+    .intersection(buf.area);
+    (!area.is_empty()).then_some(area)
+}
+
+/// The corners and sides of a border: top-left, top-right, bottom-left, bottom-right,
+/// horizontal and vertical.
+fn border_symbols(style: BorderStyle) -> [&'static str; 6] {
+    match style {
+        BorderStyle::Solid => ["┌", "┐", "└", "┘", "─", "│"],
+        BorderStyle::Double => ["╔", "╗", "╚", "╝", "═", "║"],
+        BorderStyle::Dashed => ["┌", "┐", "└", "┘", "╌", "╎"],
+        BorderStyle::Dotted => ["┌", "┐", "└", "┘", "┈", "┊"],
+    }
+}
+
+/// Draws `border` around `area`, with the page scrolled down by `scroll` rows.
+/// Only the cells that are visible in `buf` are drawn.
+fn render_border(border: Border, area: Rect, buf: &mut Buffer, scroll: u16, palette: Palette) {
+    if area.width < 2 || area.height < 2 {
+        return;
+    }
+    let [top_left, top_right, bottom_left, bottom_right, horizontal, vertical] =
+        border_symbols(border.style);
+    let style = match border.color.and_then(|c| palette.color(c)) {
+        Some(fg) => Style::default().fg(fg),
+        None => Style::default(),
+    };
+    let (right, bottom) = (area.right() - 1, area.bottom() - 1);
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            let symbol = match (x, y) {
+                (x, y) if x == area.x && y == area.y => top_left,
+                (x, y) if x == right && y == area.y => top_right,
+                (x, y) if x == area.x && y == bottom => bottom_left,
+                (x, y) if x == right && y == bottom => bottom_right,
+                (_, y) if y == area.y || y == bottom => horizontal,
+                (x, _) if x == area.x || x == right => vertical,
+                _ => continue,
+            };
+            let Some(y) = y.checked_sub(scroll) else {
+                continue;
+            };
+            if contains(buf.area, x, y) {
+                buf.get_mut(x, y).set_symbol(symbol).set_style(style);
+            }
+        }
+    }
+}
+
+fn contains(area: Rect, x: u16, y: u16) -> bool {
+    area.x <= x && x < area.right() && area.y <= y && y < area.bottom()
+}
+
+/// Renders `object` into `buf`, with the page scrolled down by `scroll` rows.
+pub fn render(object: &LayoutObject, buf: &mut Buffer, scroll: u16, palette: Palette) {
     match &object.ty {
         LayoutObjectType::Texts(texts) => {
-            texts
-                .iter()
-                .for_each(|t| Paragraph::new(t.data).render(t.area, buf));
+            for t in texts {
+                if let Some(area) = visible_area(t.area, scroll, buf) {
+                    // the start of a line left of the buffer is scrolled out of view
+                    Paragraph::new(t.data.as_ref())
+                        .style(palette.style(&t.style))
+                        .scroll((0, area.x - t.area.x))
+                        .render(area, buf);
+                }
+            }
         }
+        // images are drawn over the rendered page, see `PageView::image_placements`
+        LayoutObjectType::Image { .. } => {}
         LayoutObjectType::Block { children } => {
-            children.iter().for_each(|n| render(n, buf));
+            let background = object
+                .node
+                .filter(|node| !inline_node(node))
+                .and_then(background_color)
+                .and_then(|color| palette.color(color));
+            if let (Some(bg), Some(area)) = (background, visible_area(object.area, scroll, buf)) {
+                buf.set_style(area, Style::default().bg(bg));
+            }
+            if let Some(border) = object.node.and_then(border) {
+                render_border(border, object.area, buf, scroll, palette);
+            }
+            children
+                .iter()
+                .for_each(|n| render(n, buf, scroll, palette));
         }
     }
 }
 
-pub fn start(object: &LayoutObject) -> Result<()> {
-    stdout().execute(EnterAlternateScreen)?;
-    enable_raw_mode()?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
-    terminal.clear()?;
+/// `PageView` is a page laid out and painted once, and kept across draws, so that scrolling
+/// and what is drawn over the page only copy cells rather than style and lay it out again.
+#[cfg(feature = "tui")]
+pub struct PageView {
+    /// The area the page was laid out in, from the top of the page.
+    pub content: Rect,
+    /// The rows the page takes, and the column just right of its rightmost content.
+    pub height: u16,
+    pub right: u16,
+    /// The whole page, painted unscrolled, a slice of `slice_height` rows at a time, as a
+    /// buffer holds at most `u16::MAX` cells.
+    slices: Vec<Buffer>,
+    slice_height: u16,
+    /// The links in document order, as their targets and the cells their text takes.
+    pub links: Vec<(String, Vec<Rect>)>,
+    /// The images, as their sources and areas.
+    images: Vec<(String, Rect)>,
+    /// The cells taken by each DOM node, as `LayoutObject::areas_of` finds them.
+    areas: HashMap<NodeId, Vec<Rect>>,
+}
 
-    loop {
-        terminal.draw(|frame| render(object, frame.buffer_mut()))?;
+#[cfg(feature = "tui")]
+impl PageView {
+    /// Paints `object`, laid out in `content`, with `palette`.
+    pub fn new(object: &LayoutObject, content: Rect, palette: Palette) -> Self {
+        let height = object.area.height;
+        let right = object.content_right();
+        let width = right.max(content.right());
+        let slice_height = (u16::MAX / width.max(1)).max(1);
+        let slices = (0..height.max(1))
+            .step_by(slice_height as usize)
+            .map(|top| {
+                let mut slice = Buffer::empty(Rect::new(0, 0, width, slice_height));
+                slice.set_style(slice.area, palette.base_style());
+                render(object, &mut slice, top, palette);
+                slice
+            })
+            .collect();
+        let mut view = PageView {
+            content,
+            height,
+            right,
+            slices,
+            slice_height,
+            links: object
+                .links()
+                .into_iter()
+                .map(|link| (link.href.to_string(), link.areas))
+                .collect(),
+            images: vec![],
+            areas: HashMap::new(),
+        };
+        view.collect(object);
+        view
+    }
 
-        if event::poll(std::time::Duration::from_millis(16))? {
-            if let event::Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press && key.code == KeyCode::Char('q') {
-                    break;
+    fn collect(&mut self, object: &LayoutObject) {
+        if let Some(id) = object.node_id() {
+            let areas = match &object.ty {
+                LayoutObjectType::Texts(texts) => texts.iter().map(|t| t.area).collect(),
+                _ => vec![object.area],
+            };
+            self.areas.entry(id).or_insert(areas);
+        }
+        match &object.ty {
+            LayoutObjectType::Image { src } => self.images.push((src.to_string(), object.area)),
+            LayoutObjectType::Block { children } => {
+                children.iter().for_each(|child| self.collect(child))
+            }
+            LayoutObjectType::Texts(_) => {}
+        }
+    }
+
+    /// Returns the cells taken by the DOM node `id`.
+    pub fn areas_of(&self, id: NodeId) -> Vec<Rect> {
+        self.areas.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Copies the page into `buf`, scrolled down by `scroll` rows, as `render` would draw it.
+    pub fn paint(&self, buf: &mut Buffer, scroll: u16) {
+        let area = buf.area;
+        for y in area.top()..area.bottom() {
+            let Some(page_y) = y.checked_add(scroll) else {
+                break;
+            };
+            if page_y >= self.height {
+                break;
+            }
+            let Some(slice) = self.slices.get((page_y / self.slice_height) as usize) else {
+                break;
+            };
+            let slice_y = page_y % self.slice_height;
+            for x in area.left()..area.right() {
+                if contains(slice.area, x, slice_y) {
+                    *buf.get_mut(x, y) = slice.get(x, slice_y).clone();
                 }
             }
         }
     }
 
-    stdout().execute(LeaveAlternateScreen)?;
-    disable_raw_mode()?;
-    Ok(())
+    /// Collects where the images appear in `buf`, with the page scrolled down by `scroll` rows.
+    pub fn image_placements(&self, scroll: u16, buf: &Buffer, out: &mut Vec<Placement>) {
+        for (src, image) in &self.images {
+            if let Some(area) = visible_area(*image, scroll, buf) {
+                out.push(Placement {
+                    src: src.clone(),
+                    area,
+                    hidden_rows: scroll.saturating_sub(image.y),
+                    rows: image.height,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "tui")]
+    use super::PageView;
+    use super::{render, strip_colors, ColorSupport, Palette};
+    use crate::{
+        cssom,
+        layout::node_to_object,
+        page::{Page, Settings},
+        style::to_styled_node,
+        theme::Theme,
+    };
+    #[cfg(feature = "tui")]
+    use crate::{
+        graphics::Placement,
+        layout::{node_to_object_with_context, LayoutContext},
+    };
+    use combine::Parser;
+    use ratatui::{
+        buffer::Buffer,
+        layout::Rect,
+        style::{Color, Modifier, Style},
+    };
+
+    #[test]
+    fn test_render_scrolled() {
+        let html = "<div><div>aaa</div><div>bbb</div><div>ccc</div></div>";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 5, 2), 0);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 5, 2));
+        render(&object, &mut buf, 1, Palette::TRUE_COLOR);
+        assert_eq!(buf, Buffer::with_lines(vec!["bbb  ", "ccc  "]));
+    }
+
+    #[test]
+    fn test_palette() {
+        let red = cssom::Color::rgb(255, 0, 0);
+        let ansi16 = Palette {
+            support: ColorSupport::Ansi16,
+            ..Palette::TRUE_COLOR
+        };
+        assert_eq!(Palette::TRUE_COLOR.color(red), Some(Color::Rgb(255, 0, 0)));
+        assert_eq!(ansi16.color(red), Some(Color::LightRed));
+        assert_eq!(
+            ansi16.color(cssom::Color::rgb(0, 0, 128)),
+            Some(Color::Blue)
+        );
+        assert_eq!(
+            ansi16.color(cssom::Color::rgb(20, 20, 20)),
+            Some(Color::Black)
+        );
+        assert_eq!(
+            Palette::TRUE_COLOR.color(cssom::Color::from_name("transparent").unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_color_support() {
+        assert_eq!(
+            ColorSupport::from_env(Some("1"), Some("truecolor"), None),
+            ColorSupport::NoColor
+        );
+        // an empty `NO_COLOR` does not count
+        assert_eq!(
+            ColorSupport::from_env(Some(""), Some("24bit"), None),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("xterm-256color")),
+            ColorSupport::Ansi256
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("xterm-direct")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("xterm")),
+            ColorSupport::Ansi16
+        );
+        assert_eq!(
+            ColorSupport::from_env(None, None, Some("dumb")),
+            ColorSupport::NoColor
+        );
+
+        let palette = |support| Palette {
+            support,
+            ..Palette::TRUE_COLOR
+        };
+        let ansi256 = palette(ColorSupport::Ansi256);
+        assert_eq!(
+            ansi256.color(cssom::Color::rgb(255, 0, 0)),
+            Some(Color::Indexed(196))
+        );
+        assert_eq!(
+            ansi256.color(cssom::Color::rgb(0x80, 0x80, 0x80)),
+            Some(Color::Indexed(244))
+        );
+        assert_eq!(
+            palette(ColorSupport::NoColor).color(cssom::Color::rgb(255, 0, 0)),
+            None
+        );
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 2, 1));
+        buf.get_mut(0, 0).set_fg(Color::Red);
+        buf.get_mut(1, 0).set_bg(Color::Yellow);
+        strip_colors(&mut buf);
+        let mut expected = Buffer::empty(Rect::new(0, 0, 2, 1));
+        expected.get_mut(1, 0).modifier = Modifier::REVERSED;
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_theme() {
+        let page = Page::from_html(
+            "about:test",
+            r#"<style>p { color: navy; }</style><h1>title</h1><p>text <a href="/">link</a></p>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        let styled = to_styled_node(page.dom(), &page.stylesheet).unwrap();
+        let object = node_to_object(&styled, Rect::new(0, 0, 20, 2), 0);
+        let palette = Palette {
+            theme: Theme::DARK,
+            ..Palette::TRUE_COLOR
+        };
+        let mut buf = Buffer::empty(Rect::new(0, 0, 20, 2));
+        render(&object, &mut buf, 0, palette);
+        let rgb = |color: cssom::Color| Color::Rgb(color.r, color.g, color.b);
+
+        assert_eq!(buf.get(0, 0).fg, rgb(Theme::DARK.heading.unwrap()));
+        // navy is too dark to read on a dark terminal
+        let text = buf.get(0, 1).fg;
+        assert_ne!(text, Color::Rgb(0, 0, 128));
+        assert_ne!(text, Color::Reset);
+        assert_eq!(buf.get(5, 1).fg, rgb(Theme::DARK.link));
+    }
+
+    #[test]
+    fn test_render_color() {
+        let html = r#"<div><p class="warn">aa<b>b</b></p><p>c</p></div>"#;
+        let css = ".warn { color: #ff0000; } b { display: inline; color: blue; }";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 5, 2), 0);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 5, 2));
+        render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+        let mut expected = Buffer::with_lines(vec!["aab  ", "c    "]);
+        expected.set_style(
+            Rect::new(0, 0, 2, 1),
+            Style::default().fg(Color::Rgb(255, 0, 0)),
+        );
+        expected.set_style(
+            Rect::new(2, 0, 1, 1),
+            Style::default()
+                .fg(Color::Rgb(0, 0, 255))
+                .add_modifier(Modifier::BOLD),
+        );
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_render_modifiers() {
+        let html = r#"<div><strong>a</strong><em>b</em><del>c</del></div>"#;
+        let css = "strong, em, del { display: inline; }";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 3, 1), 0);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 3, 1));
+        render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+        let mut expected = Buffer::with_lines(vec!["abc"]);
+        expected.set_style(
+            Rect::new(0, 0, 1, 1),
+            Style::default().add_modifier(Modifier::BOLD),
+        );
+        expected.set_style(
+            Rect::new(1, 0, 1, 1),
+            Style::default().add_modifier(Modifier::ITALIC),
+        );
+        expected.set_style(
+            Rect::new(2, 0, 1, 1),
+            Style::default().add_modifier(Modifier::CROSSED_OUT),
+        );
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn test_render_border() {
+        let html = r#"<div><p class="box">ab</p></div>"#;
+        let css = ".box { border-width: 1px; }";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 5, 5), 0);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 5, 3));
+        render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+        assert_eq!(buf, Buffer::with_lines(vec!["┌──┐ ", "│ab│ ", "└──┘ "]));
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 5, 2));
+        render(&object, &mut buf, 1, Palette::TRUE_COLOR);
+        assert_eq!(buf, Buffer::with_lines(vec!["│ab│ ", "└──┘ "]));
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_page_view() {
+        let html = r#"<div><p>aaa</p><p><a href="b.html">bbb</a></p><pre>cccccccc</pre></div>"#;
+        let css = "a { display: inline; background-color: yellow; }";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let content = Rect::new(1, 0, 5, 3);
+        let object = node_to_object(&node, content, 0);
+        let view = PageView::new(&object, content, Palette::TRUE_COLOR);
+        assert_eq!((view.height, view.right), (3, 9));
+
+        // a view shows the page as it is rendered, however it is scrolled
+        for (x, scroll) in [(0, 0), (0, 1), (3, 0), (0, 5)] {
+            let area = Rect::new(x, 0, 6, 2);
+            let mut rendered = Buffer::empty(area);
+            render(&object, &mut rendered, scroll, Palette::TRUE_COLOR);
+            let mut painted = Buffer::empty(area);
+            view.paint(&mut painted, scroll);
+            assert_eq!(painted, rendered);
+        }
+
+        let link = object.links()[0].areas.clone();
+        assert_eq!(view.links, vec![("b.html".to_string(), link.clone())]);
+        let id = object.hit_test(1, 1).last().unwrap().node_id().unwrap();
+        assert_eq!(view.areas_of(id), object.areas_of(id));
+        assert_eq!(view.areas_of(id), link);
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_long_page_view() {
+        // 7000 rows of 10 columns do not fit in a single buffer
+        let lines: String = (0..7000).map(|i| format!("<p>line {i}</p>")).collect();
+        let html = format!("<div>{lines}</div>");
+        let node = &crate::html::html().parse(html.as_str()).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let content = Rect::new(0, 0, 10, 3);
+        let object = node_to_object(&node, content, 0);
+        let view = PageView::new(&object, content, Palette::TRUE_COLOR);
+        assert_eq!(view.height, 7000);
+
+        for scroll in [0, 6552, 6553, 6996] {
+            let area = Rect::new(0, 0, 10, 4);
+            let mut rendered = Buffer::empty(area);
+            render(&object, &mut rendered, scroll, Palette::TRUE_COLOR);
+            let mut painted = Buffer::empty(area);
+            view.paint(&mut painted, scroll);
+            assert_eq!(painted, rendered);
+        }
+        let mut painted = Buffer::empty(Rect::new(0, 0, 10, 1));
+        view.paint(&mut painted, 6999);
+        assert_eq!(painted.get(0, 0).symbol(), "l");
+        assert_eq!(painted.get(5, 0).symbol(), "6");
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_image_placements() {
+        let html = r#"<div><p>a</p><img src="a.png"></div>"#;
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet("").unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let context = LayoutContext {
+            image_sizes: vec![("a.png".into(), (32, 64))].into_iter().collect(),
+            max_image_height: None,
+        };
+        let object = node_to_object_with_context(&node, Rect::new(0, 0, 10, 10), 0, &context);
+
+        let view = PageView::new(&object, Rect::new(0, 0, 10, 10), Palette::TRUE_COLOR);
+        let buf = Buffer::empty(Rect::new(0, 0, 10, 3));
+        let mut placements = vec![];
+        view.image_placements(2, &buf, &mut placements);
+        assert_eq!(
+            placements,
+            vec![Placement {
+                src: "a.png".into(),
+                area: Rect::new(0, 0, 4, 3),
+                hidden_rows: 1,
+                rows: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_render_background() {
+        let html = r#"<div><div class="alert">ab<code>c</code></div><p>d<code>e</code></p></div>"#;
+        let css = ".alert { background-color: yellow; min-width: 4ch; } code { display: inline; background: #333333; }";
+        let node = &crate::html::html().parse(html).unwrap().0[0];
+        let stylesheet = crate::css::stylesheet(css).unwrap();
+        let node = crate::style::to_styled_node(node, &stylesheet).unwrap();
+        let object = node_to_object(&node, Rect::new(0, 0, 5, 3), 0);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 5, 2));
+        render(&object, &mut buf, 0, Palette::TRUE_COLOR);
+        let mut expected = Buffer::with_lines(vec!["abc  ", "de   "]);
+        expected.set_style(
+            Rect::new(0, 0, 4, 1),
+            Style::default().bg(Color::Rgb(255, 255, 0)),
+        );
+        expected.set_style(
+            Rect::new(2, 0, 1, 1),
+            Style::default().bg(Color::Rgb(0x33, 0x33, 0x33)),
+        );
+        expected.set_style(
+            Rect::new(1, 1, 1, 1),
+            Style::default().bg(Color::Rgb(0x33, 0x33, 0x33)),
+        );
+        assert_eq!(buf, expected);
+
+        let mut buf = Buffer::empty(Rect::new(0, 0, 5, 1));
+        render(&object, &mut buf, 1, Palette::TRUE_COLOR);
+        let mut expected = Buffer::with_lines(vec!["de   "]);
+        expected.set_style(
+            Rect::new(1, 0, 1, 1),
+            Style::default().bg(Color::Rgb(0x33, 0x33, 0x33)),
+        );
+        assert_eq!(buf, expected);
+    }
 }
@@ -0,0 +1,188 @@
+use crate::{
+    css,
+    export::{export, Format},
+    inspector::label,
+    layout::{node_to_object_with_context, LayoutContext},
+    page::Page,
+    style::{cascade_trace, to_styled_node, Origin},
+};
+use ratatui::layout::Rect;
+
+/// What `help` says.
+const HELP: &str = "\
+query SELECTOR   list the elements SELECTOR matches, numbered for the commands below
+style N          the properties of match N, and the rules they came from
+rect N           where match N is laid out, in columns and rows
+css RULES        add RULES to the page's stylesheet
+render           write the page out as it is rendered
+width N          render the page N columns wide
+help             this list
+quit             leave";
+
+/// `Repl` is a page explored from a prompt, as `wev repl` does: elements are found with
+/// selectors, and their styles and where they are laid out are shown, as CSS is added.
+#[derive(Debug)]
+pub struct Repl {
+    page: Page,
+    /// The columns the page is laid out and rendered in.
+    width: u16,
+    /// The paths of the elements the last query matched, which are referred to by number.
+    matches: Vec<Vec<usize>>,
+}
+
+impl Repl {
+    pub fn new(page: Page, width: u16) -> Repl {
+        Repl {
+            page,
+            width,
+            matches: vec![],
+        }
+    }
+
+    /// Runs `line`, a command typed at the prompt, and returns what to write in reply.
+    /// `None` means the user is done.
+    pub fn eval(&mut self, line: &str) -> Option<String> {
+        let line = line.trim();
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        Some(match command {
+            "" => String::new(),
+            "quit" | "exit" => return None,
+            "help" => HELP.into(),
+            "query" | "q" => self.query(argument),
+            "style" => self.with_match(argument, Repl::style),
+            "rect" => self.with_match(argument, Repl::rect),
+            "css" => match css::stylesheet(argument) {
+                Ok(stylesheet) => {
+                    let count = stylesheet.rules.len();
+                    self.page.stylesheet.rules.extend(stylesheet.rules);
+                    format!("added {} rule{}", count, if count == 1 { "" } else { "s" })
+                }
+                Err(e) => e.to_string(),
+            },
+            "render" => export(&self.page, self.width, Format::Plain),
+            "width" => match argument.parse() {
+                Ok(width) if width > 0 => {
+                    self.width = width;
+                    format!("rendering {} columns wide", width)
+                }
+                _ => format!("not a width: {}", argument),
+            },
+            _ => format!("unknown command: {}; type `help` for the commands", command),
+        })
+    }
+
+    fn query(&mut self, selector: &str) -> String {
+        if css::selector_list(selector).is_err() {
+            return format!("not a selector: {}", selector);
+        }
+        self.matches = self.page.document().query_paths(selector);
+        if self.matches.is_empty() {
+            return "no matches".into();
+        }
+        self.matches
+            .iter()
+            .enumerate()
+            .filter_map(|(i, path)| {
+                let node = self.page.dom().node_at(path)?;
+                Some(format!("[{}] {}", i, label(node)))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Runs `command` on the match numbered `argument`.
+    fn with_match(&self, argument: &str, command: fn(&Repl, &[usize]) -> String) -> String {
+        match argument
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| self.matches.get(i))
+        {
+            Some(path) => command(self, path),
+            None if self.matches.is_empty() => "no matches; find elements with `query`".into(),
+            None => format!("not a match: {}", argument),
+        }
+    }
+
+    fn style(&self, path: &[usize]) -> String {
+        let trace = cascade_trace(self.page.dom(), path, &self.page.stylesheet);
+        if trace.is_empty() {
+            return "not displayed".into();
+        }
+        trace
+            .into_iter()
+            .map(|(name, value, origin)| {
+                let origin = match origin {
+                    Origin::Rule(selector) => selector.to_string(),
+                    Origin::UserAgent => "default".into(),
+                    Origin::Inherited => "inherited".into(),
+                };
+                format!("{}: {}; /* {} */", name, value, origin)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn rect(&self, path: &[usize]) -> String {
+        let Some(styled) = to_styled_node(self.page.dom(), &self.page.stylesheet) else {
+            return "not displayed".into();
+        };
+        let area = Rect::new(0, 0, self.width, u16::MAX);
+        let object = node_to_object_with_context(&styled, area, 0, &LayoutContext::default());
+        let document = self.page.document();
+        let areas = document
+            .id_at(path)
+            .map(|id| object.areas_of(id))
+            .unwrap_or_default();
+        if areas.is_empty() {
+            return "not laid out".into();
+        }
+        areas
+            .iter()
+            .map(|area| {
+                format!(
+                    "x {}, y {}, {} wide, {} high",
+                    area.x, area.y, area.width, area.height
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Repl;
+    use crate::page::{Page, Settings};
+
+    #[test]
+    fn test_repl() {
+        let page = Page::from_html(
+            "about:test",
+            r#"<h1>Title</h1><p class="note">one</p><p>two</p>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        let mut repl = Repl::new(page, 20);
+        let mut eval = |line: &str| repl.eval(line).unwrap();
+
+        assert_eq!(eval("style 0"), "no matches; find elements with `query`");
+        assert_eq!(eval("query p"), "[0] <p class=\"note\">\n[1] <p>");
+        assert_eq!(eval("query ul"), "no matches");
+        assert_eq!(eval("q p.note"), "[0] <p class=\"note\">");
+        assert_eq!(eval("style 1"), "not a match: 1");
+        assert!(eval("style 0").contains("display: block; /* default */"));
+        assert_eq!(eval("rect 0"), "x 0, y 2, 3 wide, 1 high");
+
+        assert_eq!(
+            eval("css .note { display: none; } h1 { color: red; }"),
+            "added 2 rules"
+        );
+        assert_eq!(eval("style 0"), "not displayed");
+        assert_eq!(eval("rect 0"), "not laid out");
+        assert!(!eval("render").contains("one"));
+        assert_eq!(eval("width 0"), "not a width: 0");
+        assert!(eval("frobnicate").starts_with("unknown command: frobnicate"));
+        assert_eq!(repl.eval("quit"), None);
+    }
+}
@@ -1,16 +1,894 @@
+use crate::{
+    auth::{self, Authenticator, Credentials, Netrc},
+    cache::{self, Cache, CacheMode, Entry},
+    compression::{Coding, Decoder},
+    config::Config,
+    error::{Error, Result},
+    form::{FormData, Method},
+    queue::MAX_FETCHES_PER_HOST,
+    robots::Robots,
+};
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ACCEPT, ACCEPT_ENCODING, AUTHORIZATION,
+        CONTENT_ENCODING, IF_RANGE, RANGE,
+    },
+    Client, Proxy, RequestBuilder, Response, StatusCode,
+};
+use serde::Deserialize;
 use std::{
-    fs::File,
-    io::{self, Read},
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::Future,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
 };
+use tokio::{runtime::Runtime, sync::OnceCell};
+use tracing::Instrument;
+use url::Url;
+
+/// The `User-Agent` sent unless the user sets another.
+pub const DEFAULT_USER_AGENT: &str = concat!("wev/", env!("CARGO_PKG_VERSION"));
+
+/// The `Accept` header sent unless the user sets another, preferring HTML as browsers do.
+pub const DEFAULT_ACCEPT: &str = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+/// `RequestConfig` is what is sent along with every request.
+/// It is the `[request]` table of the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub struct RequestConfig {
+    pub user_agent: String,
+    /// Extra headers by name, like `Accept-Language` or `DNT`.
+    /// `Accept-Encoding` is set by `accept_encodings` instead.
+    pub headers: BTreeMap<String, String>,
+    /// The proxy to make every request through, like `http://proxy:8080` or
+    /// `socks5://localhost:1080`. Without one, those of `HTTPS_PROXY` and the like are used.
+    pub proxy: Option<String>,
+    /// How many seconds to wait for a connection to a host. 0 waits as long as it takes.
+    pub connect_timeout: u64,
+    /// How many seconds to wait for a response, and then for each part of its body.
+    /// 0 waits as long as it takes.
+    pub read_timeout: u64,
+    /// How many seconds a connection is kept open after a response, to be reused by the next
+    /// request to the same host. 0 keeps it open as long as the server does.
+    pub idle_timeout: u64,
+    /// How many connections to each host are kept open after their responses.
+    pub max_idle_connections: usize,
+    /// How many more times a request is made after it failed in a way that may pass,
+    /// like a timeout or a `503 Service Unavailable`.
+    pub retries: u32,
+    /// The content codings servers may compress responses with, most preferred first.
+    /// Only `gzip` and `deflate` can be decoded; `br` and `zstd` are never offered.
+    pub accept_encodings: Vec<String>,
+    /// The user names and passwords to sign in to hosts with, by host, before those of `.netrc`.
+    /// A Basic challenge over plain http is only answered with credentials typed for it.
+    pub credentials: BTreeMap<String, Credentials>,
+    /// Whether pages that the robots.txt of their host disallows are refused, as a crawler
+    /// should when wev is run over many pages.
+    pub robots_txt: bool,
+    /// How many milliseconds to leave between the requests to a host, or more if its
+    /// robots.txt asks and is obeyed. 0 leaves none.
+    pub host_delay: u64,
+}
+
+impl Default for RequestConfig {
+    fn default() -> Self {
+        RequestConfig {
+            user_agent: DEFAULT_USER_AGENT.into(),
+            headers: BTreeMap::new(),
+            proxy: None,
+            connect_timeout: 10,
+            read_timeout: 30,
+            idle_timeout: 90,
+            // as many as are used at once
+            max_idle_connections: MAX_FETCHES_PER_HOST,
+            retries: 2,
+            accept_encodings: vec!["gzip".into(), "deflate".into()],
+            credentials: BTreeMap::new(),
+            robots_txt: false,
+            host_delay: 0,
+        }
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::Config(message)
+}
+
+impl RequestConfig {
+    /// Returns the `[request]` table of `content`, a config file.
+    pub fn from_config(content: &str) -> Result<RequestConfig> {
+        Ok(Config::parse(content)?.request)
+    }
+
+    /// Adds `header`, given as `Name: value`, in place of any header of the same name.
+    pub fn add_header(&mut self, header: &str) -> Result<()> {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid_data(format!("header `{}` is not `Name: value`", header)))?;
+        let name = name.trim();
+        self.headers
+            .retain(|other, _| !other.eq_ignore_ascii_case(name));
+        self.headers.insert(name.into(), value.trim().into());
+        Ok(())
+    }
+
+    /// Fails if `accept_encodings` names a content coding that cannot be decoded.
+    pub fn check_accept_encodings(&self) -> Result<()> {
+        match self
+            .accept_encodings
+            .iter()
+            .find(|name| Coding::from_name(name).is_none())
+        {
+            Some(name) => Err(invalid_data(format!(
+                "content encoding `{}` cannot be decoded: only gzip and deflate can",
+                name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the `Accept-Encoding` header of `accept_encodings`.
+    fn accept_encoding(&self) -> Result<HeaderValue> {
+        self.check_accept_encodings()?;
+        let value = match self.accept_encodings.join(", ") {
+            value if value.is_empty() => "identity".into(),
+            value => value,
+        };
+        HeaderValue::from_str(&value).map_err(|_| invalid_data("invalid accept-encodings".into()))
+    }
+
+    /// Returns the headers sent with every request besides `User-Agent`.
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(DEFAULT_ACCEPT));
+        headers.insert(ACCEPT_ENCODING, self.accept_encoding()?);
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| invalid_data(format!("invalid header name `{}`", name)))?;
+            if name == ACCEPT_ENCODING {
+                return Err(invalid_data(
+                    "set accept-encodings instead of the Accept-Encoding header".into(),
+                ));
+            }
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| invalid_data(format!("invalid value of header `{}`", name)))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// Makes the transport requests are made with. Its client keeps connections open to be
+    /// reused, and speaks HTTP/2 to the hosts that offer it.
+    pub fn transport(&self) -> Result<Transport> {
+        let seconds = |seconds| (seconds > 0).then(|| Duration::from_secs(seconds));
+        let mut builder = Client::builder()
+            .user_agent(&self.user_agent)
+            .default_headers(self.header_map()?)
+            .pool_idle_timeout(seconds(self.idle_timeout))
+            .pool_max_idle_per_host(self.max_idle_connections)
+            .tcp_keepalive(TCP_KEEPALIVE)
+            .http2_adaptive_window(true);
+        if let Some(timeout) = seconds(self.connect_timeout) {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            let proxy = Proxy::all(proxy)
+                .map_err(|_| invalid_data(format!("invalid proxy `{}`", proxy)))?;
+            builder = builder.proxy(proxy);
+        }
+        Ok(Transport {
+            client: builder.build().map_err(RequestError::from)?,
+            read_timeout: seconds(self.read_timeout),
+            retries: self.retries,
+            authenticator: Authenticator::new(self.credentials.clone(), Netrc::load()),
+            politeness: Politeness {
+                robots_txt: self.robots_txt,
+                host_delay: Duration::from_millis(self.host_delay),
+                // robots.txt names clients by the product token, the name before the version
+                agent: self
+                    .user_agent
+                    .split(['/', ' '])
+                    .next()
+                    .unwrap_or_default()
+                    .into(),
+                robots: Mutex::default(),
+                next_request: Mutex::default(),
+            },
+            offline: AtomicBool::new(false),
+        })
+    }
+}
+
+/// How often an idle connection is probed, so that one the network dropped is not reused.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// `Transport` is how requests are made: the client all of them share, so that connections
+/// are reused between them, and what is done when they are slow or fail.
+/// It is made of a `RequestConfig`, and shared behind an `Arc` by what loads pages with it.
+#[derive(Debug)]
+pub struct Transport {
+    client: Client,
+    read_timeout: Option<Duration>,
+    retries: u32,
+    authenticator: Authenticator,
+    politeness: Politeness,
+    /// Whether requests are made from the cache only.
+    offline: AtomicBool,
+}
+
+/// `Politeness` is what keeps wev from burdening hosts when it is run over many pages:
+/// the robots.txt of each host, when it is obeyed, and when each host may be asked next.
+#[derive(Debug)]
+struct Politeness {
+    robots_txt: bool,
+    host_delay: Duration,
+    /// The product token robots.txt names wev by.
+    agent: String,
+    /// The robots.txt of each origin, read once by the first request to it.
+    robots: Mutex<HashMap<String, Arc<OnceCell<Robots>>>>,
+    next_request: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        RequestConfig::default()
+            .transport()
+            .expect("the default request config is valid")
+    }
+}
+
+impl Transport {
+    /// Signs in to `origin` with `credentials`, which the user typed, for the rest of the session.
+    pub fn sign_in(&self, origin: &str, credentials: Credentials) {
+        self.authenticator.remember(origin, credentials);
+    }
+
+    /// Makes requests from the cache only, whether its responses are fresh or not, or goes back
+    /// to making them from the network.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::Relaxed);
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::Relaxed)
+    }
+}
+
+/// `RequestError` is why a request failed, told apart so that it can be explained.
+#[derive(Debug)]
+pub enum RequestError {
+    /// The host did not answer within the read timeout.
+    Timeout,
+    /// No connection could be made to the host.
+    Connect(reqwest::Error),
+    /// The server answered with an error status.
+    Status(StatusCode),
+    /// The server asked to be signed in to, and there were no credentials for its origin
+    /// or they were refused.
+    Unauthorized {
+        origin: String,
+        realm: Option<String>,
+    },
+    /// The body could not be decoded from its content coding.
+    Decode(io::Error),
+    /// Requests are not made while offline, and the response was not in the cache.
+    Offline,
+    /// The robots.txt of the host asks that the page is not fetched.
+    Disallowed,
+    /// Anything else, like a malformed URL or a body that was cut short.
+    Other(reqwest::Error),
+}
+
+impl RequestError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            RequestError::Status(status) => Some(*status),
+            RequestError::Unauthorized { .. } => Some(StatusCode::UNAUTHORIZED),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the request may succeed if it is made again.
+    fn is_transient(&self) -> bool {
+        match self {
+            RequestError::Timeout | RequestError::Connect(_) => true,
+            RequestError::Status(status) => matches!(
+                *status,
+                StatusCode::REQUEST_TIMEOUT
+                    | StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            RequestError::Unauthorized { .. }
+            | RequestError::Decode(_)
+            | RequestError::Offline
+            | RequestError::Disallowed
+            | RequestError::Other(_) => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for RequestError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            RequestError::Timeout
+        } else if e.is_connect() {
+            RequestError::Connect(e)
+        } else if let Some(status) = e.status() {
+            RequestError::Status(status)
+        } else {
+            RequestError::Other(e)
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Timeout => write!(f, "the host took too long to answer"),
+            RequestError::Connect(e) => write!(f, "could not connect to the host: {}", e),
+            RequestError::Status(status) => write!(f, "the server answered {}", status),
+            RequestError::Unauthorized { origin, realm } => {
+                write!(f, "{} asks for a user name and password", origin)?;
+                match realm {
+                    Some(realm) => write!(f, " for \"{}\"", realm),
+                    None => Ok(()),
+                }
+            }
+            RequestError::Decode(e) => write!(f, "the body could not be decoded: {}", e),
+            RequestError::Offline => write!(f, "offline, and the page is not in the cache"),
+            RequestError::Disallowed => write!(f, "the robots.txt of the host disallows the page"),
+            RequestError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+/// How long to wait before making a request again after its `attempt`th failure:
+/// half a second, doubling each time up to 8 seconds.
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(500 << attempt.saturating_sub(1).min(4))
+}
 
-pub fn html_from_www(url: &str) -> reqwest::Result<String> {
-    let response = reqwest::blocking::get(url)?;
-    response.text()
+/// Waits for `future`, a part of a response, for no longer than `read_timeout`.
+async fn within<T>(
+    read_timeout: Option<Duration>,
+    future: impl Future<Output = reqwest::Result<T>>,
+) -> std::result::Result<T, RequestError> {
+    match read_timeout {
+        Some(limit) => tokio::time::timeout(limit, future)
+            .await
+            .map_err(|_| RequestError::Timeout)?
+            .map_err(RequestError::from),
+        None => future.await.map_err(RequestError::from),
+    }
 }
 
-pub fn html_from_local(path: &str) -> io::Result<String> {
-    let mut file = File::open(path)?;
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
-    Ok(content)
+/// Returns whether the robots.txt of the origin of `url` allows it, reading it the first time.
+/// A robots.txt that is missing allows everything, and one that cannot be read as the server
+/// is failing allows nothing.
+async fn is_allowed(transport: &Arc<Transport>, url: &Url) -> bool {
+    let politeness = &transport.politeness;
+    let origin = url.origin().ascii_serialization();
+    let cell = politeness
+        .robots
+        .lock()
+        .unwrap()
+        .entry(origin.clone())
+        .or_default()
+        .clone();
+    let robots = cell
+        .get_or_init(|| async {
+            wait_turn(transport, url).await;
+            let robots_url = format!("{}/robots.txt", origin);
+            let request = transport.client.get(&robots_url).send();
+            let response = match within(transport.read_timeout, request).await {
+                Ok(response) => response,
+                Err(_) => return Robots::default(),
+            };
+            if response.status().is_server_error() {
+                return Robots::disallow_all();
+            }
+            if !response.status().is_success() {
+                return Robots::default();
+            }
+            let mut body = ResponseBody::new(response, transport.clone());
+            let mut content = vec![];
+            while let Ok(Some(chunk)) = body.chunk().await {
+                content.extend(chunk);
+            }
+            Robots::parse(&String::from_utf8_lossy(&content), &politeness.agent)
+        })
+        .await;
+    robots.allows(&url[url::Position::BeforePath..])
+}
+
+/// Waits until the host of `url` may be asked again, and takes the turn.
+async fn wait_turn(transport: &Transport, url: &Url) {
+    let politeness = &transport.politeness;
+    let mut delay = politeness.host_delay;
+    if politeness.robots_txt {
+        let cell = politeness
+            .robots
+            .lock()
+            .unwrap()
+            .get(&url.origin().ascii_serialization())
+            .cloned();
+        if let Some(crawl_delay) = cell.and_then(|cell| cell.get().and_then(Robots::crawl_delay)) {
+            delay = delay.max(crawl_delay);
+        }
+    }
+    if delay.is_zero() {
+        return;
+    }
+    let host = url.host_str().unwrap_or_default().to_string();
+    let now = Instant::now();
+    let turn = {
+        let mut next_request = politeness.next_request.lock().unwrap();
+        let turn = next_request.get(&host).map_or(now, |&next| next.max(now));
+        next_request.insert(host, turn + delay);
+        turn
+    };
+    tokio::time::sleep_until(turn.into()).await;
+}
+
+/// Sends the request `build` makes. When `retry` is set, it is made again after a pause
+/// while it fails in a way that may pass, as many times as configured.
+/// An origin that asks to be signed in to is answered with the credentials there are for it.
+/// Error statuses are returned as errors, but `304 Not Modified` is not.
+/// Requests to the same host are spaced out and checked against its robots.txt as configured.
+async fn send(
+    transport: &Arc<Transport>,
+    build: impl Fn() -> RequestBuilder,
+    retry: bool,
+) -> std::result::Result<Response, RequestError> {
+    let retries = if retry { transport.retries } else { 0 };
+    let request = build().build()?;
+    let (method, url) = (request.method(), request.url());
+    let span = tracing::info_span!("request", %method, %url);
+    async {
+        let mut attempt = 0;
+        // a host is answered again only after its nonce expired, so that this ends
+        let mut challenges = 0;
+        if transport.politeness.robots_txt && !is_allowed(transport, url).await {
+            tracing::warn!("disallowed by robots.txt");
+            return Err(RequestError::Disallowed);
+        }
+        loop {
+            wait_turn(transport, url).await;
+            let authorization = transport.authenticator.authorization(method, url);
+            let mut builder = build();
+            if let Some(authorization) = &authorization {
+                builder = builder.header(AUTHORIZATION, authorization);
+            }
+            let result = match within(transport.read_timeout, builder.send()).await {
+                Ok(response) if response.status() == StatusCode::UNAUTHORIZED => {
+                    let headers = response.headers();
+                    challenges += 1;
+                    let authorized = authorization.is_some();
+                    if challenges <= 2
+                        && transport.authenticator.challenged(url, headers, authorized)
+                    {
+                        continue;
+                    }
+                    Err(RequestError::Unauthorized {
+                        origin: auth::origin(url),
+                        realm: auth::challenges(headers)
+                            .first()
+                            .and_then(|challenge| challenge.realm().map(str::to_string)),
+                    })
+                }
+                Ok(response)
+                    if response.status().is_client_error()
+                        || response.status().is_server_error() =>
+                {
+                    Err(RequestError::Status(response.status()))
+                }
+                result => result,
+            };
+            attempt += 1;
+            match &result {
+                Ok(response) => {
+                    tracing::info!(version = ?response.version(), "{}", response.status())
+                }
+                Err(e) => tracing::warn!(attempt, "{}", e),
+            }
+            match result {
+                Err(e) if e.is_transient() && attempt <= retries => {
+                    tokio::time::sleep(backoff(attempt)).await
+                }
+                result => return result,
+            }
+        }
+    }
+    .instrument(span)
+    .await
+}
+
+/// `ResponseBody` is the body of a response, decoded from its content coding as it is read.
+#[derive(Debug)]
+pub struct ResponseBody {
+    response: Response,
+    /// What the request was made with, which says how long to wait for the body.
+    transport: Arc<Transport>,
+    // boxed, as the state of a decoder is large
+    decoder: Box<Decoder>,
+    finished: bool,
+}
+
+impl ResponseBody {
+    pub fn new(response: Response, transport: Arc<Transport>) -> ResponseBody {
+        let decoder = Box::new(Decoder::new(
+            response
+                .headers()
+                .get(CONTENT_ENCODING)
+                .and_then(|value| value.to_str().ok()),
+        ));
+        ResponseBody {
+            response,
+            transport,
+            decoder,
+            finished: false,
+        }
+    }
+
+    /// The response, whose status and headers can be read.
+    pub fn response(&self) -> &Response {
+        &self.response
+    }
+
+    /// What the request was made with, to make more requests like it.
+    pub fn transport(&self) -> &Arc<Transport> {
+        &self.transport
+    }
+
+    /// The coding the body was sent in, once the first of it has been read.
+    pub fn coding(&self) -> Coding {
+        self.decoder.coding()
+    }
+
+    /// Reads and decodes the next part of the body, waiting no longer than the read timeout
+    /// for each part that arrives.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        while !self.finished {
+            let read_timeout = self.transport.read_timeout;
+            let decoded = match within(read_timeout, self.response.chunk()).await? {
+                Some(chunk) => self.decoder.push(&chunk),
+                None => {
+                    self.finished = true;
+                    self.decoder.finish()
+                }
+            }
+            .map_err(RequestError::Decode)?;
+            // the first bytes of an encoded body may not decode to anything yet
+            if !decoded.is_empty() {
+                return Ok(Some(decoded));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// The runtime that pages and their subresources are fetched on, shared by the whole program.
+/// Code outside of it waits for a request with `runtime().block_on(...)`.
+pub fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start the network runtime")
+    })
+}
+
+/// The cache GET responses are kept in, unless there is nowhere to keep it.
+fn http_cache() -> Option<&'static Cache> {
+    static CACHE: OnceLock<Option<Cache>> = OnceLock::new();
+    CACHE
+        .get_or_init(|| Cache::default_dir().map(Cache::new))
+        .as_ref()
+}
+
+/// `Fetched` is the answer to a GET request.
+#[derive(Debug)]
+pub enum Fetched {
+    /// A cached response that is fresh, or that may be stale when offline.
+    Cached(Entry),
+    /// A cached response that the server said has not changed (`304 Not Modified`).
+    NotModified(Entry),
+    /// A response from the server, whose body is still to be read.
+    /// It should be passed to `store` once it has been.
+    Network(Response),
+}
+
+/// Fetches `url`, using the cache as `mode` says. Offline, it is fetched from the cache
+/// whatever `mode` says, or not at all.
+pub async fn get(transport: &Arc<Transport>, url: &str, mode: CacheMode) -> Result<Fetched> {
+    let cache = http_cache();
+    if transport.is_offline() {
+        return match cache.and_then(|cache| cache.lookup(url)) {
+            Some(entry) => {
+                tracing::debug!(url, "from the cache, offline");
+                Ok(Fetched::Cached(entry))
+            }
+            None => Err(RequestError::Offline.into()),
+        };
+    }
+    let entry = cache
+        .filter(|_| mode != CacheMode::Reload)
+        .and_then(|cache| cache.lookup(url));
+    let now = cache::now();
+    let client = &transport.client;
+    if let Some(entry) = entry {
+        if mode == CacheMode::Normal && entry.is_fresh(now) {
+            tracing::debug!(url, "fresh in the cache");
+            return Ok(Fetched::Cached(entry));
+        }
+        let validators = entry.validators();
+        let request = || client.get(url).headers(validators.clone());
+        let response = send(transport, request, true).await?;
+        if response.status() != StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::Network(response));
+        }
+        let mut entry = entry;
+        if let Some(cache) = cache {
+            // the page can be shown from the cache even if it cannot be kept there
+            let _ = cache.refresh(url, &mut entry, response.headers(), now);
+        }
+        return Ok(Fetched::NotModified(entry));
+    }
+    let request = || {
+        let request = client.get(url);
+        if mode == CacheMode::Reload {
+            // caches between here and the server are bypassed too
+            request.header(reqwest::header::CACHE_CONTROL, "no-cache")
+        } else {
+            request
+        }
+    };
+    Ok(Fetched::Network(send(transport, request, true).await?))
+}
+
+/// Keeps `body`, the body of a response to `url` with `headers`, in the cache if it may be.
+pub fn store(url: &str, headers: &HeaderMap, body: &[u8]) {
+    if let Some(cache) = http_cache() {
+        // a response that cannot be cached is not an error for the page
+        let _ = cache.store(url, headers, body, cache::now());
+    }
+}
+
+/// Returns the URL `form` is submitted to, as `submit` does.
+fn submission_url(form: &FormData, method: Method, action: &str, base: &str) -> Result<Url> {
+    // a page that is not on the web can only have forms with absolute actions submitted
+    let mut url = Url::parse(base)
+        .and_then(|base| base.join(action))
+        .or_else(|_| Url::parse(action))
+        .map_err(|_| Error::InvalidUrl(action.to_string()))?;
+    if method == Method::Get {
+        url.set_query(Some(&form.urlencoded()));
+    }
+    Ok(url)
+}
+
+/// Submits `form` with `method` to `action`, the action of the form as written, resolved against
+/// `base`, the URL of the page. A GET puts the form in the query of the action, in place of any
+/// query it had, and a POST sends it urlencoded in the body. Returns the response, whose body
+/// is still to be read. A POST is not retried, since the server may have acted on the first
+/// attempt. Responses to submissions are not cached.
+pub async fn submit(
+    transport: &Arc<Transport>,
+    form: &FormData,
+    method: Method,
+    action: &str,
+    base: &str,
+) -> Result<Response> {
+    if transport.is_offline() {
+        return Err(RequestError::Offline.into());
+    }
+    let url = submission_url(form, method, action, base)?;
+    let client = &transport.client;
+    let response = match method {
+        Method::Get => send(transport, || client.get(url.clone()), true).await?,
+        Method::Post => {
+            let request = || {
+                client
+                    .post(url.clone())
+                    .header(
+                        reqwest::header::CONTENT_TYPE,
+                        "application/x-www-form-urlencoded",
+                    )
+                    .body(form.urlencoded())
+            };
+            send(transport, request, false).await?
+        }
+    };
+    Ok(response)
+}
+
+/// Requests the body of `url` from byte `offset` on, to resume a download that was cut short,
+/// if it is still the one whose `ETag` or `Last-Modified` is `validator`.
+/// The body is asked for unencoded, so that its bytes are those of the file. A server that
+/// cannot send part of it, or whose body has changed, answers `200 OK` with all of it, rather
+/// than `206 Partial Content`.
+pub async fn get_range(
+    transport: &Arc<Transport>,
+    url: &str,
+    offset: u64,
+    validator: Option<&str>,
+) -> Result<Response> {
+    if transport.is_offline() {
+        return Err(RequestError::Offline.into());
+    }
+    let client = &transport.client;
+    let request = || {
+        let request = client
+            .get(url)
+            .header(RANGE, format!("bytes={}-", offset))
+            .header(ACCEPT_ENCODING, "identity");
+        match validator {
+            Some(validator) => request.header(IF_RANGE, validator),
+            None => request,
+        }
+    };
+    Ok(send(transport, request, true).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        backoff, get, runtime, submission_url, submit, RequestConfig, RequestError, Transport,
+        DEFAULT_ACCEPT, DEFAULT_USER_AGENT,
+    };
+    use crate::{
+        cache::CacheMode,
+        error::Error,
+        form::{FormData, Method},
+    };
+    use reqwest::StatusCode;
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn test_request_config() {
+        assert_eq!(
+            RequestConfig::from_config("").unwrap(),
+            RequestConfig::default()
+        );
+        let content = r#"
+            theme = "dark"
+
+            [request]
+            user-agent = "Mozilla/5.0"
+            headers = { "Accept-Language" = "ja, en;q=0.5", DNT = "1" }
+            idle-timeout = 0
+            max-idle-connections = 2
+            robots-txt = true
+            host-delay = 1000
+        "#;
+        let mut config = RequestConfig::from_config(content).unwrap();
+        assert_eq!(config.user_agent, "Mozilla/5.0");
+        assert_eq!((config.idle_timeout, config.max_idle_connections), (0, 2));
+        assert!(config.robots_txt);
+        assert_eq!(config.host_delay, 1000);
+        assert!(config.transport().is_ok());
+        config.add_header("dnt: 0").unwrap();
+        config.add_header("Accept: text/plain").unwrap();
+        assert!(config.add_header("no colon").is_err());
+
+        let headers = config.header_map().unwrap();
+        assert_eq!(headers["accept-language"], "ja, en;q=0.5");
+        assert_eq!(headers["dnt"], "0");
+        assert_eq!(headers["accept"], "text/plain");
+        assert_eq!(
+            RequestConfig::default().header_map().unwrap()["accept"],
+            DEFAULT_ACCEPT
+        );
+        assert!(DEFAULT_USER_AGENT.starts_with("wev/"));
+
+        config.add_header("Bad Name: x").unwrap();
+        assert!(config.header_map().is_err());
+        assert!(RequestConfig::from_config("[request]\nproxy = 1").is_err());
+        let config =
+            RequestConfig::from_config("[request]\nproxy = \"http://proxy.test:8080\"").unwrap();
+        assert!(config.transport().is_ok());
+        let config = RequestConfig::from_config("[request]\nproxy = \"not a url\"").unwrap();
+        assert!(config.transport().is_err());
+    }
+
+    #[test]
+    fn test_accept_encodings() {
+        assert_eq!(
+            RequestConfig::default().header_map().unwrap()["accept-encoding"],
+            "gzip, deflate"
+        );
+        let config = RequestConfig::from_config("[request]\naccept-encodings = []").unwrap();
+        assert_eq!(config.header_map().unwrap()["accept-encoding"], "identity");
+        let config =
+            RequestConfig::from_config("[request]\naccept-encodings = [\"deflate\"]").unwrap();
+        assert_eq!(config.header_map().unwrap()["accept-encoding"], "deflate");
+        assert!(
+            RequestConfig::from_config("[request]\naccept-encodings = [\"br\", \"gzip\"]").is_err()
+        );
+        assert!(RequestConfig::from_config("[request]\naccept-encodings = [\"zstd\"]").is_err());
+        let mut config = RequestConfig {
+            accept_encodings: vec!["br".into()],
+            ..RequestConfig::default()
+        };
+        assert!(config.header_map().is_err());
+        config = RequestConfig::default();
+        config.add_header("accept-encoding: br, zstd").unwrap();
+        assert!(config.header_map().is_err());
+    }
+
+    #[test]
+    fn test_retries() {
+        let config =
+            RequestConfig::from_config("[request]\nread-timeout = 0\nretries = 5").unwrap();
+        assert_eq!(config.connect_timeout, 10);
+        assert_eq!(config.read_timeout, 0);
+        assert_eq!(config.retries, 5);
+        let transport = config.transport().unwrap();
+        assert_eq!(transport.read_timeout, None);
+
+        assert_eq!(backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff(2), Duration::from_secs(1));
+        assert_eq!(backoff(10), Duration::from_secs(8));
+
+        assert!(RequestError::Timeout.is_transient());
+        assert!(RequestError::Status(StatusCode::SERVICE_UNAVAILABLE).is_transient());
+        assert!(!RequestError::Status(StatusCode::NOT_FOUND).is_transient());
+        assert_eq!(
+            RequestError::Status(StatusCode::NOT_FOUND).to_string(),
+            "the server answered 404 Not Found"
+        );
+    }
+
+    #[test]
+    fn test_submission_url() {
+        let mut form = FormData::default();
+        form.append("q", "wev browser");
+        form.append("lang", "en");
+        let url = |method, action, base| {
+            submission_url(&form, method, action, base).map(|url| url.to_string())
+        };
+        assert_eq!(
+            url(Method::Get, "search?old=1", "https://a.com/dir/page").unwrap(),
+            "https://a.com/dir/search?q=wev+browser&lang=en"
+        );
+        assert_eq!(
+            url(Method::Post, "", "https://a.com/form?x=1").unwrap(),
+            "https://a.com/form?x=1"
+        );
+        assert_eq!(
+            url(Method::Post, "https://b.com/post", "docs/form.html").unwrap(),
+            "https://b.com/post"
+        );
+        assert!(url(Method::Get, "search", "docs/form.html").is_err());
+    }
+
+    #[test]
+    fn test_offline() {
+        let transport = Arc::new(Transport::default());
+        transport.set_offline(true);
+        let url = "https://wev.invalid/never-cached";
+        let fetched = runtime().block_on(get(&transport, url, CacheMode::Reload));
+        assert!(matches!(
+            fetched,
+            Err(Error::Network(RequestError::Offline))
+        ));
+        let form = FormData::default();
+        let posted = runtime().block_on(submit(&transport, &form, Method::Post, "", url));
+        assert!(matches!(posted, Err(Error::Network(RequestError::Offline))));
+        // only requests made with that transport are offline
+        assert!(!Transport::default().is_offline());
+        transport.set_offline(false);
+        assert!(!transport.is_offline());
+    }
 }
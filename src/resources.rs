@@ -0,0 +1,155 @@
+use crate::{
+    cache::CacheMode,
+    charset, css,
+    cssom::{SimpleSelector, Stylesheet},
+    dom::{self, Node},
+    loader,
+    page::{Image, Settings},
+    queue::Priority,
+};
+use std::collections::HashMap;
+use tokio::task::JoinSet;
+
+/// `References` are the subresources a page refers to, as written in it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct References {
+    /// The `href`s of the `<link rel="stylesheet">` elements, in document order.
+    pub stylesheets: Vec<String>,
+    /// The `src`s of the `<img>` elements, each once.
+    pub images: Vec<String>,
+}
+
+/// Walks `dom` for the stylesheets and images it refers to.
+pub fn collect(dom: &Node) -> References {
+    let link_tag = SimpleSelector::TypeSelector {
+        tag_name: "link".into(),
+    };
+    let img_tag = SimpleSelector::TypeSelector {
+        tag_name: "img".into(),
+    };
+    let stylesheets = dom::select(dom, &link_tag.into())
+        .iter()
+        .filter_map(|n| n.element())
+        .filter(|e| {
+            e.attributes
+                .get("rel")
+                .is_some_and(|rel| rel.eq_ignore_ascii_case("stylesheet"))
+        })
+        .filter_map(|e| e.attributes.get("href").cloned())
+        .collect();
+    let mut images = Vec::<String>::new();
+    for node in dom::select(dom, &img_tag.into()) {
+        if let Some(src) = node.element().and_then(|e| e.attributes.get("src")) {
+            if !images.contains(src) {
+                images.push(src.clone());
+            }
+        }
+    }
+    References {
+        stylesheets,
+        images,
+    }
+}
+
+/// `Subresources` are the fetched and decoded subresources of a page.
+#[derive(Debug, Default)]
+pub struct Subresources {
+    /// The linked stylesheets, in the order of their links.
+    pub stylesheets: Vec<Stylesheet>,
+    /// Decoded images keyed by the `src` that refers to them.
+    pub images: HashMap<String, Image>,
+}
+
+/// `Fetched` is a subresource that arrived.
+enum Fetched {
+    /// A linked stylesheet, and where its link is among the page's stylesheet links.
+    Stylesheet(usize, Stylesheet),
+    /// An image, keyed by the `src` that refers to it.
+    #[cfg(feature = "images")]
+    Image(String, Image),
+}
+
+/// Fetches `references`, found at the locations `resolve` returns, using the cache as `mode`
+/// says, with `settings`. The stylesheets are fetched before the images from the same host, which are
+/// fetched no more than `MAX_FETCHES_PER_HOST` at a time.
+/// Those that cannot be fetched or decoded are left out. Dropping the returned future
+/// cancels the fetches still being made.
+pub async fn fetch(
+    references: &References,
+    resolve: impl Fn(&str) -> String,
+    mode: CacheMode,
+    settings: &Settings,
+) -> Subresources {
+    let mut fetches = JoinSet::new();
+    for (i, href) in references.stylesheets.iter().enumerate() {
+        let (location, settings) = (resolve(href), settings.clone());
+        fetches.spawn(async move {
+            let css = loader::load(&location, mode, Priority::Stylesheet, &settings)
+                .await
+                .inspect_err(|e| tracing::warn!(url = location, "skipping a stylesheet: {}", e))
+                .ok()?;
+            let css = charset::decode(&css.body, css.content_type.as_deref());
+            let stylesheet = css::stylesheet(&css)
+                .inspect_err(|e| tracing::warn!(url = location, "skipping a stylesheet: {}", e))
+                .ok()?;
+            Some(Fetched::Stylesheet(i, stylesheet))
+        });
+    }
+    #[cfg(feature = "images")]
+    for src in &references.images {
+        let (src, location, settings) = (src.clone(), resolve(src), settings.clone());
+        fetches.spawn(async move {
+            let bytes = loader::load(&location, mode, Priority::Image, &settings)
+                .await
+                .inspect_err(|e| tracing::warn!(url = location, "skipping an image: {}", e))
+                .ok()?
+                .body;
+            let image = image::load_from_memory(&bytes)
+                .inspect_err(|e| tracing::warn!(url = location, "skipping an image: {}", e))
+                .ok()?;
+            Some(Fetched::Image(src, image))
+        });
+    }
+
+    // the stylesheets are put back in the order of their links, whichever arrived first
+    let mut stylesheets = vec![None; references.stylesheets.len()];
+    #[cfg_attr(not(feature = "images"), allow(unused_mut))]
+    let mut images = HashMap::new();
+    while let Some(fetched) = fetches.join_next().await {
+        match fetched.ok().flatten() {
+            Some(Fetched::Stylesheet(i, stylesheet)) => stylesheets[i] = Some(stylesheet),
+            #[cfg(feature = "images")]
+            Some(Fetched::Image(src, image)) => {
+                images.insert(src, image);
+            }
+            None => {}
+        }
+    }
+    Subresources {
+        stylesheets: stylesheets.into_iter().flatten().collect(),
+        images,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect, References};
+    use crate::page::{Page, Settings};
+
+    #[test]
+    fn test_collect() {
+        let page = Page::from_html(
+            "about:test",
+            r#"<link rel="icon" href="a.ico"><link rel="Stylesheet" href="a.css"><img src="a.png"><p><img src="b.png"><img src="a.png"></p><link href="b.css" rel="stylesheet"><img alt="none">"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            collect(page.dom()),
+            References {
+                stylesheets: vec!["a.css".into(), "b.css".into()],
+                images: vec!["a.png".into(), "b.png".into()],
+            }
+        );
+    }
+}
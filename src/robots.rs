@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+/// `Rule` is an `Allow` or `Disallow` line of a robots.txt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    allow: bool,
+    /// The path it applies to the start of, where `*` stands for anything and a `$` at the end
+    /// for the end of the path.
+    pattern: String,
+}
+
+/// `Robots` is what the robots.txt of a host asks of a client, as RFC 9309 reads it.
+/// Without a robots.txt, everything may be fetched.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    /// How long to wait between requests, if the host asks.
+    crawl_delay: Option<Duration>,
+}
+
+/// Returns whether `path` starts with `pattern`, a rule's pattern.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        // the earliest match leaves the most for the parts after it
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(j) => rest = &rest[j + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+impl Robots {
+    /// What a host whose robots.txt cannot be read, as it is failing, is taken to ask:
+    /// nothing is to be fetched.
+    pub fn disallow_all() -> Robots {
+        Robots {
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".into(),
+            }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Reads `content`, a robots.txt, for the client whose product token is `agent`, like
+    /// `wev`. The groups naming the client apply to it, or else those for `*`.
+    pub fn parse(content: &str, agent: &str) -> Robots {
+        let mut named = Robots::default();
+        let mut any = Robots::default();
+        let (mut is_named, mut is_any) = (false, false);
+        let (mut found_named, mut found_any) = (false, false);
+        // user-agent lines that follow one another start a group together
+        let mut in_agents = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+            if key == "user-agent" {
+                if !in_agents {
+                    (is_named, is_any) = (false, false);
+                }
+                in_agents = true;
+                if value == "*" {
+                    is_any = true;
+                    found_any = true;
+                } else if value.eq_ignore_ascii_case(agent) {
+                    is_named = true;
+                    found_named = true;
+                }
+                continue;
+            }
+            in_agents = false;
+            let rule = match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => Some(Rule {
+                    allow: key == "allow",
+                    pattern: value.to_string(),
+                }),
+                "crawl-delay" => {
+                    let delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|delay| delay.is_finite() && *delay >= 0.0)
+                        .map(Duration::from_secs_f64);
+                    for robots in [(is_named, &mut named), (is_any, &mut any)]
+                        .into_iter()
+                        .filter_map(|(applies, robots)| applies.then_some(robots))
+                    {
+                        robots.crawl_delay = delay.or(robots.crawl_delay);
+                    }
+                    None
+                }
+                _ => None,
+            };
+            if let Some(rule) = rule {
+                if is_named {
+                    named.rules.push(rule.clone());
+                }
+                if is_any {
+                    any.rules.push(rule);
+                }
+            }
+        }
+        match (found_named, found_any) {
+            (true, _) => named,
+            (false, true) => any,
+            (false, false) => Robots::default(),
+        }
+    }
+
+    /// Returns whether `path`, with its query, may be fetched. Of the rules that match it,
+    /// the longest decides, and an `Allow` wins a tie.
+    pub fn allows(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, Robots};
+    use std::time::Duration;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("/private", "/private/a.html"));
+        assert!(!matches("/private", "/public"));
+        assert!(matches("/*.pdf$", "/docs/a.pdf"));
+        assert!(!matches("/*.pdf$", "/docs/a.pdf?download"));
+        assert!(matches("/a*b*c", "/a-x-b-y-c-z"));
+        assert!(matches("/a$", "/a"));
+        assert!(!matches("/a$", "/ab"));
+    }
+
+    #[test]
+    fn test_robots() {
+        let content = "
+            # comments are ignored
+            User-agent: *
+            Disallow: /private/
+            Allow: /private/public.html
+            Crawl-delay: 2
+
+            User-agent: otherbot
+            User-agent: WEV
+            Disallow: /
+            Allow: /open
+            Crawl-delay: 0.5
+        ";
+        let robots = Robots::parse(content, "wev");
+        assert!(!robots.allows("/index.html"));
+        assert!(robots.allows("/open/a.html"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_millis(500)));
+
+        let robots = Robots::parse(content, "curl");
+        assert!(robots.allows("/index.html"));
+        assert!(!robots.allows("/private/a.html"));
+        assert!(robots.allows("/private/public.html"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_secs(2)));
+
+        assert_eq!(Robots::parse("", "wev"), Robots::default());
+        assert!(Robots::parse("User-agent: *\nDisallow:", "wev").allows("/"));
+        assert!(!Robots::disallow_all().allows("/a"));
+    }
+}
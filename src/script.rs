@@ -0,0 +1,337 @@
+use crate::{
+    dom::{self, Document, Index, Node, NodeType, Text},
+    error::{Error, Result},
+    page::{Link, Page},
+};
+use std::{cell::OnceCell, fmt, sync::OnceLock};
+
+/// `ElementRef` is an element of a `ScriptDocument`, as scripts hold on to it: its path from
+/// the root of the page.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ElementRef(pub Vec<usize>);
+
+/// `ScriptDocument` is the page as scripts see it, `document` of the DOM: what they can find
+/// in it and change.
+#[derive(Debug)]
+pub struct ScriptDocument<'a> {
+    dom: &'a mut Node,
+    /// The index of `dom`, kept across queries until the script changes the tree.
+    index: OnceCell<Index>,
+}
+
+impl<'a> ScriptDocument<'a> {
+    pub fn new(dom: &'a mut Node) -> ScriptDocument<'a> {
+        ScriptDocument {
+            dom,
+            index: OnceCell::new(),
+        }
+    }
+
+    fn document(&self) -> Document<'_> {
+        let index = self.index.get_or_init(|| Index::new(self.dom));
+        Document::with_index(self.dom, index)
+    }
+
+    /// Returns the node at `element` to change, after which the tree is indexed again.
+    fn node_mut(&mut self, element: &ElementRef) -> Option<&mut Node> {
+        self.index.take();
+        self.dom.node_at_mut(&element.0)
+    }
+
+    /// `document.querySelector(selector)`: the first element `selector` matches.
+    pub fn query_selector(&self, selector: &str) -> Option<ElementRef> {
+        self.query_selector_all(selector).into_iter().next()
+    }
+
+    /// `document.querySelectorAll(selector)`: the elements `selector` matches, in document order.
+    pub fn query_selector_all(&self, selector: &str) -> Vec<ElementRef> {
+        self.document()
+            .query_paths(selector)
+            .into_iter()
+            .map(ElementRef)
+            .collect()
+    }
+
+    /// `element.textContent`.
+    pub fn text_content(&self, element: &ElementRef) -> Option<String> {
+        Some(self.dom.node_at(&element.0)?.text_content())
+    }
+
+    /// Sets `element.textContent`, replacing what the element contains with `text`.
+    pub fn set_text_content(&mut self, element: &ElementRef, text: &str) {
+        if let Some(node) = self.node_mut(element) {
+            node.children = vec![Text::new(text.to_string())];
+        }
+    }
+
+    /// `element.getAttribute(name)`.
+    pub fn get_attribute(&self, element: &ElementRef, name: &str) -> Option<String> {
+        let element = self.dom.node_at(&element.0)?.element()?;
+        element.attributes.get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    /// `element.setAttribute(name, value)`.
+    pub fn set_attribute(&mut self, element: &ElementRef, name: &str, value: &str) {
+        if let Some(NodeType::Element(e)) = self.node_type_mut(element) {
+            e.attributes
+                .insert(name.to_ascii_lowercase(), value.to_string());
+        }
+    }
+
+    /// `element.removeAttribute(name)`.
+    pub fn remove_attribute(&mut self, element: &ElementRef, name: &str) {
+        if let Some(NodeType::Element(e)) = self.node_type_mut(element) {
+            e.attributes.remove(&name.to_ascii_lowercase());
+        }
+    }
+
+    fn node_type_mut(&mut self, element: &ElementRef) -> Option<&mut NodeType> {
+        Some(&mut self.node_mut(element)?.node_type)
+    }
+}
+
+/// `ScriptPage` is the page shown, as a script bound to a key sees it: what it can read of
+/// the page and what it asks the browser to do once it is done.
+#[derive(Debug)]
+pub struct ScriptPage<'a> {
+    page: &'a Page,
+    /// The row the page is scrolled to, which the script may change.
+    pub scroll: u16,
+    /// Where to go once the script is done, if it followed a link.
+    pub follow: Option<String>,
+    /// What to say on the status line once the script is done.
+    pub message: Option<String>,
+}
+
+impl<'a> ScriptPage<'a> {
+    pub fn new(page: &'a Page, scroll: u16) -> ScriptPage<'a> {
+        ScriptPage {
+            page,
+            scroll,
+            follow: None,
+            message: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.page.url
+    }
+
+    pub fn title(&self) -> Option<String> {
+        self.page.title()
+    }
+
+    /// The elements `selector` matches, in document order.
+    pub fn query_selector_all(&self, selector: &str) -> Vec<ElementRef> {
+        self.page
+            .document()
+            .query_paths(selector)
+            .into_iter()
+            .map(ElementRef)
+            .collect()
+    }
+
+    pub fn text_content(&self, element: &ElementRef) -> Option<String> {
+        Some(self.page.dom().node_at(&element.0)?.text_content())
+    }
+
+    pub fn get_attribute(&self, element: &ElementRef, name: &str) -> Option<String> {
+        let element = self.page.dom().node_at(&element.0)?.element()?;
+        element.attributes.get(&name.to_ascii_lowercase()).cloned()
+    }
+
+    /// The links of the page in document order, with their URLs resolved.
+    pub fn links(&self) -> Vec<Link> {
+        self.page.links()
+    }
+
+    /// Goes to `href`, resolved against the URL of the page, once the script is done.
+    pub fn follow_link(&mut self, href: &str) {
+        self.follow = Some(self.page.resolve(href));
+    }
+}
+
+/// `ScriptEngine` runs the scripts of pages, like an embedded JavaScript engine binding
+/// `document` to a `ScriptDocument`, and the scripts the user binds to keys.
+/// No engine comes with the crate; a program embedding it implements this trait.
+pub trait ScriptEngine: fmt::Debug + Send + Sync {
+    /// Runs `source`, an inline `<script>` of `document`.
+    fn run(&self, source: &str, document: &mut ScriptDocument) -> Result<()>;
+
+    /// Runs the user's script called `name` on `page`, for a key bound to `script:name`.
+    fn run_key_script(&self, name: &str, page: &mut ScriptPage) -> Result<()> {
+        let _ = page;
+        Err(Error::Config(format!("no script `{}`", name)))
+    }
+}
+
+static ENGINE: OnceLock<Box<dyn ScriptEngine>> = OnceLock::new();
+
+/// Runs the inline scripts of every page with `engine`.
+/// It has to be called before the first page is made. Until it is, scripts are not run.
+pub fn set_engine(engine: impl ScriptEngine + 'static) -> Result<()> {
+    ENGINE
+        .set(Box::new(engine))
+        .map_err(|_| Error::Config("the script engine was already set".into()))
+}
+
+/// Runs the user's script called `name` on `page` with the engine.
+/// Without one, which the `wev` binary never sets, it fails saying so.
+pub fn run_key_script(name: &str, page: &mut ScriptPage) -> Result<()> {
+    match ENGINE.get() {
+        Some(engine) => engine.run_key_script(name, page),
+        None => Err(Error::Config(
+            "no script engine is embedded in this program".into(),
+        )),
+    }
+}
+
+/// Returns whether a `<script>` with the `type` attribute `ty` is JavaScript.
+fn is_javascript(ty: Option<&str>) -> bool {
+    match ty.map(|ty| ty.trim().to_ascii_lowercase()) {
+        None => true,
+        Some(ty) => matches!(
+            ty.as_str(),
+            "" | "text/javascript" | "application/javascript" | "module"
+        ),
+    }
+}
+
+/// Returns the sources of the inline scripts of `dom`, in document order.
+/// Scripts with a `src` are not fetched, and are left out.
+fn inline_scripts(dom: &Node) -> Vec<String> {
+    dom::query(dom, "script")
+        .into_iter()
+        .filter_map(|node| {
+            let element = node.element()?;
+            let inline = !element.attributes.contains_key("src")
+                && is_javascript(element.attributes.get("type").map(String::as_str));
+            inline.then(|| node.text_content())
+        })
+        .collect()
+}
+
+/// Runs the inline scripts of `dom`, a page that was just parsed, in document order with the
+/// engine. Without one, the page is left as it was parsed. A script that fails is skipped.
+pub fn run_scripts(dom: &mut Node) {
+    if let Some(engine) = ENGINE.get() {
+        run_with(engine.as_ref(), dom);
+    }
+}
+
+fn run_with(engine: &dyn ScriptEngine, dom: &mut Node) {
+    for source in inline_scripts(dom) {
+        let _ = engine
+            .run(&source, &mut ScriptDocument::new(dom))
+            .inspect_err(|e| tracing::warn!("skipping a <script>: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_key_script, run_with, ElementRef, ScriptDocument, ScriptEngine, ScriptPage};
+    use crate::{
+        dom::Node,
+        error::Result,
+        html,
+        page::{Page, Settings},
+    };
+
+    /// Runs scripts of lines like `selector = text`, which set the text of what matches.
+    #[derive(Debug)]
+    struct Assignments;
+
+    impl ScriptEngine for Assignments {
+        fn run(&self, source: &str, document: &mut ScriptDocument) -> Result<()> {
+            for line in source.split(';').filter(|line| !line.trim().is_empty()) {
+                let (selector, text) = line.split_once('=').unwrap();
+                for element in document.query_selector_all(selector.trim()) {
+                    document.set_text_content(&element, text.trim());
+                }
+            }
+            Ok(())
+        }
+
+        /// `links` counts the links and follows the last one, and `bottom` scrolls down.
+        fn run_key_script(&self, name: &str, page: &mut ScriptPage) -> Result<()> {
+            match name {
+                "links" => {
+                    let links = page.links();
+                    page.message = Some(format!("{} links on {}", links.len(), page.url()));
+                    if let Some(last) = page.query_selector_all("a[href]").last() {
+                        let href = page.get_attribute(last, "href").unwrap();
+                        page.follow_link(&href);
+                    }
+                }
+                "bottom" => page.scroll = u16::MAX,
+                _ => return Err(crate::error::Error::Config(format!("no script `{}`", name))),
+            }
+            Ok(())
+        }
+    }
+
+    fn parse(html: &str) -> Box<Node> {
+        Node::document(html::parse(html).unwrap())
+    }
+
+    #[test]
+    fn test_script_document() {
+        let mut dom = parse(r#"<p class="a">one</p><div><p id="b" title="x">two</p></div>"#);
+        let mut document = ScriptDocument::new(&mut dom);
+        assert_eq!(document.query_selector("p"), Some(ElementRef(vec![0])));
+        assert_eq!(document.query_selector_all("p").len(), 2);
+        assert_eq!(document.query_selector("ul"), None);
+
+        let b = document.query_selector("#b").unwrap();
+        assert_eq!(document.text_content(&b).as_deref(), Some("two"));
+        assert_eq!(document.get_attribute(&b, "TITLE").as_deref(), Some("x"));
+        document.set_attribute(&b, "title", "y");
+        document.remove_attribute(&b, "id");
+        document.set_text_content(&b, "<three>");
+        assert_eq!(
+            dom.to_html(),
+            r#"<p class="a">one</p><div><p title="y">&lt;three&gt;</p></div>"#
+        );
+    }
+
+    #[test]
+    fn test_run_scripts() {
+        let mut dom = parse(
+            r#"<p id="a">no script</p><script>#a = loaded</script><script src="x.js">#a = fetched</script><script type="text/plain">#a = data</script><p id="b"></p><script type="module">#b = later</script>"#,
+        );
+        run_with(&Assignments, &mut dom);
+        let document = ScriptDocument::new(&mut dom);
+        let text = |selector| {
+            let element = document.query_selector(selector).unwrap();
+            document.text_content(&element).unwrap()
+        };
+        assert_eq!(text("#a"), "loaded");
+        assert_eq!(text("#b"), "later");
+    }
+
+    #[test]
+    fn test_key_script() {
+        let page = Page::from_html(
+            "https://a.test/dir/",
+            r#"<a href="one">One</a> <a href="../two">Two</a>"#,
+            &Settings::default(),
+        )
+        .unwrap();
+        let mut script_page = ScriptPage::new(&page, 3);
+        Assignments
+            .run_key_script("links", &mut script_page)
+            .unwrap();
+        assert_eq!(
+            script_page.message.as_deref(),
+            Some("2 links on https://a.test/dir/")
+        );
+        assert_eq!(script_page.follow.as_deref(), Some("https://a.test/two"));
+        assert_eq!(script_page.scroll, 3);
+        assert!(Assignments
+            .run_key_script("none", &mut script_page)
+            .is_err());
+        // no engine is set in the tests, as in the binary
+        assert!(run_key_script("links", &mut script_page).is_err());
+        assert_eq!(script_page.scroll, 3);
+    }
+}
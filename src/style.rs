@@ -1,6 +1,6 @@
 use crate::{
-    cssom::{CSSValue, Stylesheet},
-    dom::{Node, NodeType},
+    cssom::{CSSValue, ComplexSelector, Stylesheet},
+    dom::{Document, Node, NodeId, NodeType},
 };
 use std::collections::HashMap;
 
@@ -8,82 +8,284 @@ use std::collections::HashMap;
 /// It forms a tree as `Node` does.
 #[derive(Debug, PartialEq)]
 pub struct StyledNode<'a> {
+    /// The node it was styled from, as a `Document` of the styled tree numbers it.
+    pub id: NodeId,
     pub node_type: &'a NodeType,
     pub children: Vec<StyledNode<'a>>,
 
     pub properties: HashMap<String, CSSValue>,
 }
 
-pub fn to_styled_node<'a>(node: &'a Box<Node>, stylesheet: &Stylesheet) -> Option<StyledNode<'a>> {
-    let mut properties: HashMap<String, (u32, CSSValue)> = HashMap::new();
+pub fn to_styled_node<'a>(node: &'a Node, stylesheet: &Stylesheet) -> Option<StyledNode<'a>> {
+    let _span = tracing::debug_span!("style", rules = stylesheet.rules.len()).entered();
+    let document = Document::new(node);
+    to_styled_node_in(&document, node, NodeId(0), stylesheet, None)
+}
+
+/// `Origin` is where the value of a property came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin<'s> {
+    /// A rule of the page's stylesheet, matched by this selector.
+    Rule(&'s ComplexSelector),
+    /// The defaults of the browser, like `display: block` for `<div>`.
+    UserAgent,
+    /// The parent element.
+    Inherited,
+}
+
+/// Returns the properties of the node `id` of `document` and where each came from, given the
+/// properties of its parent.
+fn cascade<'s>(
+    document: &Document,
+    id: NodeId,
+    stylesheet: &'s Stylesheet,
+    parent: Option<&HashMap<String, CSSValue>>,
+) -> HashMap<String, (CSSValue, Origin<'s>)> {
+    let node = document.node(id);
+    let mut properties: HashMap<String, (u32, CSSValue, Origin<'s>)> = HashMap::new();
 
-    for matched_rule in stylesheet.rules.iter().filter(|r| r.matches(node)) {
-        for (selector, declaration) in matched_rule
+    for matched_rule in stylesheet.rules.iter().filter(|r| r.matches(document, id)) {
+        let Some(selector) = matched_rule
             .selectors
             .iter()
-            .zip(matched_rule.declarations.iter())
-        {
-            if let Some((specificity, _)) = properties.get(&declaration.name) {
-                if *specificity <= selector.specificity() {
+            .filter(|s| s.matches(document, id))
+            .max_by_key(|s| s.specificity())
+        else {
+            continue;
+        };
+        let selector_specificity = selector.specificity();
+        for declaration in matched_rule.declarations.iter() {
+            if let Some((specificity, _, _)) = properties.get(&declaration.name) {
+                if *specificity <= selector_specificity {
                     properties.insert(
                         declaration.name.clone(),
-                        (selector.specificity(), declaration.value.clone()),
+                        (
+                            selector_specificity,
+                            declaration.value.clone(),
+                            Origin::Rule(selector),
+                        ),
                     );
                 }
             } else {
                 properties.insert(
                     declaration.name.clone(),
-                    (selector.specificity(), declaration.value.clone()),
+                    (
+                        selector_specificity,
+                        declaration.value.clone(),
+                        Origin::Rule(selector),
+                    ),
                 );
             }
         }
     }
 
-    if properties.get("display").is_none() {
+    if !properties.contains_key("display") {
         match node.node_type {
             NodeType::Element(ref element) => match element.tag_name.as_str() {
+                "input"
+                    if element
+                        .attributes
+                        .get("type")
+                        .is_some_and(|t| t.eq_ignore_ascii_case("hidden")) =>
+                {
+                    properties.insert(
+                        "display".into(),
+                        (0, CSSValue::Keyword("none".into()), Origin::UserAgent),
+                    );
+                }
                 "area" | "base" | "basefont" | "datalist" | "head" | "link" | "meta"
                 | "noembed" | "noframes" | "param" | "rp" | "script" | "style" | "template"
                 | "title" => {
-                    properties.insert("display".into(), (0, CSSValue::Keyword("none".into())));
+                    properties.insert(
+                        "display".into(),
+                        (0, CSSValue::Keyword("none".into()), Origin::UserAgent),
+                    );
+                }
+                "a" | "abbr" | "b" | "bdi" | "bdo" | "cite" | "code" | "data" | "del" | "dfn"
+                | "em" | "i" | "img" | "ins" | "kbd" | "mark" | "q" | "s" | "samp" | "small"
+                | "span" | "strike" | "strong" | "sub" | "sup" | "time" | "u" | "var" | "input"
+                | "select" | "textarea" | "button" => {
+                    properties.insert(
+                        "display".into(),
+                        (0, CSSValue::Keyword("inline".into()), Origin::UserAgent),
+                    );
+                }
+                "li" => {
+                    properties.insert(
+                        "display".into(),
+                        (0, CSSValue::Keyword("list-item".into()), Origin::UserAgent),
+                    );
                 }
                 _ => {
-                    properties.insert("display".into(), (0, CSSValue::Keyword("block".into())));
+                    properties.insert(
+                        "display".into(),
+                        (0, CSSValue::Keyword("block".into()), Origin::UserAgent),
+                    );
                 }
             },
-            NodeType::Text(_) => {}
+            NodeType::Document => {
+                properties.insert(
+                    "display".into(),
+                    (0, CSSValue::Keyword("block".into()), Origin::UserAgent),
+                );
+            }
+            NodeType::Text(_) | NodeType::Comment(_) | NodeType::Doctype(_) => {}
+        }
+    }
+
+    if !properties.contains_key("list-style-type") {
+        if let NodeType::Element(ref element) = node.node_type {
+            let inherited = parent.and_then(|p| p.get("list-style-type"));
+            let keyword = |value: &str| Some((CSSValue::Keyword(value.into()), Origin::UserAgent));
+            let value = match element.tag_name.as_str() {
+                "ol" => keyword("decimal"),
+                // nested lists are told apart by their bullets
+                "ul" | "menu" => match inherited {
+                    Some(CSSValue::Keyword(style)) if style == "disc" => keyword("circle"),
+                    Some(CSSValue::Keyword(style)) if style == "circle" || style == "square" => {
+                        keyword("square")
+                    }
+                    _ => keyword("disc"),
+                },
+                _ => inherited.map(|value| (value.clone(), Origin::Inherited)),
+            };
+            if let Some((value, origin)) = value {
+                properties.insert("list-style-type".into(), (0, value, origin));
+            }
         }
     }
 
-    if properties.get("font-weight").is_none() {
+    if !properties.contains_key("font-weight") {
         match node.node_type {
             NodeType::Element(ref element) => match element.tag_name.as_str() {
                 "b" | "strong" => {
-                    properties.insert("font-weight".into(), (0, CSSValue::Keyword("bold".into())));
-                }
-                _ => {
                     properties.insert(
                         "font-weight".into(),
-                        (0, CSSValue::Keyword("normal".into())),
+                        (0, CSSValue::Keyword("bold".into()), Origin::UserAgent),
                     );
                 }
+                _ => {
+                    let (value, origin) = match parent.and_then(|p| p.get("font-weight")) {
+                        Some(weight) => (weight.clone(), Origin::Inherited),
+                        None => (CSSValue::Keyword("normal".into()), Origin::UserAgent),
+                    };
+                    properties.insert("font-weight".into(), (0, value, origin));
+                }
             },
-            NodeType::Text(_) => {}
+            NodeType::Document => {
+                properties.insert(
+                    "font-weight".into(),
+                    (0, CSSValue::Keyword("normal".into()), Origin::UserAgent),
+                );
+            }
+            NodeType::Text(_) | NodeType::Comment(_) | NodeType::Doctype(_) => {}
         }
     }
 
-    if properties.get("display").map(|v| &v.1) == Some(&CSSValue::Keyword("none".into())) {
+    if let NodeType::Element(ref element) = node.node_type {
+        let keyword = |value: &str| CSSValue::Keyword(value.into());
+        let defaults = match element.tag_name.as_str() {
+            "i" | "em" | "cite" | "var" | "dfn" => vec![("font-style", keyword("italic"))],
+            "u" | "ins" => vec![("text-decoration", keyword("underline"))],
+            "pre" => vec![("white-space", keyword("pre"))],
+            "s" | "strike" | "del" => vec![("text-decoration", keyword("line-through"))],
+            "a" if element.attributes.contains_key("href") => vec![
+                // the theme decides the color of links, see `layout::TextRole`
+                ("color", keyword("linktext")),
+                ("text-decoration", keyword("underline")),
+            ],
+            _ => vec![],
+        };
+        for (name, value) in defaults {
+            properties
+                .entry(name.into())
+                .or_insert((0, value, Origin::UserAgent));
+        }
+    }
+
+    properties
+        .into_iter()
+        .map(|(name, (_, value, origin))| (name, (value, origin)))
+        .collect()
+}
+
+/// Returns the properties of the node at `path` under `root`, sorted by name,
+/// with where each came from. Empty if the node is not displayed.
+pub fn cascade_trace<'s>(
+    root: &Node,
+    path: &[usize],
+    stylesheet: &'s Stylesheet,
+) -> Vec<(String, CSSValue, Origin<'s>)> {
+    let document = Document::new(root);
+    let mut id = document.root();
+    let mut properties = cascade(&document, id, stylesheet, None);
+    for &i in path {
+        if is_display_none(&properties) {
+            return vec![];
+        }
+        let Some(child) = document.children(id).nth(i) else {
+            return vec![];
+        };
+        let parent = properties
+            .into_iter()
+            .map(|(name, (value, _))| (name, value))
+            .collect();
+        id = child;
+        properties = cascade(&document, id, stylesheet, Some(&parent));
+    }
+    if is_display_none(&properties) {
+        return vec![];
+    }
+    let mut trace = properties
+        .into_iter()
+        .map(|(name, (value, origin))| (name, value, origin))
+        .collect::<Vec<_>>();
+    trace.sort_by(|a, b| a.0.cmp(&b.0));
+    trace
+}
+
+fn is_display_none(properties: &HashMap<String, (CSSValue, Origin)>) -> bool {
+    properties.get("display").map(|v| &v.0) == Some(&CSSValue::Keyword("none".into()))
+}
+
+/// Styles `node`, numbered `id` in `document`, whose parent element was styled with `parent`
+/// (if any).
+fn to_styled_node_in<'a>(
+    document: &Document,
+    node: &'a Node,
+    id: NodeId,
+    stylesheet: &Stylesheet,
+    parent: Option<&HashMap<String, CSSValue>>,
+) -> Option<StyledNode<'a>> {
+    // comments and doctypes are kept in the tree but never rendered, whatever selects them
+    if matches!(node.node_type, NodeType::Comment(_) | NodeType::Doctype(_)) {
+        return None;
+    }
+    let properties = cascade(document, id, stylesheet, parent);
+    if is_display_none(&properties) {
         return None;
     }
 
+    let properties = properties.into_iter().map(|(k, v)| (k, v.0)).collect();
+    // a closed `<details>` shows only its summary
+    let closed_details = matches!(
+        &node.node_type,
+        NodeType::Element(e) if e.tag_name == "details" && !e.attributes.contains_key("open")
+    );
+    // the children keep the numbers the document gave them, whether those were left out or not
     let children = node
         .children
         .iter()
-        .filter_map(|x| to_styled_node(x, stylesheet))
+        .zip(document.children(id))
+        .filter(|(x, _)| {
+            !closed_details
+                || matches!(&x.node_type, NodeType::Element(e) if e.tag_name == "summary")
+        })
+        .filter_map(|(x, id)| to_styled_node_in(document, x, id, stylesheet, Some(&properties)))
         .collect();
 
-    let properties = properties.into_iter().map(|(k, v)| (k, v.1)).collect();
     Some(StyledNode {
+        id,
         node_type: &node.node_type,
         properties,
         children,
@@ -96,13 +298,13 @@ mod tests {
 
     use crate::{
         css,
-        cssom::CSSValue,
-        dom::{Element, NodeType, Text},
+        cssom::{CSSValue, Unit},
+        dom::{Document, Element, NodeId, NodeType, Text},
         html,
         style::StyledNode,
     };
 
-    use super::to_styled_node;
+    use super::{cascade_trace, to_styled_node, Origin};
 
     #[test]
     fn test_styled_node() {
@@ -110,16 +312,18 @@ mod tests {
             .parse("<p class=\"foo\">hello world</p>")
             .unwrap()
             .0;
-        let stylesheet = css::stylesheet("p { color:red; }");
+        let stylesheet = css::stylesheet("p { color:red; }").unwrap();
         let nodes = to_styled_node(&dom[0], &stylesheet);
         assert_eq!(
             nodes,
             Some(StyledNode {
+                id: NodeId(0),
                 node_type: &NodeType::Element(Element {
                     tag_name: "p".into(),
                     attributes: vec![("class".into(), "foo".into())].into_iter().collect(),
                 }),
                 children: vec![StyledNode {
+                    id: NodeId(1),
                     node_type: &NodeType::Text(Text {
                         data: "hello world".into()
                     }),
@@ -137,6 +341,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rule_declarations() {
+        let dom = html::nodes()
+            .parse(r#"<p foo="bar">hello world</p>"#)
+            .unwrap()
+            .0;
+        // every declaration of a rule applies, with the specificity of the selector that
+        // matched rather than the one in the same place in the selector list
+        let stylesheet =
+            css::stylesheet("div, p[foo=bar] { color:blue; display:inline; } p { color:red; }")
+                .unwrap();
+        let nodes = to_styled_node(&dom[0], &stylesheet).unwrap();
+
+        assert_eq!(
+            nodes.properties.get("color"),
+            Some(&CSSValue::Keyword("blue".into()))
+        );
+        assert_eq!(
+            nodes.properties.get("display"),
+            Some(&CSSValue::Keyword("inline".into()))
+        );
+    }
+
+    #[test]
+    fn test_node_ids() {
+        let html = "<div><head><title>t</title></head><!-- c --><p>a<b>b</b></p></div>";
+        let dom = &html::html().parse(html).unwrap().0[0];
+        let stylesheet = css::stylesheet("").unwrap();
+        let styled = to_styled_node(dom, &stylesheet).unwrap();
+        // the nodes left out still take their places in document order
+        let document = Document::new(dom);
+        let p = &styled.children[0];
+        assert_eq!(Some(p.id), document.id_at(&[2]));
+        assert_eq!(
+            Some(p.children[1].children[0].id),
+            document.id_at(&[2, 1, 0])
+        );
+        assert_eq!(document.node(p.id).node_type, *p.node_type);
+    }
+
     #[test]
     fn test_specificity() {
         let dom = html::nodes()
@@ -161,22 +405,26 @@ mod tests {
                 color:yellow;
             }
             "#,
-        );
+        )
+        .unwrap();
         let nodes = to_styled_node(&dom[0], &stylesheet);
 
         assert_eq!(
             nodes,
             Some(StyledNode {
+                id: NodeId(0),
                 node_type: &NodeType::Element(Element {
                     tag_name: "div".into(),
                     attributes: vec![].into_iter().collect()
                 }),
                 children: vec![StyledNode {
+                    id: NodeId(1),
                     node_type: &NodeType::Element(Element {
                         tag_name: "p".into(),
                         attributes: vec![("foo".into(), "bar".into())].into_iter().collect()
                     }),
                     children: vec![StyledNode {
+                        id: NodeId(2),
                         node_type: &NodeType::Text(Text {
                             data: "hello world".into()
                         }),
@@ -201,4 +449,154 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_multiple_declarations() {
+        let dom = html::nodes().parse("<p>hello world</p>").unwrap().0;
+        let stylesheet = css::stylesheet("p { color: red; min-width: 4ch; }").unwrap();
+        let nodes = to_styled_node(&dom[0], &stylesheet).unwrap();
+
+        assert_eq!(
+            nodes.properties.get("color"),
+            Some(&CSSValue::Keyword("red".into()))
+        );
+        assert_eq!(
+            nodes.properties.get("min-width"),
+            Some(&CSSValue::Length(4.0, Unit::Ch))
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        let dom = html::nodes()
+            .parse(r#"<div class="x"><p><a>deep</a></p><a>child</a></div><a>outside</a>"#)
+            .unwrap()
+            .0;
+        let stylesheet = css::stylesheet(
+            ".x a { color: red; } .x > a { color: blue; } p a { font-style: italic; }",
+        )
+        .unwrap();
+        let style = |i: usize, path: &[usize]| {
+            let node = to_styled_node(&dom[i], &stylesheet).unwrap();
+            let node = path.iter().fold(&node, |node, &i| &node.children[i]);
+            (
+                node.properties.get("color").cloned(),
+                node.properties.get("font-style").cloned(),
+            )
+        };
+        let keyword = |value: &str| Some(CSSValue::Keyword(value.into()));
+        assert_eq!(style(0, &[0, 0]), (keyword("red"), keyword("italic")));
+        // the more specific selector wins
+        assert_eq!(style(0, &[1]), (keyword("blue"), None));
+        assert_eq!(style(1, &[]), (None, None));
+    }
+
+    #[test]
+    fn test_inherited_font_weight() {
+        let dom = html::nodes()
+            .parse("<div><b><i>bold</i></b><p>normal</p></div>")
+            .unwrap()
+            .0;
+        let stylesheet = css::stylesheet("").unwrap();
+        let nodes = to_styled_node(&dom[0], &stylesheet).unwrap();
+
+        let b = &nodes.children[0];
+        let i = &b.children[0];
+        assert_eq!(
+            i.properties.get("font-weight"),
+            Some(&CSSValue::Keyword("bold".into()))
+        );
+        assert_eq!(
+            i.properties.get("font-style"),
+            Some(&CSSValue::Keyword("italic".into()))
+        );
+        assert_eq!(
+            nodes.children[1].properties.get("font-weight"),
+            Some(&CSSValue::Keyword("normal".into()))
+        );
+    }
+
+    #[test]
+    fn test_link_defaults() {
+        let dom = html::nodes()
+            .parse(r#"<p><a href="/x">x</a><a>y</a></p>"#)
+            .unwrap()
+            .0;
+        let stylesheet = css::stylesheet("a { text-decoration: none; }").unwrap();
+        let nodes = to_styled_node(&dom[0], &stylesheet).unwrap();
+
+        let link = &nodes.children[0];
+        assert_eq!(
+            link.properties.get("display"),
+            Some(&CSSValue::Keyword("inline".into()))
+        );
+        assert_eq!(
+            link.properties.get("color"),
+            Some(&CSSValue::Keyword("linktext".into()))
+        );
+        assert_eq!(
+            link.properties.get("text-decoration"),
+            Some(&CSSValue::Keyword("none".into()))
+        );
+        assert_eq!(nodes.children[1].properties.get("color"), None);
+    }
+
+    #[test]
+    fn test_list_style_type() {
+        let dom = html::nodes()
+            .parse("<ul><li><ul><li><ol><li><ul><li>x</li></ul></li></ol></li></ul></li></ul>")
+            .unwrap()
+            .0;
+        let stylesheet = css::stylesheet("").unwrap();
+        let mut node = &to_styled_node(&dom[0], &stylesheet).unwrap();
+        // the style of each list and item on the way down to the text
+        let mut styles = vec![];
+        while let Some(CSSValue::Keyword(style)) = node.properties.get("list-style-type") {
+            styles.push(style.as_str());
+            node = &node.children[0];
+        }
+        assert_eq!(
+            styles,
+            vec!["disc", "disc", "circle", "circle", "decimal", "decimal", "disc", "disc"]
+        );
+    }
+
+    #[test]
+    fn test_cascade_trace() {
+        let dom = html::nodes()
+            .parse("<div><b class=\"note\">hi</b><span>there</span></div>")
+            .unwrap()
+            .0;
+        let stylesheet =
+            css::stylesheet("div { font-weight: bold; } .note { color: red; }").unwrap();
+        let trace = cascade_trace(&dom[0], &[0], &stylesheet);
+        assert_eq!(
+            trace,
+            vec![
+                (
+                    "color".into(),
+                    CSSValue::Keyword("red".into()),
+                    Origin::Rule(&stylesheet.rules[1].selectors[0])
+                ),
+                (
+                    "display".into(),
+                    CSSValue::Keyword("inline".into()),
+                    Origin::UserAgent
+                ),
+                (
+                    "font-weight".into(),
+                    CSSValue::Keyword("bold".into()),
+                    Origin::UserAgent
+                ),
+            ]
+        );
+
+        let trace = cascade_trace(&dom[0], &[1], &stylesheet);
+        assert!(trace.contains(&(
+            "font-weight".into(),
+            CSSValue::Keyword("bold".into()),
+            Origin::Inherited
+        )));
+        assert!(cascade_trace(&dom[0], &[5], &stylesheet).is_empty());
+    }
 }
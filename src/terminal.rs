@@ -0,0 +1,98 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    Command, ExecutableCommand,
+};
+use std::{
+    fmt,
+    io::{stdout, Result},
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+};
+
+/// Saves the terminal title on the terminal's title stack (xterm `XTWINOPS` 22).
+struct PushTitle;
+
+impl Command for PushTitle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str("\x1b[22;0t")
+    }
+}
+
+/// Restores the title saved by `PushTitle` (xterm `XTWINOPS` 23).
+struct PopTitle;
+
+impl Command for PopTitle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str("\x1b[23;0t")
+    }
+}
+
+/// Puts text on the clipboard of the terminal, which works over SSH as well (xterm OSC 52).
+struct CopyToClipboard<'a>(&'a str);
+
+impl Command for CopyToClipboard<'_> {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        write!(f, "\x1b]52;c;{}\x07", STANDARD.encode(self.0))
+    }
+}
+
+/// Copies `text` to the clipboard, and returns what to tell the user.
+pub fn copy_to_clipboard(text: &str) -> Result<String> {
+    stdout().execute(CopyToClipboard(text))?;
+    Ok(format!("copied {}", text))
+}
+
+/// Whether the terminal is in raw mode, and has to be restored.
+static TERMINAL_ENTERED: AtomicBool = AtomicBool::new(false);
+/// Whether the alternate screen is shown, unlike in inline mode.
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal back as it was before `TerminalGuard::enter`, unless it already is.
+/// Errors are ignored, as there is nothing better to do on the way out.
+fn restore_terminal() {
+    if TERMINAL_ENTERED.swap(false, Ordering::SeqCst) {
+        let _ = disable_raw_mode();
+        if ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+            let _ = stdout().execute(LeaveAlternateScreen);
+        }
+        let _ = stdout().execute(PopTitle);
+    }
+}
+
+/// `TerminalGuard` keeps the terminal in raw mode while it lives, on the alternate screen
+/// if `alternate_screen` is set. The terminal is restored when it is dropped, so also on
+/// errors, and before the message of a panic is printed.
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn enter(alternate_screen: bool) -> Result<Self> {
+        static PANIC_HOOK: Once = Once::new();
+        PANIC_HOOK.call_once(|| {
+            let hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                hook(info);
+            }));
+        });
+        // set first, so that failing halfway still undoes what was done
+        TERMINAL_ENTERED.store(true, Ordering::SeqCst);
+        let guard = TerminalGuard;
+        stdout().execute(PushTitle)?;
+        if alternate_screen {
+            ALTERNATE_SCREEN.store(true, Ordering::SeqCst);
+            stdout().execute(EnterAlternateScreen)?;
+        }
+        enable_raw_mode()?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
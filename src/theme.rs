@@ -0,0 +1,256 @@
+use crate::{config, css, cssom::Color};
+use serde::Deserialize;
+use std::{collections::HashMap, fs, io, path::PathBuf};
+
+/// The color of unvisited links when the theme leaves the terminal's colors alone.
+pub const LINK_COLOR: Color = Color::rgb(0, 0, 0xee);
+
+/// `Theme` is how pages are colored beyond what their stylesheets say.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// The color of text and of the screen behind it. `None` keeps the terminal's own.
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub link: Color,
+    /// The color of headings. `None` colors them as other text.
+    pub heading: Option<Color>,
+    /// Whether the background is dark, which decides which way page colors are made readable.
+    pub dark: bool,
+    /// The lowest contrast ratio page colors may have against the background;
+    /// lower ones are lightened or darkened. `1.0` keeps them as they are.
+    pub min_contrast: f32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DEFAULT
+    }
+}
+
+/// `ThemeConfig` is a theme as the user writes it in the themes file.
+/// Anything left out is taken from the `base` theme.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ThemeConfig {
+    base: Option<String>,
+    foreground: Option<String>,
+    background: Option<String>,
+    link: Option<String>,
+    heading: Option<String>,
+    min_contrast: Option<f32>,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Returns the relative luminance of `color` (https://www.w3.org/TR/WCAG21/#dfn-relative-luminance).
+fn luminance(color: Color) -> f32 {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color.r) + 0.7152 * channel(color.g) + 0.0722 * channel(color.b)
+}
+
+/// Returns the contrast ratio of two colors (https://www.w3.org/TR/WCAG21/#dfn-contrast-ratio).
+pub fn contrast(a: Color, b: Color) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// Mixes `amount` of `to` into `from`.
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Color {
+        a: from.a,
+        ..Color::rgb(
+            channel(from.r, to.r),
+            channel(from.g, to.g),
+            channel(from.b, to.b),
+        )
+    }
+}
+
+impl Theme {
+    /// Leaves the terminal's colors alone and shows page colors as they are.
+    pub const DEFAULT: Theme = Theme {
+        foreground: None,
+        background: None,
+        link: LINK_COLOR,
+        heading: None,
+        dark: true,
+        min_contrast: 1.0,
+    };
+
+    pub const DARK: Theme = Theme {
+        foreground: None,
+        background: None,
+        link: Color::rgb(0x6c, 0xb6, 0xff),
+        heading: Some(Color::rgb(0xff, 0xcc, 0x66)),
+        dark: true,
+        min_contrast: 4.5,
+    };
+
+    pub const LIGHT: Theme = Theme {
+        foreground: Some(Color::rgb(0x1e, 0x1e, 0x1e)),
+        background: Some(Color::rgb(0xff, 0xff, 0xff)),
+        link: LINK_COLOR,
+        heading: Some(Color::rgb(0x00, 0x00, 0x8b)),
+        dark: false,
+        min_contrast: 4.5,
+    };
+
+    pub const HIGH_CONTRAST: Theme = Theme {
+        foreground: Some(Color::rgb(0xff, 0xff, 0xff)),
+        background: Some(Color::rgb(0, 0, 0)),
+        link: Color::rgb(0xff, 0xff, 0),
+        heading: Some(Color::rgb(0, 0xff, 0xff)),
+        dark: true,
+        min_contrast: 7.0,
+    };
+
+    /// Returns the built-in theme called `name`.
+    pub fn builtin(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::DEFAULT),
+            "dark" => Some(Theme::DARK),
+            "light" => Some(Theme::LIGHT),
+            "high-contrast" => Some(Theme::HIGH_CONTRAST),
+            _ => None,
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/wev/themes.toml`, falling back to `~/.config/wev/themes.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config::config_dir()?.join("themes.toml"))
+    }
+
+    /// Returns the theme called `name`: a built-in one, or else one defined in the themes file.
+    pub fn load(name: &str) -> io::Result<Theme> {
+        if let Some(theme) = Theme::builtin(name) {
+            return Ok(theme);
+        }
+        let content = match Theme::default_path() {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(e),
+            },
+            None => String::new(),
+        };
+        Theme::from_config(name, &content)
+    }
+
+    /// Returns the theme called `name` in `content`, a TOML table of themes keyed by name.
+    pub fn from_config(name: &str, content: &str) -> io::Result<Theme> {
+        let themes: HashMap<String, ThemeConfig> =
+            toml::from_str(content).map_err(|e| invalid_data(e.to_string()))?;
+        let config = themes.get(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no theme `{}`", name))
+        })?;
+
+        let base = config.base.as_deref().unwrap_or("default");
+        let mut theme = Theme::builtin(base)
+            .ok_or_else(|| invalid_data(format!("no built-in theme `{}`", base)))?;
+        let color = |value: &str| {
+            css::color_value(value)
+                .ok_or_else(|| invalid_data(format!("invalid color `{}`", value)))
+        };
+        if let Some(foreground) = &config.foreground {
+            theme.foreground = Some(color(foreground)?);
+        }
+        if let Some(background) = &config.background {
+            let background = color(background)?;
+            theme.background = Some(background);
+            theme.dark = luminance(background) < 0.5;
+        }
+        if let Some(link) = &config.link {
+            theme.link = color(link)?;
+        }
+        if let Some(heading) = &config.heading {
+            theme.heading = Some(color(heading)?);
+        }
+        if let Some(min_contrast) = config.min_contrast {
+            theme.min_contrast = min_contrast;
+        }
+        Ok(theme)
+    }
+
+    /// Returns `color` brought to at least `min_contrast` against the background,
+    /// by mixing white into it on a dark background and black on a light one.
+    pub fn readable(&self, color: Color) -> Color {
+        let background = self.background.unwrap_or(if self.dark {
+            Color::rgb(0, 0, 0)
+        } else {
+            Color::rgb(0xff, 0xff, 0xff)
+        });
+        let towards = if self.dark {
+            Color::rgb(0xff, 0xff, 0xff)
+        } else {
+            Color::rgb(0, 0, 0)
+        };
+        (0..=10)
+            .map(|step| mix(color, towards, step as f32 / 10.0))
+            .find(|mixed| contrast(*mixed, background) >= self.min_contrast)
+            .unwrap_or(towards)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contrast, Theme};
+    use crate::cssom::Color;
+    use std::io;
+
+    #[test]
+    fn test_contrast() {
+        let black = Color::rgb(0, 0, 0);
+        let white = Color::rgb(0xff, 0xff, 0xff);
+        assert!((contrast(black, white) - 21.0).abs() < 0.01);
+        assert!((contrast(white, white) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_readable() {
+        let navy = Color::rgb(0, 0, 128);
+        assert_eq!(Theme::DEFAULT.readable(navy), navy);
+        // navy is unreadable on black, so it is lightened
+        let lightened = Theme::DARK.readable(navy);
+        assert!(contrast(lightened, Color::rgb(0, 0, 0)) >= 4.5);
+        assert!(lightened.b > lightened.r);
+        // and it is already readable on white
+        assert_eq!(Theme::LIGHT.readable(navy), navy);
+    }
+
+    #[test]
+    fn test_from_config() {
+        let config = r##"
+            [solarized]
+            base = "dark"
+            background = "#002b36"
+            link = "#268bd2"
+
+            [broken]
+            link = "bluish"
+        "##;
+        let theme = Theme::from_config("solarized", config).unwrap();
+        assert_eq!(theme.background, Some(Color::rgb(0x00, 0x2b, 0x36)));
+        assert_eq!(theme.link, Color::rgb(0x26, 0x8b, 0xd2));
+        assert_eq!(theme.heading, Theme::DARK.heading);
+        assert!(theme.dark);
+
+        assert_eq!(
+            Theme::from_config("broken", config).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            Theme::from_config("missing", config).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+}